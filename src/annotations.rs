@@ -0,0 +1,213 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::fmt::Debug;
+
+use druid::{
+    kurbo::{BezPath, Line},
+    piet::{Text, TextLayoutBuilder},
+    Color, Data, PaintCtx, Rect, RenderContext, Vec2,
+};
+
+use crate::{
+    grid_canvas::{CanvasItem, GridCanvasData},
+    snapping::GridSnapData,
+    utils::cassetta::{CassettePlayer, TapeItem},
+    GridBounds, GridIndex, GridItem,
+};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Annotation
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+// Line width of arrows and rectangles, before zoom
+const ANNOTATION_WIDTH: f64 = 2.0;
+// Length of the head of an arrow, before zoom
+const ARROW_HEAD_SIZE: f64 = 8.0;
+
+/// Note painted above the items of the grid. Annotations are anchored to cells so they follow
+/// the grid as it is panned, zoomed and rotated
+#[derive(Debug, Clone, PartialEq, Data)]
+pub enum Annotation {
+    /// Text starting at the top left corner of a cell
+    Text {
+        anchor: GridIndex,
+        text: String,
+        color: Color,
+    },
+    /// Arrow from the centre of one cell to the centre of another
+    Arrow {
+        from: GridIndex,
+        to: GridIndex,
+        color: Color,
+    },
+    /// Outline around the cells between two corners, both inclusive
+    Rect { bounds: GridBounds, color: Color },
+}
+
+impl Annotation {
+    pub fn text(anchor: GridIndex, text: impl Into<String>, color: Color) -> Self {
+        Self::Text {
+            anchor,
+            text: text.into(),
+            color,
+        }
+    }
+
+    pub fn arrow(from: GridIndex, to: GridIndex, color: Color) -> Self {
+        Self::Arrow { from, to, color }
+    }
+
+    pub fn rect(from: GridIndex, to: GridIndex, color: Color) -> Self {
+        Self::Rect {
+            bounds: GridBounds::new(from, to),
+            color,
+        }
+    }
+
+    /// Paints the annotation on a context transformed by the view of the grid, see
+    /// [`ViewTransform::affine`](crate::snapping::ViewTransform::affine)
+    pub fn paint(&self, ctx: &mut PaintCtx, snap_data: &GridSnapData) {
+        let corner = |pos: &GridIndex| snap_data.get_grid_position(pos.row, pos.col);
+        let center = |pos: &GridIndex| corner(pos) + Vec2::splat(snap_data.cell_size / 2.0);
+        match self {
+            Annotation::Text {
+                anchor,
+                text,
+                color,
+            } => {
+                let layout = ctx
+                    .text()
+                    .new_text_layout(text.clone())
+                    .text_color(*color)
+                    .build()
+                    .unwrap();
+                ctx.draw_text(&layout, corner(anchor));
+            }
+            Annotation::Arrow { from, to, color } => {
+                let (start, end) = (center(from), center(to));
+                ctx.stroke(Line::new(start, end), color, ANNOTATION_WIDTH);
+                let direction = end - start;
+                if direction.hypot() == 0.0 {
+                    return;
+                }
+                let back = direction.normalize() * ARROW_HEAD_SIZE;
+                let side = Vec2::new(-back.y, back.x) / 2.0;
+                let mut head = BezPath::new();
+                head.move_to(end);
+                head.line_to(end - back + side);
+                head.line_to(end - back - side);
+                head.close_path();
+                ctx.fill(head, color);
+            }
+            Annotation::Rect { bounds, color } => {
+                let max = bounds.max.below().right();
+                let rect = Rect::from_points(corner(&bounds.min), corner(&max));
+                ctx.stroke(rect, color, ANNOTATION_WIDTH);
+            }
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Measurement
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Distance between the centres of two cells, shown by the measure tool
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+pub struct Measurement {
+    pub from: GridIndex,
+    pub to: GridIndex,
+}
+
+impl Measurement {
+    pub fn new(from: GridIndex, to: GridIndex) -> Self {
+        Self { from, to }
+    }
+
+    /// Steps along rows and columns between the cells
+    pub fn manhattan(&self) -> usize {
+        self.from.row.abs_diff(self.to.row) + self.from.col.abs_diff(self.to.col)
+    }
+
+    /// Straight line distance between the cells, in cells
+    pub fn euclidean(&self) -> f64 {
+        let rows = self.from.row.abs_diff(self.to.row) as f64;
+        let columns = self.from.col.abs_diff(self.to.col) as f64;
+        rows.hypot(columns)
+    }
+
+    /// Both distances in cells and in world units, where a cell is `cell_size` wide
+    pub fn label(&self, cell_size: f64) -> String {
+        let (manhattan, euclidean) = (self.manhattan(), self.euclidean());
+        format!(
+            "Manhattan: {manhattan} cells ({:.1})\nEuclidean: {euclidean:.2} cells ({:.1})",
+            manhattan as f64 * cell_size,
+            euclidean * cell_size
+        )
+    }
+
+    /// Arrow between the cells with the distances written next to the second one
+    pub fn annotations(&self, cell_size: f64, color: Color) -> [Annotation; 2] {
+        [
+            Annotation::arrow(self.from, self.to, color),
+            Annotation::text(self.to.right(), self.label(cell_size), color),
+        ]
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Annotation Editing
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl<T: GridItem + PartialEq + Debug> GridCanvasData<T>
+where
+    GridCanvasData<T>: Data,
+{
+    /// Adds an annotation on top of the others as an undo step and returns its index
+    pub fn add_annotation(&mut self, annotation: Annotation) -> usize {
+        let index = self.annotations.len();
+        self.play_annotation(TapeItem::Add(index, annotation, None));
+        index
+    }
+
+    /// Removes the annotation at `index` as an undo step
+    pub fn remove_annotation(&mut self, index: usize) -> Option<Annotation> {
+        let annotation = self.annotations.get(index)?.clone();
+        self.play_annotation(TapeItem::Remove(index, annotation.clone()));
+        Some(annotation)
+    }
+
+    // Annotation edits go on the undo tape of the grid, so seeking plays them back
+    fn play_annotation(&mut self, item: TapeItem<usize, Annotation>) {
+        self.end_stroke();
+        self.annotations.advance(item.clone());
+        self.save_data.insert_and_play(CanvasItem::Annotation(item));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Measurement;
+    use crate::GridIndex;
+
+    #[test]
+    fn measures_cells_and_world_units() {
+        let measurement = Measurement::new(GridIndex::new(1, -2), GridIndex::new(-2, 2));
+        assert_eq!(measurement.manhattan(), 7);
+        assert_eq!(measurement.euclidean(), 5.0);
+        assert_eq!(
+            measurement.label(10.0),
+            "Manhattan: 7 cells (70.0)\nEuclidean: 5.00 cells (50.0)"
+        );
+        assert_eq!(
+            Measurement::new(GridIndex::new(3, 3), GridIndex::new(3, 3)).euclidean(),
+            0.0
+        );
+    }
+}
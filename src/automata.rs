@@ -0,0 +1,437 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::{collections::HashSet, fmt::Debug, time::Duration};
+
+use druid::{
+    im::{HashMap, Vector},
+    widget::Controller,
+    Data, Env, Event, EventCtx, Selector, TimerToken, Widget,
+};
+
+use crate::{
+    grid_canvas::{BatchReport, GridCanvasData},
+    utils::cassetta::TapeItem,
+    GridIndex, GridItem, Neighborhood,
+};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// CellularAutomaton
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// A cell and the cells around it, as seen by a [`CellularAutomaton`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Neighbors<'a, T> {
+    pub cell: Option<&'a T>,
+    // One entry per cell of the neighbourhood, None for the empty ones
+    pub around: Vec<Option<&'a T>>,
+}
+
+impl<'a, T: PartialEq> Neighbors<'a, T> {
+    /// Number of cells around that hold `item`
+    pub fn count(&self, item: &T) -> usize {
+        self.around
+            .iter()
+            .filter(|other| *other == &Some(item))
+            .count()
+    }
+
+    pub fn occupied(&self) -> usize {
+        self.around.iter().filter(|other| other.is_some()).count()
+    }
+}
+
+/// Rule turning every cell of the grid into its state in the next generation
+pub trait CellularAutomaton<T> {
+    /// Cells passed to [`next_state`](Self::next_state) as the ones around a cell
+    fn neighborhood(&self) -> Neighborhood {
+        Neighborhood::Moore
+    }
+
+    /// Item on the cell in the next generation, None to leave it empty
+    fn next_state(&self, neighbors: &Neighbors<T>) -> Option<T>;
+}
+
+/// Life-like automaton where cells holding `item` are alive. An empty cell comes alive with a
+/// number of live neighbours in `birth` and a live cell survives with a number in `survival`.
+/// Cells holding other items are left alone and never count as alive
+#[derive(Debug, Clone, PartialEq)]
+pub struct LifeRule<T> {
+    pub item: T,
+    pub birth: Vec<usize>,
+    pub survival: Vec<usize>,
+}
+
+impl<T> LifeRule<T> {
+    /// Conway's Game of Life, B3/S23
+    pub fn conway(item: T) -> Self {
+        Self {
+            item,
+            birth: vec![3],
+            survival: vec![2, 3],
+        }
+    }
+
+    /// Reads a rule in the usual `B3/S23` notation, in either order and case
+    pub fn from_rulestring(rule: &str, item: T) -> Result<Self, String> {
+        let mut birth = None;
+        let mut survival = None;
+        for part in rule.split('/') {
+            let part = part.trim();
+            let counts = || {
+                part[1..]
+                    .chars()
+                    .map(|c| c.to_digit(10).map(|count| count as usize))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| format!("Invalid neighbour count in {part:?}"))
+            };
+            match part.chars().next().map(|c| c.to_ascii_uppercase()) {
+                Some('B') if birth.is_none() => birth = Some(counts()?),
+                Some('S') if survival.is_none() => survival = Some(counts()?),
+                _ => return Err(format!("Invalid rule {rule:?}, expected e.g. B3/S23")),
+            }
+        }
+        match (birth, survival) {
+            (Some(birth), Some(survival)) => Ok(Self {
+                item,
+                birth,
+                survival,
+            }),
+            _ => Err(format!("Invalid rule {rule:?}, expected e.g. B3/S23")),
+        }
+    }
+}
+
+impl<T: Copy + PartialEq> CellularAutomaton<T> for LifeRule<T> {
+    fn next_state(&self, neighbors: &Neighbors<T>) -> Option<T> {
+        let alive = neighbors.count(&self.item);
+        match neighbors.cell {
+            Some(item) if *item == self.item => self.survival.contains(&alive).then_some(*item),
+            Some(item) => Some(*item),
+            None => self.birth.contains(&alive).then_some(self.item),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Stepping
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl<T: GridItem + PartialEq + Debug> GridCanvasData<T>
+where
+    GridCanvasData<T>: Data,
+{
+    /// Operations turning the grid into its next generation under `automaton`: a `BatchRemove`
+    /// of the cells that empty and a `BatchAdd` of the cells that fill or change. Only the
+    /// occupied cells and the cells around them are visited, so an empty cell with no occupied
+    /// neighbour always stays empty. Cells off the board are never filled.
+    pub fn next_generation(
+        &self,
+        automaton: &impl CellularAutomaton<T>,
+    ) -> Vector<TapeItem<GridIndex, T>> {
+        let neighborhood = automaton.neighborhood();
+        let mut candidates = HashSet::new();
+        for (pos, _) in self.grid.iter() {
+            candidates.insert(pos);
+            // Both ways round, a cell is affected by the cells it is a neighbour of
+            for offset in neighborhood.offsets(pos) {
+                candidates.insert(pos + *offset);
+                candidates.insert(pos + GridIndex::new(-offset.row, -offset.col));
+            }
+        }
+
+        let mut removed = HashMap::new();
+        let mut added = HashMap::new();
+        for pos in candidates {
+            let cell = self.grid.get(&pos);
+            let neighbors = Neighbors {
+                cell,
+                around: pos
+                    .neighbors(&neighborhood)
+                    .iter()
+                    .map(|other| self.grid.get(other))
+                    .collect(),
+            };
+            match (cell, automaton.next_state(&neighbors)) {
+                (Some(item), None) => {
+                    removed.insert(pos, *item);
+                }
+                (previous, Some(item)) if previous != Some(&item) && self.is_on_board(&pos) => {
+                    added.insert(pos, (item, previous.copied()));
+                }
+                _ => {}
+            }
+        }
+
+        let mut ops = Vector::new();
+        if !removed.is_empty() {
+            ops.push_back(TapeItem::BatchRemove(removed));
+        }
+        if !added.is_empty() {
+            ops.push_back(TapeItem::BatchAdd(added));
+        }
+        ops
+    }
+
+    /// Applies the [next generation](Self::next_generation) as a single undo step, so seeking
+    /// back through the tape walks through the earlier generations. Changes nothing while the
+    /// grid is read-only
+    pub fn step_automaton(&mut self, automaton: &impl CellularAutomaton<T>) -> BatchReport<T> {
+        if self.is_read_only() {
+            return BatchReport {
+                applied: 0,
+                rejected: Vec::new(),
+            };
+        }
+        let ops = self.next_generation(automaton);
+        self.apply_batch(ops)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// AutomatonController
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+// Steps the automaton once per tick until it settles or is paused
+pub const AUTOMATON_RUN: Selector = Selector::new("automaton-run");
+pub const AUTOMATON_PAUSE: Selector = Selector::new("automaton-pause");
+// Steps the automaton a single generation
+pub const AUTOMATON_STEP: Selector = Selector::new("automaton-step");
+// Sent when a generation changes nothing and a run stops
+pub const AUTOMATON_SETTLED: Selector = Selector::new("automaton-settled");
+
+const DEFAULT_GENERATION_RATE: f64 = 5.0;
+const MIN_GENERATION_RATE: f64 = 0.01;
+const MAX_GENERATION_RATE: f64 = 1000.0;
+
+/// Runs a [`CellularAutomaton`] on the grid of the widget it wraps, driven by the
+/// [`AUTOMATON_RUN`], [`AUTOMATON_PAUSE`] and [`AUTOMATON_STEP`] commands. Nothing is stepped
+/// while the grid is read-only
+pub struct AutomatonController<A> {
+    automaton: A,
+    // Generations per second while running
+    generation_rate: f64,
+    timer: TimerToken,
+}
+
+impl<A> AutomatonController<A> {
+    pub fn new(automaton: A) -> Self {
+        Self {
+            automaton,
+            generation_rate: DEFAULT_GENERATION_RATE,
+            timer: TimerToken::INVALID,
+        }
+    }
+
+    /// Sets the generations per second while running, kept between 0.01 and 1000. A rate that
+    /// is not a positive number is ignored
+    pub fn with_generation_rate(mut self, generations_per_second: f64) -> Self {
+        if generations_per_second.is_finite() && generations_per_second > 0.0 {
+            self.generation_rate =
+                generations_per_second.clamp(MIN_GENERATION_RATE, MAX_GENERATION_RATE);
+        }
+        self
+    }
+
+    pub fn set_automaton(&mut self, automaton: A) {
+        self.automaton = automaton;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.timer != TimerToken::INVALID
+    }
+
+    // Returns false when the generation changed nothing
+    fn step<T: GridItem + PartialEq + Debug>(
+        &self,
+        ctx: &mut EventCtx,
+        data: &mut GridCanvasData<T>,
+    ) -> bool
+    where
+        A: CellularAutomaton<T>,
+        GridCanvasData<T>: Data,
+    {
+        let changed = data.step_automaton(&self.automaton).applied > 0;
+        if changed {
+            ctx.request_paint();
+        }
+        changed
+    }
+
+    // Steps and schedules the next generation until one changes nothing. A read-only grid stops
+    // the run without settling it
+    fn run<T: GridItem + PartialEq + Debug>(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut GridCanvasData<T>,
+    ) where
+        A: CellularAutomaton<T>,
+        GridCanvasData<T>: Data,
+    {
+        if data.is_read_only() {
+            self.timer = TimerToken::INVALID;
+        } else if self.step(ctx, data) {
+            self.schedule(ctx);
+        } else {
+            self.timer = TimerToken::INVALID;
+            ctx.submit_notification(AUTOMATON_SETTLED);
+        }
+    }
+
+    fn schedule(&mut self, ctx: &mut EventCtx) {
+        self.timer = ctx.request_timer(Duration::from_secs_f64(1.0 / self.generation_rate));
+    }
+}
+
+impl<T, A, W> Controller<GridCanvasData<T>, W> for AutomatonController<A>
+where
+    T: GridItem + PartialEq + Debug,
+    GridCanvasData<T>: Data,
+    A: CellularAutomaton<T>,
+    W: Widget<GridCanvasData<T>>,
+{
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut GridCanvasData<T>,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(AUTOMATON_RUN) => {
+                self.run(ctx, data);
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(AUTOMATON_PAUSE) => {
+                self.timer = TimerToken::INVALID;
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(AUTOMATON_STEP) => {
+                self.step(ctx, data);
+                ctx.set_handled();
+            }
+            Event::Timer(token) if *token == self.timer => {
+                self.run(ctx, data);
+                ctx.set_handled();
+            }
+            _ => child.event(ctx, event, data, env),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AutomatonController, LifeRule, DEFAULT_GENERATION_RATE, MAX_GENERATION_RATE,
+        MIN_GENERATION_RATE,
+    };
+    use crate::{grid_canvas::GridCanvasData, testing::TestItem, GridIndex};
+
+    fn alive(data: &GridCanvasData<TestItem>) -> Vec<GridIndex> {
+        let mut cells: Vec<GridIndex> = data
+            .grid
+            .iter()
+            .filter(|(_, item)| **item == TestItem::Wall)
+            .map(|(pos, _)| pos)
+            .collect();
+        cells.sort_by_key(|pos| (pos.row, pos.col));
+        cells
+    }
+
+    #[test]
+    fn blinker_oscillates_and_undoes() {
+        let rule = LifeRule::conway(TestItem::Wall);
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        for col in 0..3 {
+            data.grid.insert(GridIndex::new(1, col), TestItem::Wall);
+        }
+        data.grid.insert(GridIndex::new(5, 5), TestItem::Pin);
+
+        let report = data.step_automaton(&rule);
+        assert!(report.is_complete());
+        assert_eq!(
+            alive(&data),
+            vec![
+                GridIndex::new(0, 1),
+                GridIndex::new(1, 1),
+                GridIndex::new(2, 1)
+            ]
+        );
+        assert_eq!(data.grid.get(&GridIndex::new(5, 5)), Some(&TestItem::Pin));
+
+        data.step_automaton(&rule);
+        assert_eq!(
+            alive(&data),
+            vec![
+                GridIndex::new(1, 0),
+                GridIndex::new(1, 1),
+                GridIndex::new(1, 2)
+            ]
+        );
+
+        data.seek(1);
+        assert_eq!(alive(&data)[0], GridIndex::new(0, 1));
+    }
+
+    #[test]
+    fn stays_on_the_board() {
+        let rule = LifeRule::conway(TestItem::Wall);
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.set_extent(Some(GridIndex::new(3, 3)));
+        for col in 0..3 {
+            data.grid.insert(GridIndex::new(0, col), TestItem::Wall);
+        }
+        data.step_automaton(&rule);
+        assert_eq!(
+            alive(&data),
+            vec![GridIndex::new(0, 1), GridIndex::new(1, 1)]
+        );
+    }
+
+    #[test]
+    fn read_only_grids_do_not_step() {
+        let rule = LifeRule::conway(TestItem::Wall);
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        for col in 0..3 {
+            data.grid.insert(GridIndex::new(1, col), TestItem::Wall);
+        }
+        data.set_read_only(true);
+        assert_eq!(data.step_automaton(&rule).applied, 0);
+        assert_eq!(alive(&data)[0], GridIndex::new(1, 0));
+        assert_eq!(data.save_data.position(), 0);
+    }
+
+    #[test]
+    fn generation_rates_stay_positive() {
+        let rate = |rate| {
+            AutomatonController::new(LifeRule::conway(TestItem::Wall))
+                .with_generation_rate(rate)
+                .generation_rate
+        };
+        assert_eq!(rate(12.0), 12.0);
+        assert_eq!(rate(0.0), DEFAULT_GENERATION_RATE);
+        assert_eq!(rate(-3.0), DEFAULT_GENERATION_RATE);
+        assert_eq!(rate(f64::NAN), DEFAULT_GENERATION_RATE);
+        assert_eq!(rate(f64::INFINITY), DEFAULT_GENERATION_RATE);
+        assert_eq!(rate(1e-300), MIN_GENERATION_RATE);
+        assert_eq!(rate(1e6), MAX_GENERATION_RATE);
+    }
+
+    #[test]
+    fn reads_rulestrings() {
+        let rule = LifeRule::from_rulestring("s23/B36", TestItem::Wall).unwrap();
+        assert_eq!(rule.birth, vec![3, 6]);
+        assert_eq!(rule.survival, vec![2, 3]);
+        assert_eq!(
+            LifeRule::from_rulestring("B3", TestItem::Wall),
+            Err(String::from("Invalid rule \"B3\", expected e.g. B3/S23"))
+        );
+        assert!(LifeRule::from_rulestring("B3x/S23", TestItem::Wall).is_err());
+    }
+}
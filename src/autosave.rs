@@ -0,0 +1,270 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::{
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use druid::{
+    widget::Controller, Data, Env, Event, EventCtx, LifeCycle, LifeCycleCtx, TimerToken, Widget,
+};
+
+use crate::{chunked_grid::ChunkedGrid, grid_canvas::GridCanvasData, GridItem};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// AutosaveController
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_SNAPSHOTS: usize = 5;
+const SNAPSHOT_PREFIX: &str = "autosave-";
+const SNAPSHOT_EXTENSION: &str = ".txt";
+
+/// Writes the grid of the widget it wraps to a snapshot file in a directory on a timer, so a
+/// crash loses at most one interval of work. Snapshots are numbered, the newest few are kept
+/// and the older ones deleted. The grid is turned into text by the encoder, e.g.
+/// [`GridCanvasData::to_csv`]. Nothing is encoded while the data is not
+/// [dirty](GridCanvasData::is_dirty) or the tape and the grid are where they were at the last
+/// snapshot. Read the newest one back with [`restore_latest`], or [`restore_latest_csv`] for CSV
+/// snapshots. A CSV snapshot only holds the cells, the regions, annotations, edges and wires are
+/// lost unless the encoder writes them too
+pub struct AutosaveController<T: GridItem + PartialEq + Debug> {
+    dir: PathBuf,
+    encode: Box<dyn Fn(&GridCanvasData<T>) -> String>,
+    interval: Duration,
+    // Number of snapshots kept on disk
+    snapshots: usize,
+    // Number of the next snapshot, continuing after those already in the directory
+    next: Option<u64>,
+    // Tape position and grid at the last snapshot written
+    last: Option<(usize, ChunkedGrid<T>)>,
+    timer: TimerToken,
+}
+
+impl<T: GridItem + PartialEq + Debug> AutosaveController<T>
+where
+    GridCanvasData<T>: Data,
+{
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        encode: impl Fn(&GridCanvasData<T>) -> String + 'static,
+    ) -> Self {
+        Self {
+            dir: dir.into(),
+            encode: Box::new(encode),
+            interval: DEFAULT_INTERVAL,
+            snapshots: DEFAULT_SNAPSHOTS,
+            next: None,
+            last: None,
+            timer: TimerToken::INVALID,
+        }
+    }
+
+    /// Snapshots in CSV, see [`GridCanvasData::to_csv`]
+    pub fn csv(dir: impl Into<PathBuf>, encode: impl Fn(&T) -> String + 'static) -> Self {
+        Self::new(dir, move |data: &GridCanvasData<T>| {
+            data.to_csv(',', &encode)
+        })
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_snapshots(mut self, snapshots: usize) -> Self {
+        self.snapshots = snapshots.max(1);
+        self
+    }
+
+    /// Writes a snapshot of `data` now. Returns the file written, or None if the data is not
+    /// dirty or has not changed since the last snapshot
+    pub fn save(&mut self, data: &GridCanvasData<T>) -> Result<Option<PathBuf>, String> {
+        let position = data.save_data.position();
+        let unchanged = self
+            .last
+            .as_ref()
+            .is_some_and(|(last_position, last_grid)| {
+                *last_position == position && last_grid.same(&data.grid)
+            });
+        if !data.is_dirty() || unchanged {
+            return Ok(None);
+        }
+        let text = (self.encode)(data);
+        fs::create_dir_all(&self.dir).map_err(|err| err.to_string())?;
+        let number = match self.next {
+            Some(number) => number,
+            None => snapshot_numbers(&self.dir)?
+                .last()
+                .map_or(0, |(number, _)| number + 1),
+        };
+        let path = self.dir.join(snapshot_name(number));
+        fs::write(&path, &text).map_err(|err| err.to_string())?;
+        self.next = Some(number + 1);
+        self.last = Some((position, data.grid.clone()));
+
+        let numbers = snapshot_numbers(&self.dir)?;
+        let expired = numbers.len().saturating_sub(self.snapshots);
+        for (_, old_path) in numbers.into_iter().take(expired) {
+            fs::remove_file(old_path).map_err(|err| err.to_string())?;
+        }
+        Ok(Some(path))
+    }
+}
+
+impl<T, W> Controller<GridCanvasData<T>, W> for AutosaveController<T>
+where
+    T: GridItem + PartialEq + Debug,
+    GridCanvasData<T>: Data,
+    W: Widget<GridCanvasData<T>>,
+{
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut GridCanvasData<T>,
+        env: &Env,
+    ) {
+        if let Event::Timer(token) = event {
+            if *token == self.timer {
+                if let Err(reason) = self.save(data) {
+                    log::warn!("Autosave to {} failed: {reason}", self.dir.display());
+                }
+                self.timer = ctx.request_timer(self.interval);
+                return;
+            }
+        }
+        child.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &GridCanvasData<T>,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            self.timer = ctx.request_timer(self.interval);
+        }
+        child.lifecycle(ctx, event, data, env);
+    }
+}
+
+/// Text of the newest snapshot an [`AutosaveController`] wrote to `dir`, or None if there is
+/// none. Read it back with the decoder matching the encoder, e.g. [`GridCanvasData::from_csv`]
+pub fn restore_latest(dir: impl AsRef<Path>) -> Result<Option<String>, String> {
+    let dir = dir.as_ref();
+    if !dir.exists() {
+        return Ok(None);
+    }
+    match snapshot_numbers(dir)?.pop() {
+        Some((_, path)) => fs::read_to_string(path)
+            .map(Some)
+            .map_err(|err| err.to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Replaces the cells of `data` with the newest snapshot an [`AutosaveController::csv`] wrote
+/// to `dir`, see [`GridCanvasData::replace_from_csv`]. Returns false if there is none
+pub fn restore_latest_csv<T: GridItem + PartialEq + Debug>(
+    dir: impl AsRef<Path>,
+    data: &mut GridCanvasData<T>,
+    decode: impl Fn(&str) -> Option<T>,
+) -> Result<bool, String>
+where
+    GridCanvasData<T>: Data,
+{
+    match restore_latest(dir)? {
+        Some(text) => data.replace_from_csv(&text, ',', decode).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+fn snapshot_name(number: u64) -> String {
+    format!("{SNAPSHOT_PREFIX}{number}{SNAPSHOT_EXTENSION}")
+}
+
+// Snapshots in the directory, oldest first
+fn snapshot_numbers(dir: &Path) -> Result<Vec<(u64, PathBuf)>, String> {
+    let mut numbers = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|err| err.to_string())? {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        let number = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(SNAPSHOT_PREFIX))
+            .and_then(|name| name.strip_suffix(SNAPSHOT_EXTENSION))
+            .and_then(|number| number.parse().ok());
+        if let Some(number) = number {
+            numbers.push((number, path));
+        }
+    }
+    numbers.sort();
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{restore_latest, restore_latest_csv, snapshot_name, AutosaveController};
+    use crate::{grid_canvas::GridCanvasData, testing::TestItem, GridIndex};
+
+    #[test]
+    fn keeps_the_newest_snapshots() {
+        let dir = std::env::temp_dir().join(format!("grid-autosave-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(restore_latest(&dir), Ok(None));
+
+        let mut autosave =
+            AutosaveController::csv(&dir, |_: &TestItem| String::from("W")).with_snapshots(2);
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        // Clean data is never written
+        assert_eq!(autosave.save(&data), Ok(None));
+        for col in 0..3 {
+            data.add_node(&GridIndex::new(0, col), TestItem::Wall)
+                .unwrap();
+            assert!(autosave.save(&data).unwrap().is_some());
+        }
+        // Nothing changed since the last snapshot
+        assert_eq!(autosave.save(&data), Ok(None));
+
+        assert!(!dir.join(snapshot_name(0)).exists());
+        assert!(dir.join(snapshot_name(1)).exists());
+        let latest = restore_latest(&dir).unwrap().unwrap();
+        let mut restored = GridCanvasData::new(TestItem::Wall);
+        assert_eq!(
+            restored.from_csv(&latest, ',', |_| Some(TestItem::Wall)),
+            Ok(3)
+        );
+        let mut restored = GridCanvasData::new(TestItem::Wall);
+        assert_eq!(
+            restore_latest_csv(&dir, &mut restored, |_| Some(TestItem::Wall)),
+            Ok(true)
+        );
+        assert!(restored.grid == data.grid);
+
+        // A new controller continues the numbering
+        let mut autosave = AutosaveController::csv(&dir, |_: &TestItem| String::from("W"));
+        data.add_node(&GridIndex::new(1, 0), TestItem::Wall)
+            .unwrap();
+        assert_eq!(autosave.save(&data), Ok(Some(dir.join(snapshot_name(3)))));
+        data.mark_saved();
+        data.add_node(&GridIndex::new(2, 0), TestItem::Wall)
+            .unwrap();
+        // Undoing back to the saved position leaves nothing to write
+        data.seek(4);
+        assert_eq!(autosave.save(&data), Ok(None));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,17 +3,17 @@ use std::hash::Hash;
 
 use druid::im::HashMap;
 use druid::{
-    BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx,
-    Point, Size, UpdateCtx, Widget, WidgetId, WidgetPod,
+    Affine, BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, Size, UpdateCtx, Widget, WidgetId, WidgetPod,
 };
 ///A container that allows for arbitrary layout.
 ///
 ///This widget allows you to lay widgets out at any point, and to allow that positioning to be dependent on the data.
-///This is facilitated by the [`CanvasLayout`] trait, and will most typically be used by wrapping your desired widgets
-///in a [`CanvasWrap`] wrapper.
+///Children are most typically made with the [`CanvasExt`] methods on your desired widgets, e.g.
+///`legend.pinned(|data| data.anchor)`, and added with [`Canvas::with_child`].
 ///
-///[`CanvasLayout`]: trait.CanvasLayout.html
-///[`CanvasWrap`]: struct.CanvasWrap.html
+///[`CanvasExt`]: trait.CanvasExt.html
+///[`Canvas::with_child`]: struct.Canvas.html#method.with_child
 
 //////////////////////////////////////////////////////////////////////////////////////////////////////
 ///
@@ -22,10 +22,13 @@ use druid::{
 /////////////////////////////////////////////////////////////////////////////////////////////////////
 #[allow(dead_code)]
 pub struct Canvas<T> {
-    pub children: Vec<Child<T>>,
+    children: Vec<Child<T>>,
+    // Indices of the children from the bottom to the top, see paint_order
+    order: Vec<usize>,
     pub position_map: HashMap<PointKey, usize>,
-    pub offset: Point,
-    pub scale: f64,
+    /// Maps positions of the widget the canvas is painted in to the coordinates its children are
+    /// laid out in, i.e. the inverse of the transform the canvas is painted with
+    pub transform: Affine,
 }
 
 impl<T: Data> Default for Canvas<T> {
@@ -38,20 +41,201 @@ impl<T> Canvas<T> {
     pub fn new() -> Self {
         Self {
             children: vec![],
+            order: vec![],
             position_map: HashMap::new(),
-            offset: Point::ZERO,
-            scale: 1.,
+            transform: Affine::IDENTITY,
         }
     }
+
+    pub fn children(&self) -> &[Child<T>] {
+        &self.children
+    }
+
+    /// The child at `index`. Its z-index is changed with [`set_z_index`](Self::set_z_index)
+    pub fn child_mut(&mut self, index: usize) -> Option<&mut Child<T>> {
+        self.children.get_mut(index)
+    }
+
+    /// Indices of the children from the bottom to the top, by z-index. Children with the same
+    /// z-index are in the order they were added or given their z-index
+    pub fn paint_order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// Removes the child at `index`, moving the last child into its place like
+    /// `Vec::swap_remove`. The moved child keeps its place in the paint order
+    pub fn remove(&mut self, index: usize) -> Child<T> {
+        let child = self.children.swap_remove(index);
+        let last = self.children.len();
+        self.order.retain(|other| *other != index);
+        for other in self.order.iter_mut().filter(|other| **other == last) {
+            *other = index;
+        }
+        self.position_map.retain(|_, other| *other != index);
+        for (_, other) in self.position_map.iter_mut() {
+            if *other == last {
+                *other = index;
+            }
+        }
+        child
+    }
+
+    pub fn clear(&mut self) {
+        self.children.clear();
+        self.order.clear();
+        self.position_map.clear();
+    }
+
+    /// Moves the child at `index` to the top of the children with z-index `z_index`, returning
+    /// false if there is no such child
+    pub fn set_z_index(&mut self, index: usize, z_index: i32) -> bool {
+        let Some(child) = self.children.get_mut(index) else {
+            return false;
+        };
+        child.set_z_index(z_index);
+        self.order.retain(|other| *other != index);
+        self.insert_in_order(index);
+        true
+    }
+
+    // Puts the child at `index` above the children with the same or a lower z-index
+    fn insert_in_order(&mut self, index: usize) {
+        let z_index = self.children[index].z_index();
+        let children = &self.children;
+        let at = self
+            .order
+            .partition_point(|other| children[*other].z_index() <= z_index);
+        self.order.insert(at, index);
+    }
+
+    /// Paints the child at `key` above every other child, returning false if there is none
+    pub fn bring_to_front(&mut self, key: &PointKey) -> bool {
+        let top = self.children.iter().map(Child::z_index).max();
+        self.restack(key, top.map_or(0, |top| top.saturating_add(1)))
+    }
+
+    /// Paints the child at `key` below every other child, returning false if there is none
+    pub fn send_to_back(&mut self, key: &PointKey) -> bool {
+        let bottom = self.children.iter().map(Child::z_index).min();
+        self.restack(key, bottom.map_or(0, |bottom| bottom.saturating_sub(1)))
+    }
+
+    /// True if every explicit child is in the position map under its position and every entry of
+    /// the map points at a child laid out there. Implicit children are mapped at the position of
+    /// their last layout
+    pub fn is_consistent(&self) -> bool {
+        let mapped = self
+            .children
+            .iter()
+            .enumerate()
+            .all(|(index, child)| match child {
+                Child::Explicit { position, .. } => {
+                    self.position_map.get(&PointKey::from(*position)) == Some(&index)
+                }
+                Child::Implicit { .. } => true,
+            });
+        mapped
+            && self
+                .position_map
+                .iter()
+                .all(|(key, index)| match self.children.get(*index) {
+                    Some(Child::Explicit { position, .. }) => PointKey::from(*position) == *key,
+                    Some(Child::Implicit { .. }) => true,
+                    None => false,
+                })
+    }
+
+    /// Panics in debug builds if the position map is out of sync with the children. Checked on
+    /// every update, `rebuild_position_map` brings the map back in sync
+    pub fn debug_assert_consistent(&self) {
+        debug_assert!(
+            self.is_consistent(),
+            "Position map of the canvas is out of sync with its children"
+        );
+    }
+
+    /// Maps every explicit child under its position again. Of several children at the same
+    /// position only the one added last is kept. Returns the number of children dropped
+    pub fn rebuild_position_map(&mut self) -> usize {
+        let mut last = HashMap::new();
+        for (index, child) in self.children.iter().enumerate() {
+            if let Child::Explicit { position, .. } = child {
+                last.insert(PointKey::from(*position), index);
+            }
+        }
+        let len = self.children.len();
+        let mut index = 0;
+        self.order.clear();
+        self.children.retain(|child| {
+            let keep = match child {
+                Child::Explicit { position, .. } => {
+                    last.get(&PointKey::from(*position)) == Some(&index)
+                }
+                Child::Implicit { .. } => true,
+            };
+            index += 1;
+            keep
+        });
+
+        self.position_map.clear();
+        for (index, child) in self.children.iter().enumerate() {
+            if let Child::Explicit { position, .. } = child {
+                self.position_map.insert(PointKey::from(*position), index);
+            }
+        }
+        for index in 0..self.children.len() {
+            self.insert_in_order(index);
+        }
+        len - self.children.len()
+    }
+
+    fn restack(&mut self, key: &PointKey, z_index: i32) -> bool {
+        match self.position_map.get(key) {
+            Some(index) => self.set_z_index(*index, z_index),
+            None => false,
+        }
+    }
+}
+
+impl<T: Data> Canvas<T> {
+    /// Builder-style variant of `add`
+    pub fn with_child(mut self, child: Child<T>) -> Self {
+        self.add(child);
+        self
+    }
+
+    /// Adds a child above the ones added before it. Once the canvas is running the caller has to
+    /// call `children_changed` on its context so the child gets laid out
+    pub fn add(&mut self, child: Child<T>) {
+        if let Child::Explicit { position, .. } = &child {
+            self.position_map
+                .insert(PointKey::from(*position), self.children.len());
+        }
+        self.children.push(child);
+        self.insert_in_order(self.children.len() - 1);
+    }
+
+    /// Adds a child whose position is computed from the data on every layout
+    pub fn add_implicit_child(
+        &mut self,
+        widget: impl Widget<T> + 'static,
+        closure: impl Fn(&T) -> Point + 'static,
+    ) {
+        self.add(Child::implicit(widget, closure));
+    }
 }
 
 impl<T: Data> Widget<T> for Canvas<T> {
-    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut T, _env: &Env) {
-        //we're letting their own filtering handle event filtering
-        //we may want to revisit that decision
-        // for child in self.children.iter_mut().filter_map(|x| x.widget_mut()) {
-        //     child.event(ctx, event, data, env);
-        // }
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        // The pods hit test the mouse against their layout rects, so mouse positions are moved
+        // into the space of the children first. Children painted last are on top and get the
+        // event first, once one of them handles it the others are skipped
+        let event = to_child_space(event, self.transform);
+        for index in self.order.iter().rev() {
+            if let Some(child) = self.children[*index].widget_mut() {
+                child.event(ctx, &event, data, env);
+            }
+        }
     }
 
     fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
@@ -65,6 +249,8 @@ impl<T: Data> Widget<T> for Canvas<T> {
     }
 
     fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &T, data: &T, env: &Env) {
+        // Children added on top of another explicit child hide it from the position map
+        self.debug_assert_consistent();
         for child in self.children.iter_mut().filter_map(|x| x.widget_mut()) {
             child.update(ctx, data, env);
         }
@@ -74,13 +260,17 @@ impl<T: Data> Widget<T> for Canvas<T> {
         let mut temp = HashMap::new();
 
         for (index, child) in self.children.iter_mut().enumerate() {
+            // Pan, zoom and rotation are left to the transform the canvas is painted with
             let (origin, _) = child.positioned_layout(ctx, data, env);
-            let absolute_origin = self.offset.to_vec2() / self.scale + origin.to_vec2();
-            child
-                .widget_mut()
-                .unwrap()
-                .set_origin(ctx, absolute_origin.to_point());
-            temp.insert(origin.into(), index);
+            child.widget_mut().unwrap().set_origin(ctx, origin);
+            // Explicit children keep their place in the map over implicit ones laid out at the
+            // same position
+            let key = PointKey::from(origin);
+            if let Child::Explicit { .. } = child {
+                temp.insert(key, index);
+            } else {
+                temp.entry(key).or_insert(index);
+            }
         }
 
         self.position_map = temp;
@@ -92,29 +282,90 @@ impl<T: Data> Widget<T> for Canvas<T> {
     fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
         //TODO: filter painting based on our extents? (don't draw widgets entirely outside our bounds?)
         //It's the main reason we keep and update the rect
-        for child in self.children.iter_mut() {
-            child.widget_mut().unwrap().paint(ctx, data, env);
+        for index in self.order.iter() {
+            self.children[*index]
+                .widget_mut()
+                .unwrap()
+                .paint(ctx, data, env);
         }
     }
 }
 
+// Copy of an event with the mouse position mapped by `transform`
+fn to_child_space(event: &Event, transform: Affine) -> Event {
+    let mut event = event.clone();
+    if let Event::MouseDown(mouse)
+    | Event::MouseUp(mouse)
+    | Event::MouseMove(mouse)
+    | Event::Wheel(mouse) = &mut event
+    {
+        mouse.pos = transform * mouse.pos;
+    }
+    event
+}
+
 //////////////////////////////////////////////////////////////////////////////////////////////////////
 ///
 /// Canvas Child Wrap
 ///
 /////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Children with a higher z-index are painted above the others and get events first
 pub enum Child<T> {
     Implicit {
         inner: WidgetPod<T, Box<dyn Widget<T>>>,
         closure: Box<dyn Fn(&T) -> Point>,
+        z_index: i32,
     },
     Explicit {
         inner: WidgetPod<T, Box<dyn Widget<T>>>,
         position: Point,
+        z_index: i32,
     },
 }
 
+impl<T> Child<T> {
+    pub fn z_index(&self) -> i32 {
+        match self {
+            Child::Explicit { z_index, .. } | Child::Implicit { z_index, .. } => *z_index,
+        }
+    }
+
+    // Children on a canvas are restacked through Canvas::set_z_index, which keeps the paint order
+    fn set_z_index(&mut self, value: i32) {
+        match self {
+            Child::Explicit { z_index, .. } | Child::Implicit { z_index, .. } => *z_index = value,
+        }
+    }
+}
+
 impl<T: Data> Child<T> {
+    /// Child laid out at the point `closure` returns for the data
+    pub fn implicit(
+        widget: impl Widget<T> + 'static,
+        closure: impl Fn(&T) -> Point + 'static,
+    ) -> Self {
+        Child::Implicit {
+            inner: WidgetPod::new(Box::new(widget)),
+            closure: Box::new(closure),
+            z_index: 0,
+        }
+    }
+
+    /// Child laid out at a fixed point
+    pub fn explicit(widget: impl Widget<T> + 'static, position: Point) -> Self {
+        Child::Explicit {
+            inner: WidgetPod::new(Box::new(widget)),
+            position,
+            z_index: 0,
+        }
+    }
+
+    /// Builder-style variant of `set_z_index`
+    pub fn with_z_index(mut self, value: i32) -> Self {
+        self.set_z_index(value);
+        self
+    }
+
     fn widget_mut(&mut self) -> Option<&mut WidgetPod<T, Box<dyn Widget<T>>>> {
         match self {
             Child::Explicit { inner, .. } | Child::Implicit { inner, .. } => Some(inner),
@@ -130,7 +381,9 @@ impl<T: Data> Child<T> {
 
     fn positioned_layout(&mut self, ctx: &mut LayoutCtx, data: &T, env: &Env) -> (Point, Size) {
         match self {
-            Child::Explicit { inner, position } => {
+            Child::Explicit {
+                inner, position, ..
+            } => {
                 let size = inner.layout(
                     ctx,
                     &BoxConstraints::new(Size::ZERO, Size::new(f64::INFINITY, f64::INFINITY)),
@@ -139,7 +392,7 @@ impl<T: Data> Child<T> {
                 );
                 (*position, size)
             }
-            Child::Implicit { inner, closure } => {
+            Child::Implicit { inner, closure, .. } => {
                 let desired_origin = (closure)(data);
                 let desired_size = inner.layout(
                     ctx,
@@ -156,23 +409,60 @@ impl<T: Data> Child<T> {
     }
 }
 
+///////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Canvas Extension
+///
+///////////////////////////////////////////////////////////////////////////////////////////////
+/// Turns any widget into a canvas child
+pub trait CanvasExt<T: Data>: Widget<T> + Sized + 'static {
+    /// Floats the widget at the point `closure` returns for the data, e.g. a legend that follows
+    /// a data-dependent anchor
+    fn pinned(self, closure: impl Fn(&T) -> Point + 'static) -> Child<T> {
+        Child::implicit(self, closure)
+    }
+
+    /// Places the widget at a fixed point of the canvas
+    fn placed_at(self, position: Point) -> Child<T> {
+        Child::explicit(self, position)
+    }
+}
+
+impl<T: Data, W: Widget<T> + 'static> CanvasExt<T> for W {}
+
 ///////////////////////////////////////////////////////////////////////////////////////////////
 ///
 /// PointKey
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////
+// Largest coordinate a PointKey holds exactly, beyond it neighbouring f64 values are more than one
+// pixel apart and positions can no longer be told apart
+pub const MAX_KEY_COORDINATE: f64 = 9_007_199_254_740_992.0;
+
 #[derive(Debug, PartialEq, Hash, Eq, Clone)]
 pub struct PointKey {
     /// The x coordinate.
-    pub x: i32,
+    pub x: i64,
     /// The y coordinate.
-    pub y: i32,
+    pub y: i64,
 }
 
 impl PointKey {
-    pub fn new(x: i32, y: i32) -> Self {
+    pub fn new(x: i64, y: i64) -> Self {
         Self { x, y }
     }
+
+    /// Key of a point rounded to whole pixels, or None if a coordinate is not finite or too large
+    /// to be told apart from its neighbours
+    pub fn checked_from(value: Point) -> Option<Self> {
+        let coordinate = |value: f64| {
+            (value.is_finite() && value.abs() <= MAX_KEY_COORDINATE).then(|| value.round() as i64)
+        };
+        Some(Self {
+            x: coordinate(value.x)?,
+            y: coordinate(value.y)?,
+        })
+    }
 }
 
 impl Default for PointKey {
@@ -181,11 +471,15 @@ impl Default for PointKey {
     }
 }
 
+// Points out of range are clamped to the largest key and NaN ends up at 0, see
+// PointKey::checked_from to catch them instead
 impl From<Point> for PointKey {
     fn from(value: Point) -> Self {
+        let coordinate =
+            |value: f64| value.clamp(-MAX_KEY_COORDINATE, MAX_KEY_COORDINATE).round() as i64;
         Self {
-            x: value.x as i32,
-            y: value.y as i32,
+            x: coordinate(value.x),
+            y: coordinate(value.y),
         }
     }
 }
@@ -193,8 +487,158 @@ impl From<Point> for PointKey {
 impl Into<Point> for PointKey {
     fn into(self) -> Point {
         Point {
-            x: self.x.into(),
-            y: self.y.into(),
+            x: self.x as f64,
+            y: self.y as f64,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use druid::{
+        widget::SizedBox, Affine, Event, Modifiers, MouseButton, MouseButtons, MouseEvent, Point,
+        Vec2,
+    };
+
+    use super::{to_child_space, Canvas, CanvasExt, Child, PointKey, MAX_KEY_COORDINATE};
+
+    #[test]
+    fn point_keys_round_trip() {
+        for point in [
+            Point::new(0.0, 0.0),
+            Point::new(-15.0, 30.0),
+            Point::new(1.5e10, -1.5e10),
+            Point::new(MAX_KEY_COORDINATE, -MAX_KEY_COORDINATE),
+        ] {
+            let key = PointKey::checked_from(point).unwrap();
+            assert_eq!(Into::<Point>::into(key.clone()), point);
+            assert_eq!(PointKey::from(point), key);
+        }
+        // Positions past the range of an i32 stay distinct
+        assert_ne!(
+            PointKey::from(Point::new(3.0e9, 0.0)),
+            PointKey::from(Point::new(4.0e9, 0.0))
+        );
+    }
+
+    #[test]
+    fn rejects_points_out_of_range() {
+        assert_eq!(PointKey::checked_from(Point::new(f64::NAN, 0.0)), None);
+        assert_eq!(PointKey::checked_from(Point::new(0.0, f64::INFINITY)), None);
+        assert_eq!(PointKey::checked_from(Point::new(1e300, 0.0)), None);
+        assert_eq!(
+            PointKey::from(Point::new(f64::NAN, 0.0)),
+            PointKey::new(0, 0)
+        );
+        assert_eq!(
+            PointKey::from(Point::new(f64::NEG_INFINITY, 0.0)),
+            PointKey::new(-MAX_KEY_COORDINATE as i64, 0)
+        );
+    }
+
+    #[test]
+    fn maps_mouse_events_to_the_children() {
+        let mouse = MouseEvent {
+            pos: Point::new(40.0, 10.0),
+            window_pos: Point::new(140.0, 110.0),
+            buttons: MouseButtons::new(),
+            mods: Modifiers::empty(),
+            count: 1,
+            focus: false,
+            button: MouseButton::Left,
+            wheel_delta: Vec2::ZERO,
+        };
+        // Zoomed in twice over
+        let transform = Affine::scale(2.0).inverse();
+        match to_child_space(&Event::MouseDown(mouse.clone()), transform) {
+            Event::MouseDown(moved) => {
+                assert_eq!(moved.pos, Point::new(20.0, 5.0));
+                assert_eq!(moved.window_pos, mouse.window_pos);
+            }
+            event => panic!("unexpected {event:?}"),
+        }
+        assert!(matches!(
+            to_child_space(&Event::WindowConnected, transform),
+            Event::WindowConnected
+        ));
+    }
+
+    #[test]
+    fn restacks_children() {
+        let mut canvas: Canvas<()> = Canvas::new();
+        for x in 0..3 {
+            canvas.add(SizedBox::empty().placed_at(Point::new(x as f64 * 10.0, 0.0)));
+        }
+        assert_eq!(canvas.paint_order(), [0, 1, 2]);
+        assert!(canvas.bring_to_front(&PointKey::new(0, 0)));
+        assert!(canvas.send_to_back(&PointKey::new(20, 0)));
+        assert_eq!(canvas.paint_order(), [2, 1, 0]);
+        assert!(!canvas.bring_to_front(&PointKey::new(5, 0)));
+        assert!(canvas.set_z_index(1, -1));
+        assert_eq!(canvas.paint_order(), [2, 1, 0]);
+        assert!(!canvas.set_z_index(3, 0));
+    }
+
+    #[test]
+    fn removing_keeps_the_paint_order() {
+        let mut canvas: Canvas<()> = Canvas::new();
+        for x in 0..4 {
+            canvas.add(SizedBox::empty().placed_at(Point::new(x as f64 * 10.0, 0.0)));
+        }
+        canvas.set_z_index(0, 0);
+        assert_eq!(canvas.paint_order(), [1, 2, 3, 0]);
+        // The last child moves to index 1 and stays between the other two
+        canvas.remove(1);
+        assert_eq!(canvas.paint_order(), [2, 1, 0]);
+        assert_eq!(canvas.position_map.get(&PointKey::new(30, 0)), Some(&1));
+        assert_eq!(canvas.position_map.get(&PointKey::new(10, 0)), None);
+        canvas.remove(2);
+        assert_eq!(canvas.paint_order(), [1, 0]);
+        canvas.clear();
+        assert!(canvas.paint_order().is_empty() && canvas.children().is_empty());
+    }
+
+    #[test]
+    fn adds_implicit_children() {
+        let mut canvas: Canvas<Point> = Canvas::new().with_child(
+            SizedBox::empty()
+                .placed_at(Point::new(5.0, 5.0))
+                .with_z_index(2),
+        );
+        canvas.add_implicit_child(SizedBox::empty(), |anchor: &Point| {
+            *anchor + Vec2::new(1.0, 0.0)
+        });
+        assert_eq!(canvas.children().len(), 2);
+        assert_eq!(canvas.paint_order(), [1, 0]);
+        match &canvas.children()[1] {
+            Child::Implicit { closure, .. } => {
+                assert_eq!(closure(&Point::new(2.0, 3.0)), Point::new(3.0, 3.0))
+            }
+            Child::Explicit { .. } => panic!("expected an implicit child"),
+        }
+    }
+
+    #[test]
+    fn rebuilds_the_position_map() {
+        let mut canvas: Canvas<()> = Canvas::new();
+        for x in [0.0, 10.0, 20.0, 10.0] {
+            canvas.add(SizedBox::empty().placed_at(Point::new(x, 0.0)));
+        }
+        canvas.add_implicit_child(SizedBox::empty(), |_| Point::ZERO);
+        // The second child at (10, 0) took the place of the first one
+        assert!(!canvas.is_consistent());
+
+        assert_eq!(canvas.rebuild_position_map(), 1);
+        assert!(canvas.is_consistent());
+        assert_eq!(canvas.children().len(), 4);
+        assert_eq!(canvas.position_map.get(&PointKey::new(10, 0)), Some(&2));
+
+        canvas.position_map.insert(PointKey::new(20, 0), 0);
+        assert!(!canvas.is_consistent());
+        canvas.rebuild_position_map();
+        canvas.position_map.remove(&PointKey::new(0, 0));
+        assert!(!canvas.is_consistent());
+        assert_eq!(canvas.rebuild_position_map(), 0);
+        assert!(canvas.is_consistent());
+    }
+}
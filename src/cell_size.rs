@@ -0,0 +1,128 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use druid::{
+    lens,
+    widget::{CrossAxisAlignment, Flex, Label, MainAxisAlignment, Slider},
+    BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx,
+    Point, Size, UpdateCtx, Widget, WidgetExt, WidgetPod,
+};
+
+use crate::snapping::GridSnapDataAccess;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// CellSizeController Widget
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+// Whole pixels keep the children aligned with the cells
+const DEFAULT_STEP: f64 = 1.0;
+
+/// Slider and label for the cell size of a grid. Sizes stay within the range of the slider and
+/// on its steps, and the grid is rescaled around its origin, or around the point of the grid
+/// widget given with `with_anchor`, see [`GridSnapDataAccess::rescale_cells`]
+pub struct CellSizeController<T> {
+    min: f64,
+    max: f64,
+    step: f64,
+    anchor: Option<Point>,
+    inner: WidgetPod<T, Box<dyn Widget<T>>>,
+}
+
+impl<T: Data + GridSnapDataAccess> CellSizeController<T> {
+    pub fn new(min: f64, max: f64) -> Self {
+        let min = min.max(f64::EPSILON);
+        Self::build(min, max.max(min), DEFAULT_STEP, None)
+    }
+
+    pub fn with_step(self, step: f64) -> Self {
+        Self::build(self.min, self.max, step.max(f64::EPSILON), self.anchor)
+    }
+
+    /// Keeps the point of the grid drawn at `anchor`, a position of the grid widget, in place
+    /// while rescaling, e.g. the centre of the view
+    pub fn with_anchor(self, anchor: Point) -> Self {
+        Self::build(self.min, self.max, self.step, Some(anchor))
+    }
+
+    fn build(min: f64, max: f64, step: f64, anchor: Option<Point>) -> Self {
+        let cell_size = lens::Map::new(
+            |data: &T| data.get_cell_size(),
+            move |data: &mut T, size: f64| {
+                let anchor = anchor.unwrap_or_else(|| data.get_offset());
+                data.rescale_cells(snap_cell_size(size, min, max, step), anchor);
+            },
+        );
+        let decimals = if step.fract() == 0.0 { 0 } else { 2 };
+
+        let inner = Flex::row()
+            .with_child(Label::new(move |data: &T, _: &Env| {
+                format!("Cell Size: {:.*}", decimals, data.get_cell_size())
+            }))
+            .with_child(
+                Slider::new()
+                    .with_range(min, max)
+                    .with_step(step)
+                    .lens(cell_size),
+            )
+            .main_axis_alignment(MainAxisAlignment::SpaceBetween)
+            .cross_axis_alignment(CrossAxisAlignment::Center)
+            .must_fill_main_axis(true);
+
+        Self {
+            min,
+            max,
+            step,
+            anchor,
+            inner: WidgetPod::new(Box::new(inner)),
+        }
+    }
+}
+
+// Size within `min` and `max` on the closest step from `min`
+fn snap_cell_size(size: f64, min: f64, max: f64, step: f64) -> f64 {
+    let steps = ((size.clamp(min, max) - min) / step).round();
+    (min + steps * step).min(max)
+}
+
+impl<T: Data + GridSnapDataAccess> Widget<T> for CellSizeController<T> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        self.inner.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        self.inner.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &T, data: &T, env: &Env) {
+        self.inner.update(ctx, data, env);
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        let size = self.inner.layout(ctx, bc, data, env);
+        self.inner.set_origin(ctx, Point::ORIGIN);
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        self.inner.paint(ctx, data, env);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::snap_cell_size;
+
+    #[test]
+    fn keeps_sizes_on_the_slider() {
+        assert_eq!(snap_cell_size(12.4, 5.0, 40.0, 1.0), 12.0);
+        assert_eq!(snap_cell_size(12.6, 5.0, 40.0, 1.0), 13.0);
+        assert_eq!(snap_cell_size(1.0, 5.0, 40.0, 1.0), 5.0);
+        assert_eq!(snap_cell_size(100.0, 5.0, 40.0, 1.0), 40.0);
+        // Steps count from the low end of the range and never pass the high end
+        assert_eq!(snap_cell_size(11.0, 5.0, 12.0, 4.0), 12.0);
+        assert_eq!(snap_cell_size(8.0, 5.0, 40.0, 4.0), 9.0);
+    }
+}
@@ -0,0 +1,625 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::{fmt::Debug, hash::Hash, sync::Arc};
+
+use druid::{
+    im::{HashMap, OrdMap},
+    Data,
+};
+
+use crate::{
+    utils::cassetta::{CassettePlayer, TapeItem},
+    GridBounds, GridIndex,
+};
+
+// Side of a chunk in cells
+pub const CHUNK_SIZE: isize = 32;
+const CHUNK_CELLS: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Chunk
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+// Dense block of cells. The cells are shared between clones until one of them is modified
+#[derive(Clone, PartialEq)]
+struct Chunk<T> {
+    cells: Arc<Vec<Option<T>>>,
+    len: usize,
+}
+
+impl<T: Clone> Chunk<T> {
+    fn new() -> Self {
+        Self {
+            cells: Arc::new(vec![None; CHUNK_CELLS]),
+            len: 0,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// ChunkedGrid
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Sparse grid storage made of `CHUNK_SIZE`×`CHUNK_SIZE` dense chunks keyed by chunk coordinate.
+/// Chunks are dropped as soon as they become empty, so iterating over an area only visits the
+/// chunks that hold items. The number of items in every row and column is kept alongside, so the
+/// bounds of the items are known without visiting them, and so is the number of cells holding
+/// every distinct item.
+#[derive(Clone, PartialEq)]
+pub struct ChunkedGrid<T> {
+    chunks: HashMap<GridIndex, Chunk<T>>,
+    len: usize,
+    row_counts: OrdMap<isize, usize>,
+    col_counts: OrdMap<isize, usize>,
+    item_counts: HashMap<T, usize>,
+}
+
+impl<T: Clone + Hash + Eq> ChunkedGrid<T> {
+    // Constructors
+    pub fn new() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            len: 0,
+            row_counts: OrdMap::new(),
+            col_counts: OrdMap::new(),
+            item_counts: HashMap::new(),
+        }
+    }
+
+    // Queries
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, pos: &GridIndex) -> Option<&T> {
+        let (chunk_pos, index) = locate(pos);
+        self.chunks
+            .get(&chunk_pos)
+            .and_then(|chunk| chunk.cells[index].as_ref())
+    }
+
+    pub fn contains_key(&self, pos: &GridIndex) -> bool {
+        self.get(pos).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (GridIndex, &T)> {
+        self.chunks
+            .iter()
+            .flat_map(|(chunk_pos, chunk)| chunk_cells(*chunk_pos, chunk))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = GridIndex> + '_ {
+        self.iter().map(|(pos, _)| pos)
+    }
+
+    /// Items inside the area spanned by two corners (inclusive). Chunks outside the area and
+    /// empty chunks are skipped entirely. The chunks of the area are looked up one by one unless
+    /// the area spans more chunks than the grid holds, then the held chunks are filtered instead
+    pub fn iter_area(
+        &self,
+        from: GridIndex,
+        to: GridIndex,
+    ) -> impl Iterator<Item = (GridIndex, &T)> {
+        let (min, max) = (
+            GridIndex::new(from.row.min(to.row), from.col.min(to.col)),
+            GridIndex::new(from.row.max(to.row), from.col.max(to.col)),
+        );
+        let (min_chunk, _) = locate(&min);
+        let (max_chunk, _) = locate(&max);
+        let span = |from: isize, to: isize| (to as i128 - from as i128 + 1) as u128;
+        let by_lookup = span(min_chunk.row, max_chunk.row)
+            .checked_mul(span(min_chunk.col, max_chunk.col))
+            .map_or(false, |chunks| chunks <= self.chunks.len() as u128);
+
+        let rows = if by_lookup {
+            min_chunk.row..=max_chunk.row
+        } else {
+            1..=0
+        };
+        let looked_up = rows.flat_map(move |row| {
+            (min_chunk.col..=max_chunk.col).filter_map(move |col| {
+                let chunk_pos = GridIndex::new(row, col);
+                self.chunks.get(&chunk_pos).map(|chunk| (chunk_pos, chunk))
+            })
+        });
+        let filtered = self
+            .chunks
+            .iter()
+            .take(if by_lookup { 0 } else { usize::MAX })
+            .filter(move |(chunk_pos, _)| {
+                (min_chunk.row..=max_chunk.row).contains(&chunk_pos.row)
+                    && (min_chunk.col..=max_chunk.col).contains(&chunk_pos.col)
+            })
+            .map(|(chunk_pos, chunk)| (*chunk_pos, chunk));
+
+        looked_up
+            .chain(filtered)
+            .flat_map(|(chunk_pos, chunk)| chunk_cells(chunk_pos, chunk))
+            .filter(move |(pos, _)| {
+                (min.row..=max.row).contains(&pos.row) && (min.col..=max.col).contains(&pos.col)
+            })
+    }
+
+    /// Top left cell of every chunk that holds at least one item
+    pub fn chunk_origins(&self) -> impl Iterator<Item = GridIndex> + '_ {
+        self.chunks
+            .keys()
+            .map(|chunk_pos| GridIndex::new(chunk_pos.row * CHUNK_SIZE, chunk_pos.col * CHUNK_SIZE))
+    }
+
+    /// Smallest rectangle holding every item, or None for an empty grid
+    pub fn bounds(&self) -> Option<GridBounds> {
+        let (min_row, _) = self.row_counts.get_min()?;
+        let (max_row, _) = self.row_counts.get_max()?;
+        let (min_col, _) = self.col_counts.get_min()?;
+        let (max_col, _) = self.col_counts.get_max()?;
+        Some(GridBounds {
+            min: GridIndex::new(*min_row, *min_col),
+            max: GridIndex::new(*max_row, *max_col),
+        })
+    }
+
+    /// Every distinct item on the grid with the number of cells holding it
+    pub fn item_counts(&self) -> impl Iterator<Item = (&T, usize)> {
+        self.item_counts.iter().map(|(item, count)| (item, *count))
+    }
+
+    pub fn to_hash_map(&self) -> HashMap<GridIndex, T> {
+        self.iter().map(|(pos, item)| (pos, item.clone())).collect()
+    }
+
+    /// Operations that turn `old` into this grid, one per cell that differs, in row-major order.
+    /// Chunks the two grids still share are skipped without visiting their cells
+    pub fn changes_since(&self, old: &Self) -> Vec<TapeItem<GridIndex, T>> {
+        let cell = |chunk: Option<&Chunk<T>>, index: usize| {
+            chunk.and_then(|chunk| chunk.cells[index].as_ref())
+        };
+        let added_chunks = self
+            .chunks
+            .keys()
+            .filter(|chunk_pos| !old.chunks.contains_key(chunk_pos));
+        let mut changes = Vec::new();
+        for chunk_pos in old.chunks.keys().chain(added_chunks) {
+            let (old_chunk, chunk) = (old.chunks.get(chunk_pos), self.chunks.get(chunk_pos));
+            if let (Some(old_chunk), Some(chunk)) = (old_chunk, chunk) {
+                if Arc::ptr_eq(&old_chunk.cells, &chunk.cells) {
+                    continue;
+                }
+            }
+            for index in 0..CHUNK_CELLS {
+                let pos = cell_pos(*chunk_pos, index);
+                let change = match (cell(old_chunk, index), cell(chunk, index)) {
+                    (previous_item, Some(item)) if previous_item != Some(item) => {
+                        TapeItem::Add(pos, item.clone(), previous_item.cloned())
+                    }
+                    (Some(previous_item), None) => TapeItem::Remove(pos, previous_item.clone()),
+                    _ => continue,
+                };
+                changes.push((pos, change));
+            }
+        }
+        changes.sort_by_key(|(pos, _)| (pos.row, pos.col));
+        changes.into_iter().map(|(_, change)| change).collect()
+    }
+
+    // Manipulators
+    pub fn insert(&mut self, pos: GridIndex, item: T) -> Option<T> {
+        let (chunk_pos, index) = locate(&pos);
+        let chunk = self.chunks.entry(chunk_pos).or_insert_with(Chunk::new);
+        let previous_item = Arc::make_mut(&mut chunk.cells)[index].replace(item.clone());
+        match &previous_item {
+            Some(previous_item) => uncount_item(&mut self.item_counts, previous_item),
+            None => {
+                chunk.len += 1;
+                self.len += 1;
+                *self.row_counts.entry(pos.row).or_insert(0) += 1;
+                *self.col_counts.entry(pos.col).or_insert(0) += 1;
+            }
+        }
+        *self.item_counts.entry(item).or_insert(0) += 1;
+        previous_item
+    }
+
+    pub fn remove(&mut self, pos: &GridIndex) -> Option<T> {
+        let (chunk_pos, index) = locate(pos);
+        let chunk = self.chunks.get_mut(&chunk_pos)?;
+        chunk.cells[index].as_ref()?;
+        let previous_item = Arc::make_mut(&mut chunk.cells)[index].take();
+        chunk.len -= 1;
+        self.len -= 1;
+        if chunk.len == 0 {
+            self.chunks.remove(&chunk_pos);
+        }
+        uncount(&mut self.row_counts, pos.row);
+        uncount(&mut self.col_counts, pos.col);
+        if let Some(previous_item) = &previous_item {
+            uncount_item(&mut self.item_counts, previous_item);
+        }
+        previous_item
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&GridIndex, &T) -> bool) {
+        let removed: Vec<GridIndex> = self
+            .iter()
+            .filter(|(pos, item)| !f(pos, item))
+            .map(|(pos, _)| pos)
+            .collect();
+        for pos in removed.iter() {
+            self.remove(pos);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.len = 0;
+        self.row_counts.clear();
+        self.col_counts.clear();
+        self.item_counts.clear();
+    }
+}
+
+// Takes an item off the count of a row or column, dropping the row or column once it is empty
+fn uncount(counts: &mut OrdMap<isize, usize>, line: isize) {
+    if let Some(count) = counts.get_mut(&line) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(&line);
+        }
+    }
+}
+
+// Takes a cell off the count of an item, dropping the item once no cell holds it
+fn uncount_item<T: Clone + Hash + Eq>(counts: &mut HashMap<T, usize>, item: &T) {
+    if let Some(count) = counts.get_mut(item) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(item);
+        }
+    }
+}
+
+// Chunk coordinate of a cell and the index of the cell within the chunk
+fn locate(pos: &GridIndex) -> (GridIndex, usize) {
+    let chunk_pos = GridIndex::new(
+        pos.row.div_euclid(CHUNK_SIZE),
+        pos.col.div_euclid(CHUNK_SIZE),
+    );
+    let index = pos.row.rem_euclid(CHUNK_SIZE) * CHUNK_SIZE + pos.col.rem_euclid(CHUNK_SIZE);
+    (chunk_pos, index as usize)
+}
+
+fn chunk_cells<T>(chunk_pos: GridIndex, chunk: &Chunk<T>) -> impl Iterator<Item = (GridIndex, &T)> {
+    chunk
+        .cells
+        .iter()
+        .enumerate()
+        .filter_map(move |(index, cell)| {
+            cell.as_ref().map(|item| (cell_pos(chunk_pos, index), item))
+        })
+}
+
+// Cell at `index` of the chunk at `chunk_pos`, the reverse of `locate`
+fn cell_pos(chunk_pos: GridIndex, index: usize) -> GridIndex {
+    let index = index as isize;
+    GridIndex::new(
+        chunk_pos.row * CHUNK_SIZE + index / CHUNK_SIZE,
+        chunk_pos.col * CHUNK_SIZE + index % CHUNK_SIZE,
+    )
+}
+
+impl<T: Clone + Hash + Eq> Default for ChunkedGrid<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone + Hash + Eq + 'static> Data for ChunkedGrid<T> {
+    fn same(&self, other: &Self) -> bool {
+        self.len == other.len && self.chunks.ptr_eq(&other.chunks)
+    }
+}
+
+impl<T: Clone + Hash + Eq + Debug> Debug for ChunkedGrid<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Clone + Hash + Eq> FromIterator<(GridIndex, T)> for ChunkedGrid<T> {
+    fn from_iter<I: IntoIterator<Item = (GridIndex, T)>>(iter: I) -> Self {
+        let mut grid = Self::new();
+        for (pos, item) in iter {
+            grid.insert(pos, item);
+        }
+        grid
+    }
+}
+
+impl<T: Clone + Hash + Eq> CassettePlayer<GridIndex, T> for ChunkedGrid<T> {
+    fn advance(&mut self, item: TapeItem<GridIndex, T>) {
+        match item {
+            TapeItem::Add(pos, current_item, _) => {
+                self.insert(pos, current_item);
+            }
+            TapeItem::Remove(pos, _) => {
+                self.remove(&pos);
+            }
+            TapeItem::Move(from_pos, to_pos, item) => {
+                self.remove(&from_pos);
+                self.insert(to_pos, item);
+            }
+            TapeItem::BatchAdd(items) => {
+                for (pos, (current_item, _)) in items {
+                    self.insert(pos, current_item);
+                }
+            }
+            TapeItem::BatchRemove(items) => {
+                for (pos, _) in items {
+                    self.remove(&pos);
+                }
+            }
+            TapeItem::Group(items) => {
+                for item in items {
+                    self.advance(item);
+                }
+            }
+        }
+    }
+
+    fn rewind(&mut self, item: TapeItem<GridIndex, T>) {
+        match item {
+            TapeItem::Add(pos, _, previous_item) => {
+                self.remove(&pos);
+                if let Some(previous_item) = previous_item {
+                    self.insert(pos, previous_item);
+                }
+            }
+            TapeItem::Remove(pos, previous_item) => {
+                self.insert(pos, previous_item);
+            }
+            TapeItem::Move(from_pos, to_pos, item) => {
+                self.remove(&to_pos);
+                self.insert(from_pos, item);
+            }
+            TapeItem::BatchAdd(items) => {
+                for (pos, (_, previous_item)) in items {
+                    self.remove(&pos);
+                    if let Some(previous_item) = previous_item {
+                        self.insert(pos, previous_item);
+                    }
+                }
+            }
+            TapeItem::BatchRemove(items) => {
+                for (pos, previous_item) in items {
+                    self.insert(pos, previous_item);
+                }
+            }
+            TapeItem::Group(items) => {
+                for item in items.into_iter().rev() {
+                    self.rewind(item);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChunkedGrid, CHUNK_SIZE};
+    use crate::{
+        utils::cassetta::{CassettePlayer, TapeItem},
+        GridBounds, GridIndex,
+    };
+
+    #[test]
+    fn insert_and_remove_across_chunks() {
+        let mut grid = ChunkedGrid::new();
+        let cells = [
+            GridIndex::new(0, 0),
+            GridIndex::new(-1, -1),
+            GridIndex::new(CHUNK_SIZE, 3),
+            GridIndex::new(-CHUNK_SIZE - 1, CHUNK_SIZE * 4),
+        ];
+        for (value, pos) in cells.iter().enumerate() {
+            assert_eq!(grid.insert(*pos, value), None);
+        }
+        assert_eq!(grid.insert(cells[0], 9), Some(0));
+        assert_eq!(grid.len(), 4);
+        assert_eq!(grid.chunk_origins().count(), 4);
+        for (value, pos) in cells.iter().enumerate().skip(1) {
+            assert_eq!(grid.get(pos), Some(&value));
+        }
+
+        let mut keys: Vec<GridIndex> = grid.keys().collect();
+        keys.sort_by_key(|pos| (pos.row, pos.col));
+        let mut expected = cells.to_vec();
+        expected.sort_by_key(|pos| (pos.row, pos.col));
+        assert_eq!(keys, expected);
+
+        assert_eq!(
+            grid.bounds(),
+            Some(GridBounds::new(
+                GridIndex::new(-CHUNK_SIZE - 1, -1),
+                GridIndex::new(CHUNK_SIZE, CHUNK_SIZE * 4)
+            ))
+        );
+
+        assert_eq!(grid.remove(&cells[1]), Some(1));
+        assert_eq!(grid.remove(&cells[1]), None);
+        assert_eq!(grid.len(), 3);
+        assert_eq!(grid.chunk_origins().count(), 3);
+    }
+
+    #[test]
+    fn bounds_follow_edits() {
+        let mut grid = ChunkedGrid::new();
+        assert_eq!(grid.bounds(), None);
+        for pos in [
+            GridIndex::new(-3, 2),
+            GridIndex::new(4, -5),
+            GridIndex::new(4, 7),
+            GridIndex::new(0, 0),
+        ] {
+            grid.insert(pos, 0);
+        }
+        let bounds = grid.bounds().unwrap();
+        assert_eq!(
+            bounds,
+            GridBounds::new(GridIndex::new(-3, -5), GridIndex::new(4, 7))
+        );
+        assert_eq!(bounds.extent(), GridIndex::new(8, 13));
+        assert_eq!(bounds.center(), GridIndex::new(0, 1));
+
+        // Row 4 keeps an item until both are gone
+        grid.remove(&GridIndex::new(4, -5));
+        assert_eq!(
+            grid.bounds(),
+            Some(GridBounds::new(GridIndex::new(-3, 0), GridIndex::new(4, 7)))
+        );
+        grid.remove(&GridIndex::new(4, 7));
+        grid.remove(&GridIndex::new(-3, 2));
+        assert_eq!(
+            grid.bounds(),
+            Some(GridBounds::new(GridIndex::new(0, 0), GridIndex::new(0, 0)))
+        );
+        grid.clear();
+        assert_eq!(grid.bounds(), None);
+    }
+
+    #[test]
+    fn counts_cells_per_item() {
+        let mut grid = ChunkedGrid::new();
+        grid.insert(GridIndex::new(0, 0), 'a');
+        grid.insert(GridIndex::new(0, 1), 'a');
+        grid.insert(GridIndex::new(CHUNK_SIZE, 0), 'b');
+        let counts = |grid: &ChunkedGrid<char>| {
+            let mut counts: Vec<(char, usize)> = grid
+                .item_counts()
+                .map(|(item, count)| (*item, count))
+                .collect();
+            counts.sort_unstable();
+            counts
+        };
+        assert_eq!(counts(&grid), vec![('a', 2), ('b', 1)]);
+
+        // Replacing an item moves the cell to the count of the new one
+        grid.insert(GridIndex::new(0, 0), 'b');
+        assert_eq!(counts(&grid), vec![('a', 1), ('b', 2)]);
+        grid.remove(&GridIndex::new(0, 1));
+        assert_eq!(counts(&grid), vec![('b', 2)]);
+        grid.clear();
+        assert!(counts(&grid).is_empty());
+    }
+
+    #[test]
+    fn iter_area_and_retain() {
+        let mut grid: ChunkedGrid<isize> = (0..100)
+            .map(|col| (GridIndex::new(col % 7, col), col))
+            .collect();
+        let area: Vec<isize> = grid
+            .iter_area(GridIndex::new(6, 40), GridIndex::new(0, 35))
+            .map(|(_, item)| *item)
+            .collect();
+        assert_eq!(area.len(), 6);
+        assert!(area.iter().all(|col| (35..=40).contains(col)));
+
+        grid.retain(|pos, _| pos.col < CHUNK_SIZE);
+        assert_eq!(grid.len(), CHUNK_SIZE as usize);
+        assert_eq!(grid.chunk_origins().count(), 1);
+    }
+
+    #[test]
+    fn changes_since_an_older_grid() {
+        let mut old = ChunkedGrid::new();
+        old.insert(GridIndex::new(0, 0), 1);
+        old.insert(GridIndex::new(1, 1), 2);
+        old.insert(GridIndex::new(CHUNK_SIZE * 3, 0), 3);
+        let mut grid = old.clone();
+        assert!(grid.changes_since(&old).is_empty());
+
+        grid.insert(GridIndex::new(1, 1), 4);
+        grid.remove(&GridIndex::new(0, 0));
+        grid.insert(GridIndex::new(-1, CHUNK_SIZE), 5);
+        let changes = grid.changes_since(&old);
+        assert_eq!(
+            changes,
+            vec![
+                TapeItem::Add(GridIndex::new(-1, CHUNK_SIZE), 5, None),
+                TapeItem::Remove(GridIndex::new(0, 0), 1),
+                TapeItem::Add(GridIndex::new(1, 1), 4, Some(2)),
+            ]
+        );
+        for change in changes {
+            old.advance(change);
+        }
+        assert!(old == grid);
+    }
+
+    #[test]
+    fn iter_area_matches_a_full_scan() {
+        let grid: ChunkedGrid<isize> = (-200..200)
+            .map(|i| (GridIndex::new(i * 3, i * 7), i))
+            .collect();
+        let areas = [
+            // Fewer chunks than the grid holds
+            (GridIndex::new(-40, -90), GridIndex::new(50, 100)),
+            // More chunks than the grid holds
+            (
+                GridIndex::new(-10_000, -10_000),
+                GridIndex::new(10_000, 10_000),
+            ),
+        ];
+        for (from, to) in areas {
+            let mut area: Vec<isize> = grid.iter_area(from, to).map(|(_, item)| *item).collect();
+            let mut scan: Vec<isize> = grid
+                .iter()
+                .filter(|(pos, _)| {
+                    (from.row..=to.row).contains(&pos.row) && (from.col..=to.col).contains(&pos.col)
+                })
+                .map(|(_, item)| *item)
+                .collect();
+            area.sort_unstable();
+            scan.sort_unstable();
+            assert!(!area.is_empty());
+            assert_eq!(area, scan);
+        }
+    }
+
+    #[test]
+    fn extreme_indices() {
+        let corners = [
+            GridIndex::new(isize::MIN, isize::MIN),
+            GridIndex::new(isize::MIN, isize::MAX),
+            GridIndex::new(isize::MAX, isize::MIN),
+            GridIndex::new(isize::MAX, isize::MAX),
+        ];
+        let mut grid = ChunkedGrid::new();
+        for (value, pos) in corners.iter().enumerate() {
+            assert_eq!(grid.insert(*pos, value), None);
+        }
+        for (value, pos) in corners.iter().enumerate() {
+            assert_eq!(grid.get(pos), Some(&value));
+        }
+        assert_eq!(grid.chunk_origins().count(), 4);
+        let bounds = grid.bounds().unwrap();
+        assert_eq!(bounds, GridBounds::new(corners[0], corners[3]));
+        assert_eq!(bounds.extent(), GridIndex::new(isize::MAX, isize::MAX));
+        assert_eq!(bounds.center(), GridIndex::new(-1, -1));
+        assert!(bounds.contains(&GridIndex::new(0, 0)));
+        assert_eq!(grid.iter_area(corners[0], corners[3]).count(), 4);
+        assert_eq!(
+            grid.iter_area(GridIndex::new(isize::MAX - 1, 0), corners[3])
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,114 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::{any::Any, rc::Rc};
+
+use druid::{Point, Selector, WidgetId};
+
+use crate::GridIndex;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Item Drag
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+// Drag and drop between grids. Dragged and dropped items are broadcast to every grid of the app,
+// whatever its item type
+/// Sent by the grid an item is dragged out of while the pointer moves
+pub const ITEM_DRAGGED: Selector<AnyDrag> = Selector::new("grid-item-dragged");
+/// Sent by the grid an item is dragged out of when the pointer is released
+pub const ITEM_DROPPED: Selector<AnyDrag> = Selector::new("grid-item-dropped");
+/// Sent back to the source by the grid that took a moved item
+pub const ITEM_DROP_ACCEPTED: Selector<AnyDrag> = Selector::new("grid-item-drop-accepted");
+
+/// What happens to a dragged item in the grid it was dragged out of once another grid takes it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DragMode {
+    /// The item stays, e.g. in a palette of items to pick from
+    Copy,
+    /// The item is removed, if it is still there
+    Move,
+}
+
+/// Payload of the drag and drop commands sent between `GridCanvas` widgets, see
+/// `GridCanvas::with_drag_out`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ItemDrag<T> {
+    /// Grid the item is dragged out of
+    pub source: WidgetId,
+    /// Cell of the source grid the item is on
+    pub from: GridIndex,
+    pub item: T,
+    pub mode: DragMode,
+    /// Position of the pointer in window coordinates
+    pub window_pos: Point,
+}
+
+/// Payload of the drag and drop commands, an [`ItemDrag`] of any item type. A grid only picks
+/// up the drags of its own item type, so grids of different item types can share an app
+#[derive(Clone)]
+pub struct AnyDrag(Rc<dyn Any>);
+
+impl AnyDrag {
+    pub fn new<T: 'static>(drag: ItemDrag<T>) -> Self {
+        Self(Rc::new(drag))
+    }
+
+    /// The drag if it carries an item of type `T`
+    pub fn get<T: 'static>(&self) -> Option<&ItemDrag<T>> {
+        self.0.downcast_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::{Point, WidgetId};
+
+    use super::{DragMode, ItemDrag};
+    use crate::{grid_canvas::GridCanvasData, testing::TestItem, GridIndex};
+
+    fn drag(item: TestItem, mode: DragMode) -> ItemDrag<TestItem> {
+        ItemDrag {
+            source: WidgetId::next(),
+            from: GridIndex::new(0, 0),
+            item,
+            mode,
+            window_pos: Point::ZERO,
+        }
+    }
+
+    #[test]
+    fn drops_follow_the_rules_of_the_target() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        let (wall, pin) = (GridIndex::new(1, 1), GridIndex::new(2, 2));
+        data.grid.insert(wall, TestItem::Wall);
+        data.grid.insert(pin, TestItem::Pin);
+
+        // Copies are added on top of other items, moves need an empty cell
+        assert_eq!(
+            data.accept_drop(wall, &drag(TestItem::Pin, DragMode::Copy)),
+            Ok(())
+        );
+        assert_eq!(data.grid.get(&wall), Some(&TestItem::Pin));
+        assert!(data
+            .accept_drop(pin, &drag(TestItem::Wall, DragMode::Copy))
+            .is_err());
+        assert!(data
+            .accept_drop(pin, &drag(TestItem::Wall, DragMode::Move))
+            .is_err());
+        let empty = GridIndex::new(3, 3);
+        assert_eq!(
+            data.accept_drop(empty, &drag(TestItem::Wall, DragMode::Move)),
+            Ok(())
+        );
+        assert_eq!(data.grid.get(&empty), Some(&TestItem::Wall));
+        assert_eq!(data.grid.get(&pin), Some(&TestItem::Pin));
+
+        data.set_read_only(true);
+        assert!(data
+            .accept_drop(GridIndex::new(4, 4), &drag(TestItem::Wall, DragMode::Copy))
+            .is_err());
+    }
+}
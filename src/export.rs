@@ -0,0 +1,557 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::fmt::{Debug, Write};
+
+use druid::{
+    im::{HashMap, Vector},
+    Color, Data, ImageBuf,
+};
+
+use crate::{
+    grid_canvas::GridCanvasData,
+    snapping::{AxisOrder, CoordinateSystem},
+    utils::cassetta::TapeItem,
+    GridIndex, GridItem,
+};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// SVG Export
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgOptions {
+    pub grid_lines: bool,
+    pub labels: bool,
+    // Fill behind the cells, transparent if None
+    pub background: Option<Color>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            grid_lines: true,
+            labels: true,
+            background: None,
+        }
+    }
+}
+
+/// SVG document with one rect per occupied cell, in the colour of its item. The document spans
+/// the occupied cells with a margin of one cell and uses the cell size of the grid as units.
+pub fn to_svg<T: GridItem + PartialEq + Debug>(
+    data: &GridCanvasData<T>,
+    options: &SvgOptions,
+) -> String
+where
+    GridCanvasData<T>: Data,
+{
+    let cell_size = data.snap_data.cell_size;
+    let (origin, rows, cols) = match data.bounds() {
+        Some(bounds) => {
+            let extent = bounds.extent();
+            (
+                bounds.min.above().left(),
+                extent.row as f64 + 2.0,
+                extent.col as f64 + 2.0,
+            )
+        }
+        None => (GridIndex::new(0, 0), 0.0, 0.0),
+    };
+    let (width, height) = (cols * cell_size, rows * cell_size);
+
+    let mut svg = String::new();
+    // Writing to a String can not fail
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    );
+    if let Some(background) = &options.background {
+        let _ = writeln!(
+            svg,
+            r#"  <rect width="{width}" height="{height}"{}/>"#,
+            fill(background)
+        );
+    }
+
+    let mut cells: Vec<(GridIndex, &T)> = data.grid.iter().collect();
+    cells.sort_by_key(|(pos, _)| (pos.row, pos.col));
+    for (pos, item) in cells.iter() {
+        let x = (pos.col - origin.col) as f64 * cell_size;
+        let y = (pos.row - origin.row) as f64 * cell_size;
+        let _ = writeln!(
+            svg,
+            r#"  <rect x="{x}" y="{y}" width="{cell_size}" height="{cell_size}"{}/>"#,
+            fill(&item.get_color())
+        );
+    }
+
+    if options.grid_lines && rows > 0 {
+        let mut path = String::new();
+        for row in 0..=rows {
+            let _ = write!(path, "M0 {}H{width}", row as f64 * cell_size);
+        }
+        for col in 0..=cols {
+            let _ = write!(path, "M{} 0V{height}", col as f64 * cell_size);
+        }
+        let _ = writeln!(
+            svg,
+            r##"  <path d="{path}" fill="none" stroke="#dcdcdc" stroke-width="{}"/>"##,
+            cell_size * 0.05
+        );
+    }
+
+    if options.labels {
+        let font_size = cell_size / 3.3;
+        for (pos, item) in cells.iter() {
+            let x = (pos.col - origin.col) as f64 * cell_size + cell_size / 2.0;
+            let y = (pos.row - origin.row) as f64 * cell_size + cell_size / 2.0;
+            let _ = writeln!(
+                svg,
+                r##"  <text x="{x}" y="{y}" font-size="{font_size}" text-anchor="middle" dominant-baseline="central" fill="#f2f0e6">{}</text>"##,
+                escape(&item.get_short_text())
+            );
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Fill attributes of a colour, with the opacity left out when the colour is opaque
+fn fill(color: &Color) -> String {
+    let (r, g, b, a) = color.as_rgba8();
+    if a == u8::MAX {
+        format!(r##" fill="#{r:02x}{g:02x}{b:02x}""##)
+    } else {
+        format!(
+            r##" fill="#{r:02x}{g:02x}{b:02x}" fill-opacity="{:.3}""##,
+            a as f64 / 255.0
+        )
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// CSV/TSV Import and Export
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+const CSV_FIELDS: usize = 3;
+
+// Names of the columns, the coordinates in the order the user reads them
+fn csv_header(coordinates: &CoordinateSystem) -> [&'static str; CSV_FIELDS] {
+    match coordinates.order {
+        AxisOrder::RowMajor => ["row", "col", "item"],
+        AxisOrder::ColumnMajor => ["x", "y", "item"],
+    }
+}
+
+impl<T: GridItem + PartialEq + Debug> GridCanvasData<T>
+where
+    GridCanvasData<T>: Data,
+{
+    /// Delimited text with two coordinate columns, an `item` column and one record per occupied
+    /// cell, sorted by coordinate. Cells are written in the coordinates shown to the user, see
+    /// [`GridSnapData::coordinates`](crate::snapping::GridSnapData::coordinates), under `row`
+    /// and `col` or under `x` and `y` for column major coordinates. Items are turned into text
+    /// by `encode`. Use `,` as the delimiter for CSV and `\t` for TSV. Only the cells are
+    /// written, not the regions, annotations, edges or wires.
+    pub fn to_csv(&self, delimiter: char, encode: impl Fn(&T) -> String) -> String {
+        let coordinates = &self.snap_data.coordinates;
+        let mut cells: Vec<((isize, isize), &T)> = self
+            .grid
+            .iter()
+            .map(|(pos, item)| (coordinates.to_user(pos), item))
+            .collect();
+        cells.sort_by_key(|(coordinate, _)| *coordinate);
+
+        let mut text = String::new();
+        write_record(&mut text, delimiter, &csv_header(coordinates));
+        for ((first, second), item) in cells {
+            let fields = [first.to_string(), second.to_string(), encode(item)];
+            write_record(&mut text, delimiter, &fields);
+        }
+        text
+    }
+
+    /// Places the cells of text written by [`to_csv`](Self::to_csv) as a single undo step and
+    /// returns how many were read. Coordinates are read in the coordinate system of the grid and
+    /// items are read back by `decode`. The header record is optional. Nothing is placed if any
+    /// record is malformed.
+    pub fn from_csv(
+        &mut self,
+        text: &str,
+        delimiter: char,
+        decode: impl Fn(&str) -> Option<T>,
+    ) -> Result<usize, String> {
+        let cells = self.read_csv(text, delimiter, decode)?;
+        let count = cells.len();
+        let map: HashMap<GridIndex, (T, Option<T>)> = cells
+            .into_iter()
+            .map(|(pos, item)| (pos, (item, self.grid.get(&pos).copied())))
+            .collect();
+        if count > 0 {
+            self.submit_to_stack_and_process(Vector::unit(TapeItem::BatchAdd(map)));
+        }
+        Ok(count)
+    }
+
+    /// Like [`from_csv`](Self::from_csv) but clears the grid first, e.g. to restore a snapshot.
+    /// The grid is left alone if any record is malformed. Only the cells are replaced, the
+    /// regions, annotations, edges and wires are not part of the text
+    pub fn replace_from_csv(
+        &mut self,
+        text: &str,
+        delimiter: char,
+        decode: impl Fn(&str) -> Option<T>,
+    ) -> Result<usize, String> {
+        let cells = self.read_csv(text, delimiter, decode)?;
+        self.clear_all();
+        let count = cells.len();
+        if count > 0 {
+            let map = cells
+                .into_iter()
+                .map(|(pos, item)| (pos, (item, None)))
+                .collect();
+            self.submit_to_stack_and_process(Vector::unit(TapeItem::BatchAdd(map)));
+        }
+        Ok(count)
+    }
+
+    // Cells of text written by to_csv, in grid indices
+    fn read_csv(
+        &self,
+        text: &str,
+        delimiter: char,
+        decode: impl Fn(&str) -> Option<T>,
+    ) -> Result<HashMap<GridIndex, T>, String> {
+        let mut cells = HashMap::new();
+        for (index, (line, fields)) in parse_records(text, delimiter)?.into_iter().enumerate() {
+            if fields.len() != CSV_FIELDS {
+                return Err(format!(
+                    "Line {line}: expected {CSV_FIELDS} fields, found {}",
+                    fields.len()
+                ));
+            }
+            let pos = match (fields[0].trim().parse(), fields[1].trim().parse()) {
+                (Ok(first), Ok(second)) => self.snap_data.coordinates.from_user((first, second)),
+                // Header
+                _ if index == 0 => continue,
+                _ => return Err(format!("Line {line}: invalid row or column")),
+            };
+            let item = decode(&fields[2])
+                .ok_or_else(|| format!("Line {line}: unknown item {:?}", fields[2]))?;
+            cells.insert(pos, item);
+        }
+        Ok(cells)
+    }
+}
+
+fn write_record(text: &mut String, delimiter: char, fields: &[impl AsRef<str>]) {
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            text.push(delimiter);
+        }
+        let field = field.as_ref();
+        if field.contains([delimiter, '"', '\n', '\r']) {
+            text.push('"');
+            text.push_str(&field.replace('"', "\"\""));
+            text.push('"');
+        } else {
+            text.push_str(field);
+        }
+    }
+    text.push('\n');
+}
+
+// Splits delimited text into records, with quoting as in RFC 4180. Each record comes with the
+// line it starts on. Blank lines are skipped
+fn parse_records(text: &str, delimiter: char) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let (mut line, mut start_line) = (1, 1);
+    let mut quoted = false;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                _ => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+        } else if c == '"' && field.is_empty() {
+            quoted = true;
+        } else if c == delimiter {
+            record.push(std::mem::take(&mut field));
+        } else if c == '\n' {
+            record.push(std::mem::take(&mut field));
+            push_record(&mut records, start_line, std::mem::take(&mut record));
+            line += 1;
+            start_line = line;
+        } else if c != '\r' {
+            field.push(c);
+        }
+    }
+    if quoted {
+        return Err(format!("Line {start_line}: unterminated quote"));
+    }
+    record.push(field);
+    push_record(&mut records, start_line, record);
+    Ok(records)
+}
+
+fn push_record(records: &mut Vec<(usize, Vec<String>)>, line: usize, record: Vec<String>) {
+    if !(record.len() == 1 && record[0].is_empty()) {
+        records.push((line, record));
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Image Import
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl<T: GridItem + PartialEq + Debug> GridCanvasData<T>
+where
+    GridCanvasData<T>: Data,
+{
+    /// Traces an image onto the grid with one cell per `block` × `block` pixels, e.g. to load a
+    /// maze bitmap or a floorplan raster. The pixels of a block are averaged and `map` picks the
+    /// item for the colour, or leaves the cell empty with None. Cells are placed relative to
+    /// `origin` and returned as one BatchAdd for
+    /// [`submit_to_stack_and_process`](Self::submit_to_stack_and_process).
+    pub fn trace_image(
+        &self,
+        image: &ImageBuf,
+        block: usize,
+        origin: GridIndex,
+        map: impl Fn(&Color) -> Option<T>,
+    ) -> TapeItem<GridIndex, T> {
+        let block = block.max(1);
+        let (width, height) = (image.width(), image.height());
+        let pixels: Vec<Color> = image.pixel_colors().flatten().collect();
+
+        let mut cells: HashMap<GridIndex, (T, Option<T>)> = HashMap::new();
+        for row in 0..height.div_ceil(block) {
+            for col in 0..width.div_ceil(block) {
+                let mut sum = [0u32; 4];
+                let mut count = 0;
+                for y in row * block..((row + 1) * block).min(height) {
+                    for x in col * block..((col + 1) * block).min(width) {
+                        let (r, g, b, a) = pixels[y * width + x].as_rgba8();
+                        for (total, channel) in sum.iter_mut().zip([r, g, b, a]) {
+                            *total += channel as u32;
+                        }
+                        count += 1;
+                    }
+                }
+                let [r, g, b, a] = sum.map(|total| (total / count) as u8);
+                if let Some(item) = map(&Color::rgba8(r, g, b, a)) {
+                    let pos = origin + GridIndex::new(row as isize, col as isize);
+                    cells.insert(pos, (item, self.grid.get(&pos).copied()));
+                }
+            }
+        }
+        TapeItem::BatchAdd(cells)
+    }
+
+    /// Traces an image with [`trace_image`](Self::trace_image) and places the cells as a single
+    /// undo step
+    pub fn import_image(
+        &mut self,
+        image: &ImageBuf,
+        block: usize,
+        origin: GridIndex,
+        map: impl Fn(&Color) -> Option<T>,
+    ) {
+        let item = self.trace_image(image, block, origin, map);
+        self.submit_to_stack_and_process(Vector::unit(item));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::{piet::ImageFormat, Color, ImageBuf};
+
+    use super::{escape, parse_records, to_svg, SvgOptions};
+    use crate::{
+        grid_canvas::GridCanvasData, snapping::CoordinateSystem, testing::TestItem, GridIndex,
+        GridItem,
+    };
+
+    #[test]
+    fn svg_has_a_rect_and_label_per_cell() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.grid.insert(GridIndex::new(2, 3), TestItem::Wall);
+        data.grid.insert(GridIndex::new(4, 3), TestItem::Pin);
+
+        let svg = to_svg(&data, &SvgOptions::default());
+        let cell_size = data.snap_data.cell_size;
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(&format!(r#"width="{}""#, 3.0 * cell_size)));
+        assert!(svg.contains(&format!(r#"height="{}""#, 5.0 * cell_size)));
+        assert_eq!(svg.matches("<rect").count(), 2);
+        assert!(svg.contains(r##"fill="#000000""##));
+        assert!(svg.contains(r#"fill-opacity="0.502""#));
+        assert!(svg.contains(">&lt;big pin&gt;</text>"));
+        assert_eq!(svg.matches("<path").count(), 1);
+
+        let options = SvgOptions {
+            grid_lines: false,
+            labels: false,
+            background: Some(Color::WHITE),
+        };
+        let svg = to_svg(&data, &options);
+        assert_eq!(svg.matches("<rect").count(), 3);
+        assert!(!svg.contains("<text") && !svg.contains("<path"));
+    }
+
+    #[test]
+    fn escapes_markup() {
+        assert_eq!(escape(r#"a&b"'"#), "a&amp;b&quot;&apos;");
+    }
+
+    fn encode(item: &TestItem) -> String {
+        item.get_short_text()
+    }
+
+    fn decode(text: &str) -> Option<TestItem> {
+        match text {
+            "W" => Some(TestItem::Wall),
+            "<big pin>" => Some(TestItem::Pin),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn csv_round_trips() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.grid.insert(GridIndex::new(-2, 3), TestItem::Wall);
+        data.grid.insert(GridIndex::new(4, 0), TestItem::Pin);
+
+        let csv = data.to_csv(',', encode);
+        assert_eq!(csv, "row,col,item\n-2,3,W\n4,0,<big pin>\n");
+        let tsv = data.to_csv('\t', encode);
+        assert_eq!(tsv.lines().nth(1), Some("-2\t3\tW"));
+
+        let mut imported = GridCanvasData::new(TestItem::Wall);
+        assert_eq!(imported.from_csv(&tsv, '\t', decode), Ok(2));
+        assert_eq!(
+            imported.grid.get(&GridIndex::new(-2, 3)),
+            Some(&TestItem::Wall)
+        );
+        assert_eq!(
+            imported.grid.get(&GridIndex::new(4, 0)),
+            Some(&TestItem::Pin)
+        );
+        assert_eq!(imported.grid.len(), 2);
+    }
+
+    #[test]
+    fn csv_uses_the_user_coordinates() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.snap_data.coordinates = CoordinateSystem::math(GridIndex::new(10, 0));
+        data.grid.insert(GridIndex::new(8, 3), TestItem::Wall);
+        data.grid.insert(GridIndex::new(11, -1), TestItem::Pin);
+
+        let csv = data.to_csv(',', encode);
+        assert_eq!(csv, "x,y,item\n-1,-1,<big pin>\n3,2,W\n");
+
+        let mut imported = GridCanvasData::new(TestItem::Wall);
+        imported.snap_data.coordinates = data.snap_data.coordinates;
+        assert_eq!(imported.from_csv(&csv, ',', decode), Ok(2));
+        assert!(imported.grid == data.grid);
+    }
+
+    #[test]
+    fn csv_import_rejects_bad_records() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        assert_eq!(
+            data.from_csv("1,1,W\n2,x,W\n", ',', decode),
+            Err(String::from("Line 2: invalid row or column"))
+        );
+        assert_eq!(
+            data.from_csv("1,1,Q", ',', decode),
+            Err(String::from("Line 1: unknown item \"Q\""))
+        );
+        assert_eq!(
+            data.from_csv("1,1", ',', decode),
+            Err(String::from("Line 1: expected 3 fields, found 2"))
+        );
+        assert!(data.grid.is_empty());
+    }
+
+    #[test]
+    fn replacing_from_bad_csv_keeps_the_grid() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.grid.insert(GridIndex::new(0, 0), TestItem::Pin);
+        assert!(data
+            .replace_from_csv("1,1,W\n2,x,W\n", ',', decode)
+            .is_err());
+        assert_eq!(data.grid.get(&GridIndex::new(0, 0)), Some(&TestItem::Pin));
+
+        assert_eq!(data.replace_from_csv("1,1,W\n", ',', decode), Ok(1));
+        assert_eq!(data.grid.get(&GridIndex::new(0, 0)), None);
+        assert_eq!(data.grid.get(&GridIndex::new(1, 1)), Some(&TestItem::Wall));
+    }
+
+    #[test]
+    fn parses_quoted_fields() {
+        let records = parse_records("a,\"b,\"\"c\"\"\"\r\n\n\"d\ne\",f", ',').unwrap();
+        assert_eq!(
+            records,
+            vec![
+                (1, vec![String::from("a"), String::from("b,\"c\"")]),
+                (3, vec![String::from("d\ne"), String::from("f")]),
+            ]
+        );
+        assert!(parse_records("\"a", ',').is_err());
+    }
+
+    #[test]
+    fn traces_image_blocks() {
+        // 3 × 2 pixels, the left 2 × 2 block is black, the right column white
+        let black = [0x00, 0x00, 0x00, 0xff];
+        let white = [0xff, 0xff, 0xff, 0xff];
+        let pixels: Vec<u8> = [black, black, white, black, black, white].concat();
+        let image = ImageBuf::from_raw(pixels, ImageFormat::RgbaSeparate, 3, 2);
+
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        let to_wall = |color: &Color| (color.as_rgba8().0 < 0x80).then_some(TestItem::Wall);
+        data.import_image(&image, 2, GridIndex::new(1, 1), to_wall);
+        assert_eq!(data.grid.len(), 1);
+        assert_eq!(data.grid.get(&GridIndex::new(1, 1)), Some(&TestItem::Wall));
+
+        data.import_image(&image, 1, GridIndex::new(10, 0), to_wall);
+        assert_eq!(data.grid.len(), 5);
+        assert!(!data.grid.contains_key(&GridIndex::new(10, 2)));
+    }
+}
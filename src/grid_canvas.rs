@@ -1,24 +1,54 @@
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 use druid::{
     im::{HashMap, HashSet, Vector},
-    widget::{Label, LabelText},
-    Affine, BoxConstraints, Color, Data, Env, Event, EventCtx, Insets, LayoutCtx, Lens, LifeCycle,
-    LifeCycleCtx, MouseButton, PaintCtx, Point, Rect, RenderContext, Selector, Size, TextAlignment,
-    UpdateCtx, Widget, WidgetPod,
+    kurbo::{BezPath, Circle, Line},
+    piet::{
+        Device, ImageFormat, LineCap, LineJoin, PietText, PietTextLayout, StrokeStyle, Text,
+        TextLayout, TextLayoutBuilder,
+    },
+    BoxConstraints, Color, Data, Env, Event, EventCtx, FontFamily, ImageBuf, Insets, KbKey, Key,
+    LayoutCtx, Lens, LifeCycle, LifeCycleCtx, LinearGradient, MouseButton, PaintCtx, Point, Rect,
+    RenderContext, Selector, Size, Target, TextAlignment, TimerToken, UnitPoint, UpdateCtx, Vec2,
+    Widget, WidgetId,
 };
-use druid_color_thesaurus::white;
 use log::debug;
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap as StdHashMap, VecDeque},
+    fmt::Debug,
+    hash::Hash,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 ///
 /// Imports
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////////
-use std::{fmt::Debug, time::Instant};
+#[cfg(feature = "png")]
+use std::{fs::File, io::BufWriter};
 
 use crate::{
-    canvas::{Canvas, Child, PointKey},
+    annotations::{Annotation, Measurement},
+    canvas::{Canvas, Child},
+    chunked_grid::ChunkedGrid,
+    drag_drop::{AnyDrag, DragMode, ItemDrag, ITEM_DRAGGED, ITEM_DROPPED, ITEM_DROP_ACCEPTED},
+    headers::GridHeaderDataAccess,
+    item_editor::{EditedItem, ItemEditor, ItemEditorBuilder, CLOSE_ITEM_EDITOR},
+    model::play_expected,
+    panning::OVERSCROLL_LIMIT,
+    session::{Session, SessionEvent, SessionOp, SessionRecorder},
     snapping::GridSnapData,
-    utils::cassetta::{Cassetta, CassettePlayer, TapeItem},
-    GridAction, GridIndex, GridItem, GridState,
+    theme,
+    utils::{
+        cassetta::{Cassetta, CassettePlayer, PlaybackDataAccess, TapeItem, TapeRecorder},
+        graphema::{Components, DynamicLatticeGraph, Lattice2D},
+        spoor::core::{Net, SearchGraph, SearchSnapshot},
+    },
+    CellTransform, EdgeIndex, GridAction, GridBounds, GridIndex, GridItem, GridRegion,
+    GridSelection, GridState, Neighborhood, Wire,
 };
 
 //////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -28,6 +58,158 @@ use crate::{
 /////////////////////////////////////////////////////////////////////////////////////////////////////
 pub const SET_DISABLED: Selector = Selector::new("disabled-grid-state");
 pub const SET_ENABLED: Selector = Selector::new("idle-grid-state");
+// Renders every occupied cell into an image, whatever part of the grid is in view
+pub const EXPORT_IMAGE: Selector<ImageExport> = Selector::new("grid-export-image");
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Notification Selectors
+///
+/////////////////////////////////////////////////////////////////////////////////////////////////////
+// Sent when an edit is rejected by the item or its neighbours. Payload is the cell and the reason
+pub const EDIT_REJECTED: Selector<(GridIndex, String)> = Selector::new("grid-edit-rejected");
+// Sent with the image rendered by EXPORT_IMAGE
+pub const IMAGE_EXPORTED: Selector<ImageBuf> = Selector::new("grid-image-exported");
+// Sent when EXPORT_IMAGE fails. Payload is the reason
+pub const EXPORT_FAILED: Selector<String> = Selector::new("grid-export-failed");
+// Sent by the context menu gesture so the app can show its own menu. Payload is the cell and
+// the mouse position in window coordinates
+pub const CONTEXT_MENU_REQUESTED: Selector<(GridIndex, Point)> =
+    Selector::new("grid-context-menu-requested");
+
+// Env keys styling the cursor overlay. All of them are optional
+/// Outline the cell under the pointer, on by default
+pub const CURSOR_HIGHLIGHT: Key<bool> = Key::new("grid-canvas.cursor-highlight");
+/// Draw lines across the whole canvas through the pointer, off by default
+pub const CURSOR_CROSSHAIR: Key<bool> = Key::new("grid-canvas.cursor-crosshair");
+pub use crate::theme::CURSOR_COLOR;
+/// Width of the cell outline, the crosshair lines are half as wide
+pub const CURSOR_WIDTH: Key<f64> = Key::new("grid-canvas.cursor-width");
+
+pub use crate::model::BatchReport;
+
+/// Payload of `EXPORT_IMAGE`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageExport {
+    /// Image pixels per logical pixel of the canvas
+    pub scale: f64,
+    /// PNG file the image is also written to. Needs the `png` feature
+    pub path: Option<PathBuf>,
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// Canvas Tape
+//
+//////////////////////////////////////////////////////////////////////////////////////
+/// Undo step of a [`GridCanvasData`]. The annotations are edited on the same tape as the grid,
+/// so undo and redo go through both in the order the edits were made
+#[derive(Clone, Debug, PartialEq, Data)]
+pub enum CanvasItem<T: Clone + Hash + Eq> {
+    Grid(TapeItem<GridIndex, T>),
+    Annotation(TapeItem<usize, Annotation>),
+}
+
+impl<T: Clone + Hash + Eq> CanvasItem<T> {
+    /// The edit of the grid, None for an edit of the annotations
+    pub fn grid_item(&self) -> Option<&TapeItem<GridIndex, T>> {
+        match self {
+            CanvasItem::Grid(item) => Some(item),
+            CanvasItem::Annotation(_) => None,
+        }
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// Grid Subscriptions
+//
+//////////////////////////////////////////////////////////////////////////////////////
+/// Change passed to the subscribers of a [`GridCanvasData`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GridChange<'a, T: Clone + Hash + Eq> {
+    /// The item was played on the grid
+    Applied(&'a TapeItem<GridIndex, T>),
+    /// The item was undone, the grid is back to how it was before it was played
+    Reverted(&'a TapeItem<GridIndex, T>),
+    /// The item was played on the grid by [`GridCanvasData::apply_remote`], outside the tape
+    Remote(&'a TapeItem<GridIndex, T>),
+}
+
+pub type SubscriptionId = usize;
+
+type Subscriber<T> = Box<dyn FnMut(GridChange<'_, T>)>;
+
+struct SubscriberList<T: Clone + Hash + Eq> {
+    next_id: SubscriptionId,
+    subscribers: Vec<(SubscriptionId, Subscriber<T>)>,
+}
+
+#[derive(Clone)]
+struct Subscribers<T: Clone + Hash + Eq>(Rc<RefCell<SubscriberList<T>>>);
+
+impl<T: Clone + Hash + Eq> Subscribers<T> {
+    fn new() -> Self {
+        Self(Rc::new(RefCell::new(SubscriberList {
+            next_id: 0,
+            subscribers: Vec::new(),
+        })))
+    }
+
+    fn add(&self, subscriber: Subscriber<T>) -> SubscriptionId {
+        let mut list = self.0.borrow_mut();
+        let id = list.next_id;
+        list.next_id += 1;
+        list.subscribers.push((id, subscriber));
+        id
+    }
+
+    fn remove(&self, id: SubscriptionId) -> bool {
+        let mut list = self.0.borrow_mut();
+        let len = list.subscribers.len();
+        list.subscribers.retain(|(other, _)| *other != id);
+        list.subscribers.len() != len
+    }
+
+    // Undone items are reported before the items played after them
+    fn notify(
+        &self,
+        reverted: &Vector<TapeItem<GridIndex, T>>,
+        applied: &Vector<TapeItem<GridIndex, T>>,
+    ) {
+        let mut list = self.0.borrow_mut();
+        for (_, subscriber) in list.subscribers.iter_mut() {
+            for item in reverted.iter() {
+                subscriber(GridChange::Reverted(item));
+            }
+            for item in applied.iter() {
+                subscriber(GridChange::Applied(item));
+            }
+        }
+    }
+
+    fn notify_remote(&self, item: &TapeItem<GridIndex, T>) {
+        let mut list = self.0.borrow_mut();
+        for (_, subscriber) in list.subscribers.iter_mut() {
+            subscriber(GridChange::Remote(item));
+        }
+    }
+}
+
+impl<T: Clone + Hash + Eq> Debug for Subscribers<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Subscribers")
+            .field(&self.0.borrow().subscribers.len())
+            .finish()
+    }
+}
+
+// Subscribers are not part of the value of the grid
+impl<T: Clone + Hash + Eq> PartialEq for Subscribers<T> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
 
 //////////////////////////////////////////////////////////////////////////////////////
 //
@@ -38,9 +220,50 @@ pub const SET_ENABLED: Selector = Selector::new("idle-grid-state");
 pub struct GridCanvasData<T: GridItem + PartialEq + Debug> {
     action: GridAction,
     pub grid_item: T,
-    pub grid: HashMap<GridIndex, T>,
+    pub grid: ChunkedGrid<T>,
     // Data Hierarchy
-    pub save_data: Cassetta<TapeItem<GridIndex, T>>,
+    pub save_data: Cassetta<CanvasItem<T>>,
+    pub recorder: TapeRecorder<TapeItem<GridIndex, T>>,
+    // Batch of the operations performed since the last MouseDown
+    pub(crate) stroke: Option<TapeItem<GridIndex, T>>,
+    // Grid the change list is computed against while in ECO mode
+    eco_baseline: Option<HashMap<GridIndex, T>>,
+    // Lattice with one vertex per occupied cell, updated with every change to the grid
+    #[data(ignore)]
+    graph_view: Option<Arc<DynamicLatticeGraph>>,
+    // Callbacks told about every change to the grid, shared between clones
+    #[data(ignore)]
+    subscribers: Subscribers<T>,
+    // Rejects every editing action of the widget while set
+    #[lens(ignore)]
+    pub(crate) read_only: bool,
+    // Set by the changes that are not on the undo tape, i.e. remote items, regions, edges and
+    // wires, until mark_saved. Changes on the tape are compared against its saved position
+    dirty: bool,
+    // Items on the edges between cells, e.g. the walls of a maze, painted as thin segments
+    #[lens(ignore)]
+    edges: HashMap<EdgeIndex, T>,
+    // Routes painted as lines over the cells, one per net
+    #[lens(ignore)]
+    wires: HashMap<Net, Wire>,
+    // Notes painted above everything else, the last one on top
+    pub annotations: Vector<Annotation>,
+    // Named rectangles painted over the grid, in the order they were added
+    pub regions: Vector<GridRegion>,
+    // Row or column picked from a header
+    pub selection: Option<GridSelection>,
+    // Cells picked with the select-same gesture
+    pub selected_cells: HashSet<GridIndex>,
+    // Number of rows and columns of a finite board anchored at (0, 0), None for an unbounded grid
+    #[lens(ignore)]
+    pub(crate) extent: Option<GridIndex>,
+    // Cells counted as adjacent by area fills and routes
+    pub neighborhood: Neighborhood,
+    // Step of a debugged path search painted over the grid, see ShortestPath::debug_steps
+    pub search_debug: Option<Arc<SearchSnapshot>>,
+    // Editing operations recorded since start_session
+    #[data(ignore)]
+    session: Option<SessionRecorder<T>>,
     pub snap_data: GridSnapData,
 }
 
@@ -52,61 +275,388 @@ where
         Self {
             action: GridAction::Dynamic,
             grid_item: item_type,
-            grid: HashMap::new(),
+            grid: ChunkedGrid::new(),
             save_data: Cassetta::new(),
+            recorder: TapeRecorder::new(),
+            stroke: None,
+            eco_baseline: None,
+            graph_view: None,
+            subscribers: Subscribers::new(),
+            read_only: false,
+            dirty: false,
+            edges: HashMap::new(),
+            wires: HashMap::new(),
+            annotations: Vector::new(),
+            regions: Vector::new(),
+            selection: None,
+            selected_cells: HashSet::new(),
+            extent: None,
+            neighborhood: Neighborhood::default(),
+            search_debug: None,
+            session: None,
             snap_data: GridSnapData::new(15.0),
         }
     }
 
+    /// True if the data changed since it was created or last marked as saved, see [`DirtyLens`].
+    /// Undoing or redoing back to the saved position of the tape makes it clean again
+    pub fn is_dirty(&self) -> bool {
+        self.dirty || self.stroke.is_some() || !self.save_data.is_at_saved()
+    }
+
+    /// Clears the dirty flag, e.g. once the app has written the grid to a file
+    pub fn mark_saved(&mut self) {
+        self.end_stroke();
+        self.save_data.mark_saved();
+        self.dirty = false;
+    }
+
+    // Sets the dirty flag for changes that do not go on the undo tape
+    fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
     pub fn set_cell_size(&mut self, cell_size: f64) {
         self.snap_data.cell_size = cell_size;
     }
 
-    // Basic Grid methods
-    fn add_node(&mut self, pos: &GridIndex, item: T) -> bool {
-        self.save_data.clear_delta();
-        let option = self.grid.get(pos);
+    pub fn set_action(&mut self, action: GridAction) {
+        self.action = action;
+        self.record_event(SessionEvent::SetAction(action));
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Rejects every editing action of the widget and the editing methods that check for it
+    /// while set
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+        self.record_event(SessionEvent::SetReadOnly(read_only));
+    }
+
+    /// Number of rows and columns of a finite board anchored at (0, 0), None for an unbounded
+    /// grid
+    pub fn extent(&self) -> Option<GridIndex> {
+        self.extent
+    }
+
+    /// Makes the board finite, or unbounded with None. Items outside of it stay where they are
+    pub fn set_extent(&mut self, extent: Option<GridIndex>) {
+        self.extent = extent;
+        self.record_event(SessionEvent::SetExtent(extent));
+    }
+
+    // Hit testing methods
+    /// Cell under a point in the coordinates of the widget
+    pub fn index_at(&self, point: Point) -> GridIndex {
+        let (row, col) = self.snap_data.get_grid_index(point);
+        GridIndex::new(row, col)
+    }
+
+    /// Cell under a point in the coordinates of the widget and the item on it, if any
+    pub fn cell_at(&self, point: Point) -> Option<(GridIndex, &T)> {
+        let pos = self.index_at(point);
+        self.grid.get(&pos).map(|item| (pos, item))
+    }
+
+    /// Edge near a point in the coordinates of the widget and the item on it, if any
+    pub fn edge_at(&self, point: Point) -> Option<(EdgeIndex, &T)> {
+        let edge = self.snap_data.get_edge_index(point)?;
+        self.edges.get(&edge).map(|item| (edge, item))
+    }
+
+    /// Area of the widget covered by a cell, or its bounding box while the view is rotated
+    pub fn rect_of(&self, index: GridIndex) -> Rect {
+        self.snap_data.get_screen_cell_rect(index.row, index.col)
+    }
+
+    /// Smallest rectangle holding every occupied cell, or None for an empty grid. Kept up to
+    /// date as cells are added and removed
+    pub fn bounds(&self) -> Option<GridBounds> {
+        self.grid.bounds()
+    }
+
+    /// Area covered by the occupied cells before pan and zoom are applied
+    pub fn content_bounds(&self) -> Option<Rect> {
+        self.bounds().map(|bounds| {
+            let below = bounds.max.below().right();
+            Rect::from_points(
+                self.snap_data
+                    .get_grid_position(bounds.min.row, bounds.min.col),
+                self.snap_data.get_grid_position(below.row, below.col),
+            )
+        })
+    }
+
+    // Gesture methods
+    /// Fills the empty cells connected to `pos`, see [`Self::neighborhood`], with `item` as a single undo step. `pos` itself
+    /// is always part of the area so a fill can start from a freshly painted cell. Fails when
+    /// the area is larger than `max_cells`, e.g. on an unbounded grid, or the grid is read-only.
+    pub fn fill_area(&mut self, pos: GridIndex, item: T, max_cells: usize) -> Result<(), String> {
+        if self.read_only {
+            return Err(String::from("Grid is read-only"));
+        }
+        let mut area = HashSet::new();
+        let mut frontier = vec![pos];
+        while let Some(cell) = frontier.pop() {
+            if area.contains(&cell)
+                || !self.is_on_board(&cell)
+                || (cell != pos && self.grid.contains_key(&cell))
+            {
+                continue;
+            }
+            if area.len() == max_cells {
+                return Err(String::from("Area is too large to fill"));
+            }
+            area.insert(cell);
+            frontier.extend(cell.neighbors(&self.neighborhood));
+        }
 
-        let command_item;
-        if option.is_none() {
-            command_item = TapeItem::Add(*pos, item, None);
+        let map: HashMap<GridIndex, (T, Option<T>)> = area
+            .into_iter()
+            .filter(|cell| self.add_rejection(cell, &item).is_none())
+            .map(|cell| (cell, (item, self.grid.get(&cell).copied())))
+            .collect();
+        let event = SessionEvent::Fill(pos, item, max_cells);
+        if map.is_empty() {
+            self.record_event(event);
         } else {
-            command_item = TapeItem::Add(*pos, item, Some(*option.unwrap()));
+            self.process_stack_list(Vector::unit(TapeItem::BatchAdd(map)), Some(event));
         }
+        Ok(())
+    }
 
-        if item.can_add(option) {
-            self.grid.insert(*pos, item);
-            self.save_data.insert_and_play(command_item);
-            return true;
+    /// Selects every cell that holds the same kind of item as `pos`. Picking an empty cell
+    /// clears the selection
+    pub fn select_same(&mut self, pos: &GridIndex) {
+        self.selected_cells = match self.grid.get(pos) {
+            Some(item) => self
+                .grid
+                .iter()
+                .filter(|(_, other)| *other == item)
+                .map(|(other_pos, _)| other_pos)
+                .collect(),
+            None => HashSet::new(),
+        };
+        self.record_event(SessionEvent::SelectSame(*pos));
+    }
+
+    /// Rotates or flips the selected items within their bounding box as a single undo step,
+    /// passing each one through [`GridItem::transformed`]. The selection follows the items. Fails
+    /// without changing anything when an item would land on an item outside the selection or
+    /// be rejected there.
+    pub fn transform_selection(&mut self, transform: CellTransform) -> Result<(), String> {
+        if self.read_only {
+            return Err(String::from("Grid is read-only"));
+        }
+        let items: Vec<(GridIndex, T)> = self
+            .selected_cells
+            .iter()
+            .filter_map(|pos| self.grid.get(pos).map(|item| (*pos, *item)))
+            .collect();
+        if items.is_empty() {
+            return Err(String::from("Nothing is selected"));
+        }
+        let (min, max) =
+            items
+                .iter()
+                .skip(1)
+                .fold((items[0].0, items[0].0), |(min, max), (pos, _)| {
+                    (
+                        GridIndex::new(min.row.min(pos.row), min.col.min(pos.col)),
+                        GridIndex::new(max.row.max(pos.row), max.col.max(pos.col)),
+                    )
+                });
+        let targets: Vec<(GridIndex, T)> = items
+            .iter()
+            .map(|(pos, item)| (transform.apply(*pos, min, max), item.transformed(transform)))
+            .collect();
+
+        // A target may only land on a cell of the selection or on an empty one
+        if let Some((pos, _)) = targets
+            .iter()
+            .find(|(pos, _)| self.grid.contains_key(pos) && !self.selected_cells.contains(pos))
+        {
+            return Err(format!("Cell ({}, {}): Cell is taken", pos.row, pos.col));
+        }
+
+        let removed = items.iter().copied().collect();
+        let added = targets
+            .iter()
+            .map(|(pos, item)| (*pos, (*item, None)))
+            .collect();
+        let ops = Vector::from(vec![
+            TapeItem::BatchRemove(removed),
+            TapeItem::BatchAdd(added),
+        ]);
+        // The batch is tried on a copy first so that nothing changes unless every item can
+        // leave its cell and every target passes, including against the targets placed before it
+        let mut trial = BatchReport {
+            applied: 0,
+            rejected: Vec::new(),
+        };
+        self.clone().apply_batch_items(ops.clone(), &mut trial);
+        if let Some((op, reason)) = trial.rejected.first() {
+            return Err(match op {
+                TapeItem::Add(pos, ..) | TapeItem::Remove(pos, _) => {
+                    format!("Cell ({}, {}): {}", pos.row, pos.col, reason)
+                }
+                _ => reason.clone(),
+            });
         }
-        false
+
+        self.play_batch(ops, Some(SessionEvent::Transform(transform)));
+        self.selected_cells = targets.into_iter().map(|(pos, _)| pos).collect();
+        Ok(())
     }
 
-    fn remove_node(&mut self, pos: &GridIndex) -> bool {
-        self.save_data.clear_delta();
-        if let Some(item) = self.grid.remove(pos) {
-            if item.can_remove() {
-                let command_item = TapeItem::Remove(*pos, item);
-                self.save_data.insert_and_play(command_item);
-                return true;
-            } else {
-                self.grid.insert(*pos, item);
+    // Export methods
+    /// Renders every occupied cell, with a margin of one cell, into an image with `scale`
+    /// pixels per logical pixel. Fails when the grid is empty, the scale is not a positive
+    /// number or either side of the image would be longer than `MAX_IMAGE_SIDE` pixels
+    pub fn render_image(&self, scale: f64) -> Result<ImageBuf, String> {
+        let bounds = self.bounds().ok_or_else(|| String::from("Grid is empty"))?;
+        let origin = bounds.min.above().left();
+        let cell_size = Size::new(self.snap_data.cell_size, self.snap_data.cell_size);
+        let (width, height) = image_size(bounds.extent(), self.snap_data.cell_size, scale)?;
+
+        let mut device = Device::new().map_err(|err| err.to_string())?;
+        let mut target = device
+            .bitmap_target(width, height, scale)
+            .map_err(|err| err.to_string())?;
+        {
+            let mut rc = target.render_context();
+            rc.clear(None, theme::default_color(&theme::GRID_BACKGROUND));
+            let labels = LabelCache::new();
+            for (pos, item) in self.grid.iter() {
+                let point = Point::new(
+                    (pos.col - origin.col) as f64 * cell_size.width,
+                    (pos.row - origin.row) as f64 * cell_size.height,
+                );
+                rc.fill(Rect::from_origin_size(point, cell_size), &item.get_color());
+                let label = labels.layout(
+                    rc.text(),
+                    &item.get_short_text(),
+                    cell_size.width,
+                    theme::default_color(&theme::GRID_LABEL_TEXT),
+                );
+                let label_offset = (cell_size.to_vec2() - label.size().to_vec2()) / 2.0;
+                rc.draw_text(&label, point + label_offset);
+            }
+            rc.finish().map_err(|err| err.to_string())?;
+        }
+        target
+            .to_image_buf(ImageFormat::RgbaSeparate)
+            .map_err(|err| err.to_string())
+    }
+
+    // Routing methods
+    /// Finds the path from `from` to `to` with the fewest steps between neighbouring cells that
+    /// only crosses empty cells outside the halos of other items. The search gives up after
+    /// `max_expansions` cells.
+    pub fn find_route(
+        &self,
+        from: GridIndex,
+        to: GridIndex,
+        item: &T,
+        max_expansions: usize,
+    ) -> Option<Vec<GridIndex>> {
+        self.find_route_around(from, to, item, max_expansions, &self.halo_cells())
+    }
+
+    /// Like [`find_route`](Self::find_route) with the halo cells collected beforehand by
+    /// [`halo_cells`](Self::halo_cells), e.g. once for all the searches of a route drag
+    pub fn find_route_around(
+        &self,
+        from: GridIndex,
+        to: GridIndex,
+        item: &T,
+        max_expansions: usize,
+        halos: &HashMap<GridIndex, T>,
+    ) -> Option<Vec<GridIndex>> {
+        let is_free = |pos: &GridIndex| {
+            *pos == to
+                || (!self.grid.contains_key(pos)
+                    && halos.get(pos).is_none_or(|other| other == item))
+        };
+        let heuristic = |pos: &GridIndex| self.neighborhood.min_steps(*pos, to);
+
+        let mut open = BinaryHeap::new();
+        let mut costs: StdHashMap<GridIndex, usize> = StdHashMap::new();
+        let mut parents: StdHashMap<GridIndex, GridIndex> = StdHashMap::new();
+        costs.insert(from, 0);
+        open.push(Reverse((heuristic(&from), 0, from.row, from.col)));
+
+        let mut expansions = 0;
+        while let Some(Reverse((_, cost, row, col))) = open.pop() {
+            let pos = GridIndex::new(row, col);
+            if pos == to {
+                let mut route = vec![pos];
+                let mut current = pos;
+                while let Some(parent) = parents.get(&current) {
+                    route.push(*parent);
+                    current = *parent;
+                }
+                route.reverse();
+                return Some(route);
+            }
+            if costs.get(&pos).is_some_and(|best| cost > *best) {
+                continue;
+            }
+            expansions += 1;
+            if expansions > max_expansions {
+                return None;
+            }
+            for next in pos.neighbors(&self.neighborhood) {
+                let next_cost = cost + 1;
+                if is_free(&next) && costs.get(&next).is_none_or(|best| next_cost < *best) {
+                    costs.insert(next, next_cost);
+                    parents.insert(next, pos);
+                    open.push(Reverse((
+                        next_cost + heuristic(&next),
+                        next_cost,
+                        next.row,
+                        next.col,
+                    )));
+                }
             }
         }
-        false
+        None
     }
-    fn move_node(&mut self, from: &GridIndex, to: &GridIndex) -> bool {
-        self.save_data.clear_delta();
-        let item = self.grid.get(from).unwrap();
-        let other = self.grid.get(to);
-        if item.can_move(other) {
-            let item = self.grid.remove(from).unwrap();
-            self.grid.insert(*to, item);
-            let command_item = TapeItem::Move(*from, *to, item);
-            self.save_data.insert_and_play(command_item);
-            return true;
+
+    /// Places `item` on every empty cell of the route as a single undo step. Fails without
+    /// changing anything when the grid is read-only or one of the cells rejects the item, e.g. a
+    /// route found before the grid changed
+    pub fn commit_route(&mut self, route: &[GridIndex], item: T) -> Result<(), String> {
+        if self.read_only {
+            return Err(String::from("Grid is read-only"));
         }
-        false
+        let mut map = HashMap::new();
+        for pos in route {
+            if !self.grid.contains_key(pos) {
+                if let Some(reason) = self.add_rejection(pos, &item) {
+                    return Err(format!("Cell ({}, {}): {reason}", pos.row, pos.col));
+                }
+                map.insert(*pos, (item, None));
+            }
+        }
+        self.end_stroke();
+        if map.is_empty() {
+            return Ok(());
+        }
+        for pos in map.keys() {
+            self.grid.insert(*pos, item);
+        }
+        let command_item = TapeItem::BatchAdd(map);
+        self.recorder.record(command_item.clone());
+        self.push_change(
+            command_item,
+            Some(SessionEvent::Route(route.to_vec(), item)),
+        );
+        self.sync_derived();
+        Ok(())
     }
 
     // Auxiliary Grid Methods
@@ -151,15 +701,32 @@ where
         for (pos, (current_item, _)) in &map {
             self.grid.insert(*pos, *current_item);
         }
-        self.save_data.insert_and_play(TapeItem::BatchAdd(map));
+        self.push_change(TapeItem::BatchAdd(map), None);
+        self.sync_derived();
         // ctx.submit_command(Command::new(TRIGGER_CHANGE, (), Target::Widget(id)));
     }
 
+    /// Places `item` at every (column, row) of a layout, e.g. from
+    /// [`layered_layout`](crate::utils::lokigo::layered_layout), relative to `origin` as a single undo step
+    pub fn add_layout(&mut self, layout: &[(usize, usize)], origin: GridIndex, item: T) {
+        let map: HashMap<GridIndex, (T, Option<T>)> = layout
+            .iter()
+            .map(|(col, row)| {
+                let pos = origin + GridIndex::new(*row as isize, *col as isize);
+                (pos, (item, self.grid.get(&pos).copied()))
+            })
+            .collect();
+        self.submit_to_stack_and_process(Vector::unit(TapeItem::BatchAdd(map)));
+    }
+
     // Clear Grid methods
     pub fn clear_all(&mut self) {
-        self.save_data
-            .insert_and_play(TapeItem::BatchRemove(self.grid.clone()));
+        self.push_change(
+            TapeItem::BatchRemove(self.grid.to_hash_map()),
+            Some(SessionEvent::Clear),
+        );
         self.grid.clear();
+        self.sync_derived();
         // ctx.submit_command(Command::new(TRIGGER_CHANGE, (), Target::Widget(id)));
     }
     pub fn clear_except(&mut self, set: HashSet<T>) {
@@ -174,7 +741,8 @@ where
                 }
             })
         }
-        self.save_data.insert_and_play(TapeItem::BatchRemove(map));
+        self.push_change(TapeItem::BatchRemove(map), None);
+        self.sync_derived();
     }
     pub fn clear_only(&mut self, set: HashSet<T>) {
         let mut map: HashMap<GridIndex, T> = HashMap::new();
@@ -188,330 +756,2030 @@ where
                 }
             })
         }
-        self.save_data.insert_and_play(TapeItem::BatchRemove(map));
+        self.push_change(TapeItem::BatchRemove(map), None);
+        self.sync_derived();
+    }
+    /// Removes every item inside the named region that allows it as a single undo step
+    pub fn clear_region(&mut self, name: &str) {
+        let mut map = self.cells_in_region(name);
+        map.retain(|_, item| item.can_remove());
+        if map.is_empty() {
+            return;
+        }
+        for pos in map.keys() {
+            self.grid.remove(pos);
+        }
+        self.push_change(TapeItem::BatchRemove(map), None);
+        self.sync_derived();
     }
 
-    // Save stack methods
-    fn validate_stack_list(
-        &mut self,
-        list: Vector<TapeItem<GridIndex, T>>,
-    ) -> (HashMap<GridIndex, T>, Vector<TapeItem<GridIndex, T>>) {
-        let mut stack_list = Vector::new();
-        let mut pos_map = HashMap::new();
-
-        for stack_item in list {
-            match stack_item {
-                TapeItem::Add(pos, current_item, _) => {
-                    let other = self.grid.get(&pos);
-                    if current_item.can_add(other) {
-                        stack_list.push_back(stack_item);
-                        pos_map.insert(pos, current_item);
-                    }
-                }
-                TapeItem::Remove(pos, current_item) => {
-                    if current_item.can_remove() {
-                        stack_list.push_back(stack_item);
-                        pos_map.remove(&pos);
-                    }
-                }
-                TapeItem::Move(from_pos, to_pos, current_item) => {
-                    let other = self.grid.get(&to_pos);
-                    if current_item.can_move(other) {
-                        stack_list.push_back(stack_item);
-                        pos_map.remove(&from_pos);
-                        pos_map.insert(to_pos, current_item);
-                    }
-                }
-                TapeItem::BatchAdd(mut map) => {
-                    map.retain(|pos, (current_item, _)| {
-                        let option = self.grid.get(pos);
-                        if current_item.can_add(option) {
-                            pos_map.insert(*pos, *current_item);
-                        }
-                        current_item.can_add(option)
-                    });
-
-                    if !map.is_empty() {
-                        stack_list.push_back(TapeItem::BatchAdd(map));
-                    }
-                }
-                TapeItem::BatchRemove(mut map) => {
-                    map.retain(|pos, current_item| {
-                        if current_item.can_remove() {
-                            pos_map.remove(pos);
-                        }
-                        current_item.can_remove()
-                    });
-                    if !map.is_empty() {
-                        stack_list.push_back(TapeItem::BatchRemove(map));
-                    }
-                }
+    // Region methods
+    /// Adds a region, replacing any region with the same name
+    pub fn add_region(&mut self, region: GridRegion) {
+        match self
+            .regions
+            .iter()
+            .position(|other| other.name == region.name)
+        {
+            Some(index) => {
+                self.regions.set(index, region);
             }
+            None => self.regions.push_back(region),
         }
-        (pos_map, stack_list)
+        self.mark_dirty();
+    }
+
+    pub fn remove_region(&mut self, name: &str) -> Option<GridRegion> {
+        let index = self.regions.iter().position(|region| region.name == name)?;
+        self.mark_dirty();
+        Some(self.regions.remove(index))
+    }
+
+    pub fn region(&self, name: &str) -> Option<&GridRegion> {
+        self.regions.iter().find(|region| region.name == name)
+    }
+
+    // Edge and wire methods
+    /// Items on the edges between cells
+    pub fn edges(&self) -> &HashMap<EdgeIndex, T> {
+        &self.edges
+    }
+
+    /// Puts `item` on `edge`, returning the item it replaces
+    pub fn set_edge(&mut self, edge: EdgeIndex, item: T) -> Option<T> {
+        self.mark_dirty();
+        self.edges.insert(edge, item)
+    }
+
+    pub fn remove_edge(&mut self, edge: &EdgeIndex) -> Option<T> {
+        let item = self.edges.remove(edge)?;
+        self.mark_dirty();
+        Some(item)
+    }
+
+    /// Wires painted over the cells, one per net
+    pub fn wires(&self) -> &HashMap<Net, Wire> {
+        &self.wires
+    }
+
+    /// Routes `net` along `wire`, returning the wire it replaces
+    pub fn set_wire(&mut self, net: Net, wire: Wire) -> Option<Wire> {
+        self.mark_dirty();
+        self.wires.insert(net, wire)
     }
 
-    pub fn submit_to_stack(&mut self, list: Vector<TapeItem<GridIndex, T>>) {
-        let (_, save_list) = self.validate_stack_list(list);
-        self.save_data.append(save_list);
+    pub fn remove_wire(&mut self, net: &Net) -> Option<Wire> {
+        let wire = self.wires.remove(net)?;
+        self.mark_dirty();
+        Some(wire)
     }
 
-    pub fn submit_to_stack_and_process(&mut self, list: Vector<TapeItem<GridIndex, T>>) {
-        let (pos_map, save_list) = self.validate_stack_list(list);
-        for (pos, item) in pos_map.iter() {
-            self.grid.insert(*pos, *item);
+    /// Occupied cells inside the named region. Empty if there is no such region
+    pub fn cells_in_region(&self, name: &str) -> HashMap<GridIndex, T> {
+        match self.region(name) {
+            Some(region) => self
+                .grid
+                .iter_area(region.min, region.max)
+                .map(|(pos, item)| (pos, *item))
+                .collect(),
+            None => HashMap::new(),
         }
-        self.save_data.append_and_play(save_list);
     }
-}
 
-//////////////////////////////////////////////////////////////////////////////////////////////////////
-///
-/// GridCanvas Widget
-///
-/////////////////////////////////////////////////////////////////////////////////////////////////////
-// TODO: Keep as widget to perform scaling/translation of child children paint methods
-// TODO: Move Snapping System out of main to lib and attach to Canvas
-// TODO: Add canvas to grid widget
-pub struct GridCanvas<T: GridItem + PartialEq + Debug>
-where
-    GridCanvasData<T>: Data,
-{
-    start_pos: GridIndex,
-    state: GridState,
-    // canvas: WidgetPod<GridCanvasData<T>, Canvas<GridCanvasData<T>>>,
-    canvas: Canvas<GridCanvasData<T>>,
-}
+    /// Number of items of each kind, either on the whole grid or inside the named region
+    pub fn item_counts(&self, region: Option<&str>) -> HashMap<T, usize> {
+        let mut counts = HashMap::new();
+        let mut count = |item: &T| *counts.entry(*item).or_insert(0) += 1;
+        match region {
+            Some(name) => self.cells_in_region(name).values().for_each(count),
+            None => self.grid.iter().for_each(|(_, item)| count(item)),
+        }
+        counts
+    }
 
-impl<T: Clone + GridItem + Debug> GridCanvas<T>
-where
-    GridCanvasData<T>: Data,
-{
-    pub fn new() -> Self {
-        let canvas = Canvas::new();
-        GridCanvas {
-            start_pos: GridIndex { row: 0, col: 0 },
-            state: GridState::Idle,
-            // canvas: WidgetPod::new(canvas),
-            canvas,
+    /// Replaces the item at `pos` with an edited copy as a single undo step, e.g. from the item
+    /// editor popup. The old item has to be removable and the new one has to fit on the cell
+    /// once it is empty
+    pub fn edit_item(&mut self, pos: GridIndex, item: T) -> Result<(), String> {
+        if self.read_only {
+            return Err(String::from("Grid is read-only"));
+        }
+        let Some(previous_item) = self.grid.get(&pos).copied() else {
+            return Err(String::from("Cell is empty"));
+        };
+        if previous_item == item {
+            return Ok(());
+        }
+        if let Some(reason) = previous_item
+            .can_remove_reason()
+            .or_else(|| item.can_add_reason(None))
+        {
+            return Err(reason);
         }
+        self.insert_item(pos, item);
+        Ok(())
     }
 
-    pub fn invalidation_area(&self, pos: GridIndex, cell_size: f64) -> Rect {
-        let point = Point {
-            x: cell_size * pos.col as f64,
-            y: cell_size * pos.row as f64,
+    /// Places an item dragged out of another grid at `pos` as a single undo step. Copies follow
+    /// the add rules of the item and moves its move rules
+    pub fn accept_drop(&mut self, pos: GridIndex, drag: &ItemDrag<T>) -> Result<(), String> {
+        if self.read_only {
+            return Err(String::from("Grid is read-only"));
+        }
+        let rejection = match drag.mode {
+            DragMode::Copy => self.add_rejection(&pos, &drag.item),
+            DragMode::Move => self.move_rejection(&pos, &drag.item),
         };
-        Rect::from_origin_size(
-            point,
-            Size {
-                width: cell_size,
-                height: cell_size,
-            },
-        )
-    }
-
-    // For index based layout containers the position will be replaced by an index
-    // Might need two variants for this: add and add_relocate in case you don't want
-    // to remove the the exist at the to position. Useful for drag and drop between
-    // different containers
-    // A third method
-    pub fn add_child(&mut self, child: impl Widget<GridCanvasData<T>> + 'static, from: PointKey) {
-        let canvas = &mut self.canvas;
-        let delete_index = canvas.position_map.remove(&from);
-
-        if let Some(delete_index) = delete_index {
-            let last_index = canvas.children.len() - 1;
-            let child = canvas.children.remove(last_index);
-            if last_index != delete_index {
-                // Update position map
-                if let Child::Explicit { position, .. } = &child {
-                    let key: PointKey = <Point as Into<PointKey>>::into(*position);
-                    canvas.position_map.remove(&key);
-                    canvas.position_map.insert(key, delete_index);
-                }
-                canvas.children.remove(delete_index);
-                canvas.children.insert(delete_index, child);
-            }
-        }
-
-        let inner: WidgetPod<GridCanvasData<T>, Box<dyn Widget<GridCanvasData<T>>>> =
-            WidgetPod::new(Box::new(child));
-        let index = canvas.children.len();
-        canvas.children.insert(
-            index,
-            Child::Explicit {
-                inner,
-                position: from.clone().into(),
-            },
-        );
-        canvas.position_map.insert(from, index);
-    }
-
-    // For index based layout containers the position will be replaced by an index
-    pub fn remove_child(&mut self, from: PointKey) {
-        // Swap item at index with last item and then delete
-        let canvas = &mut self.canvas;
-        let delete_index = canvas.position_map.remove(&from);
-        let last_index = canvas.children.len() - 1;
-        if let Some(delete_index) = delete_index {
-            let child = canvas.children.remove(last_index);
-            if last_index != delete_index {
-                // Update position map
-                if let Child::Explicit { position, .. } = &child {
-                    let key: PointKey = <Point as Into<PointKey>>::into(*position);
-                    canvas.position_map.remove(&key);
-                    canvas.position_map.insert(key, delete_index);
-                }
-                canvas.children.remove(delete_index);
-                canvas.children.insert(delete_index, child);
-            }
-        }
-    }
-
-    // For index based layout containers the position will be replaced by an index
-    // Might need two variants for this: move and move_relocate in case you don't want
-    // to remove the the exist at the to position. Useful for drag and drop within the
-    // same container
-    pub fn move_child(&mut self, from: PointKey, to: PointKey) {
-        let canvas = &mut self.canvas;
-        let index_from = canvas.position_map.remove(&from);
-
-        if let Some(old_index) = index_from {
-            let inner = canvas.children.remove(old_index);
-            match inner {
-                Child::Explicit { inner, .. } => {
-                    let index = canvas.children.len();
-                    canvas.children.insert(
-                        index,
-                        Child::Explicit {
-                            inner,
-                            position: to.clone().into(),
-                        },
-                    );
-                    canvas.position_map.insert(to, index);
-                }
-                _ => panic!(),
-            }
+        if let Some(reason) = rejection {
+            return Err(reason);
         }
+        self.insert_item(pos, drag.item);
+        Ok(())
     }
 
-    fn advance(&mut self, item: TapeItem<GridIndex, T>, data: &GridCanvasData<T>) {
-        let size = Size::new(data.snap_data.cell_size, data.snap_data.cell_size);
-        match item {
-            TapeItem::Add(grid_index, item, _) => {
-                let from: PointKey = data
-                    .snap_data
-                    .get_grid_position(grid_index.row, grid_index.col)
-                    .into();
-                let child = GridChild::new(item.get_short_text(), item.get_color(), size);
-                self.add_child(child, from);
-            }
-            TapeItem::Remove(grid_index, _) => {
-                let from: PointKey = data
-                    .snap_data
-                    .get_grid_position(grid_index.row, grid_index.col)
-                    .into();
-                self.remove_child(from);
-            }
-            TapeItem::Move(from_grid_index, to_grid_index, _) => {
-                let from: PointKey = data
-                    .snap_data
-                    .get_grid_position(from_grid_index.row, from_grid_index.col)
-                    .into();
-                let to: PointKey = data
-                    .snap_data
-                    .get_grid_position(to_grid_index.row, to_grid_index.col)
-                    .into();
-                self.move_child(from, to);
-            }
-            TapeItem::BatchAdd(items) => {
-                for (grid_index, (item, _)) in items.into_iter() {
-                    let from: PointKey = data
-                        .snap_data
-                        .get_grid_position(grid_index.row, grid_index.col)
-                        .into();
-                    let child = GridChild::new(item.get_short_text(), item.get_color(), size);
-                    self.add_child(child, from);
-                }
-            }
-            TapeItem::BatchRemove(items) => {
-                for (grid_index, _) in items {
-                    let from: PointKey = data
-                        .snap_data
-                        .get_grid_position(grid_index.row, grid_index.col)
-                        .into();
-                    self.remove_child(from);
-                }
-            }
+    /// Removes a moved item another grid has taken, see [`accept_drop`](Self::accept_drop).
+    /// Returns false if the item is no longer on its cell or can not be removed
+    pub fn remove_dropped(&mut self, drag: &ItemDrag<T>) -> bool {
+        let removal = Vector::unit(TapeItem::Remove(drag.from, drag.item));
+        self.apply_batch(removal).is_complete()
+    }
+
+    // Subscription methods
+    /// Calls `subscriber` with every item played on the grid from now on, including the
+    /// provisional items of a running stroke, and with every item undone. Derived structures
+    /// can follow the grid incrementally this way instead of being rebuilt. Subscribers are
+    /// shared by the clones of the data and must not subscribe or unsubscribe themselves.
+    pub fn subscribe(
+        &mut self,
+        subscriber: impl FnMut(GridChange<'_, T>) + 'static,
+    ) -> SubscriptionId {
+        self.subscribers.add(Box::new(subscriber))
+    }
+
+    /// Calls `sender` with an item for every local change to the grid, in the order they are
+    /// made: the items played, including those of a running stroke, and the inverse of the
+    /// items undone. Playing them on another grid with [`apply_remote`](Self::apply_remote)
+    /// keeps it in sync, e.g. by sending them over a channel. Items from `apply_remote` are not
+    /// passed on, so two grids can send to each other. Remove it with
+    /// [`unsubscribe`](Self::unsubscribe)
+    pub fn subscribe_operations(
+        &mut self,
+        mut sender: impl FnMut(&TapeItem<GridIndex, T>) + 'static,
+    ) -> SubscriptionId {
+        self.subscribe(move |change| match change {
+            GridChange::Applied(item) => sender(item),
+            GridChange::Reverted(item) => sender(&item.inverse()),
+            GridChange::Remote(_) => {}
+        })
+    }
+
+    /// Removes a subscriber, returning false if it was already gone
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        self.subscribers.remove(id)
+    }
+
+    /// Plays an item made by another grid, see [`subscribe_operations`](Self::subscribe_operations).
+    /// The item is not recorded on the undo tape, so undo only covers the local edits, and the
+    /// rules of the items are not checked. Fails without changing anything if a cell does not
+    /// hold what the item expects to find there, in which case the grids have drifted apart and
+    /// can be brought back together with [`diff`](Self::diff)
+    pub fn apply_remote(&mut self, item: TapeItem<GridIndex, T>) -> Result<(), String> {
+        let mut grid = self.grid.clone();
+        if let Err(pos) = play_expected(&mut grid, &item) {
+            return Err(format!("Cell ({}, {}) has changed", pos.row, pos.col));
         }
+        self.grid = grid;
+        self.dirty = true;
+        if let Some(graph) = &mut self.graph_view {
+            Arc::make_mut(graph).advance(item.clone());
+        }
+        self.subscribers.notify_remote(&item);
+        if self.is_recording_session() {
+            self.record_event(SessionEvent::Remote(SessionOp::from_tape(&item)));
+        }
+        Ok(())
     }
 
-    fn rewind(&mut self, item: TapeItem<GridIndex, T>, data: &GridCanvasData<T>) {
-        let size = Size::new(data.snap_data.cell_size, data.snap_data.cell_size);
-        match item {
-            TapeItem::Add(grid_index, _, previous_item) => {
-                let from: PointKey = data
-                    .snap_data
-                    .get_grid_position(grid_index.row, grid_index.col)
-                    .into();
-                self.remove_child(from.clone());
-                if let Some(item) = previous_item {
-                    let child = GridChild::new(item.get_short_text(), item.get_color(), size);
-                    self.add_child(child, from);
+    // Session methods
+    /// Starts recording the editing operations performed on the data, see [`Session`]. A
+    /// session that is already being recorded is thrown away
+    pub fn start_session(&mut self) {
+        self.session = Some(SessionRecorder::new());
+    }
+
+    /// Stops recording and returns the operations recorded since
+    /// [`start_session`](Self::start_session)
+    pub fn stop_session(&mut self) -> Option<Session<T>> {
+        self.session.take().map(SessionRecorder::finish)
+    }
+
+    pub fn is_recording_session(&self) -> bool {
+        self.session.is_some()
+    }
+
+    pub(crate) fn record_event(&mut self, event: SessionEvent<T>) {
+        if let Some(session) = self.session.as_mut() {
+            session.record(event);
+        }
+    }
+
+    /// Performs a recorded operation the way the widget or the caller did when it was
+    /// recorded. The operation is recorded again while a session is being recorded
+    pub fn apply_session_event(&mut self, event: &SessionEvent<T>) -> Result<(), String> {
+        match event {
+            SessionEvent::SetAction(action) => self.set_action(*action),
+            // The recorded EndStroke events close the strokes again
+            SessionEvent::Add(pos, item) => self.stroke_add_node(pos, *item)?,
+            SessionEvent::Remove(pos) => self.stroke_remove_node(pos)?,
+            SessionEvent::Move(from, to) => {
+                if !self.grid.contains_key(from) {
+                    return Err(format!("Cell ({}, {}) is empty", from.row, from.col));
                 }
+                self.move_node(from, to)?
             }
-            TapeItem::Remove(grid_index, previous_item) => {
-                let from: PointKey = data
-                    .snap_data
-                    .get_grid_position(grid_index.row, grid_index.col)
-                    .into();
-                let child = GridChild::new(
-                    previous_item.get_short_text(),
-                    previous_item.get_color(),
-                    size,
-                );
-                self.add_child(child, from);
-            }
-            TapeItem::Move(from_grid_index, to_grid_index, _) => {
-                let from: PointKey = data
-                    .snap_data
-                    .get_grid_position(from_grid_index.row, from_grid_index.col)
-                    .into();
-                let to: PointKey = data
-                    .snap_data
-                    .get_grid_position(to_grid_index.row, to_grid_index.col)
-                    .into();
-                self.move_child(to, from);
+            SessionEvent::Route(route, item) => self.commit_route(route, *item)?,
+            SessionEvent::Fill(pos, item, max_cells) => self.fill_area(*pos, *item, *max_cells)?,
+            SessionEvent::SelectSame(pos) => self.select_same(pos),
+            SessionEvent::Transform(transform) => self.transform_selection(*transform)?,
+            SessionEvent::EndStroke => self.end_stroke(),
+            SessionEvent::CancelStroke => self.cancel_stroke(),
+            SessionEvent::Apply(ops) => self.apply_session_ops(ops)?,
+            SessionEvent::Queue(steps) => {
+                // Each step sees the grid as the steps before it leave it
+                let mut grid = self.grid.clone();
+                let items = steps
+                    .iter()
+                    .map(|ops| session_item(&mut grid, ops))
+                    .collect::<Result<_, _>>()?;
+                self.queue(items);
             }
-            TapeItem::BatchAdd(items) => {
-                for (grid_index, (_, previous_item)) in items.into_iter() {
-                    let from: PointKey = data
-                        .snap_data
-                        .get_grid_position(grid_index.row, grid_index.col)
-                        .into();
-                    self.remove_child(from.clone());
-                    if let Some(item) = previous_item {
-                        let child = GridChild::new(item.get_short_text(), item.get_color(), size);
-                        self.add_child(child, from);
-                    }
-                }
+            SessionEvent::Remote(ops) => {
+                let item = session_item(&mut self.grid.clone(), ops)?;
+                self.apply_remote(item)?
             }
-            TapeItem::BatchRemove(items) => {
-                for (grid_index, item) in items {
-                    let from: PointKey = data
-                        .snap_data
-                        .get_grid_position(grid_index.row, grid_index.col)
-                        .into();
-                    let child = GridChild::new(item.get_short_text(), item.get_color(), size);
-                    self.add_child(child, from);
+            SessionEvent::SetExtent(extent) => self.set_extent(*extent),
+            SessionEvent::SetReadOnly(read_only) => self.set_read_only(*read_only),
+            SessionEvent::Seek(index) => self.seek(*index),
+            SessionEvent::SwitchBranch(index) => {
+                if !self.switch_branch(*index) {
+                    return Err(format!("There is no branch {index} to switch to"));
                 }
             }
+            SessionEvent::Clear => self.clear_all(),
         }
+        Ok(())
     }
-}
 
-impl<T: GridItem + PartialEq + Debug> Widget<GridCanvasData<T>> for GridCanvas<T>
+    // Plays recorded cell changes as a single undo step. The rules of the items are not checked,
+    // but every cell has to hold what the changes expect, otherwise none of them are applied
+    fn apply_session_ops(&mut self, ops: &[SessionOp<T>]) -> Result<(), String> {
+        self.end_stroke();
+        let mut grid = self.grid.clone();
+        let item = session_item(&mut grid, ops)?;
+        if !ops.is_empty() {
+            self.grid = grid;
+            self.push_change(item, Some(SessionEvent::Apply(ops.to_vec())));
+            self.sync_derived();
+        }
+        Ok(())
+    }
+
+    // Macro methods
+    pub fn replay(&mut self, recording: Vector<TapeItem<GridIndex, T>>, offset: GridIndex) {
+        let list = recording
+            .into_iter()
+            .map(|item| item.map_keys(|pos| pos + offset))
+            .collect();
+        self.submit_to_stack_and_play(list);
+    }
+
+    // Graph view methods
+    /// Starts keeping a lattice of the given size in sync with the grid, with one vertex per
+    /// occupied cell. Cells outside the lattice are ignored.
+    pub fn enable_graph_view(&mut self, columns: usize, rows: usize) {
+        let mut lattice = Lattice2D::new(columns, rows);
+        for pos in self.grid.keys() {
+            if let Some(vertex) = to_vertex(&pos) {
+                lattice.add_vertex(vertex);
+            }
+        }
+        self.graph_view = Some(Arc::new(DynamicLatticeGraph::new(lattice)));
+    }
+
+    pub fn disable_graph_view(&mut self) -> Option<DynamicLatticeGraph> {
+        self.graph_view.take().map(Arc::unwrap_or_clone)
+    }
+
+    pub fn graph_view(&self) -> Option<&DynamicLatticeGraph> {
+        self.graph_view.as_deref()
+    }
+
+    /// The graph view as a graph to search on, without copying it. A search holding it keeps
+    /// seeing the grid as it was, the next edit copies the graph view instead of changing it
+    pub fn search_graph(&self) -> Option<SearchGraph> {
+        self.graph_view.clone().map(SearchGraph::from)
+    }
+
+    // Brings the graph view and the subscribers up to date with the latest change to the grid
+    pub(crate) fn sync_derived(&mut self) {
+        let (reverted, applied) = self.grid_delta();
+        if let Some(graph) = &mut self.graph_view {
+            let graph = Arc::make_mut(graph);
+            for item in reverted.iter() {
+                graph.rewind(item.clone());
+            }
+            for item in applied.iter() {
+                graph.advance(item.clone());
+            }
+        }
+        self.subscribers.notify(&reverted, &applied);
+    }
+
+    /// Applies the vertices added and removed by a graph algorithm to the grid as a single undo
+    /// step. Added vertices are filled with `item`
+    pub fn apply_graph_edits(
+        &mut self,
+        added: &[(usize, usize)],
+        removed: &[(usize, usize)],
+        item: T,
+    ) {
+        self.end_stroke();
+        let removed: HashMap<GridIndex, T> = removed
+            .iter()
+            .filter_map(|(col, row)| {
+                let pos = GridIndex::new(*row as isize, *col as isize);
+                self.grid.get(&pos).map(|current_item| (pos, *current_item))
+            })
+            .collect();
+        let added: HashMap<GridIndex, (T, Option<T>)> = added
+            .iter()
+            .map(|(col, row)| {
+                let pos = GridIndex::new(*row as isize, *col as isize);
+                (pos, (item, self.grid.get(&pos).copied()))
+            })
+            .collect();
+
+        let mut list = Vector::new();
+        if !removed.is_empty() {
+            list.push_back(TapeItem::BatchRemove(removed));
+        }
+        if !added.is_empty() {
+            list.push_back(TapeItem::BatchAdd(added));
+        }
+        self.submit_group_and_play(list);
+    }
+
+    /// Fills every vertex of the components with the item `item_for` gives its label as a
+    /// single undo step, so each component shows up in its own color. Items whose color
+    /// follows their net, like net colored routes, tell the components apart best
+    pub fn paint_components(&mut self, components: &Components, item_for: impl Fn(usize) -> T) {
+        self.end_stroke();
+        let added: HashMap<GridIndex, (T, Option<T>)> = components
+            .vertices()
+            .map(|((col, row), label)| {
+                let pos = GridIndex::new(row as isize, col as isize);
+                (pos, (item_for(label), self.grid.get(&pos).copied()))
+            })
+            .collect();
+        if added.is_empty() {
+            return;
+        }
+        self.submit_to_stack_and_process(Vector::unit(TapeItem::BatchAdd(added)));
+    }
+
+    // ECO methods
+    /// Freezes the current grid as the baseline that later edits are compared against
+    pub fn freeze_baseline(&mut self) {
+        self.end_stroke();
+        self.eco_baseline = Some(self.grid.to_hash_map());
+    }
+
+    pub fn release_baseline(&mut self) -> Option<HashMap<GridIndex, T>> {
+        self.eco_baseline.take()
+    }
+
+    pub fn is_eco(&self) -> bool {
+        self.eco_baseline.is_some()
+    }
+
+    /// Minimal list of changes made since the baseline was frozen, with one group per item kind.
+    /// Cells that were cleared are grouped under the item they used to hold, and the groups are
+    /// ordered by their first cell in row-major order.
+    /// The list is empty when no baseline has been frozen.
+    pub fn change_list(&self) -> Vector<TapeItem<GridIndex, T>> {
+        let baseline = match &self.eco_baseline {
+            Some(baseline) => baseline,
+            None => return Vector::new(),
+        };
+
+        let mut added: StdHashMap<T, HashMap<GridIndex, (T, Option<T>)>> = StdHashMap::new();
+        let mut removed: StdHashMap<T, HashMap<GridIndex, T>> = StdHashMap::new();
+        for item in TapeItem::diff(baseline, &self.grid.to_hash_map()) {
+            match item {
+                TapeItem::Add(pos, current_item, previous_item) => {
+                    added
+                        .entry(current_item)
+                        .or_default()
+                        .insert(pos, (current_item, previous_item));
+                }
+                TapeItem::Remove(pos, previous_item) => {
+                    removed
+                        .entry(previous_item)
+                        .or_default()
+                        .insert(pos, previous_item);
+                }
+                _ => {}
+            }
+        }
+
+        let kinds: HashSet<T> = added.keys().chain(removed.keys()).copied().collect();
+        let mut kinds: Vec<T> = kinds.into_iter().collect();
+        // Every cell is in a single group, so the first cells tell the groups apart
+        kinds.sort_by_key(|kind| {
+            let added_cells = added.get(kind).into_iter().flat_map(|map| map.keys());
+            let removed_cells = removed.get(kind).into_iter().flat_map(|map| map.keys());
+            added_cells
+                .chain(removed_cells)
+                .map(|pos| (pos.row, pos.col))
+                .min()
+        });
+        kinds
+            .into_iter()
+            .map(|kind| {
+                let mut group = Vector::new();
+                if let Some(map) = removed.remove(&kind) {
+                    group.push_back(TapeItem::BatchRemove(map));
+                }
+                if let Some(map) = added.remove(&kind) {
+                    group.push_back(TapeItem::BatchAdd(map));
+                }
+                TapeItem::Group(group)
+            })
+            .collect()
+    }
+
+    /// Applies a change list, possibly produced by another document, as a single undo step.
+    /// The replaced items are taken from this grid and removals of empty cells are dropped.
+    pub fn apply_change_list(&mut self, list: Vector<TapeItem<GridIndex, T>>) {
+        self.end_stroke();
+        let list = list
+            .into_iter()
+            .filter_map(|item| self.rebase(item))
+            .collect();
+        self.submit_group_and_play(list);
+    }
+
+    fn rebase(&self, item: TapeItem<GridIndex, T>) -> Option<TapeItem<GridIndex, T>> {
+        match item {
+            TapeItem::Add(pos, current_item, _) => Some(TapeItem::Add(
+                pos,
+                current_item,
+                self.grid.get(&pos).copied(),
+            )),
+            TapeItem::Remove(pos, _) => self
+                .grid
+                .get(&pos)
+                .map(|previous_item| TapeItem::Remove(pos, *previous_item)),
+            TapeItem::Move(from_pos, to_pos, _) => self
+                .grid
+                .get(&from_pos)
+                .map(|current_item| TapeItem::Move(from_pos, to_pos, *current_item)),
+            TapeItem::BatchAdd(map) => {
+                let map: HashMap<_, _> = map
+                    .into_iter()
+                    .map(|(pos, (current_item, _))| {
+                        (pos, (current_item, self.grid.get(&pos).copied()))
+                    })
+                    .collect();
+                (!map.is_empty()).then_some(TapeItem::BatchAdd(map))
+            }
+            TapeItem::BatchRemove(map) => {
+                let map: HashMap<_, _> = map
+                    .into_iter()
+                    .filter_map(|(pos, _)| self.grid.get(&pos).map(|item| (pos, *item)))
+                    .collect();
+                (!map.is_empty()).then_some(TapeItem::BatchRemove(map))
+            }
+            TapeItem::Group(items) => {
+                let items: Vector<_> = items
+                    .into_iter()
+                    .filter_map(|item| self.rebase(item))
+                    .collect();
+                (!items.is_empty()).then_some(TapeItem::Group(items))
+            }
+        }
+    }
+}
+
+impl<T: GridItem + PartialEq + Debug> PlaybackDataAccess for GridCanvasData<T>
+where
+    GridCanvasData<T>: Data,
+{
+    fn get_position(&self) -> usize {
+        self.save_data.position()
+    }
+
+    fn get_len(&self) -> usize {
+        self.save_data.len()
+    }
+
+    fn set_position(&mut self, index: usize) {
+        self.seek(index);
+    }
+}
+
+impl<T: GridItem + PartialEq + Debug> GridHeaderDataAccess for GridCanvasData<T>
+where
+    GridCanvasData<T>: Data,
+{
+    fn get_snap_data(&self) -> &GridSnapData {
+        &self.snap_data
+    }
+
+    fn get_selection(&self) -> Option<GridSelection> {
+        self.selection
+    }
+
+    fn set_selection(&mut self, selection: Option<GridSelection>) {
+        self.selection = selection;
+    }
+
+    fn get_extent(&self) -> Option<GridIndex> {
+        self.extent
+    }
+
+    fn set_extent(&mut self, extent: Option<GridIndex>) {
+        GridCanvasData::set_extent(self, extent);
+    }
+}
+
+/// Read-only lens to [`GridCanvasData::is_dirty`], e.g. for an "unsaved changes" label or to
+/// disable a save button. Writes through it are ignored, use `mark_saved` instead
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DirtyLens;
+
+impl<T: GridItem + PartialEq + Debug> Lens<GridCanvasData<T>, bool> for DirtyLens
+where
+    GridCanvasData<T>: Data,
+{
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &GridCanvasData<T>, f: F) -> V {
+        f(&data.is_dirty())
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut GridCanvasData<T>, f: F) -> V {
+        let mut dirty = data.is_dirty();
+        f(&mut dirty)
+    }
+}
+
+/// Lens to [`GridCanvasData::is_read_only`], e.g. for a switch. Writes go through
+/// `set_read_only` so that a session being recorded sees them
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReadOnlyLens;
+
+impl<T: GridItem + PartialEq + Debug> Lens<GridCanvasData<T>, bool> for ReadOnlyLens
+where
+    GridCanvasData<T>: Data,
+{
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &GridCanvasData<T>, f: F) -> V {
+        f(&data.read_only)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut GridCanvasData<T>, f: F) -> V {
+        let mut read_only = data.read_only;
+        let value = f(&mut read_only);
+        if read_only != data.read_only {
+            data.set_read_only(read_only);
+        }
+        value
+    }
+}
+
+// Areas covered by the horizontal and vertical lines of the crosshair through `pointer`, drawn
+// half as wide as the cursor, with a pixel to spare for antialiasing
+fn crosshair_rects(pointer: Point, size: Size, width: f64) -> [Rect; 2] {
+    let reach = width / 4.0 + 1.0;
+    [
+        Rect::new(0.0, pointer.y - reach, size.width, pointer.y + reach),
+        Rect::new(pointer.x - reach, 0.0, pointer.x + reach, size.height),
+    ]
+}
+
+// Longest side in pixels of an exported image
+const MAX_IMAGE_SIDE: usize = 16384;
+
+// Size in pixels of the image of `extent` cells and a margin of one cell around them
+fn image_size(extent: GridIndex, cell_size: f64, scale: f64) -> Result<(usize, usize), String> {
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err(format!("Scale must be a positive number, got {scale}"));
+    }
+    let side = |cells: isize| (cells as f64 + 2.0) * cell_size * scale;
+    let (width, height) = (side(extent.col).ceil(), side(extent.row).ceil());
+    if !(width <= MAX_IMAGE_SIDE as f64 && height <= MAX_IMAGE_SIDE as f64) {
+        return Err(format!(
+            "Image of {width}×{height} pixels is larger than {MAX_IMAGE_SIDE}×{MAX_IMAGE_SIDE}"
+        ));
+    }
+    Ok((width as usize, height as usize))
+}
+
+// Writes an image as a PNG file
+#[cfg(feature = "png")]
+fn save_png(image: &ImageBuf, path: &Path) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| err.to_string())?;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        image.width() as u32,
+        image.height() as u32,
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+    writer
+        .write_image_data(image.raw_pixels())
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "png"))]
+fn save_png(_image: &ImageBuf, _path: &Path) -> Result<(), String> {
+    Err(String::from("Saving images needs the png feature"))
+}
+
+// Plays recorded cell changes on the grid, turning them into a tape item that holds what each
+// change replaced. Fails on the first change that finds a cell empty or a target cell taken
+fn session_item<T: Clone + Hash + Eq>(
+    grid: &mut ChunkedGrid<T>,
+    ops: &[SessionOp<T>],
+) -> Result<TapeItem<GridIndex, T>, String> {
+    let mut items = Vector::new();
+    for op in ops {
+        let item = match op {
+            SessionOp::Put(pos, item) => TapeItem::Add(*pos, item.clone(), grid.get(pos).cloned()),
+            SessionOp::Take(pos) => match grid.get(pos) {
+                Some(item) => TapeItem::Remove(*pos, item.clone()),
+                None => return Err(format!("Cell ({}, {}) is empty", pos.row, pos.col)),
+            },
+            SessionOp::Shift(from, to) => match (grid.get(from), grid.contains_key(to)) {
+                (Some(item), false) => TapeItem::Move(*from, *to, item.clone()),
+                (None, _) => return Err(format!("Cell ({}, {}) is empty", from.row, from.col)),
+                (_, true) => return Err(format!("Cell ({}, {}) is taken", to.row, to.col)),
+            },
+        };
+        grid.advance(item.clone());
+        items.push_back(item);
+    }
+    Ok(TapeItem::Group(items))
+}
+
+// Lattice vertex of a grid cell. Cells with negative coordinates have none
+fn to_vertex(pos: &GridIndex) -> Option<(usize, usize)> {
+    (pos.row >= 0 && pos.col >= 0).then_some((pos.col as usize, pos.row as usize))
+}
+
+impl<V: Clone> CassettePlayer<GridIndex, V> for DynamicLatticeGraph {
+    fn advance(&mut self, item: TapeItem<GridIndex, V>) {
+        match item {
+            TapeItem::Add(pos, _, _) => {
+                if let Some(vertex) = to_vertex(&pos) {
+                    self.add_vertex(vertex);
+                }
+            }
+            TapeItem::Remove(pos, _) => {
+                if let Some(vertex) = to_vertex(&pos) {
+                    self.remove_vertex(vertex);
+                }
+            }
+            TapeItem::Move(from_pos, to_pos, _) => {
+                if let Some(vertex) = to_vertex(&from_pos) {
+                    self.remove_vertex(vertex);
+                }
+                if let Some(vertex) = to_vertex(&to_pos) {
+                    self.add_vertex(vertex);
+                }
+            }
+            TapeItem::BatchAdd(items) => {
+                for vertex in items.keys().filter_map(to_vertex) {
+                    self.add_vertex(vertex);
+                }
+            }
+            TapeItem::BatchRemove(items) => {
+                for vertex in items.keys().filter_map(to_vertex) {
+                    self.remove_vertex(vertex);
+                }
+            }
+            TapeItem::Group(items) => {
+                for item in items {
+                    self.advance(item);
+                }
+            }
+        }
+    }
+
+    fn rewind(&mut self, item: TapeItem<GridIndex, V>) {
+        match item {
+            TapeItem::Add(pos, _, previous_item) => {
+                if previous_item.is_none() {
+                    if let Some(vertex) = to_vertex(&pos) {
+                        self.remove_vertex(vertex);
+                    }
+                }
+            }
+            TapeItem::Remove(pos, _) => {
+                if let Some(vertex) = to_vertex(&pos) {
+                    self.add_vertex(vertex);
+                }
+            }
+            TapeItem::Move(from_pos, to_pos, _) => {
+                if let Some(vertex) = to_vertex(&to_pos) {
+                    self.remove_vertex(vertex);
+                }
+                if let Some(vertex) = to_vertex(&from_pos) {
+                    self.add_vertex(vertex);
+                }
+            }
+            TapeItem::BatchAdd(items) => {
+                for (pos, (_, previous_item)) in items.iter() {
+                    if previous_item.is_none() {
+                        if let Some(vertex) = to_vertex(pos) {
+                            self.remove_vertex(vertex);
+                        }
+                    }
+                }
+            }
+            TapeItem::BatchRemove(items) => {
+                for vertex in items.keys().filter_map(to_vertex) {
+                    self.add_vertex(vertex);
+                }
+            }
+            TapeItem::Group(items) => {
+                for item in items.into_iter().rev() {
+                    self.rewind(item);
+                }
+            }
+        }
+    }
+}
+
+const HALO_ALPHA: f64 = 0.25;
+const ROUTE_PREVIEW_ALPHA: f64 = 0.5;
+// Bounds the search that runs on every mouse move while routing
+const ROUTE_SEARCH_LIMIT: usize = 4096;
+const FILL_LIMIT: usize = 4096;
+const REJECTED_ALPHA: f64 = 0.6;
+const REJECTED_FLASH: Duration = Duration::from_millis(300);
+const LOCK_BADGE_SIZE: f64 = 24.0;
+const LOCK_BADGE_MARGIN: f64 = 6.0;
+// Space between the item editor popup and its backdrop
+const EDITOR_PADDING: f64 = 6.0;
+const DROP_GHOST_ALPHA: f64 = 0.5;
+// Distance of the off-screen content arrow from the edge of the widget
+const INDICATOR_MARGIN: f64 = 28.0;
+const INDICATOR_SIZE: f64 = 10.0;
+const PROGRESS_HEIGHT: f64 = 3.0;
+// Alpha of the overscroll glow at the edge once the overscroll is at its limit
+const OVERSCROLL_ALPHA: f64 = 0.6;
+const SEARCH_ALPHA: f64 = 0.35;
+// Smallest cell on screen, in pixels, with room for the costs of a search node
+const SEARCH_COSTS_MIN_CELL: f64 = 36.0;
+// Cells changed in one update above which the whole widget is repainted
+const DAMAGE_CELL_LIMIT: usize = 256;
+const REGION_ALPHA: f64 = 0.15;
+// Thickness of an edge item as a fraction of the cell size
+const EDGE_WIDTH: f64 = 0.2;
+// Thickness of a wire as a fraction of the cell size
+const WIRE_WIDTH: f64 = 0.3;
+const SELECTION_ALPHA: f64 = 0.3;
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// GridCanvas Widget
+///
+/////////////////////////////////////////////////////////////////////////////////////////////////////
+/// How the items of the grid are drawn
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RenderMode {
+    /// One `GridChild` widget per occupied cell
+    Widgets,
+    /// Cells painted straight from the grid with cached text layouts. Scales to much larger
+    /// grids but the items are not widgets, so they get no hot or active state
+    Lightweight,
+}
+
+/// What a gesture does to the cell it lands on
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GestureAction {
+    None,
+    /// Fills the empty area around the cell with the current item
+    Fill,
+    /// Selects every cell holding the same kind of item
+    SelectSame,
+    /// Sends `CONTEXT_MENU_REQUESTED`
+    ContextMenu,
+    /// Sends `GridCanvas::CELL_INSPECTED`
+    Inspect,
+    /// Opens the item editor on an occupied cell, see `GridCanvas::with_item_editor`
+    Edit,
+}
+
+/// Alternatives to right-click for single-button mice and touch screens. The first click of a
+/// double-click and the press of a long-press are still handled as a normal click.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GestureConfig {
+    pub double_click: GestureAction,
+    pub long_press: GestureAction,
+    pub long_press_delay: Duration,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            double_click: GestureAction::None,
+            long_press: GestureAction::None,
+            long_press_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+// TODO: Keep as widget to perform scaling/translation of child children paint methods
+// TODO: Move Snapping System out of main to lib and attach to Canvas
+// TODO: Add canvas to grid widget
+pub struct GridCanvas<T: GridItem + PartialEq + Debug>
+where
+    GridCanvasData<T>: Data,
+{
+    start_pos: GridIndex,
+    // Cell the running stroke or move started from, the anchor of Shift-constrained drags
+    stroke_origin: GridIndex,
+    state: GridState,
+    route_preview: Vec<GridIndex>,
+    // Halo cells collected when the running route started, searched around on every move
+    route_halos: HashMap<GridIndex, T>,
+    // Distance shown by the measure tool, until the next measurement or a change of action
+    measurement: Option<Measurement>,
+    // Cells flashed red after an edit on them was rejected
+    rejected_cells: Vec<GridIndex>,
+    flash_timer: TimerToken,
+    render_mode: RenderMode,
+    gestures: GestureConfig,
+    // Cell and window position of the press a long-press timer is running for
+    long_press: Option<(GridIndex, Point)>,
+    long_press_timer: TimerToken,
+    // Tape position and macro length when the running interaction started, restored on cancel
+    running_start: (usize, usize),
+    // Cell last picked under the pointer, highlighted and used by hysteresis snapping
+    hover_cell: Option<GridIndex>,
+    // Last pointer position over the widget, for the crosshair
+    pointer: Option<Point>,
+    // Most cells whose children are updated per frame, None to update them all at once
+    child_budget: Option<usize>,
+    // Single cell items waiting for their children to be updated, true to advance and false to
+    // rewind, and how many were queued since the queue was last empty
+    pending_children: VecDeque<(TapeItem<GridIndex, T>, bool)>,
+    pending_total: usize,
+    // Origin of the widget in window coordinates, for hit testing
+    window_origin: Point,
+    // Label layouts shared by the children and the lightweight mode
+    labels: LabelCache,
+    item_editor_builder: Option<ItemEditorBuilder<T>>,
+    // Popup opened by the edit gesture, painted over the grid until it is closed
+    item_editor: Option<ItemEditor<T>>,
+    drag_out: Option<DragMode>,
+    // Item pressed on while dragging out is enabled, and whether it has left the widget
+    drag: Option<ItemDrag<T>>,
+    dragging_out: bool,
+    // Cell and item another grid is dragging over this one
+    drop_ghost: Option<(GridIndex, T)>,
+    // canvas: WidgetPod<GridCanvasData<T>, Canvas<GridCanvasData<T>>>,
+    canvas: Canvas<GridCanvasData<T>>,
+    // Index of the child of every occupied cell and the cell of every child
+    cell_children: HashMap<GridIndex, usize>,
+    child_cells: Vec<GridIndex>,
+}
+
+impl<T: Clone + GridItem + Debug> GridCanvas<T>
+where
+    GridCanvasData<T>: Data,
+{
+    // Notifications sent when user interaction changes the grid
+    /// Payload is the cell and the item that was placed on it
+    pub const CELL_ADDED: Selector<(GridIndex, T)> = Selector::new("grid-cell-added");
+    /// Payload is the cell and the item that was removed from it
+    pub const CELL_REMOVED: Selector<(GridIndex, T)> = Selector::new("grid-cell-removed");
+    /// Payload is the cell the item was moved from, the cell it was moved to and the item
+    pub const CELL_MOVED: Selector<(GridIndex, GridIndex, T)> = Selector::new("grid-cell-moved");
+    /// Sent by the inspect gesture. Payload is the cell and the item on it
+    pub const CELL_INSPECTED: Selector<(GridIndex, Option<T>)> =
+        Selector::new("grid-cell-inspected");
+
+    pub fn new() -> Self {
+        let canvas = Canvas::new();
+        GridCanvas {
+            start_pos: GridIndex { row: 0, col: 0 },
+            stroke_origin: GridIndex { row: 0, col: 0 },
+            state: GridState::Idle,
+            route_preview: Vec::new(),
+            route_halos: HashMap::new(),
+            measurement: None,
+            rejected_cells: Vec::new(),
+            flash_timer: TimerToken::INVALID,
+            render_mode: RenderMode::Widgets,
+            gestures: GestureConfig::default(),
+            long_press: None,
+            long_press_timer: TimerToken::INVALID,
+            running_start: (0, 0),
+            hover_cell: None,
+            pointer: None,
+            child_budget: None,
+            pending_children: VecDeque::new(),
+            pending_total: 0,
+            window_origin: Point::ORIGIN,
+            labels: LabelCache::new(),
+            item_editor_builder: None,
+            item_editor: None,
+            drag_out: None,
+            drag: None,
+            dragging_out: false,
+            drop_ghost: None,
+            // canvas: WidgetPod::new(canvas),
+            canvas,
+            cell_children: HashMap::new(),
+            child_cells: Vec::new(),
+        }
+    }
+
+    /// Should be picked before the widget is added, the child widgets are not migrated
+    pub fn with_render_mode(mut self, render_mode: RenderMode) -> Self {
+        self.render_mode = render_mode;
+        self
+    }
+
+    pub fn render_mode(&self) -> RenderMode {
+        self.render_mode
+    }
+
+    #[cfg(any(test, feature = "test-support"))]
+    pub(crate) fn canvas(&self) -> &Canvas<GridCanvasData<T>> {
+        &self.canvas
+    }
+
+    #[cfg(any(test, feature = "test-support"))]
+    pub(crate) fn has_child(&self, pos: GridIndex) -> bool {
+        self.cell_children.contains_key(&pos)
+    }
+
+    // True while the children are catching up with the grid, see with_child_budget
+    #[cfg(any(test, feature = "test-support"))]
+    pub(crate) fn has_pending_children(&self) -> bool {
+        !self.pending_children.is_empty()
+    }
+
+    /// Updates the children of at most `cells_per_frame` cells per animation frame instead of
+    /// all at once, so huge batches do not block the UI. A progress bar is shown until the
+    /// children catch up with the grid
+    pub fn with_child_budget(mut self, cells_per_frame: usize) -> Self {
+        self.child_budget = Some(cells_per_frame.max(1));
+        self
+    }
+
+    pub fn with_gestures(mut self, gestures: GestureConfig) -> Self {
+        self.gestures = gestures;
+        self
+    }
+
+    pub fn set_gestures(&mut self, gestures: GestureConfig) {
+        self.gestures = gestures;
+    }
+
+    /// Popup opened by `GestureAction::Edit` next to the cell, bound to a copy of its item. Every
+    /// change the popup makes is written back to the grid, an edit the item rejects closes it.
+    /// Escape, a click outside of it or `CLOSE_ITEM_EDITOR` close it as well
+    pub fn with_item_editor(
+        mut self,
+        builder: impl Fn(&EditedItem<T>) -> Box<dyn Widget<EditedItem<T>>> + 'static,
+    ) -> Self {
+        self.item_editor_builder = Some(Box::new(builder));
+        self
+    }
+
+    pub fn is_editing_item(&self) -> bool {
+        self.item_editor.is_some()
+    }
+
+    /// Lets items be dragged out of this grid and dropped on another `GridCanvas` of the app, see
+    /// [`ITEM_DRAGGED`](crate::drag_drop::ITEM_DRAGGED).
+    /// The drag starts once the pointer leaves the widget with the button held on an item, which
+    /// cancels whatever the press was doing to this grid. The other grid decides with its own
+    /// rules whether it takes the item and shows where it would land meanwhile
+    pub fn with_drag_out(mut self, mode: DragMode) -> Self {
+        self.drag_out = Some(mode);
+        self
+    }
+
+    fn notify_changes(&self, ctx: &mut EventCtx, items: &Vector<TapeItem<GridIndex, T>>)
+    where
+        T: 'static,
+    {
+        for item in items.iter() {
+            match item {
+                TapeItem::Add(pos, item, _) => {
+                    ctx.submit_notification(Self::CELL_ADDED.with((*pos, *item)));
+                }
+                TapeItem::Remove(pos, item) => {
+                    ctx.submit_notification(Self::CELL_REMOVED.with((*pos, *item)));
+                }
+                TapeItem::Move(from_pos, to_pos, item) => {
+                    ctx.submit_notification(Self::CELL_MOVED.with((*from_pos, *to_pos, *item)));
+                }
+                TapeItem::BatchAdd(map) => {
+                    for (pos, (item, _)) in map.iter() {
+                        ctx.submit_notification(Self::CELL_ADDED.with((*pos, *item)));
+                    }
+                }
+                TapeItem::BatchRemove(map) => {
+                    for (pos, item) in map.iter() {
+                        ctx.submit_notification(Self::CELL_REMOVED.with((*pos, *item)));
+                    }
+                }
+                TapeItem::Group(items) => self.notify_changes(ctx, items),
+            }
+        }
+    }
+
+    // The editing steps below do not need the event context so that tests can drive them. They
+    // return the cell and the reason of an edit that was rejected
+
+    // A button pressed on a cell while Idle picks the running action and makes its first edit
+    fn press(
+        &mut self,
+        data: &mut GridCanvasData<T>,
+        grid_index: GridIndex,
+        button: MouseButton,
+    ) -> Result<(), (GridIndex, String)> {
+        let option = data.grid.get(&grid_index);
+        if button == MouseButton::Left {
+            match data.action {
+                GridAction::Dynamic => {
+                    self.state = GridState::Running(GridAction::Dynamic);
+                    match option {
+                        Some(item) if *item == data.grid_item => data.action = GridAction::Move,
+                        _ => data.action = GridAction::Add,
+                    }
+                }
+                GridAction::Move => {
+                    if option.is_some() {
+                        self.state = GridState::Running(GridAction::Move);
+                    }
+                }
+                GridAction::Route => {
+                    // Routing starts from an existing pin
+                    if option.is_some() {
+                        self.state = GridState::Running(GridAction::Route);
+                        self.start_pos = grid_index;
+                        self.route_preview.clear();
+                        self.route_halos = data.halo_cells();
+                    }
+                }
+                GridAction::Measure => {
+                    self.state = GridState::Running(GridAction::Measure);
+                    self.measurement = Some(Measurement::new(grid_index, grid_index));
+                }
+                _ => {
+                    self.state = GridState::Running(data.action);
+                }
+            }
+        } else if button == MouseButton::Right {
+            if let GridAction::Dynamic = data.action {
+                self.state = GridState::Running(data.action);
+                data.action = GridAction::Remove;
+            }
+        }
+
+        if let GridState::Running(_) = self.state {
+            self.running_start = (data.save_data.position(), data.recorder.recording.len());
+            self.stroke_origin = grid_index;
+            let is_occupied = option.is_some();
+            if data.action == GridAction::Move && is_occupied {
+                self.start_pos = grid_index;
+            }
+            let result = if data.action == GridAction::Add {
+                data.stroke_add_node(&grid_index, data.grid_item)
+            } else if data.action == GridAction::Remove && is_occupied {
+                data.stroke_remove_node(&grid_index)
+            } else {
+                Ok(())
+            };
+            result.map_err(|reason| (grid_index, reason))?;
+        }
+        Ok(())
+    }
+
+    // Carries the running action on to the cell under the pointer
+    fn drag_to(
+        &mut self,
+        data: &mut GridCanvasData<T>,
+        mut grid_index: GridIndex,
+        shift: bool,
+    ) -> Result<(), (GridIndex, String)> {
+        // Angle snapping, or Shift, keeps the drag on a straight line from where it started
+        if matches!(
+            data.action,
+            GridAction::Add | GridAction::Remove | GridAction::Move
+        ) {
+            let snap_data = &data.snap_data;
+            match snap_data.angle_snap.filter(|_| snap_data.snap_enabled) {
+                Some(step) => grid_index = grid_index.snap_to_angle(self.stroke_origin, step),
+                None if shift => grid_index = grid_index.snap_to_line(self.stroke_origin),
+                None => {}
+            }
+        }
+        let rejected = |reason| (grid_index, reason);
+
+        match data.action {
+            GridAction::Add => data
+                .stroke_add_node(&grid_index, data.grid_item)
+                .map_err(rejected)?,
+            GridAction::Move => {
+                if self.start_pos != grid_index {
+                    data.move_node(&self.start_pos, &grid_index)
+                        .map_err(rejected)?;
+                    self.start_pos = grid_index;
+                }
+            }
+            GridAction::Remove => {
+                if data.grid.contains_key(&grid_index) {
+                    data.stroke_remove_node(&grid_index).map_err(rejected)?;
+                }
+            }
+            GridAction::Route => {
+                self.route_preview = data
+                    .find_route_around(
+                        self.start_pos,
+                        grid_index,
+                        &data.grid_item,
+                        ROUTE_SEARCH_LIMIT,
+                        &self.route_halos,
+                    )
+                    .unwrap_or_default();
+            }
+            GridAction::Measure => {
+                if let Some(measurement) = &mut self.measurement {
+                    measurement.to = grid_index;
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    // Releasing the button that started a stroke or move ends it and commits it to the tape
+    fn release(&mut self, data: &mut GridCanvasData<T>, button: MouseButton) {
+        if button == MouseButton::Right
+            && self.state == GridState::Running(GridAction::Dynamic)
+            && data.action == GridAction::Remove
+        {
+            self.state = GridState::Idle;
+            data.action = GridAction::Dynamic;
+        } else if button == MouseButton::Left
+            && self.state == GridState::Running(GridAction::Dynamic)
+        {
+            self.state = GridState::Idle;
+            data.action = GridAction::Dynamic;
+        } else if button == MouseButton::Left {
+            self.state = GridState::Idle;
+        }
+
+        if self.state == GridState::Idle {
+            data.end_stroke();
+        }
+    }
+
+    // Ends a running stroke, move or route so that the state can change underneath it
+    fn end_interaction(&mut self, ctx: &mut EventCtx, data: &mut GridCanvasData<T>) {
+        if let GridState::Running(action) = self.state {
+            if action == GridAction::Dynamic {
+                data.action = GridAction::Dynamic;
+            }
+            data.end_stroke();
+            self.route_preview.clear();
+            self.route_halos.clear();
+            self.measurement = None;
+            ctx.request_paint();
+        }
+    }
+
+    // Reverts everything the running interaction has done to the grid and returns to Idle
+    fn cancel_interaction(&mut self, ctx: &mut EventCtx, data: &mut GridCanvasData<T>) {
+        if let GridState::Running(action) = self.state {
+            let (position, recording_len) = self.running_start;
+            data.cancel_stroke();
+            // Moves are recorded one step at a time
+            if data.save_data.position() > position {
+                data.seek(position);
+                data.save_data.clear_redo();
+            }
+            data.recorder.recording.truncate(recording_len);
+            if action == GridAction::Dynamic {
+                data.action = GridAction::Dynamic;
+            }
+            self.route_preview.clear();
+            self.route_halos.clear();
+            self.measurement = None;
+            self.long_press = None;
+            self.long_press_timer = TimerToken::INVALID;
+            self.state = GridState::Idle;
+            ctx.request_paint();
+        }
+    }
+
+    // Cell under the pointer according to the snap rounding mode of the grid
+    fn pointer_cell(&mut self, data: &GridCanvasData<T>, position: Point) -> GridIndex {
+        let previous = self.hover_cell.map(|pos| (pos.row, pos.col));
+        let (row, col) = data.snap_data.snap_grid_index(position, previous);
+        let grid_index = GridIndex::new(row, col);
+        self.hover_cell = Some(grid_index);
+        grid_index
+    }
+
+    fn perform_gesture(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut GridCanvasData<T>,
+        action: GestureAction,
+        pos: GridIndex,
+        window_pos: Point,
+    ) where
+        T: 'static,
+    {
+        match action {
+            GestureAction::None => {}
+            GestureAction::Fill => {
+                if let Err(reason) = data.fill_area(pos, data.grid_item, FILL_LIMIT) {
+                    self.reject(ctx, pos, reason);
+                }
+            }
+            GestureAction::SelectSame => {
+                data.select_same(&pos);
+                ctx.request_paint();
+            }
+            GestureAction::ContextMenu => {
+                ctx.submit_notification(CONTEXT_MENU_REQUESTED.with((pos, window_pos)));
+            }
+            GestureAction::Inspect => {
+                let item = data.grid.get(&pos).copied();
+                ctx.submit_notification(Self::CELL_INSPECTED.with((pos, item)));
+            }
+            GestureAction::Edit => self.open_item_editor(ctx, data, pos),
+        }
+    }
+
+    fn open_item_editor(&mut self, ctx: &mut EventCtx, data: &GridCanvasData<T>, pos: GridIndex)
+    where
+        T: 'static,
+    {
+        let (Some(builder), Some(item)) = (&self.item_editor_builder, data.grid.get(&pos)) else {
+            return;
+        };
+        if data.read_only {
+            self.reject(ctx, pos, String::from("Grid is read-only"));
+            return;
+        }
+        self.item_editor = Some(ItemEditor::new(builder, pos, *item));
+        ctx.children_changed();
+        ctx.request_layout();
+    }
+
+    fn close_item_editor(&mut self, ctx: &mut EventCtx) {
+        if self.item_editor.take().is_some() {
+            ctx.children_changed();
+            ctx.request_paint();
+        }
+    }
+
+    // Gives the event to the open item editor and writes its changes back to the grid. Returns
+    // true if the grid should not handle the event
+    fn item_editor_event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut GridCanvasData<T>,
+        env: &Env,
+    ) -> bool
+    where
+        T: 'static,
+    {
+        let Some(editor) = &mut self.item_editor else {
+            return false;
+        };
+        match event {
+            Event::Command(cmd) if cmd.is(CLOSE_ITEM_EDITOR) => {
+                self.close_item_editor(ctx);
+                ctx.set_handled();
+                return true;
+            }
+            Event::KeyDown(key) if key.key == KbKey::Escape => {
+                self.close_item_editor(ctx);
+                ctx.set_handled();
+                return true;
+            }
+            // The click goes on to the grid
+            Event::MouseDown(e) if !editor.pod.layout_rect().contains(e.pos) => {
+                self.close_item_editor(ctx);
+                return false;
+            }
+            _ => {}
+        }
+        if !editor.pod.is_initialized() {
+            return false;
+        }
+
+        let before = editor.data.item;
+        editor.pod.event(ctx, event, &mut editor.data, env);
+        let EditedItem { pos, item } = editor.data;
+        if item != before {
+            if let Err(reason) = data.edit_item(pos, item) {
+                self.close_item_editor(ctx);
+                self.reject(ctx, pos, reason);
+            }
+        }
+        let over_popup = match event {
+            Event::MouseDown(e) | Event::MouseUp(e) | Event::MouseMove(e) | Event::Wheel(e) => self
+                .item_editor
+                .as_ref()
+                .is_some_and(|editor| editor.pod.layout_rect().contains(e.pos)),
+            _ => false,
+        };
+        ctx.is_handled() || over_popup
+    }
+
+    // Drags out of this grid and drops from other grids. Returns true if the grid should not
+    // handle the event
+    fn drag_drop_event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut GridCanvasData<T>,
+    ) -> bool
+    where
+        T: 'static,
+    {
+        let bounds = ctx.size().to_rect();
+        match event {
+            Event::MouseDown(e) if e.button.is_left() => {
+                let Some(mode) = self.drag_out.filter(|_| self.state == GridState::Idle) else {
+                    return false;
+                };
+                let from = data.index_at(e.pos);
+                if let Some(item) = data.grid.get(&from) {
+                    self.drag = Some(ItemDrag {
+                        source: ctx.widget_id(),
+                        from,
+                        item: *item,
+                        mode,
+                        window_pos: e.window_pos,
+                    });
+                    // Pointer events keep coming once it leaves the widget
+                    ctx.set_active(true);
+                }
+                false
+            }
+            Event::MouseMove(e) if self.drag.is_some() => {
+                if !self.dragging_out && !bounds.contains(e.pos) {
+                    self.dragging_out = true;
+                    self.cancel_interaction(ctx, data);
+                }
+                match &mut self.drag {
+                    Some(drag) if self.dragging_out => {
+                        drag.window_pos = e.window_pos;
+                        ctx.submit_command(
+                            ITEM_DRAGGED.with(AnyDrag::new(*drag)).to(Target::Global),
+                        );
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            Event::MouseUp(e) if e.button.is_left() => {
+                let Some(mut drag) = self.drag.take() else {
+                    return false;
+                };
+                ctx.set_active(false);
+                if !std::mem::take(&mut self.dragging_out) {
+                    return false;
+                }
+                drag.window_pos = e.window_pos;
+                ctx.submit_command(ITEM_DROPPED.with(AnyDrag::new(drag)).to(Target::Global));
+                true
+            }
+            Event::Command(cmd) => {
+                let id = ctx.widget_id();
+                // Drags of other item types are left alone
+                let drag_of =
+                    |selector: Selector<AnyDrag>| cmd.get(selector).and_then(AnyDrag::get::<T>);
+                if let Some(drag) = drag_of(ITEM_DRAGGED) {
+                    let ghost = self
+                        .drop_cell(id, drag, data, bounds)
+                        .map(|pos| (pos, drag.item));
+                    if ghost != self.drop_ghost {
+                        self.drop_ghost = ghost;
+                        ctx.request_paint();
+                    }
+                } else if let Some(drag) = drag_of(ITEM_DROPPED) {
+                    if self.drop_ghost.take().is_some() {
+                        ctx.request_paint();
+                    }
+                    let Some(pos) = self.drop_cell(id, drag, data, bounds) else {
+                        return false;
+                    };
+                    match data.accept_drop(pos, drag) {
+                        Ok(()) => {
+                            ctx.submit_notification(Self::CELL_ADDED.with((pos, drag.item)));
+                            if drag.mode == DragMode::Move {
+                                let accepted = ITEM_DROP_ACCEPTED.with(AnyDrag::new(*drag));
+                                ctx.submit_command(accepted.to(drag.source));
+                            }
+                        }
+                        Err(reason) => self.reject(ctx, pos, reason),
+                    }
+                } else if let Some(drag) = drag_of(ITEM_DROP_ACCEPTED) {
+                    if data.remove_dropped(drag) {
+                        ctx.submit_notification(Self::CELL_REMOVED.with((drag.from, drag.item)));
+                    }
+                    ctx.set_handled();
+                    return true;
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    // Cell of this grid, `id`, that an item dragged out of another grid lands on, None if the
+    // pointer is outside of the widget, of `bounds`, or the grid takes no drops
+    fn drop_cell(
+        &self,
+        id: WidgetId,
+        drag: &ItemDrag<T>,
+        data: &GridCanvasData<T>,
+        bounds: Rect,
+    ) -> Option<GridIndex> {
+        let pos = drag.window_pos - self.window_origin.to_vec2();
+        (drag.source != id && self.state != GridState::Disabled && bounds.contains(pos))
+            .then(|| data.index_at(pos))
+    }
+
+    // Translucent copy of an item another grid is dragging over this one
+    fn paint_drop_ghost(&self, ctx: &mut PaintCtx, data: &GridCanvasData<T>) {
+        let Some((pos, item)) = &self.drop_ghost else {
+            return;
+        };
+        let cell_size = Size::new(data.snap_data.cell_size, data.snap_data.cell_size);
+        let origin = data.snap_data.get_grid_position(pos.row, pos.col);
+        let rect = Rect::from_origin_size(origin, cell_size);
+        let color = item.get_color();
+        ctx.fill(rect, &color.with_alpha(DROP_GHOST_ALPHA));
+        ctx.stroke(rect, &color, 1.0);
+    }
+
+    // Backdrop and widget of the open item editor, above everything else
+    fn paint_item_editor(&mut self, ctx: &mut PaintCtx, env: &Env) {
+        let Some(editor) = &mut self.item_editor else {
+            return;
+        };
+        if !editor.pod.is_initialized() {
+            return;
+        }
+        let backdrop = editor
+            .pod
+            .layout_rect()
+            .inflate(EDITOR_PADDING, EDITOR_PADDING);
+        ctx.fill(
+            backdrop.to_rounded_rect(4.0),
+            &theme::color(env, &theme::OVERLAY_BACKGROUND),
+        );
+        editor.pod.paint(ctx, &editor.data, env);
+    }
+
+    fn reject(&mut self, ctx: &mut EventCtx, pos: GridIndex, reason: String) {
+        if !self.rejected_cells.contains(&pos) {
+            self.rejected_cells.push(pos);
+        }
+        self.flash_timer = ctx.request_timer(REJECTED_FLASH);
+        ctx.submit_notification(EDIT_REJECTED.with((pos, reason)));
+        ctx.request_paint();
+    }
+
+    // Padlock in the top right corner shown while the grid is read-only
+    fn paint_lock_badge(&self, ctx: &mut PaintCtx, env: &Env) {
+        let origin = Point::new(
+            ctx.size().width - LOCK_BADGE_SIZE - LOCK_BADGE_MARGIN,
+            LOCK_BADGE_MARGIN,
+        );
+        let badge = Rect::from_origin_size(origin, Size::new(LOCK_BADGE_SIZE, LOCK_BADGE_SIZE));
+        ctx.fill(
+            badge.to_rounded_rect(4.0),
+            &theme::color(env, &theme::OVERLAY_BACKGROUND),
+        );
+
+        let foreground = theme::color(env, &theme::OVERLAY_FOREGROUND);
+        let center = badge.center();
+        let shackle = Rect::from_center_size(center - Vec2::new(0.0, 2.0), Size::new(8.0, 10.0));
+        ctx.stroke(shackle.to_rounded_rect(4.0), &foreground, 2.0);
+        let body = Rect::from_center_size(center + Vec2::new(0.0, 3.0), Size::new(12.0, 9.0));
+        ctx.fill(body, &foreground);
+    }
+
+    // Open and closed sets of a debugged search. The g, h and f costs of the nodes are printed
+    // once the cells are large enough to fit them
+    fn paint_search_overlay(&self, ctx: &mut PaintCtx, data: &GridCanvasData<T>, env: &Env) {
+        let snapshot = match &data.search_debug {
+            Some(snapshot) => snapshot.clone(),
+            None => return,
+        };
+        let view = ctx.size().to_rect();
+        let scaled_cell_size = data.snap_data.scaled_cell_size();
+        let show_costs = scaled_cell_size >= SEARCH_COSTS_MIN_CELL;
+        let text_color = theme::color(env, &theme::GRID_LABEL_TEXT);
+
+        ctx.with_save(|ctx| {
+            ctx.transform(data.snap_data.rotation_transform());
+
+            for (nodes, color) in [
+                (&snapshot.open, theme::color(env, &theme::SEARCH_OPEN_FILL)),
+                (
+                    &snapshot.closed,
+                    theme::color(env, &theme::SEARCH_CLOSED_FILL),
+                ),
+            ] {
+                for node in nodes.iter() {
+                    // Search positions are (column, row)
+                    let (col, row) = node.position;
+                    let pos = GridIndex::new(row as isize, col as isize);
+                    if view.intersect(data.rect_of(pos)).area() == 0.0 {
+                        continue;
+                    }
+                    let rect = data.snap_data.get_cell_rect(pos.row, pos.col);
+                    ctx.fill(rect, &color.with_alpha(SEARCH_ALPHA));
+                    if show_costs {
+                        let (g, h, f) = node.costs();
+                        let h = h.map_or(String::from("-"), |h| h.to_string());
+                        let costs = ctx
+                            .text()
+                            .new_text_layout(format!("g{g} h{h}\nf{f}"))
+                            .font(FontFamily::MONOSPACE, scaled_cell_size / 5.0)
+                            .text_color(text_color)
+                            .build()
+                            .unwrap();
+                        ctx.draw_text(&costs, rect.origin() + Vec2::new(2.0, 2.0));
+                    }
+                }
+            }
+
+            if let Some((col, row)) = snapshot.current {
+                let rect = data.snap_data.get_cell_rect(row as isize, col as isize);
+                ctx.stroke(
+                    rect.inset(-1.0),
+                    &theme::color(env, &theme::SEARCH_CURRENT),
+                    2.0,
+                );
+            }
+        });
+    }
+
+    // Outline of the cell under the pointer and the crosshair, styled by the CURSOR_* env keys
+    fn paint_cursor(&self, ctx: &mut PaintCtx, data: &GridCanvasData<T>, env: &Env) {
+        let color = theme::color(env, &CURSOR_COLOR);
+        let width = env.try_get(CURSOR_WIDTH).unwrap_or(2.0);
+        if env.try_get(CURSOR_HIGHLIGHT).unwrap_or(true) {
+            if let Some(pos) = self.hover_cell {
+                // Drawn inside the cell so the damage rect of the cell covers it
+                let rect = data.snap_data.get_cell_rect(pos.row, pos.col);
+                ctx.with_save(|ctx| {
+                    ctx.transform(data.snap_data.rotation_transform());
+                    ctx.stroke(rect.inset(-width / 2.0), &color, width);
+                });
+            }
+        }
+        if env.try_get(CURSOR_CROSSHAIR).unwrap_or(false) {
+            if let Some(pointer) = self.pointer {
+                let size = ctx.size();
+                let horizontal = Line::new((0.0, pointer.y), (size.width, pointer.y));
+                let vertical = Line::new((pointer.x, 0.0), (pointer.x, size.height));
+                ctx.stroke(horizontal, &color, width / 2.0);
+                ctx.stroke(vertical, &color, width / 2.0);
+            }
+        }
+    }
+
+    // Arrow on the edge of the widget pointing at the occupied cells while all of them are out of
+    // view, labelled with how many cells away they are
+    fn paint_content_indicator(&self, ctx: &mut PaintCtx, data: &GridCanvasData<T>, env: &Env) {
+        let Some(GridBounds { min, max }) = data.bounds() else {
+            return;
+        };
+        let below = max.below().right();
+        let snap_data = &data.snap_data;
+        let view = ctx.size().to_rect();
+        let content = snap_data.view.to_screen_rect(Rect::from_points(
+            snap_data.get_grid_position(min.row, min.col),
+            snap_data.get_grid_position(below.row, below.col),
+        ));
+        if view.intersect(content).area() > 0.0 {
+            return;
+        }
+
+        // Where the line from the centre of the widget to the content meets the inset edge
+        let center = view.center();
+        let direction = content.center() - center;
+        let half = view.inset(-INDICATOR_MARGIN).size() / 2.0;
+        let reach = (half.width / direction.x.abs()).min(half.height / direction.y.abs());
+        let tip = center + direction * reach.min(1.0);
+        let unit = direction.normalize();
+        let normal = Vec2::new(-unit.y, unit.x) * INDICATOR_SIZE * 0.6;
+        let base = tip - unit * INDICATOR_SIZE;
+
+        ctx.fill(
+            Circle::new(tip - unit * INDICATOR_SIZE * 0.4, INDICATOR_SIZE * 1.2),
+            &theme::color(env, &theme::OVERLAY_BACKGROUND),
+        );
+        let foreground = theme::color(env, &theme::OVERLAY_FOREGROUND);
+        let mut arrow = BezPath::new();
+        arrow.move_to(tip + unit * INDICATOR_SIZE * 0.2);
+        arrow.line_to(base + normal);
+        arrow.line_to(base - normal);
+        arrow.close_path();
+        ctx.fill(arrow, &foreground);
+
+        let ((top, left), (bottom, right)) = snap_data.get_visible_indices(view.size());
+        let rows = min
+            .row
+            .saturating_sub(bottom)
+            .max(top.saturating_sub(max.row))
+            .max(0);
+        let cols = min
+            .col
+            .saturating_sub(right)
+            .max(left.saturating_sub(max.col))
+            .max(0);
+        let label = ctx
+            .text()
+            .new_text_layout(format!("{} cells", rows.max(cols)))
+            .font(FontFamily::SYSTEM_UI, 11.0)
+            .text_color(foreground)
+            .build()
+            .unwrap();
+        let label_center = tip - unit * INDICATOR_SIZE * 3.5;
+        ctx.draw_text(&label, label_center - label.size().to_vec2() / 2.0);
+    }
+
+    // Glow on the edges the view was panned against, as deep as the overscroll of the pan
+    fn paint_overscroll(&self, ctx: &mut PaintCtx, data: &GridCanvasData<T>, env: &Env) {
+        let overscroll = data.snap_data.pan_data.overscroll;
+        if overscroll == Vec2::ZERO {
+            return;
+        }
+        let size = ctx.size();
+        let color = theme::color(env, &theme::OVERSCROLL_GLOW);
+        // A positive overscroll pushed the view against the left or top edge
+        let edges = [
+            (
+                overscroll.x,
+                UnitPoint::LEFT,
+                UnitPoint::RIGHT,
+                Rect::new(0.0, 0.0, overscroll.x, size.height),
+            ),
+            (
+                -overscroll.x,
+                UnitPoint::RIGHT,
+                UnitPoint::LEFT,
+                Rect::new(size.width + overscroll.x, 0.0, size.width, size.height),
+            ),
+            (
+                overscroll.y,
+                UnitPoint::TOP,
+                UnitPoint::BOTTOM,
+                Rect::new(0.0, 0.0, size.width, overscroll.y),
+            ),
+            (
+                -overscroll.y,
+                UnitPoint::BOTTOM,
+                UnitPoint::TOP,
+                Rect::new(0.0, size.height + overscroll.y, size.width, size.height),
+            ),
+        ];
+        for (depth, start, end, rect) in edges {
+            if depth <= 0.0 {
+                continue;
+            }
+            let alpha = OVERSCROLL_ALPHA * (depth / OVERSCROLL_LIMIT).min(1.0);
+            let glow =
+                LinearGradient::new(start, end, (color.with_alpha(alpha), color.with_alpha(0.0)));
+            ctx.fill(rect, &glow);
+        }
+    }
+
+    // Lightweight counterpart of the child widgets for the cells between two corners
+    fn paint_cells(
+        &self,
+        ctx: &mut PaintCtx,
+        data: &GridCanvasData<T>,
+        from: GridIndex,
+        to: GridIndex,
+        env: &Env,
+    ) {
+        let cell_size = Size::new(data.snap_data.cell_size, data.snap_data.cell_size);
+        let text_color = theme::color(env, &theme::GRID_LABEL_TEXT);
+        for (pos, item) in data.grid.iter_area(from, to) {
+            let origin = data.snap_data.get_grid_position(pos.row, pos.col);
+            let rect = Rect::from_origin_size(origin, cell_size);
+            ctx.fill(rect, &item.get_color());
+
+            let label = self.labels.layout(
+                ctx.text(),
+                &item.get_short_text(),
+                cell_size.width,
+                text_color,
+            );
+            let label_offset = (cell_size.to_vec2() - label.size().to_vec2()) / 2.0;
+            ctx.draw_text(&label, origin + label_offset);
+        }
+    }
+
+    /// Area of the widget covered by the cell at `pos`, after zoom, pan and rotation are applied
+    pub fn invalidation_area(&self, pos: GridIndex, snap_data: &GridSnapData) -> Rect {
+        snap_data.get_screen_cell_rect(pos.row, pos.col)
+    }
+
+    // Hit testing methods
+    // Pan, zoom and rotation live in GridSnapData, so the window origin is all that is left
+    /// Cell under a point in window coordinates and the item on it, if any
+    pub fn cell_at<'a>(
+        &self,
+        data: &'a GridCanvasData<T>,
+        window_point: Point,
+    ) -> Option<(GridIndex, &'a T)> {
+        data.cell_at(window_point - self.window_origin.to_vec2())
+    }
+
+    /// Area of the window covered by a cell, or its bounding box while the view is rotated
+    pub fn rect_of(&self, data: &GridCanvasData<T>, index: GridIndex) -> Rect {
+        data.rect_of(index) + self.window_origin.to_vec2()
+    }
+
+    // Repaints only the cells touched by the changes to the grid, including the halos around
+    // them. Large batches fall back to repainting the whole widget
+    fn invalidate_changes(
+        &self,
+        ctx: &mut UpdateCtx,
+        changes: &[TapeItem<GridIndex, T>],
+        old_data: &GridCanvasData<T>,
+        data: &GridCanvasData<T>,
+    ) {
+        let cells: Vec<GridIndex> = changes.iter().flat_map(TapeItem::keys).collect();
+        if cells.is_empty() || cells.len() > DAMAGE_CELL_LIMIT {
+            ctx.request_paint();
+            return;
+        }
+
+        let scaled_cell_size = data.snap_data.scaled_cell_size();
+        for pos in cells {
+            let halo = [data.grid.get(&pos), old_data.grid.get(&pos)]
+                .into_iter()
+                .flatten()
+                .map(|item| item.get_halo())
+                .max()
+                .unwrap_or(0);
+            let damage = self.invalidation_area(pos, &data.snap_data).inflate(
+                halo as f64 * scaled_cell_size,
+                halo as f64 * scaled_cell_size,
+            );
+            // One extra pixel so antialiased edges are repainted too
+            ctx.request_paint_rect(damage.inflate(1.0, 1.0));
+        }
+    }
+
+    /// Adds the child of the cell at `pos`, replacing the one already there. The child is laid
+    /// out at the cell, wherever the cell is on screen at the time
+    pub fn add_child(&mut self, child: impl Widget<GridCanvasData<T>> + 'static, pos: GridIndex) {
+        self.remove_child(pos);
+        self.cell_children.insert(pos, self.canvas.children().len());
+        self.child_cells.push(pos);
+        self.canvas.add(Child::implicit(child, cell_origin(pos)));
+    }
+
+    // Queues the child updates of a tape item one cell at a time
+    fn queue_child_updates(&mut self, item: &TapeItem<GridIndex, T>, forward: bool) {
+        match item {
+            TapeItem::BatchAdd(map) => {
+                for (pos, (item, previous_item)) in map.iter() {
+                    let cell = TapeItem::Add(*pos, *item, *previous_item);
+                    self.pending_children.push_back((cell, forward));
+                }
+            }
+            TapeItem::BatchRemove(map) => {
+                for (pos, item) in map.iter() {
+                    let cell = TapeItem::Remove(*pos, *item);
+                    self.pending_children.push_back((cell, forward));
+                }
+            }
+            // Groups are undone back to front
+            TapeItem::Group(items) if forward => {
+                for item in items.iter() {
+                    self.queue_child_updates(item, forward);
+                }
+            }
+            TapeItem::Group(items) => {
+                for item in items.iter().rev() {
+                    self.queue_child_updates(item, forward);
+                }
+            }
+            item => self.pending_children.push_back((item.clone(), forward)),
+        }
+        self.pending_total = self.pending_total.max(self.pending_children.len());
+    }
+
+    // Applies the next queued child updates within the budget
+    fn apply_pending_children(&mut self, ctx: &mut EventCtx, data: &GridCanvasData<T>) {
+        let budget = self.child_budget.unwrap_or(usize::MAX);
+        for _ in 0..budget {
+            match self.pending_children.pop_front() {
+                Some((item, true)) => self.advance(item),
+                Some((item, false)) => self.rewind(item),
+                None => break,
+            }
+        }
+        if self.pending_children.is_empty() {
+            self.pending_total = 0;
+        } else {
+            ctx.request_anim_frame();
+        }
+        self.verify_children(data);
+        ctx.children_changed();
+        ctx.request_paint();
+    }
+
+    // Bar along the top of the widget while queued child updates are being applied
+    fn paint_child_progress(&self, ctx: &mut PaintCtx, env: &Env) {
+        if self.pending_total == 0 {
+            return;
+        }
+        let done = self.pending_total - self.pending_children.len();
+        let width = ctx.size().width;
+        let track = Rect::new(0.0, 0.0, width, PROGRESS_HEIGHT);
+        ctx.fill(track, &theme::color(env, &theme::OVERLAY_BACKGROUND));
+        let progress = done as f64 / self.pending_total as f64;
+        ctx.fill(
+            track.with_size(Size::new(width * progress, PROGRESS_HEIGHT)),
+            &theme::color(env, &theme::SELECTION_FILL),
+        );
+    }
+
+    // Recreates one child per occupied cell
+    fn rebuild_children(&mut self, data: &GridCanvasData<T>) {
+        self.pending_children.clear();
+        self.pending_total = 0;
+        self.canvas.clear();
+        self.cell_children.clear();
+        self.child_cells.clear();
+        for (pos, item) in data.grid.iter() {
+            self.add_item_child(pos, item);
+        }
+    }
+
+    // Why the children and the cells they are keyed by disagree, if they do
+    pub(crate) fn child_inconsistency(&self) -> Option<String> {
+        let len = self.canvas.children().len();
+        if self.child_cells.len() != len || self.cell_children.len() != len {
+            return Some(format!(
+                "{len} children for {} cells and {} keys",
+                self.child_cells.len(),
+                self.cell_children.len()
+            ));
+        }
+        self.child_cells
+            .iter()
+            .enumerate()
+            .find(|(index, pos)| self.cell_children.get(*pos) != Some(index))
+            .map(|(index, pos)| {
+                format!(
+                    "Child {index} of cell ({}, {}) is not keyed",
+                    pos.row, pos.col
+                )
+            })
+    }
+
+    // Runs after every batch of child updates. Debug builds stop at children that drifted from
+    // their cells so it gets fixed, release builds rebuild them. Children that do not match the
+    // grid, e.g. after the grid was replaced without a delta, are rebuilt too
+    fn verify_children(&mut self, data: &GridCanvasData<T>) {
+        if let Some(reason) = self.child_inconsistency() {
+            if cfg!(debug_assertions) {
+                panic!("Grid children out of sync: {reason}");
+            }
+            log::warn!("Grid children out of sync, rebuilding them: {reason}");
+            self.rebuild_children(data);
+        } else if self.pending_children.is_empty()
+            && self.canvas.children().len() != data.grid.len()
+        {
+            self.rebuild_children(data);
+        }
+    }
+
+    /// Removes the child of the cell at `pos`, if there is one
+    pub fn remove_child(&mut self, pos: GridIndex) {
+        // The last child takes the place of the removed one
+        if let Some(index) = self.cell_children.remove(&pos) {
+            self.canvas.remove(index);
+            self.child_cells.swap_remove(index);
+            if let Some(moved) = self.child_cells.get(index) {
+                self.cell_children.insert(*moved, index);
+            }
+        }
+    }
+
+    /// Moves the child of the cell at `from` to the cell at `to`, replacing the one there
+    pub fn move_child(&mut self, from: GridIndex, to: GridIndex) {
+        if from == to || !self.cell_children.contains_key(&from) {
+            return;
+        }
+        self.remove_child(to);
+        // The child stays at its index, so the indices of the others stay valid
+        if let Some(index) = self.cell_children.remove(&from) {
+            if let Some(Child::Implicit { closure, .. }) = self.canvas.child_mut(index) {
+                *closure = Box::new(cell_origin(to));
+            }
+            self.child_cells[index] = to;
+            self.cell_children.insert(to, index);
+        }
+    }
+
+    fn add_item_child(&mut self, pos: GridIndex, item: &T) {
+        let child = GridChild::new(item.get_short_text(), item.get_color(), self.labels.clone());
+        self.add_child(child, pos);
+    }
+
+    // Puts back the child of the item the cell held before, if any
+    fn restore_child(&mut self, pos: GridIndex, previous_item: Option<T>) {
+        match previous_item {
+            Some(item) => self.add_item_child(pos, &item),
+            None => self.remove_child(pos),
+        }
+    }
+
+    fn advance(&mut self, item: TapeItem<GridIndex, T>) {
+        match item {
+            TapeItem::Add(pos, item, _) => self.add_item_child(pos, &item),
+            TapeItem::Remove(pos, _) => self.remove_child(pos),
+            TapeItem::Move(from, to, _) => self.move_child(from, to),
+            TapeItem::BatchAdd(items) => {
+                for (pos, (item, _)) in items {
+                    self.add_item_child(pos, &item);
+                }
+            }
+            TapeItem::BatchRemove(items) => {
+                for pos in items.keys() {
+                    self.remove_child(*pos);
+                }
+            }
+            TapeItem::Group(items) => {
+                for item in items {
+                    self.advance(item);
+                }
+            }
+        }
+    }
+
+    fn rewind(&mut self, item: TapeItem<GridIndex, T>) {
+        match item {
+            TapeItem::Add(pos, _, previous_item) => self.restore_child(pos, previous_item),
+            TapeItem::Remove(pos, item) => self.add_item_child(pos, &item),
+            TapeItem::Move(from, to, _) => self.move_child(to, from),
+            TapeItem::BatchAdd(items) => {
+                for (pos, (_, previous_item)) in items {
+                    self.restore_child(pos, previous_item);
+                }
+            }
+            TapeItem::BatchRemove(items) => {
+                for (pos, item) in items {
+                    self.add_item_child(pos, &item);
+                }
+            }
+            TapeItem::Group(items) => {
+                for item in items.into_iter().rev() {
+                    self.rewind(item);
+                }
+            }
+        }
+    }
+}
+
+// Origin of the cell at `pos` in the coordinates of the canvas, for the child of the cell
+fn cell_origin<T: GridItem + PartialEq + Debug>(
+    pos: GridIndex,
+) -> impl Fn(&GridCanvasData<T>) -> Point + 'static {
+    move |data| data.snap_data.get_grid_position(pos.row, pos.col)
+}
+
+impl<T: GridItem + PartialEq + Debug> Widget<GridCanvasData<T>> for GridCanvas<T>
 where
     GridCanvasData<T>: Data,
 {
@@ -523,7 +2791,131 @@ where
         env: &Env,
     ) {
         // println!("Canvas Wrapper Event");
+        // Children get the event first, e.g. a button in a cell takes its clicks from the editing
+        // tools. They are painted with the view transform
+        self.canvas.transform = data.snap_data.view.affine().inverse();
+        // The item editor is above the children
+        if self.item_editor_event(ctx, event, data, env) {
+            return;
+        }
+        self.canvas.event(ctx, event, data, env);
+        if ctx.is_handled() || self.drag_drop_event(ctx, event, data) {
+            return;
+        }
+        let grid = data.grid.clone();
+
+        // Exporting does not change the grid so it works in every state
+        if let Event::Command(cmd) = event {
+            if let Some(export) = cmd.get(EXPORT_IMAGE) {
+                let result = data.render_image(export.scale).and_then(|image| {
+                    if let Some(path) = &export.path {
+                        save_png(&image, path)?;
+                    }
+                    Ok(image)
+                });
+                match result {
+                    Ok(image) => ctx.submit_notification(IMAGE_EXPORTED.with(image)),
+                    Err(reason) => ctx.submit_notification(EXPORT_FAILED.with(reason)),
+                }
+                ctx.set_handled();
+            }
+        }
+
+        if let Event::AnimFrame(_) = event {
+            if !self.pending_children.is_empty() {
+                self.apply_pending_children(ctx, data);
+            }
+        }
+
+        if let Event::Timer(token) = event {
+            if *token == self.flash_timer {
+                self.rejected_cells.clear();
+                self.flash_timer = TimerToken::INVALID;
+                ctx.request_paint();
+            }
+        }
+
+        // Read-only mode follows the data so it can be toggled from anywhere in the app
+        if data.read_only && !matches!(self.state, GridState::Disabled | GridState::ViewOnly) {
+            self.end_interaction(ctx, data);
+            self.state = GridState::ViewOnly;
+        } else if !data.read_only && self.state == GridState::ViewOnly {
+            self.state = GridState::Idle;
+        }
+
+        // Keep the cursor overlay on the cell under the pointer. The cell is picked once per move
+        // since hysteresis depends on the cell picked before
+        let mut move_cell = None;
+        if let Event::MouseMove(e) = event {
+            let previous = self.hover_cell;
+            let grid_index = self.pointer_cell(data, e.pos);
+            move_cell = Some(grid_index);
+            let previous_pointer = self.pointer.replace(e.pos);
+            if previous != Some(grid_index) {
+                if let Some(previous) = previous {
+                    ctx.request_paint_rect(self.invalidation_area(previous, &data.snap_data));
+                }
+                ctx.request_paint_rect(self.invalidation_area(grid_index, &data.snap_data));
+            }
+            // Only the lines of the crosshair are repainted, where it was and where it is now
+            if env.try_get(CURSOR_CROSSHAIR).unwrap_or(false) {
+                let width = env.try_get(CURSOR_WIDTH).unwrap_or(2.0);
+                let size = ctx.size();
+                for pointer in previous_pointer.into_iter().chain(Some(e.pos)) {
+                    for rect in crosshair_rects(pointer, size, width) {
+                        ctx.request_paint_rect(rect);
+                    }
+                }
+            }
+        }
+
+        // Gestures run on top of the normal click handling, see GestureConfig
+        let mut gesture_fired = false;
+        if self.state != GridState::Disabled {
+            match event {
+                Event::MouseDown(e) if e.button.is_left() => {
+                    let grid_index = self.pointer_cell(data, e.pos);
+                    if e.count == 2 && self.gestures.double_click != GestureAction::None {
+                        let action = self.gestures.double_click;
+                        self.perform_gesture(ctx, data, action, grid_index, e.window_pos);
+                        gesture_fired = true;
+                    } else if self.gestures.long_press != GestureAction::None {
+                        self.long_press = Some((grid_index, e.window_pos));
+                        self.long_press_timer = ctx.request_timer(self.gestures.long_press_delay);
+                    }
+                }
+                Event::MouseMove(_) => {
+                    if self
+                        .long_press
+                        .is_some_and(|(pos, _)| Some(pos) != move_cell)
+                    {
+                        self.long_press = None;
+                        self.long_press_timer = TimerToken::INVALID;
+                    }
+                }
+                Event::MouseUp(_) => {
+                    self.long_press = None;
+                    self.long_press_timer = TimerToken::INVALID;
+                }
+                Event::Timer(token) if *token == self.long_press_timer => {
+                    self.long_press_timer = TimerToken::INVALID;
+                    if let Some((pos, window_pos)) = self.long_press.take() {
+                        // The press turns into the gesture instead of a drag
+                        if let GridState::Running(_) = self.state {
+                            self.end_interaction(ctx, data);
+                            self.state = GridState::Idle;
+                        }
+                        let action = self.gestures.long_press;
+                        self.perform_gesture(ctx, data, action, pos, window_pos);
+                        gesture_fired = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+
         match &self.state {
+            _ if gesture_fired => {}
             GridState::Idle => {
                 // info!("Idle State");
                 match event {
@@ -533,60 +2925,17 @@ where
                         }
                     }
                     Event::MouseDown(e) => {
-                        let (row, col) = data.snap_data.get_grid_index(e.pos);
-                        let grid_index = GridIndex::new(row, col);
-                        let option = data.grid.get(&grid_index);
-
-                        if self.state == GridState::Idle {
-                            if e.button == MouseButton::Left {
-                                // info!("Left Click");
-                                // info!("Start State: {:?}", self.state);
-                                // info!("Start Action: {:?}", data.action);
-                                match data.action {
-                                    GridAction::Dynamic => {
-                                        self.state = GridState::Running(GridAction::Dynamic);
-                                        match option {
-                                            None => {
-                                                data.action = GridAction::Add;
-                                            }
-                                            Some(item) => {
-                                                if *item == data.grid_item {
-                                                    data.action = GridAction::Move
-                                                } else {
-                                                    data.action = GridAction::Add
-                                                }
-                                            }
-                                        }
-                                    }
-                                    GridAction::Move => {
-                                        if option.is_some() {
-                                            self.state = GridState::Running(GridAction::Move);
-                                        }
-                                    }
-                                    _ => {
-                                        self.state = GridState::Running(data.action);
-                                    }
-                                }
-                            } else if e.button == MouseButton::Right {
-                                // info!("Right Click");
-                                if let GridAction::Dynamic = data.action {
-                                    self.state = GridState::Running(data.action);
-                                    data.action = GridAction::Remove;
-                                }
-                            }
+                        let grid_index = self.pointer_cell(data, e.pos);
+                        if let Err((pos, reason)) = self.press(data, grid_index, e.button) {
+                            self.reject(ctx, pos, reason);
                         }
-
-                        if let GridState::Running(_) = self.state {
-                            if data.action == GridAction::Add {
-                                data.add_node(&grid_index, data.grid_item);
-                            } else if data.action == GridAction::Remove && option.is_some() {
-                                data.remove_node(&grid_index);
-                            } else if data.action == GridAction::Move && option.is_some() {
-                                self.start_pos = grid_index;
+                        if let GridState::Running(action) = self.state {
+                            // Focus is needed to receive Escape
+                            ctx.request_focus();
+                            if action == GridAction::Measure {
+                                ctx.request_paint();
                             }
                         }
-                        // info!("Acquire State: {:?}", self.state);
-                        // info!("Acquire Action: {:?}", data.action);
                     }
 
                     _ => {}
@@ -596,48 +2945,63 @@ where
                 // info!("Running State");
                 match event {
                     Event::MouseMove(e) => {
-                        let (row, col) = data.snap_data.get_grid_index(e.pos);
-                        let grid_index = GridIndex::new(row, col);
-                        let option = data.grid.get(&grid_index);
+                        let grid_index =
+                            move_cell.unwrap_or_else(|| self.pointer_cell(data, e.pos));
+                        if let Err((pos, reason)) = self.drag_to(data, grid_index, e.mods.shift()) {
+                            self.reject(ctx, pos, reason);
+                        }
+                        if matches!(data.action, GridAction::Route | GridAction::Measure) {
+                            ctx.request_paint();
+                        }
+                    }
 
-                        match data.action {
-                            GridAction::Add => {
-                                data.add_node(&grid_index, data.grid_item);
-                            }
-                            GridAction::Move => {
-                                if self.start_pos != grid_index {
-                                    if data.move_node(&self.start_pos, &grid_index) {
-                                        self.start_pos = grid_index;
-                                    }
-                                }
-                            }
-                            GridAction::Remove => {
-                                if option.is_some() {
-                                    data.remove_node(&grid_index);
+                    Event::MouseDown(e) if self.state == GridState::Running(GridAction::Route) => {
+                        // Left click commits the route to the clicked cell, any other cancels it
+                        if e.button == MouseButton::Left {
+                            let grid_index = self.pointer_cell(data, e.pos);
+                            if let Some(route) = data.find_route_around(
+                                self.start_pos,
+                                grid_index,
+                                &data.grid_item,
+                                ROUTE_SEARCH_LIMIT,
+                                &self.route_halos,
+                            ) {
+                                if let Err(reason) = data.commit_route(&route, data.grid_item) {
+                                    self.reject(ctx, grid_index, reason);
                                 }
                             }
-                            _ => (),
                         }
+                        self.route_preview.clear();
+                        self.route_halos.clear();
+                        self.state = GridState::Idle;
+                        ctx.request_paint();
                     }
 
-                    Event::MouseUp(e) => {
-                        if e.button == MouseButton::Right
-                            && self.state == GridState::Running(GridAction::Dynamic)
-                            && data.action == GridAction::Remove
-                        {
-                            self.state = GridState::Idle;
-                            data.action = GridAction::Dynamic;
-                        } else if e.button == MouseButton::Left
-                            && self.state == GridState::Running(GridAction::Dynamic)
-                        {
-                            self.state = GridState::Idle;
-                            data.action = GridAction::Dynamic;
-                        } else if e.button == MouseButton::Left {
-                            self.state = GridState::Idle;
+                    // The second click fixes the measurement, it stays shown until the next one
+                    Event::MouseDown(e)
+                        if self.state == GridState::Running(GridAction::Measure) =>
+                    {
+                        let grid_index = self.pointer_cell(data, e.pos);
+                        match &mut self.measurement {
+                            Some(measurement) if e.button == MouseButton::Left => {
+                                measurement.to = grid_index
+                            }
+                            _ => self.measurement = None,
                         }
-                        // info!("Release State: {:?}", self.state);
-                        // info!("Release Action: {:?}", data.action);
+                        self.state = GridState::Idle;
+                        ctx.request_paint();
+                    }
+
+                    Event::KeyDown(key_event) if key_event.key == KbKey::Escape => {
+                        self.cancel_interaction(ctx, data);
+                        ctx.set_handled();
                     }
+
+                    // Routing and measuring continue after the button that picked the first cell is released
+                    Event::MouseUp(_) if self.state == GridState::Running(GridAction::Route) => {}
+                    Event::MouseUp(_) if self.state == GridState::Running(GridAction::Measure) => {}
+
+                    Event::MouseUp(e) => self.release(data, e.button),
                     _ => {}
                 }
             }
@@ -648,8 +3012,24 @@ where
                     }
                 }
             }
+            GridState::ViewOnly => {
+                if let Event::Command(cmd) = event {
+                    if cmd.is(SET_DISABLED) {
+                        self.state = GridState::Disabled;
+                    }
+                }
+            }
+        }
+
+        // The latest change is in the delta when a mouse event has changed the grid
+        if (matches!(
+            event,
+            Event::MouseDown(_) | Event::MouseMove(_) | Event::MouseUp(_)
+        ) || gesture_fired)
+            && !grid.same(&data.grid)
+        {
+            self.notify_changes(ctx, &data.grid_delta().1);
         }
-        self.canvas.event(ctx, event, data, env);
     }
 
     fn lifecycle(
@@ -660,20 +3040,23 @@ where
         env: &Env,
     ) {
         // println!("Canvas Wrapper ({:?}) Lifecycle: {:?}", ctx.widget_id(), event);
-        // TODO: Handle ViewContext Changed
-        if let LifeCycle::WidgetAdded = event {
-            for (grid_index, item) in data.grid.iter() {
-                let from = data
-                    .snap_data
-                    .get_grid_position(grid_index.row, grid_index.col);
-                let size = Size::new(data.snap_data.cell_size, data.snap_data.cell_size);
-                let child = GridChild::new(item.get_short_text(), item.get_color(), size);
-                self.add_child(child, from.into())
-            }
+        if let LifeCycle::ViewContextChanged(view_context) = event {
+            self.window_origin = view_context.window_origin;
+        }
+        if let LifeCycle::HotChanged(false) = event {
+            self.hover_cell = None;
+            self.pointer = None;
+            ctx.request_paint();
+        }
+        if let (LifeCycle::WidgetAdded, RenderMode::Widgets) = (event, self.render_mode) {
+            self.rebuild_children(data);
             ctx.children_changed();
         }
 
         self.canvas.lifecycle(ctx, event, data, env);
+        if let Some(editor) = &mut self.item_editor {
+            editor.pod.lifecycle(ctx, event, &editor.data, env);
+        }
     }
 
     fn update(
@@ -685,24 +3068,82 @@ where
     ) {
         self.canvas.update(ctx, old_data, data, env);
         // self.canvas.update(ctx, data, env);
+        // The measurement only lasts while the measure tool is picked
+        if data.action != GridAction::Measure && self.measurement.take().is_some() {
+            ctx.request_paint();
+        }
+        // The editor follows changes made to its item elsewhere, e.g. an undo, and goes away
+        // with the item
+        if let Some(editor) = &mut self.item_editor {
+            match data.grid.get(&editor.data.pos) {
+                Some(item) if !data.read_only => {
+                    editor.data.item = *item;
+                    if editor.pod.is_initialized() {
+                        editor.pod.update(ctx, &editor.data, env);
+                    }
+                }
+                _ => {
+                    self.item_editor = None;
+                    ctx.children_changed();
+                    ctx.request_paint();
+                }
+            }
+        }
+        if old_data.read_only != data.read_only
+            || !old_data.search_debug.same(&data.search_debug)
+            || !old_data.edges().same(data.edges())
+            || !old_data.wires().same(data.wires())
+            || !old_data.annotations.same(&data.annotations)
+            || old_data.snap_data.pan_data.overscroll != data.snap_data.pan_data.overscroll
+        {
+            ctx.request_paint();
+        }
         debug!("\n{:?}", Instant::now());
-        debug!("add item: {:?}", data.save_data.add_delta);
-        for item in data.save_data.add_delta.iter() {
-            self.advance(item.clone(), data);
-            ctx.children_changed();
+        // Children take their position and size from the cell size at layout, so they are kept
+        // rather than rebuilt
+        let resized = old_data.snap_data.cell_size != data.snap_data.cell_size;
+        if resized {
+            // Labels are laid out again at the new size
+            self.labels.clear();
+            // The pointer is over another cell once the cells have changed size
+            self.hover_cell = None;
+            if let Some(pointer) = self.pointer {
+                self.pointer_cell(data, pointer);
+            }
+            ctx.request_layout();
             ctx.request_paint();
         }
-
-        debug!("delete item: {:?}", data.save_data.remove_delta);
-        for item in data.save_data.remove_delta.iter() {
-            self.rewind(item.clone(), data);
+        // The cells are compared with the old grid rather than read from the tape, which only
+        // holds the latest local change and none of the remote items
+        let changes = if old_data.grid.same(&data.grid) {
+            Vec::new()
+        } else {
+            data.grid.changes_since(&old_data.grid)
+        };
+        // In lightweight mode there are no children to keep in sync, the cells are painted
+        // from the grid
+        if self.render_mode == RenderMode::Widgets && self.child_budget.is_some() {
+            // The children are updated over the next frames, see apply_pending_children
+            if !changes.is_empty() {
+                for item in changes.iter() {
+                    self.queue_child_updates(item, true);
+                }
+                ctx.request_anim_frame();
+            }
+        } else if self.render_mode == RenderMode::Widgets && !changes.is_empty() {
+            debug!("changed cells: {:?}", changes);
+            for item in changes.iter() {
+                self.advance(item.clone());
+            }
+            self.verify_children(data);
             ctx.children_changed();
-            ctx.request_paint();
+        }
+        if !resized && !old_data.grid.same(&data.grid) {
+            self.invalidate_changes(ctx, &changes, old_data, data);
         }
 
-        if old_data.snap_data.pan_data.offset != data.snap_data.pan_data.offset
-            || old_data.snap_data.zoom_data.zoom_scale != data.snap_data.zoom_data.zoom_scale
-        {
+        // The item editor is laid out next to its cell on the screen
+        if old_data.snap_data.view != data.snap_data.view {
             ctx.request_layout()
         }
     }
@@ -717,11 +3158,24 @@ where
         // let origin = Point::new(0., 0.);
         //debug!("Box constraints width: {:?}", bc.max().width);
         //debug!("Box constraints height: {:?}", bc.max().height);
-        self.canvas.offset = data.snap_data.pan_data.offset;
-        self.canvas.scale = data.snap_data.zoom_data.zoom_scale;
         self.canvas.layout(ctx, bc, data, env);
-
-        // self.canvas.set_origin(ctx, data.snap_data.pan_data.absolute_offset);
+        // The item editor opens below and to the right of its cell, as far as it fits
+        if let Some(editor) = &mut self.item_editor {
+            if editor.pod.is_initialized() {
+                let popup_bc = BoxConstraints::new(Size::ZERO, bc.max());
+                let size = editor.pod.layout(ctx, &popup_bc, &editor.data, env);
+                let anchor = data.rect_of(editor.data.pos);
+                let origin = Point::new(
+                    (anchor.x1 + EDITOR_PADDING)
+                        .min(bc.max().width - size.width - EDITOR_PADDING)
+                        .max(EDITOR_PADDING),
+                    (anchor.y1 + EDITOR_PADDING)
+                        .min(bc.max().height - size.height - EDITOR_PADDING)
+                        .max(EDITOR_PADDING),
+                );
+                editor.pod.set_origin(ctx, origin);
+            }
+        }
 
         bc.max()
     }
@@ -735,92 +3189,1091 @@ where
         // let paint_rectangles = damage_region.rects();
 
         ctx.with_save(|ctx| {
-            let scale = Affine::scale(data.snap_data.zoom_data.zoom_scale);
+            // Everything in here is painted in the coordinates of the unzoomed grid
+            let view = data.snap_data.view;
+            ctx.transform(view.affine());
 
-            // ctx.transform(translate);
-            ctx.transform(scale);
+            // Halos are painted below the children as translucent bands
+            let cell_size = Size::new(data.snap_data.cell_size, data.snap_data.cell_size);
+            // Only the chunks around the visible cells are visited
+            let ((top, left), (bottom, right)) = data.snap_data.get_visible_indices(ctx.size());
+            let visible = (GridIndex::new(top, left), GridIndex::new(bottom, right));
+            for (pos, item) in data.halo_cells_in(visible.0, visible.1) {
+                let origin = data.snap_data.get_grid_position(pos.row, pos.col);
+                let rect = Rect::from_origin_size(origin, cell_size);
+                ctx.fill(rect, &item.get_color().with_alpha(HALO_ALPHA));
+            }
 
             // self.canvas.paint_always(ctx, data, env);
-            self.canvas.paint(ctx, data, env);
+            match self.render_mode {
+                RenderMode::Widgets => self.canvas.paint(ctx, data, env),
+                RenderMode::Lightweight => self.paint_cells(ctx, data, visible.0, visible.1, env),
+            }
+
+            let preview_color = data.grid_item.get_color().with_alpha(ROUTE_PREVIEW_ALPHA);
+            for pos in self.route_preview.iter() {
+                let origin = data.snap_data.get_grid_position(pos.row, pos.col);
+                let rect = Rect::from_origin_size(origin, cell_size);
+                ctx.fill(rect, &preview_color);
+            }
+
+            let edge_width = data.snap_data.cell_size * EDGE_WIDTH;
+            let visible_bounds = GridBounds::new(visible.0, visible.1);
+            for (edge, item) in data.edges().iter() {
+                let (first, second) = edge.cells();
+                if !visible_bounds.contains(&first) && !visible_bounds.contains(&second) {
+                    continue;
+                }
+                let (from, to) = edge.endpoints();
+                let line = Line::new(
+                    data.snap_data.get_grid_position(from.row, from.col),
+                    data.snap_data.get_grid_position(to.row, to.col),
+                );
+                ctx.stroke(line, &item.get_color(), edge_width);
+            }
+
+            let wire_style = StrokeStyle::new()
+                .line_cap(LineCap::Round)
+                .line_join(LineJoin::Round);
+            let wire_width = data.snap_data.cell_size * WIRE_WIDTH;
+            let center = |pos: &GridIndex| {
+                data.snap_data.get_grid_position(pos.row, pos.col) + cell_size.to_vec2() / 2.0
+            };
+            for wire in data.wires().values() {
+                let mut cells = wire.cells.iter();
+                let Some(first) = cells.next() else {
+                    continue;
+                };
+                // A wire of a single cell is a dot
+                if wire.cells.len() == 1 {
+                    ctx.fill(Circle::new(center(first), wire_width / 2.0), &wire.color);
+                    continue;
+                }
+                let mut path = BezPath::new();
+                path.move_to(center(first));
+                for pos in cells {
+                    path.line_to(center(pos));
+                }
+                ctx.stroke_styled(path, &wire.color, wire_width, &wire_style);
+            }
+
+            for (index, region) in data.regions.iter().enumerate() {
+                let key = &theme::REGION_COLORS[index % theme::REGION_COLORS.len()];
+                let color = theme::color(env, key);
+                let from = data
+                    .snap_data
+                    .get_grid_position(region.min.row, region.min.col);
+                let size = Size::new(
+                    region.columns() as f64 * data.snap_data.cell_size,
+                    region.rows() as f64 * data.snap_data.cell_size,
+                );
+                let rect = Rect::from_origin_size(from, size);
+                ctx.fill(rect, &color.with_alpha(REGION_ALPHA));
+                ctx.stroke(rect, &color, 1.0);
+                let label = ctx
+                    .text()
+                    .new_text_layout(region.name.clone())
+                    .text_color(color)
+                    .build()
+                    .unwrap();
+                ctx.draw_text(&label, rect.origin() + Vec2::new(2.0, 1.0));
+            }
+
+            // Part of the unzoomed grid the widget shows
+            let shown = view.to_world_rect(ctx.size().to_rect());
+            let selection_color =
+                theme::color(env, &theme::SELECTION_FILL).with_alpha(SELECTION_ALPHA);
+            if let Some(selection) = data.selection {
+                let rect = match selection {
+                    GridSelection::Row(row) => Rect::new(
+                        shown.x0,
+                        row as f64 * cell_size.width,
+                        shown.x1,
+                        (row + 1) as f64 * cell_size.width,
+                    ),
+                    GridSelection::Column(col) => Rect::new(
+                        col as f64 * cell_size.width,
+                        shown.y0,
+                        (col + 1) as f64 * cell_size.width,
+                        shown.y1,
+                    ),
+                };
+                ctx.fill(rect, &selection_color);
+            }
+            for pos in data.selected_cells.iter() {
+                let origin = data.snap_data.get_grid_position(pos.row, pos.col);
+                let rect = Rect::from_origin_size(origin, cell_size);
+                ctx.fill(rect, &selection_color);
+            }
+            if let Some(extent) = data.extent {
+                let board = Rect::from_origin_size(
+                    Point::ORIGIN,
+                    Size::new(
+                        extent.col as f64 * cell_size.width,
+                        extent.row as f64 * cell_size.width,
+                    ),
+                );
+                ctx.stroke(board, &theme::color(env, &theme::BOARD_EDGE), 2.0);
+            }
+
+            let rejected_color =
+                theme::color(env, &theme::REJECTED_FILL).with_alpha(REJECTED_ALPHA);
+            for pos in self.rejected_cells.iter() {
+                let origin = data.snap_data.get_grid_position(pos.row, pos.col);
+                let rect = Rect::from_origin_size(origin, cell_size);
+                ctx.fill(rect, &rejected_color);
+            }
+
+            for annotation in data.annotations.iter() {
+                annotation.paint(ctx, &data.snap_data);
+            }
+            if let Some(measurement) = &self.measurement {
+                let color = theme::color(env, &theme::MEASURE_COLOR);
+                for annotation in measurement.annotations(data.snap_data.cell_size, color) {
+                    annotation.paint(ctx, &data.snap_data);
+                }
+            }
+            self.paint_drop_ghost(ctx, data);
         });
+
+        self.paint_search_overlay(ctx, data, env);
+        if self.state != GridState::Disabled && !data.read_only {
+            self.paint_cursor(ctx, data, env);
+        }
+        self.paint_child_progress(ctx, env);
+        self.paint_content_indicator(ctx, data, env);
+        self.paint_overscroll(ctx, data, env);
+        if data.read_only {
+            self.paint_lock_badge(ctx, env);
+        }
+        self.paint_item_editor(ctx, env);
     }
 }
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 
 const LABEL_INSETS: Insets = Insets::uniform_xy(1., 1.);
+// Label text sizes are rounded to this step so that small size changes reuse cached layouts
+const LABEL_SIZE_STEP: f64 = 0.5;
+// Layouts kept before the cache starts over, so texts that are no longer drawn go away
+const MAX_LABEL_LAYOUTS: usize = 1024;
 
-pub struct GridChild<T> {
-    label_text: Label<T>,
-    label_size: Size, // Needed to shift label to correct position when painting
-    color: Color,
-    size: Size,
+/// Text layouts shared by every label of a canvas, keyed by text, text size and color. Thousands
+/// of cells with the same short text end up drawing a single layout. Every text is stored once
+/// and layouts refer to it by index, so looking a layout up does not allocate. The cache is
+/// emptied once it holds `MAX_LABEL_LAYOUTS` layouts and whenever the cell size changes.
+#[derive(Clone, Default)]
+pub struct LabelCache {
+    inner: Rc<RefCell<LabelLayouts>>,
+}
+
+#[derive(Default)]
+struct LabelLayouts {
+    texts: StdHashMap<String, usize>,
+    layouts: StdHashMap<(usize, u32, u32), PietTextLayout>,
+}
+
+impl LabelCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Label of a cell of `cell_width`, built the first time the text is drawn at this size
+    pub fn layout(
+        &self,
+        text: &mut PietText,
+        label: &str,
+        cell_width: f64,
+        color: Color,
+    ) -> PietTextLayout {
+        let step = (cell_width / 3.3 / LABEL_SIZE_STEP).round().max(1.0);
+        let mut inner = self.inner.borrow_mut();
+        if inner.layouts.len() >= MAX_LABEL_LAYOUTS {
+            inner.texts.clear();
+            inner.layouts.clear();
+        }
+        let text_index = match inner.texts.get(label).copied() {
+            Some(index) => index,
+            None => {
+                let index = inner.texts.len();
+                inner.texts.insert(label.to_string(), index);
+                index
+            }
+        };
+        inner
+            .layouts
+            .entry((text_index, step as u32, color.as_rgba_u32()))
+            .or_insert_with(|| {
+                text.new_text_layout(label.to_string())
+                    .font(FontFamily::SYSTEM_UI, step * LABEL_SIZE_STEP)
+                    .text_color(color)
+                    .alignment(TextAlignment::Center)
+                    .max_width(cell_width - LABEL_INSETS.x_value())
+                    .build()
+                    .unwrap()
+            })
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.borrow().layouts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.borrow().layouts.is_empty()
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.texts.clear();
+        inner.layouts.clear();
+    }
 }
 
-impl<T: Data> GridChild<T> {
-    pub fn new(text: impl Into<LabelText<T>>, color: Color, size: Size) -> Self {
-        // let foo = Label::new(tooltip_text).tooltip();
-        let mut label_text = Label::new(text);
-        label_text.set_line_break_mode(druid::widget::LineBreaking::WordWrap);
-        label_text.set_text_color(white::ALABASTER);
-        label_text.set_text_size(size.width / 3.3);
-        label_text.set_text_alignment(TextAlignment::Center);
+pub struct GridChild {
+    text: String,
+    color: Color,
+    labels: LabelCache,
+}
 
+impl GridChild {
+    /// Child of a single cell. It is as big as a cell of the grid at the time of layout
+    pub fn new(text: impl Into<String>, color: Color, labels: LabelCache) -> Self {
         GridChild {
-            label_text,
-            label_size: Size::ZERO,
+            text: text.into(),
             color,
-            size,
+            labels,
         }
     }
 }
 
-impl<T: Data> Widget<T> for GridChild<T> {
-    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut T, _env: &Env) {
+impl<T: GridItem + PartialEq + Debug> Widget<GridCanvasData<T>> for GridChild
+where
+    GridCanvasData<T>: Data,
+{
+    fn event(
+        &mut self,
+        _ctx: &mut EventCtx,
+        _event: &Event,
+        _data: &mut GridCanvasData<T>,
+        _env: &Env,
+    ) {
         // Add tooltip logic on hover
     }
 
-    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        _data: &GridCanvasData<T>,
+        _env: &Env,
+    ) {
         if let LifeCycle::HotChanged(_) | LifeCycle::DisabledChanged(_) = event {
             ctx.request_paint();
         }
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &GridCanvasData<T>,
+        data: &GridCanvasData<T>,
+        _env: &Env,
+    ) {
+        if old_data.snap_data.cell_size != data.snap_data.cell_size {
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &GridCanvasData<T>,
+        _env: &Env,
+    ) -> Size {
+        let cell_size = data.snap_data.cell_size;
+        bc.constrain(Size::new(cell_size, cell_size))
+    }
 
-        // if let LifeCycle::Internal(RouteWidgetAdded) = event {
-        //     self.label_text.lifecycle(ctx, &LifeCycle::WidgetAdded, data, env);
-        // }
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &GridCanvasData<T>, env: &Env) {
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &self.color);
 
-        self.label_text.lifecycle(ctx, event, data, env);
+        let text_color = theme::color(env, &theme::GRID_LABEL_TEXT);
+        let label = self
+            .labels
+            .layout(ctx.text(), &self.text, size.width, text_color);
+        let label_offset = (size.to_vec2() - label.size().to_vec2()) / 2.0;
+        ctx.draw_text(&label, label_offset.to_point());
     }
+}
 
-    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &T, data: &T, env: &Env) {
-        self.label_text.update(ctx, old_data, data, env);
-        ctx.request_paint();
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use druid::{
+        im::{HashMap, Vector},
+        Color, Lens, MouseButton, Point, Rect, Size, WidgetId,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{
+        crosshair_rects, image_size, DirtyLens, GridCanvas, GridCanvasData, GridChange,
+        MAX_IMAGE_SIDE,
+    };
+    use crate::{
+        annotations::Annotation,
+        canvas::Child,
+        drag_drop::{AnyDrag, DragMode, ItemDrag},
+        testing::{arbitrary_tape, check_children, TestItem},
+        utils::cassetta::{CassettePlayer, TapeItem},
+        CellSide, CellTransform, EdgeIndex, GridAction, GridIndex, GridRegion, GridState,
+    };
+
+    #[test]
+    fn annotations_share_the_undo_tape() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        let note = Annotation::text(GridIndex::new(-1, 2), "Entry", Color::WHITE);
+        let arrow = Annotation::arrow(GridIndex::new(0, 0), GridIndex::new(3, 4), Color::WHITE);
+        assert_eq!(data.add_annotation(note.clone()), 0);
+        data.add_node(&GridIndex::new(1, 1), TestItem::Wall)
+            .unwrap();
+        assert_eq!(data.add_annotation(arrow.clone()), 1);
+        assert_eq!(data.remove_annotation(0), Some(note.clone()));
+        assert_eq!(data.remove_annotation(5), None);
+        assert_eq!(data.save_data.position(), 4);
+
+        data.seek(2);
+        assert_eq!(data.annotations, Vector::unit(note.clone()));
+        assert_eq!(data.grid.get(&GridIndex::new(1, 1)), Some(&TestItem::Wall));
+        data.seek(1);
+        assert_eq!(data.grid.get(&GridIndex::new(1, 1)), None);
+        data.seek(0);
+        assert!(data.annotations.is_empty());
+        data.seek(3);
+        assert_eq!(data.annotations, Vector::from(vec![note, arrow.clone()]));
+        data.seek(4);
+        assert_eq!(data.annotations, Vector::unit(arrow));
+        assert!(data.is_dirty());
     }
 
-    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
-        let padding = Size::new(LABEL_INSETS.x_value(), LABEL_INSETS.y_value());
-        let label_bc = bc.shrink(padding).loosen();
-        self.label_size = self.label_text.layout(ctx, &label_bc, data, env);
-        let baseline = self.label_text.baseline_offset();
-        ctx.set_baseline_offset(baseline + LABEL_INSETS.y1);
-        let actual_size = bc.constrain(self.size);
-        actual_size
+    #[test]
+    fn tracks_unsaved_changes() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        assert!(!data.is_dirty());
+        data.add_node_perimeter(GridIndex::new(0, 0), 2, 2, TestItem::Wall);
+        assert!(DirtyLens.with(&data, |dirty| *dirty));
+
+        // Writes through the lens are dropped
+        DirtyLens.with_mut(&mut data, |dirty| *dirty = false);
+        assert!(data.is_dirty());
+        data.mark_saved();
+        assert!(!data.is_dirty());
+
+        data.seek(0);
+        assert!(data.is_dirty());
+        data.mark_saved();
+        data.add_region(GridRegion::new(
+            "room",
+            GridIndex::new(0, 0),
+            GridIndex::new(1, 1),
+        ));
+        assert!(data.is_dirty());
     }
 
-    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
-        let is_active = ctx.is_active() && !ctx.is_disabled();
-        let is_hot = ctx.is_hot();
-        let size = ctx.size();
-        // A hack to get it to do the right thing
-        // let rect = Rect::from_origin_size(self.position, self.size);
-        let rect = size.to_rect();
+    #[test]
+    fn undoing_back_to_the_save_is_clean() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.add_node(&GridIndex::new(0, 0), TestItem::Wall)
+            .unwrap();
+        data.mark_saved();
+        data.add_node(&GridIndex::new(0, 1), TestItem::Wall)
+            .unwrap();
+        assert!(data.is_dirty());
+        data.seek(1);
+        assert!(!data.is_dirty());
+        data.seek(0);
+        assert!(data.is_dirty());
+        data.seek(1);
+        assert!(!data.is_dirty());
 
-        ctx.fill(rect, &self.color);
+        // A stroke is unsaved until it ends, even before it is on the tape
+        data.stroke_add_node(&GridIndex::new(1, 1), TestItem::Wall)
+            .unwrap();
+        assert!(data.is_dirty());
+        data.cancel_stroke();
+        assert!(!data.is_dirty());
 
-        let label_offset = (size.to_vec2() - self.label_size.to_vec2()) / 2.0;
+        // A new edit after undoing past the save leaves no way back to it
+        data.seek(0);
+        data.add_node(&GridIndex::new(0, 0), TestItem::Wall)
+            .unwrap();
+        assert!(data.is_dirty());
 
-        ctx.with_save(|ctx| {
-            ctx.transform(Affine::translate(label_offset));
-            self.label_text.paint(ctx, data, env);
+        data.mark_saved();
+        assert_eq!(
+            data.set_edge(
+                EdgeIndex::new(GridIndex::new(0, 0), CellSide::Top),
+                TestItem::Wall
+            ),
+            None
+        );
+        assert!(data.is_dirty());
+        data.mark_saved();
+        assert_eq!(
+            data.remove_edge(&EdgeIndex::new(GridIndex::new(0, 0), CellSide::Top)),
+            Some(TestItem::Wall)
+        );
+        assert!(data.is_dirty());
+    }
+
+    #[test]
+    fn switching_branches_moves_the_grid_both_ways() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.save_data.set_undo_tree(true);
+        let cells: Vec<GridIndex> = (0..3).map(|col| GridIndex::new(0, col)).collect();
+        for pos in cells.iter() {
+            data.add_node(pos, TestItem::Wall).unwrap();
+        }
+        data.seek(1);
+        data.add_node(&GridIndex::new(1, 0), TestItem::Wall)
+            .unwrap();
+
+        // Back from the new edit to where the old steps fork off
+        assert!(data.switch_branch(0));
+        assert_eq!(data.save_data.position(), 1);
+        assert_eq!(data.grid.len(), 1);
+        data.seek(3);
+        assert!(cells
+            .iter()
+            .all(|pos| data.grid.get(pos) == Some(&TestItem::Wall)));
+
+        // Forward from before the fork to the new edit
+        data.seek(0);
+        assert!(data.switch_branch(0));
+        assert_eq!(data.save_data.position(), 1);
+        assert_eq!(data.grid.get(&cells[0]), Some(&TestItem::Wall));
+        assert_eq!(data.grid.len(), 1);
+        data.seek(2);
+        assert_eq!(data.grid.get(&GridIndex::new(1, 0)), Some(&TestItem::Wall));
+        assert_eq!(data.grid.len(), 2);
+    }
+
+    #[test]
+    fn nodes_added_from_code_are_single_steps() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        let (first, second) = (GridIndex::new(0, 0), GridIndex::new(0, 1));
+        data.add_node(&first, TestItem::Wall).unwrap();
+        data.add_node(&second, TestItem::Wall).unwrap();
+        data.remove_node(&first).unwrap();
+        assert_eq!(data.save_data.position(), 3);
+
+        data.seek(2);
+        assert_eq!(data.grid.len(), 2);
+        data.seek(1);
+        assert!(data.grid.contains_key(&first));
+        assert!(!data.grid.contains_key(&second));
+
+        // Strokes of the widget stay open until it ends them
+        data.stroke_add_node(&second, TestItem::Wall).unwrap();
+        data.stroke_add_node(&GridIndex::new(0, 2), TestItem::Wall)
+            .unwrap();
+        assert_eq!(data.save_data.position(), 1);
+        data.end_stroke();
+        assert_eq!(data.save_data.position(), 2);
+    }
+
+    #[test]
+    fn routes_end_the_running_stroke() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.stroke_add_node(&GridIndex::new(1, 1), TestItem::Wall)
+            .unwrap();
+        let (from, to) = (GridIndex::new(0, 0), GridIndex::new(2, 2));
+        let halos = data.halo_cells();
+        let route = data
+            .find_route_around(from, to, &TestItem::Wall, 100, &halos)
+            .unwrap();
+        assert_eq!(
+            data.find_route(from, to, &TestItem::Wall, 100),
+            Some(route.clone())
+        );
+        assert!(!route.contains(&GridIndex::new(1, 1)));
+
+        data.commit_route(&route, TestItem::Wall).unwrap();
+        assert_eq!(data.save_data.position(), 2);
+        data.seek(1);
+        assert_eq!(data.grid.len(), 1);
+        assert!(data.grid.contains_key(&GridIndex::new(1, 1)));
+    }
+
+    #[test]
+    fn routes_follow_the_add_rules() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.set_extent(Some(GridIndex::new(4, 4)));
+        let route = [
+            GridIndex::new(3, 2),
+            GridIndex::new(3, 3),
+            GridIndex::new(3, 4),
+        ];
+        // The last cell is off the board
+        assert!(data.commit_route(&route, TestItem::Wall).is_err());
+        assert!(data.commit_route(&route[..2], TestItem::Weight(0)).is_err());
+        data.set_read_only(true);
+        assert_eq!(
+            data.commit_route(&route[..2], TestItem::Wall),
+            Err(String::from("Grid is read-only"))
+        );
+        assert!(data.grid.is_empty());
+        assert_eq!(data.save_data.position(), 0);
+
+        data.set_read_only(false);
+        data.commit_route(&route[..2], TestItem::Wall).unwrap();
+        assert_eq!(data.grid.len(), 2);
+    }
+
+    #[test]
+    fn strokes_are_one_step_from_press_to_release() {
+        let mut widget = GridCanvas::new();
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.action = GridAction::Add;
+        widget
+            .press(&mut data, GridIndex::new(0, 0), MouseButton::Left)
+            .unwrap();
+        assert_eq!(widget.state, GridState::Running(GridAction::Add));
+        widget
+            .drag_to(&mut data, GridIndex::new(0, 1), false)
+            .unwrap();
+        // Shift keeps the stroke on the row it started on
+        widget
+            .drag_to(&mut data, GridIndex::new(1, 4), true)
+            .unwrap();
+        widget.release(&mut data, MouseButton::Left);
+
+        assert_eq!(widget.state, GridState::Idle);
+        assert_eq!(data.grid.len(), 3);
+        assert!(data.grid.contains_key(&GridIndex::new(0, 4)));
+        assert_eq!(data.save_data.position(), 1);
+        data.seek(0);
+        assert!(data.grid.is_empty());
+    }
+
+    #[test]
+    fn dynamic_presses_pick_the_action_from_the_cell() {
+        let mut widget = GridCanvas::new();
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.add_node(&GridIndex::new(2, 2), TestItem::Wall)
+            .unwrap();
+
+        // Pressing on the selected item picks it up
+        widget
+            .press(&mut data, GridIndex::new(2, 2), MouseButton::Left)
+            .unwrap();
+        assert_eq!(data.action, GridAction::Move);
+        widget
+            .drag_to(&mut data, GridIndex::new(2, 3), false)
+            .unwrap();
+        widget.release(&mut data, MouseButton::Left);
+        assert_eq!(data.action, GridAction::Dynamic);
+        assert_eq!(data.grid.get(&GridIndex::new(2, 3)), Some(&TestItem::Wall));
+        assert_eq!(data.grid.get(&GridIndex::new(2, 2)), None);
+
+        // The right button erases
+        widget
+            .press(&mut data, GridIndex::new(2, 3), MouseButton::Right)
+            .unwrap();
+        assert_eq!(data.action, GridAction::Remove);
+        widget.release(&mut data, MouseButton::Right);
+        assert_eq!(widget.state, GridState::Idle);
+        assert_eq!(data.action, GridAction::Dynamic);
+        assert!(data.grid.is_empty());
+    }
+
+    #[test]
+    fn strokes_carry_on_past_rejected_cells() {
+        let mut widget = GridCanvas::new();
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.add_node(&GridIndex::new(0, 1), TestItem::Pin).unwrap();
+        data.action = GridAction::Add;
+        widget
+            .press(&mut data, GridIndex::new(0, 0), MouseButton::Left)
+            .unwrap();
+        let (pos, _) = widget
+            .drag_to(&mut data, GridIndex::new(0, 1), false)
+            .unwrap_err();
+        assert_eq!(pos, GridIndex::new(0, 1));
+        widget
+            .drag_to(&mut data, GridIndex::new(0, 2), false)
+            .unwrap();
+        widget.release(&mut data, MouseButton::Left);
+
+        assert_eq!(data.grid.get(&GridIndex::new(0, 1)), Some(&TestItem::Pin));
+        assert_eq!(data.grid.len(), 3);
+        assert_eq!(data.save_data.position(), 2);
+    }
+
+    #[test]
+    fn transforms_move_the_selection_as_one_step() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        let row: Vec<GridIndex> = (0..3).map(|col| GridIndex::new(0, col)).collect();
+        for pos in row.iter() {
+            data.add_node(pos, TestItem::Wall).unwrap();
+        }
+        data.selected_cells = row.iter().copied().collect();
+
+        data.transform_selection(CellTransform::RotateClockwise)
+            .unwrap();
+        let column: Vec<GridIndex> = (0..3).map(|row| GridIndex::new(row, 0)).collect();
+        assert!(column.iter().all(|pos| data.grid.contains_key(pos)));
+        assert_eq!(data.grid.len(), 3);
+        assert_eq!(data.selected_cells, column.iter().copied().collect());
+        assert_eq!(data.save_data.position(), 4);
+        data.seek(3);
+        assert!(row.iter().all(|pos| data.grid.contains_key(pos)));
+    }
+
+    #[test]
+    fn transforms_that_fail_change_nothing() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        for col in 0..3 {
+            data.add_node(&GridIndex::new(0, col), TestItem::Wall)
+                .unwrap();
+        }
+        data.selected_cells = (0..3).map(|col| GridIndex::new(0, col)).collect();
+        let (grid, position) = (data.grid.clone(), data.save_data.position());
+
+        // The last target is outside the board, the ones before it are fine
+        data.set_extent(Some(GridIndex::new(2, 3)));
+        let error = data
+            .transform_selection(CellTransform::RotateClockwise)
+            .unwrap_err();
+        assert_eq!(error, "Cell (2, 0): Outside the board");
+        assert_eq!((&data.grid, data.save_data.position()), (&grid, position));
+
+        // The last target is taken by an item outside the selection
+        data.set_extent(None);
+        data.add_node(&GridIndex::new(2, 0), TestItem::Wall)
+            .unwrap();
+        let (grid, position) = (data.grid.clone(), data.save_data.position());
+        let error = data
+            .transform_selection(CellTransform::RotateClockwise)
+            .unwrap_err();
+        assert_eq!(error, "Cell (2, 0): Cell is taken");
+        assert_eq!((&data.grid, data.save_data.position()), (&grid, position));
+
+        data.set_read_only(true);
+        assert!(data
+            .transform_selection(CellTransform::FlipHorizontal)
+            .is_err());
+        assert_eq!(data.grid, grid);
+    }
+
+    #[test]
+    fn clearing_a_region_keeps_items_that_refuse_removal() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        let (wall, anchor) = (GridIndex::new(0, 0), GridIndex::new(1, 1));
+        data.add_node(&wall, TestItem::Wall).unwrap();
+        data.add_node(&anchor, TestItem::Pin).unwrap();
+        data.add_region(GridRegion::new(
+            "room",
+            GridIndex::new(0, 0),
+            GridIndex::new(2, 2),
+        ));
+
+        data.clear_region("room");
+        assert!(!data.grid.contains_key(&wall));
+        assert_eq!(data.grid.get(&anchor), Some(&TestItem::Pin));
+        assert_eq!(data.save_data.position(), 3);
+
+        // Nothing left that can be removed, so nothing is recorded
+        data.clear_region("room");
+        assert_eq!(data.save_data.position(), 3);
+        data.seek(2);
+        assert_eq!(data.grid.len(), 2);
+    }
+
+    #[test]
+    fn batches_apply_what_passes_as_one_step() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        let (first, second, third) = (
+            GridIndex::new(0, 0),
+            GridIndex::new(0, 1),
+            GridIndex::new(0, 2),
+        );
+        data.add_node(&first, TestItem::Wall).unwrap();
+        let report = data.apply_batch(Vector::from(vec![
+            TapeItem::Add(second, TestItem::Wall, None),
+            TapeItem::Remove(GridIndex::new(5, 5), TestItem::Wall),
+            TapeItem::Move(first, third, TestItem::Wall),
+            // Sees the move before it, so the cell is empty by now
+            TapeItem::Remove(first, TestItem::Wall),
+        ]));
+        assert_eq!(report.applied, 2);
+        assert!(!report.is_complete());
+        let reasons: Vec<&str> = report
+            .rejected
+            .iter()
+            .map(|(_, reason)| reason.as_str())
+            .collect();
+        assert_eq!(reasons, vec!["Cell is empty", "Cell is empty"]);
+        assert!(data.grid.contains_key(&second) && data.grid.contains_key(&third));
+        assert!(!data.grid.contains_key(&first));
+
+        assert_eq!(data.save_data.position(), 2);
+        data.seek(1);
+        assert_eq!(data.grid.len(), 1);
+        assert!(data.grid.contains_key(&first));
+
+        // Nothing passing leaves the tape alone
+        let report = data.apply_batch(Vector::unit(TapeItem::Remove(third, TestItem::Wall)));
+        assert_eq!(report.applied, 0);
+        assert_eq!(data.save_data.position(), 1);
+
+        // A read-only grid turns down every cell of the batch
+        data.set_read_only(true);
+        let mut added = HashMap::new();
+        added.insert(second, (TestItem::Wall, None));
+        added.insert(third, (TestItem::Wall, None));
+        let report = data.apply_batch(Vector::from(vec![
+            TapeItem::BatchAdd(added),
+            TapeItem::Remove(first, TestItem::Wall),
+        ]));
+        assert_eq!(report.applied, 0);
+        assert_eq!(report.rejected.len(), 3);
+        assert!(report
+            .rejected
+            .iter()
+            .all(|(_, reason)| reason == "Grid is read-only"));
+        assert_eq!(data.grid.len(), 1);
+        assert_eq!(data.save_data.position(), 1);
+    }
+
+    #[test]
+    fn read_only_grids_are_not_filled() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.set_extent(Some(GridIndex::new(3, 3)));
+        data.set_read_only(true);
+        assert_eq!(
+            data.fill_area(GridIndex::new(0, 0), TestItem::Wall, 16),
+            Err(String::from("Grid is read-only"))
+        );
+        assert!(data.grid.is_empty());
+
+        data.set_read_only(false);
+        data.fill_area(GridIndex::new(0, 0), TestItem::Wall, 16)
+            .unwrap();
+        assert_eq!(data.grid.len(), 9);
+    }
+
+    #[test]
+    fn change_lists_are_ordered_by_first_cell() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.add_node(&GridIndex::new(0, 0), TestItem::Pad).unwrap();
+        data.freeze_baseline();
+        data.add_node(&GridIndex::new(5, 0), TestItem::Wall)
+            .unwrap();
+        data.add_node(&GridIndex::new(9, 9), TestItem::Weight(1))
+            .unwrap();
+        data.add_node(&GridIndex::new(2, 7), TestItem::Weight(2))
+            .unwrap();
+        data.remove_node(&GridIndex::new(0, 0)).unwrap();
+
+        let first_cells: Vec<GridIndex> = data
+            .change_list()
+            .iter()
+            .map(|group| group.keys()[0])
+            .collect();
+        assert_eq!(
+            first_cells,
+            vec![
+                GridIndex::new(0, 0),
+                GridIndex::new(2, 7),
+                GridIndex::new(5, 0),
+                GridIndex::new(9, 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn crosshair_covers_only_its_lines() {
+        let [horizontal, vertical] =
+            crosshair_rects(Point::new(30.0, 40.0), Size::new(200.0, 100.0), 4.0);
+        assert_eq!(horizontal, Rect::new(0.0, 38.0, 200.0, 42.0));
+        assert_eq!(vertical, Rect::new(28.0, 0.0, 32.0, 100.0));
+    }
+
+    #[test]
+    fn subscribers_see_every_change() {
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let seen = changes.clone();
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        let id = data.subscribe(move |change| {
+            seen.borrow_mut().push(match change {
+                GridChange::Applied(item) => ("applied", item.clone()),
+                GridChange::Reverted(item) => ("reverted", item.clone()),
+                GridChange::Remote(item) => ("remote", item.clone()),
+            })
         });
+
+        let (local, remote) = (GridIndex::new(0, 0), GridIndex::new(1, 1));
+        data.add_node(&local, TestItem::Wall).unwrap();
+        data.seek(0);
+        data.apply_remote(TapeItem::Add(remote, TestItem::Wall, None))
+            .unwrap();
+        // The stroke is played cell by cell and undone as a whole
+        assert_eq!(
+            *changes.borrow(),
+            vec![
+                ("applied", TapeItem::Add(local, TestItem::Wall, None)),
+                (
+                    "reverted",
+                    TapeItem::BatchAdd(HashMap::unit(local, (TestItem::Wall, None)))
+                ),
+                ("remote", TapeItem::Add(remote, TestItem::Wall, None)),
+            ]
+        );
+
+        assert!(data.unsubscribe(id));
+        assert!(!data.unsubscribe(id));
+        data.add_node(&local, TestItem::Wall).unwrap();
+        assert_eq!(changes.borrow().len(), 3);
+    }
+
+    #[test]
+    fn sizes_exported_images() {
+        assert_eq!(image_size(GridIndex::new(2, 3), 10.0, 2.0), Ok((100, 80)));
+        assert_eq!(image_size(GridIndex::new(0, 0), 15.0, 1.5), Ok((45, 45)));
+        for scale in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(image_size(GridIndex::new(2, 3), 10.0, scale).is_err());
+        }
+        let cells = (MAX_IMAGE_SIDE / 10) as isize;
+        assert!(image_size(GridIndex::new(0, cells - 2), 10.0, 1.0).is_ok());
+        assert!(image_size(GridIndex::new(0, cells), 10.0, 1.0).is_err());
+        assert!(image_size(GridIndex::new(isize::MAX, 0), 10.0, 1.0).is_err());
+
+        let data = GridCanvasData::new(TestItem::Wall);
+        assert!(data.render_image(1.0).is_err());
+    }
+
+    #[test]
+    fn halos_keep_other_items_away() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        assert!(data.halo_cells().is_empty());
+        data.add_node(&GridIndex::new(0, 0), TestItem::Wall)
+            .unwrap();
+        assert!(data.halo_cells().is_empty());
+        // Far away cells and items of the same kind are never inside a halo
+        data.add_node(&GridIndex::new(40, 40), TestItem::Pad)
+            .unwrap();
+        assert!(data
+            .add_node(&GridIndex::new(42, 38), TestItem::Wall)
+            .is_err());
+        assert!(data.is_in_halo(&GridIndex::new(38, 42), &TestItem::Wall));
+        assert!(!data.is_in_halo(&GridIndex::new(43, 40), &TestItem::Wall));
+        assert!(!data.is_in_halo(&GridIndex::new(41, 41), &TestItem::Pad));
+        // Items placed next to a wider halo are kept out of it too
+        assert!(data.is_in_halo(&GridIndex::new(1, 2), &TestItem::Pad));
+        assert_eq!(data.halo_cells().len(), 24);
+        // Areas next to the item see its whole halo, and areas out of its reach see none of it
+        assert_eq!(
+            data.halo_cells_in(GridIndex::new(42, 42), GridIndex::new(45, 45)),
+            data.halo_cells()
+        );
+        assert!(data
+            .halo_cells_in(GridIndex::new(43, 43), GridIndex::new(45, 45))
+            .is_empty());
+        data.grid
+            .insert(GridIndex::new(isize::MIN, isize::MAX), TestItem::Pad);
+        assert_eq!(
+            data.halo_cells_in(
+                GridIndex::new(isize::MIN, isize::MAX),
+                GridIndex::new(isize::MIN, isize::MAX)
+            )
+            .len(),
+            8
+        );
+        data.grid.remove(&GridIndex::new(isize::MIN, isize::MAX));
+
+        data.remove_node(&GridIndex::new(40, 40)).unwrap();
+        assert!(data.halo_cells().is_empty());
+        assert!(!data.is_in_halo(&GridIndex::new(41, 41), &TestItem::Wall));
+    }
+
+    #[test]
+    fn diffs_turn_one_grid_into_another() {
+        let mut from = GridCanvasData::new(TestItem::Wall);
+        from.grid.insert(GridIndex::new(0, 0), TestItem::Wall);
+        from.grid.insert(GridIndex::new(1, 1), TestItem::Wall);
+        let mut to = GridCanvasData::new(TestItem::Wall);
+        to.grid.insert(GridIndex::new(1, 1), TestItem::Wall);
+        to.grid.insert(GridIndex::new(40, -3), TestItem::Wall);
+        to.grid.insert(GridIndex::new(-2, 5), TestItem::Wall);
+
+        let diff = from.diff(&to);
+        assert_eq!(
+            diff,
+            Vector::from(vec![
+                TapeItem::Add(GridIndex::new(-2, 5), TestItem::Wall, None),
+                TapeItem::Remove(GridIndex::new(0, 0), TestItem::Wall),
+                TapeItem::Add(GridIndex::new(40, -3), TestItem::Wall, None),
+            ])
+        );
+        assert!(to.diff(&to).is_empty());
+
+        // A grid that no longer matches the diff is left alone
+        let mut changed = from.clone();
+        changed.grid.remove(&GridIndex::new(0, 0));
+        assert_eq!(
+            changed.apply_diff(diff.clone()),
+            Err(String::from("Cell (0, 0) has changed"))
+        );
+        assert_eq!(changed.grid.len(), 1);
+
+        assert_eq!(from.apply_diff(diff), Ok(3));
+        assert!(from.grid == to.grid);
+        from.seek(0);
+        assert_eq!(from.grid.len(), 2);
+        assert!(from.grid.contains_key(&GridIndex::new(0, 0)));
+    }
+
+    #[test]
+    fn streams_operations_to_another_grid() {
+        let mut local = GridCanvasData::new(TestItem::Wall);
+        let mut remote = GridCanvasData::new(TestItem::Wall);
+        let sent = Rc::new(RefCell::new(Vec::new()));
+        let outbox = sent.clone();
+        local.subscribe_operations(move |item| outbox.borrow_mut().push(item.clone()));
+        let echoed = Rc::new(RefCell::new(0));
+        let echoes = echoed.clone();
+        remote.subscribe_operations(move |_| *echoes.borrow_mut() += 1);
+
+        local.add_node_perimeter(GridIndex::new(0, 0), 3, 3, TestItem::Wall);
+        let mut target = local.clone();
+        target.grid.insert(GridIndex::new(5, 5), TestItem::Wall);
+        target.grid.remove(&GridIndex::new(0, 0));
+        local.apply_diff(local.diff(&target)).unwrap();
+        local.seek(1);
+        for item in sent.take() {
+            assert_eq!(remote.apply_remote(item), Ok(()));
+        }
+        assert!(remote.grid == local.grid);
+        assert_eq!(*echoed.borrow(), 0);
+        // The undo tape is left alone
+        assert!(remote.save_data.is_empty());
+        assert!(remote.save_data.add_delta.is_empty());
+        assert!(remote.is_dirty());
+
+        assert_eq!(
+            remote.apply_remote(TapeItem::Remove(GridIndex::new(5, 5), TestItem::Wall)),
+            Err(String::from("Cell (5, 5) has changed"))
+        );
+    }
+
+    #[test]
+    fn remote_items_add_up_until_the_widget_updates() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        let mut widget = GridCanvas::new();
+        data.add_node(&GridIndex::new(0, 0), TestItem::Wall)
+            .unwrap();
+        widget.advance(data.grid_delta().1[0].clone());
+        let old_data = data.clone();
+
+        // Two remote items with a local edit in between and a single update, as the widget
+        // sees them. The local edit replaces the delta of the tape
+        let (first, second) = (GridIndex::new(0, 1), GridIndex::new(2, 2));
+        data.apply_remote(TapeItem::Add(first, TestItem::Wall, None))
+            .unwrap();
+        data.add_node(&GridIndex::new(3, 3), TestItem::Wall)
+            .unwrap();
+        data.apply_remote(TapeItem::Add(second, TestItem::Wall, None))
+            .unwrap();
+        assert_eq!(data.grid_delta().1.len(), 1);
+        for item in data.grid.changes_since(&old_data.grid) {
+            widget.advance(item);
+        }
+        assert_eq!(check_children(&widget, &data), Ok(()));
+        assert_eq!(widget.canvas().children().len(), 4);
+    }
+
+    #[test]
+    fn children_follow_the_tape() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        let mut widget = GridCanvas::new();
+        let tape = arbitrary_tape(&mut rng, &HashMap::new(), 8, 8, &[TestItem::Wall], 80);
+        for item in tape.iter() {
+            data.grid.advance(item.clone());
+            widget.advance(item.clone());
+            assert_eq!(check_children(&widget, &data), Ok(()));
+        }
+        for item in tape.iter().rev() {
+            data.grid.rewind(item.clone());
+            widget.rewind(item.clone());
+            assert_eq!(check_children(&widget, &data), Ok(()));
+        }
+        assert!(widget.canvas().children().is_empty());
+    }
+
+    #[test]
+    fn drags_reach_the_grids_of_their_item_type() {
+        let (source, target) = (WidgetId::next(), WidgetId::next());
+        let from = GridIndex::new(0, 0);
+        let mut source_data = GridCanvasData::new(TestItem::Wall);
+        source_data.grid.insert(from, TestItem::Wall);
+        let drag = ItemDrag {
+            source,
+            from,
+            item: TestItem::Wall,
+            mode: DragMode::Move,
+            window_pos: Point::new(125.0, 35.0),
+        };
+        let payload = AnyDrag::new(drag);
+        assert!(payload.get::<char>().is_none());
+        let received = *payload.get::<TestItem>().unwrap();
+
+        // The target grid sits at (100, 20) in the window
+        let mut widget: GridCanvas<TestItem> = GridCanvas::new();
+        widget.window_origin = Point::new(100.0, 20.0);
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        let bounds = Rect::new(0.0, 0.0, 50.0, 50.0);
+        let pos = widget.drop_cell(target, &received, &data, bounds);
+        assert_eq!(pos, Some(GridIndex::new(1, 1)));
+        assert_eq!(widget.drop_cell(source, &received, &data, bounds), None);
+        let outside = ItemDrag {
+            window_pos: Point::new(90.0, 35.0),
+            ..received
+        };
+        assert_eq!(widget.drop_cell(target, &outside, &data, bounds), None);
+
+        assert_eq!(data.accept_drop(pos.unwrap(), &received), Ok(()));
+        assert_eq!(data.grid.get(&GridIndex::new(1, 1)), Some(&TestItem::Wall));
+        assert!(source_data.remove_dropped(&received));
+        assert!(source_data.grid.is_empty());
+        assert!(!source_data.remove_dropped(&received));
+    }
+
+    #[test]
+    fn children_are_keyed_by_cell() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        let mut widget = GridCanvas::new();
+        let (from, to) = (GridIndex::new(1, 2), GridIndex::new(3, 4));
+        for item in [
+            TapeItem::Add(from, TestItem::Wall, None),
+            TapeItem::Move(from, to, TestItem::Wall),
+        ] {
+            data.grid.advance(item.clone());
+            widget.advance(item);
+        }
+        assert!(widget.has_child(to) && !widget.has_child(from));
+        assert_eq!(check_children(&widget, &data), Ok(()));
+
+        // The child follows its cell when the cells change size, without being rebuilt
+        data.snap_data.cell_size = 7.3;
+        match &widget.canvas().children()[0] {
+            Child::Implicit { closure, .. } => {
+                assert_eq!(closure(&data), Point::new(4.0 * 7.3, 3.0 * 7.3))
+            }
+            Child::Explicit { .. } => panic!("Grid children are laid out at their cell"),
+        }
     }
 }
@@ -0,0 +1,317 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::ops::RangeInclusive;
+
+use druid::{
+    piet::{Text, TextLayout, TextLayoutBuilder},
+    BoxConstraints, Cursor, Data, Env, Event, EventCtx, FontFamily, KbKey, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget,
+};
+
+use crate::{
+    snapping::{CoordinateSystem, GridSnapData},
+    theme, GridIndex, GridSelection,
+};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// GridHeaderData
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+pub trait GridHeaderDataAccess {
+    fn get_snap_data(&self) -> &GridSnapData;
+    fn get_selection(&self) -> Option<GridSelection>;
+    fn set_selection(&mut self, selection: Option<GridSelection>);
+    // Number of rows and columns of a finite board, None for an unbounded grid
+    fn get_extent(&self) -> Option<GridIndex>;
+    fn set_extent(&mut self, extent: Option<GridIndex>);
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// GridHeader Widget
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+pub const HEADER_THICKNESS: f64 = 24.0;
+const HEADER_TEXT_SIZE: f64 = 10.0;
+// Distance in pixels from the board edge where a drag resizes the board
+const RESIZE_TOLERANCE: f64 = 4.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HeaderAxis {
+    Rows,
+    Columns,
+}
+
+/// Strip of row or column indices drawn next to a grid canvas. The header follows the pan and
+/// zoom of the canvas so it has to start at the same position as the canvas along its axis.
+/// Clicking an index selects the whole row or column and dragging the edge of a finite board
+/// resizes it.
+pub struct GridHeader {
+    axis: HeaderAxis,
+    // Extent of the board when the running resize started, restored by Escape
+    resize_start: Option<GridIndex>,
+}
+
+impl GridHeader {
+    pub fn rows() -> Self {
+        Self {
+            axis: HeaderAxis::Rows,
+            resize_start: None,
+        }
+    }
+
+    pub fn columns() -> Self {
+        Self {
+            axis: HeaderAxis::Columns,
+            resize_start: None,
+        }
+    }
+
+    // Position of the mouse along the axis of the header
+    fn along(&self, point: Point) -> f64 {
+        match self.axis {
+            HeaderAxis::Rows => point.y,
+            HeaderAxis::Columns => point.x,
+        }
+    }
+
+    // Screen offset and size of a cell along the axis of the header
+    fn metrics(&self, snap_data: &GridSnapData) -> (f64, f64) {
+        let view = snap_data.view;
+        let offset = match self.axis {
+            HeaderAxis::Rows => view.offset.y,
+            HeaderAxis::Columns => view.offset.x,
+        };
+        (offset, snap_data.cell_size * view.scale)
+    }
+
+    fn index_at(&self, position: f64, snap_data: &GridSnapData) -> isize {
+        let (offset, scaled_cell_size) = self.metrics(snap_data);
+        ((position - offset) / scaled_cell_size).floor() as isize
+    }
+
+    // Rows or columns under `clip`, a rect in the coordinates of the header. None when the rect
+    // does not overlap the header
+    fn visible_indices(
+        &self,
+        clip: Rect,
+        size: Size,
+        snap_data: &GridSnapData,
+    ) -> Option<RangeInclusive<isize>> {
+        let clip = clip.intersect(size.to_rect());
+        if clip.area() <= 0.0 {
+            return None;
+        }
+        let (start, end) = match self.axis {
+            HeaderAxis::Rows => (clip.y0, clip.y1),
+            HeaderAxis::Columns => (clip.x0, clip.x1),
+        };
+        Some(self.index_at(start, snap_data)..=self.index_at(end, snap_data))
+    }
+
+    fn length(&self, extent: GridIndex) -> isize {
+        match self.axis {
+            HeaderAxis::Rows => extent.row,
+            HeaderAxis::Columns => extent.col,
+        }
+    }
+
+    fn with_length(&self, extent: GridIndex, length: isize) -> GridIndex {
+        match self.axis {
+            HeaderAxis::Rows => GridIndex::new(length, extent.col),
+            HeaderAxis::Columns => GridIndex::new(extent.row, length),
+        }
+    }
+
+    // Index shown on the header for a row or column of the grid
+    fn user_index(&self, index: isize, coordinates: &CoordinateSystem) -> isize {
+        match self.axis {
+            HeaderAxis::Rows => coordinates.user_row(index),
+            HeaderAxis::Columns => coordinates.user_col(index),
+        }
+    }
+
+    fn selection(&self, index: isize) -> GridSelection {
+        match self.axis {
+            HeaderAxis::Rows => GridSelection::Row(index),
+            HeaderAxis::Columns => GridSelection::Column(index),
+        }
+    }
+
+    fn is_on_board_edge<T: GridHeaderDataAccess>(&self, position: f64, data: &T) -> bool {
+        data.get_extent().is_some_and(|extent| {
+            let (offset, scaled_cell_size) = self.metrics(data.get_snap_data());
+            let edge = self.length(extent) as f64 * scaled_cell_size + offset;
+            (position - edge).abs() <= RESIZE_TOLERANCE
+        })
+    }
+
+    fn cell_rect(&self, size: Size, start: f64, scaled_cell_size: f64) -> Rect {
+        match self.axis {
+            HeaderAxis::Rows => Rect::new(0.0, start, size.width, start + scaled_cell_size),
+            HeaderAxis::Columns => Rect::new(start, 0.0, start + scaled_cell_size, size.height),
+        }
+    }
+}
+
+impl<T: Data + GridHeaderDataAccess> Widget<T> for GridHeader {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, _env: &Env) {
+        match event {
+            Event::MouseDown(mouse_event) if mouse_event.button.is_left() => {
+                let position = self.along(mouse_event.pos);
+                if self.is_on_board_edge(position, data) {
+                    self.resize_start = data.get_extent();
+                    ctx.set_active(true);
+                    ctx.request_focus();
+                } else {
+                    let index = self.index_at(position, data.get_snap_data());
+                    let selection = self.selection(index);
+                    if data.get_selection() == Some(selection) {
+                        data.set_selection(None);
+                    } else {
+                        data.set_selection(Some(selection));
+                    }
+                }
+                ctx.set_handled();
+            }
+            Event::MouseMove(mouse_event) => {
+                let position = self.along(mouse_event.pos);
+                if self.resize_start.is_some() {
+                    if let Some(extent) = data.get_extent() {
+                        let (offset, scaled_cell_size) = self.metrics(data.get_snap_data());
+                        let length =
+                            (((position - offset) / scaled_cell_size).round() as isize).max(1);
+                        if length != self.length(extent) {
+                            data.set_extent(Some(self.with_length(extent, length)));
+                        }
+                    }
+                    ctx.set_handled();
+                }
+                if self.resize_start.is_some() || self.is_on_board_edge(position, data) {
+                    match self.axis {
+                        HeaderAxis::Rows => ctx.set_cursor(&Cursor::ResizeUpDown),
+                        HeaderAxis::Columns => ctx.set_cursor(&Cursor::ResizeLeftRight),
+                    }
+                } else {
+                    ctx.clear_cursor();
+                }
+            }
+            Event::MouseUp(_) if self.resize_start.is_some() => {
+                self.resize_start = None;
+                ctx.set_active(false);
+                ctx.set_handled();
+            }
+            Event::KeyDown(key_event) if key_event.key == KbKey::Escape => {
+                if let Some(extent) = self.resize_start.take() {
+                    data.set_extent(Some(extent));
+                    ctx.set_active(false);
+                    ctx.set_handled();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &T, _env: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &T, data: &T, _env: &Env) {
+        if !old_data.same(data) {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &T, _env: &Env) -> Size {
+        let size = match self.axis {
+            HeaderAxis::Rows => Size::new(HEADER_THICKNESS, bc.max().height),
+            HeaderAxis::Columns => Size::new(bc.max().width, HEADER_THICKNESS),
+        };
+        bc.constrain(size)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        let size = ctx.size();
+        ctx.fill(
+            size.to_rect(),
+            &theme::color(env, &theme::HEADER_BACKGROUND),
+        );
+        let border = theme::color(env, &theme::HEADER_BORDER);
+        let text = theme::color(env, &theme::GRID_LABEL_TEXT);
+
+        let snap_data = data.get_snap_data();
+        let (offset, scaled_cell_size) = self.metrics(snap_data);
+        // Only the cells in the part being repainted are drawn
+        let visible = self
+            .visible_indices(ctx.region().bounding_box(), size, snap_data)
+            .unwrap_or(1..=0);
+        let length = data.get_extent().map(|extent| self.length(extent));
+        let coordinates = snap_data.coordinates;
+
+        ctx.with_save(|ctx| {
+            ctx.clip(size.to_rect());
+            for index in visible {
+                let start = index as f64 * scaled_cell_size + offset;
+                let rect = self.cell_rect(size, start, scaled_cell_size);
+                let on_board = length.is_none_or(|length| (0..length).contains(&index));
+                if data.get_selection() == Some(self.selection(index)) {
+                    ctx.fill(rect, &theme::color(env, &theme::SELECTION_FILL));
+                }
+                ctx.stroke(rect, &border, 1.0);
+
+                let text_color = if on_board { text } else { border };
+                let label = ctx
+                    .text()
+                    .new_text_layout(self.user_index(index, &coordinates).to_string())
+                    .font(FontFamily::SYSTEM_UI, HEADER_TEXT_SIZE)
+                    .text_color(text_color)
+                    .build()
+                    .unwrap();
+                let label_size = label.size();
+                ctx.draw_text(&label, rect.center() - label_size.to_vec2() / 2.0);
+            }
+
+            if let Some(length) = length {
+                let edge = length as f64 * scaled_cell_size + offset;
+                let handle = match self.axis {
+                    HeaderAxis::Rows => Rect::new(0.0, edge - 1.5, size.width, edge + 1.5),
+                    HeaderAxis::Columns => Rect::new(edge - 1.5, 0.0, edge + 1.5, size.height),
+                };
+                ctx.fill(handle, &theme::color(env, &theme::BOARD_EDGE));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::{Point, Rect, Size};
+
+    use super::GridHeader;
+    use crate::snapping::GridSnapData;
+
+    #[test]
+    fn visible_indices_follow_the_clip_rect() {
+        let mut snap_data = GridSnapData::new(10.0);
+        let size = Size::new(24.0, 200.0);
+        let rows = GridHeader::rows();
+        let columns = GridHeader::columns();
+
+        let clip = Rect::new(0.0, 35.0, 24.0, 62.0);
+        assert_eq!(rows.visible_indices(clip, size, &snap_data), Some(3..=6));
+        // Parts of the clip rect outside the header are ignored
+        let clip = Rect::new(-50.0, -50.0, 500.0, 15.0);
+        assert_eq!(rows.visible_indices(clip, size, &snap_data), Some(0..=1));
+        assert_eq!(columns.visible_indices(clip, size, &snap_data), Some(0..=2));
+        let clip = Rect::new(0.0, 300.0, 24.0, 400.0);
+        assert_eq!(rows.visible_indices(clip, size, &snap_data), None);
+
+        // Pan and zoom move the indices under the rect
+        snap_data.view.offset = Point::new(0.0, -100.0);
+        snap_data.view.scale = 2.0;
+        let clip = Rect::new(0.0, 0.0, 24.0, 40.0);
+        assert_eq!(rows.visible_indices(clip, size, &snap_data), Some(5..=7));
+    }
+}
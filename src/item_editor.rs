@@ -0,0 +1,80 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use druid::{Data, Lens, Selector, Widget, WidgetPod};
+
+use crate::{GridIndex, GridItem};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Command Selectors
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+// Closes the item editor of the grid, e.g. from a done button inside the popup
+pub const CLOSE_ITEM_EDITOR: Selector = Selector::new("grid-close-item-editor");
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Edited Item
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Data of the item editor popup. Changes to `item` are written back to the grid as an undo
+/// step each
+#[derive(Clone, Copy, PartialEq, Debug, Lens)]
+pub struct EditedItem<T> {
+    pub pos: GridIndex,
+    pub item: T,
+}
+
+impl<T: GridItem + 'static> Data for EditedItem<T> {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+/// Builds the popup widget for the item that was double-clicked
+pub type ItemEditorBuilder<T> = Box<dyn Fn(&EditedItem<T>) -> Box<dyn Widget<EditedItem<T>>>>;
+
+// Popup of the grid widget and the item it edits
+pub(crate) struct ItemEditor<T> {
+    pub pod: WidgetPod<EditedItem<T>, Box<dyn Widget<EditedItem<T>>>>,
+    pub data: EditedItem<T>,
+}
+
+impl<T: GridItem + 'static> ItemEditor<T> {
+    pub fn new(builder: &ItemEditorBuilder<T>, pos: GridIndex, item: T) -> Self {
+        let data = EditedItem { pos, item };
+        Self {
+            pod: WidgetPod::new(builder(&data)),
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{grid_canvas::GridCanvasData, testing::TestItem, GridIndex};
+
+    #[test]
+    fn edits_items_in_place() {
+        let mut data = GridCanvasData::new(TestItem::Weight(1));
+        let (weight, anchor) = (GridIndex::new(0, 0), GridIndex::new(1, 1));
+        data.grid.insert(weight, TestItem::Weight(1));
+        data.grid.insert(anchor, TestItem::Pin);
+
+        assert_eq!(data.edit_item(weight, TestItem::Weight(5)), Ok(()));
+        assert_eq!(data.grid.get(&weight), Some(&TestItem::Weight(5)));
+        assert!(data.edit_item(weight, TestItem::Weight(0)).is_err());
+        assert!(data.edit_item(anchor, TestItem::Weight(2)).is_err());
+        assert!(data
+            .edit_item(GridIndex::new(4, 4), TestItem::Weight(2))
+            .is_err());
+        assert_eq!(data.grid.get(&weight), Some(&TestItem::Weight(5)));
+        assert_eq!(data.grid.get(&anchor), Some(&TestItem::Pin));
+
+        data.set_read_only(true);
+        assert!(data.edit_item(weight, TestItem::Weight(3)).is_err());
+    }
+}
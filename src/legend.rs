@@ -0,0 +1,166 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::fmt::Debug;
+
+use crate::{grid_canvas::GridCanvasData, theme, GridItem};
+use druid::{
+    piet::{PietTextLayout, Text, TextLayout, TextLayoutBuilder},
+    BoxConstraints, Data, Env, Event, EventCtx, FontFamily, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget,
+};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// GridLegend Widget
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+const LEGEND_TEXT_SIZE: f64 = 12.0;
+const LEGEND_ROW_HEIGHT: f64 = 20.0;
+const LEGEND_SWATCH_SIZE: f64 = 12.0;
+const LEGEND_PADDING: f64 = 6.0;
+
+/// Lists the items on the grid with their colour and how many cells they occupy, most common
+/// first. The counts follow the grid as it changes.
+pub struct GridLegend<T> {
+    // Only count the items inside this region
+    region: Option<String>,
+    entries: Vec<(T, usize)>,
+    layouts: Vec<PietTextLayout>,
+}
+
+impl<T: GridItem + PartialEq + Debug> GridLegend<T>
+where
+    GridCanvasData<T>: Data,
+{
+    pub fn new() -> Self {
+        Self {
+            region: None,
+            entries: Vec::new(),
+            layouts: Vec::new(),
+        }
+    }
+
+    pub fn with_region(mut self, name: impl Into<String>) -> Self {
+        self.region = Some(name.into());
+        self
+    }
+
+    fn refresh(&mut self, data: &GridCanvasData<T>) {
+        let mut entries: Vec<(T, usize)> = data
+            .item_counts(self.region.as_deref())
+            .into_iter()
+            .collect();
+        entries.sort_by(|(item, count), (other, other_count)| {
+            other_count
+                .cmp(count)
+                .then_with(|| item.get_name().cmp(&other.get_name()))
+        });
+        self.entries = entries;
+    }
+}
+
+impl<T: GridItem + PartialEq + Debug> Default for GridLegend<T>
+where
+    GridCanvasData<T>: Data,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: GridItem + PartialEq + Debug> Widget<GridCanvasData<T>> for GridLegend<T>
+where
+    GridCanvasData<T>: Data,
+{
+    fn event(
+        &mut self,
+        _ctx: &mut EventCtx,
+        _event: &Event,
+        _data: &mut GridCanvasData<T>,
+        _env: &Env,
+    ) {
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &GridCanvasData<T>,
+        _env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            self.refresh(data);
+        }
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &GridCanvasData<T>,
+        data: &GridCanvasData<T>,
+        _env: &Env,
+    ) {
+        if !old_data.grid.same(&data.grid) || !old_data.regions.same(&data.regions) {
+            self.refresh(data);
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &GridCanvasData<T>,
+        env: &Env,
+    ) -> Size {
+        let text_color = theme::color(env, &theme::GRID_LABEL_TEXT);
+        self.layouts = self
+            .entries
+            .iter()
+            .map(|(item, count)| {
+                ctx.text()
+                    .new_text_layout(format!("{}: {}", item.get_name(), count))
+                    .font(FontFamily::SYSTEM_UI, LEGEND_TEXT_SIZE)
+                    .text_color(text_color)
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+
+        let text_width = self
+            .layouts
+            .iter()
+            .map(|layout| layout.size().width)
+            .fold(0.0, f64::max);
+        let size = Size::new(
+            text_width + LEGEND_SWATCH_SIZE + 3.0 * LEGEND_PADDING,
+            self.entries.len() as f64 * LEGEND_ROW_HEIGHT + 2.0 * LEGEND_PADDING,
+        );
+        bc.constrain(size)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &GridCanvasData<T>, env: &Env) {
+        let border = theme::color(env, &theme::HEADER_BORDER);
+        for (index, ((item, _), layout)) in self.entries.iter().zip(&self.layouts).enumerate() {
+            let top = LEGEND_PADDING + index as f64 * LEGEND_ROW_HEIGHT;
+            let swatch = Rect::from_origin_size(
+                Point::new(
+                    LEGEND_PADDING,
+                    top + (LEGEND_ROW_HEIGHT - LEGEND_SWATCH_SIZE) / 2.0,
+                ),
+                Size::new(LEGEND_SWATCH_SIZE, LEGEND_SWATCH_SIZE),
+            );
+            ctx.fill(swatch, &item.get_color());
+            ctx.stroke(swatch, &border, 1.0);
+
+            let text_origin = Point::new(
+                swatch.x1 + LEGEND_PADDING,
+                top + (LEGEND_ROW_HEIGHT - layout.size().height) / 2.0,
+            );
+            ctx.draw_text(layout, text_origin);
+        }
+    }
+}
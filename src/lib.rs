@@ -1,26 +1,50 @@
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 use canvas::Canvas;
-use druid::im::{HashMap, HashSet};
+use druid::im::{HashMap, HashSet, Vector};
 use druid::{Color, Data, Size};
 use grid_canvas::{GridCanvas, GridCanvasData, GridChild};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 ///
 /// Imports
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 use std::hash::Hash;
+use std::ops::Add;
+use std::sync::Arc;
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 
+pub mod annotations;
+pub mod automata;
+pub mod autosave;
 pub mod canvas;
+pub mod cell_size;
+pub mod chunked_grid;
+pub mod drag_drop;
+pub mod export;
 pub mod grid_canvas;
+pub mod headers;
+pub mod item_editor;
+pub mod legend;
+pub mod mazes;
+pub mod model;
 ///
 /// Modules
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////////
+pub mod palette;
 pub mod panning;
 pub mod rotation;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod session;
 pub mod snapping;
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
+pub mod theme;
+pub mod timeline;
 pub mod utils;
 
 pub mod zooming;
@@ -31,6 +55,7 @@ pub mod zooming;
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Data)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct GridIndex {
     pub row: isize,
     pub col: isize,
@@ -45,20 +70,33 @@ impl From<(isize, isize)> for GridIndex {
     }
 }
 
+impl Add for GridIndex {
+    type Output = GridIndex;
+
+    // Saturates at the edges of the index range instead of wrapping around to the other side
+    fn add(self, other: GridIndex) -> GridIndex {
+        GridIndex {
+            row: self.row.saturating_add(other.row),
+            col: self.col.saturating_add(other.col),
+        }
+    }
+}
+
 impl GridIndex {
     pub fn new(row: isize, col: isize) -> Self {
         Self { row, col }
     }
+    // Neighbours of cells at the edge of the index range saturate to the cell itself
     pub fn above(self) -> GridIndex {
         GridIndex {
-            row: self.row - 1,
+            row: self.row.saturating_sub(1),
             col: self.col,
         }
     }
 
     pub fn below(self) -> GridIndex {
         GridIndex {
-            row: self.row + 1,
+            row: self.row.saturating_add(1),
             col: self.col,
         }
     }
@@ -66,17 +104,60 @@ impl GridIndex {
     pub fn left(self) -> GridIndex {
         GridIndex {
             row: self.row,
-            col: self.col - 1,
+            col: self.col.saturating_sub(1),
         }
     }
 
     pub fn right(self) -> GridIndex {
         GridIndex {
             row: self.row,
-            col: self.col + 1,
+            col: self.col.saturating_add(1),
         }
     }
 
+    /// Closest cell to `self` on a horizontal, vertical or 45° line through `origin`. Diagonals
+    /// that would run off the grid stop at its edge
+    pub fn snap_to_line(self, origin: GridIndex) -> GridIndex {
+        // Wide enough that far apart cells can not overflow
+        let (rows, cols) = (
+            self.row as i128 - origin.row as i128,
+            self.col as i128 - origin.col as i128,
+        );
+        // tan(22.5°) is close to 2/5
+        if 5 * rows.abs() <= 2 * cols.abs() {
+            GridIndex::new(origin.row, self.col)
+        } else if 5 * cols.abs() <= 2 * rows.abs() {
+            GridIndex::new(self.row, origin.col)
+        } else {
+            let steps = (rows.abs() + cols.abs()) / 2;
+            let clamp = |value: i128| value.clamp(isize::MIN as i128, isize::MAX as i128) as isize;
+            GridIndex::new(
+                clamp(origin.row as i128 + steps * rows.signum()),
+                clamp(origin.col as i128 + steps * cols.signum()),
+            )
+        }
+    }
+
+    /// Closest cell to `self` on a line through `origin` at a multiple of `step` degrees
+    pub fn snap_to_angle(self, origin: GridIndex, step: f64) -> GridIndex {
+        let (rows, cols) = (
+            self.row.saturating_sub(origin.row) as f64,
+            self.col.saturating_sub(origin.col) as f64,
+        );
+        if step <= 0.0 || (rows == 0.0 && cols == 0.0) {
+            return self;
+        }
+        let step = step.to_radians();
+        let angle = (rows.atan2(cols) / step).round() * step;
+        let (sin, cos) = angle.sin_cos();
+        // Length of the projection onto the line
+        let length = cols * cos + rows * sin;
+        GridIndex::new(
+            origin.row.saturating_add((length * sin).round() as isize),
+            origin.col.saturating_add((length * cos).round() as isize),
+        )
+    }
+
     // Also known in vlsi as the Manhattan Architecture
     pub fn neighbors_rectilinear(self) -> [GridIndex; 4] {
         let above = self.above();
@@ -96,6 +177,372 @@ impl GridIndex {
         let below_right = below.right();
         [above_left, above_right, below_left, below_right]
     }
+
+    /// Cells adjacent to `self` according to `neighborhood`
+    pub fn neighbors(self, neighborhood: &Neighborhood) -> Vec<GridIndex> {
+        neighborhood
+            .offsets(self)
+            .iter()
+            .map(|offset| self + *offset)
+            .collect()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// EdgeIndex
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Side of a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Data)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CellSide {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+/// Edge between two neighbouring cells, for items such as walls that sit between cells rather
+/// than on them. An edge is named by a cell and one of its sides, the bottom of a cell being the
+/// top of the cell below it. Edges are kept as the top or left side of a cell so each edge has a
+/// single index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Data)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EdgeIndex {
+    cell: GridIndex,
+    side: CellSide,
+}
+
+impl EdgeIndex {
+    pub fn new(cell: GridIndex, side: CellSide) -> Self {
+        match side {
+            CellSide::Top | CellSide::Left => Self { cell, side },
+            CellSide::Bottom => Self {
+                cell: cell.below(),
+                side: CellSide::Top,
+            },
+            CellSide::Right => Self {
+                cell: cell.right(),
+                side: CellSide::Left,
+            },
+        }
+    }
+
+    /// Cell the edge is the top or left side of
+    pub fn cell(&self) -> GridIndex {
+        self.cell
+    }
+
+    /// Either [`CellSide::Top`] or [`CellSide::Left`]
+    pub fn side(&self) -> CellSide {
+        self.side
+    }
+
+    /// True for an edge between a cell and the one below it
+    pub fn is_horizontal(&self) -> bool {
+        self.side == CellSide::Top
+    }
+
+    /// The two cells the edge separates, top or left one first
+    pub fn cells(&self) -> (GridIndex, GridIndex) {
+        match self.side {
+            CellSide::Top => (self.cell.above(), self.cell),
+            _ => (self.cell.left(), self.cell),
+        }
+    }
+
+    /// Grid corners the edge runs between, as the row and column of the grid lines
+    pub fn endpoints(&self) -> (GridIndex, GridIndex) {
+        match self.side {
+            CellSide::Top => (self.cell, self.cell.right()),
+            _ => (self.cell, self.cell.below()),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// CellTransform
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Quarter turns and mirror images of a block of cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Data)]
+pub enum CellTransform {
+    RotateClockwise,
+    RotateCounterClockwise,
+    /// Mirrors the columns, left becomes right
+    FlipHorizontal,
+    /// Mirrors the rows, top becomes bottom
+    FlipVertical,
+}
+
+impl CellTransform {
+    /// Where `pos` ends up when the block between the corners `min` and `max` (inclusive) is
+    /// transformed. Rotated blocks keep their top left corner, so four turns in the same
+    /// direction bring every cell back to where it started
+    pub fn apply(self, pos: GridIndex, min: GridIndex, max: GridIndex) -> GridIndex {
+        match self {
+            Self::RotateClockwise => {
+                GridIndex::new(min.row + (pos.col - min.col), min.col + (max.row - pos.row))
+            }
+            Self::RotateCounterClockwise => {
+                GridIndex::new(min.row + (max.col - pos.col), min.col + (pos.row - min.row))
+            }
+            Self::FlipHorizontal => GridIndex::new(pos.row, min.col + max.col - pos.col),
+            Self::FlipVertical => GridIndex::new(min.row + max.row - pos.row, pos.col),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Neighborhood
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+const VON_NEUMANN_OFFSETS: [GridIndex; 4] = [
+    GridIndex { row: -1, col: 0 },
+    GridIndex { row: 1, col: 0 },
+    GridIndex { row: 0, col: -1 },
+    GridIndex { row: 0, col: 1 },
+];
+const MOORE_OFFSETS: [GridIndex; 8] = [
+    GridIndex { row: -1, col: 0 },
+    GridIndex { row: 1, col: 0 },
+    GridIndex { row: 0, col: -1 },
+    GridIndex { row: 0, col: 1 },
+    GridIndex { row: -1, col: -1 },
+    GridIndex { row: -1, col: 1 },
+    GridIndex { row: 1, col: -1 },
+    GridIndex { row: 1, col: 1 },
+];
+const KNIGHT_OFFSETS: [GridIndex; 8] = [
+    GridIndex { row: -2, col: -1 },
+    GridIndex { row: -2, col: 1 },
+    GridIndex { row: -1, col: -2 },
+    GridIndex { row: -1, col: 2 },
+    GridIndex { row: 1, col: -2 },
+    GridIndex { row: 1, col: 2 },
+    GridIndex { row: 2, col: -1 },
+    GridIndex { row: 2, col: 1 },
+];
+const HEX_EVEN_ROW_OFFSETS: [GridIndex; 6] = [
+    GridIndex { row: -1, col: -1 },
+    GridIndex { row: -1, col: 0 },
+    GridIndex { row: 0, col: -1 },
+    GridIndex { row: 0, col: 1 },
+    GridIndex { row: 1, col: -1 },
+    GridIndex { row: 1, col: 0 },
+];
+const HEX_ODD_ROW_OFFSETS: [GridIndex; 6] = [
+    GridIndex { row: -1, col: 0 },
+    GridIndex { row: -1, col: 1 },
+    GridIndex { row: 0, col: -1 },
+    GridIndex { row: 0, col: 1 },
+    GridIndex { row: 1, col: 0 },
+    GridIndex { row: 1, col: 1 },
+];
+
+/// Which cells count as adjacent, shared by flood fills and routing so they agree on what a
+/// connected area is
+#[derive(Debug, Clone, PartialEq, Eq, Data, Default)]
+pub enum Neighborhood {
+    /// The four cells sharing an edge
+    #[default]
+    VonNeumann,
+    /// The eight cells sharing an edge or a corner
+    Moore,
+    /// The eight cells a knight's move away
+    Knight,
+    /// The six cells around a hexagon, with odd rows shifted half a cell to the right
+    Hex,
+    /// Cells at the given offsets
+    Custom(Arc<[GridIndex]>),
+}
+
+impl Neighborhood {
+    /// Offsets of the neighbours of `pos`, which only depend on the cell for [`Self::Hex`]
+    pub fn offsets(&self, pos: GridIndex) -> &[GridIndex] {
+        match self {
+            Self::VonNeumann => &VON_NEUMANN_OFFSETS,
+            Self::Moore => &MOORE_OFFSETS,
+            Self::Knight => &KNIGHT_OFFSETS,
+            Self::Hex if pos.row.rem_euclid(2) == 0 => &HEX_EVEN_ROW_OFFSETS,
+            Self::Hex => &HEX_ODD_ROW_OFFSETS,
+            Self::Custom(offsets) => offsets,
+        }
+    }
+
+    pub fn is_adjacent(&self, from: GridIndex, to: GridIndex) -> bool {
+        self.offsets(from).iter().any(|offset| from + *offset == to)
+    }
+
+    /// Lower bound of the number of steps between two cells, for use as a search heuristic
+    pub fn min_steps(&self, from: GridIndex, to: GridIndex) -> usize {
+        let (rows, cols) = (
+            (to.row - from.row).unsigned_abs(),
+            (to.col - from.col).unsigned_abs(),
+        );
+        match self {
+            Self::VonNeumann => rows + cols,
+            Self::Moore | Self::Hex => rows.max(cols),
+            _ => {
+                // No step covers more rows or columns than the longest offset
+                let reach = self
+                    .offsets(from)
+                    .iter()
+                    .map(|offset| offset.row.unsigned_abs().max(offset.col.unsigned_abs()))
+                    .max()
+                    .unwrap_or(0);
+                if reach == 0 {
+                    0
+                } else {
+                    rows.max(cols).div_ceil(reach)
+                }
+            }
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// GridRegion
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Named rectangle of cells, e.g. "Room A" or "IO bank"
+#[derive(Debug, Clone, PartialEq, Eq, Data)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GridRegion {
+    pub name: String,
+    // Top left and bottom right corners, both inclusive
+    pub min: GridIndex,
+    pub max: GridIndex,
+}
+
+impl GridRegion {
+    /// Region spanned by two opposite corners given in any order
+    pub fn new(name: impl Into<String>, from: GridIndex, to: GridIndex) -> Self {
+        Self {
+            name: name.into(),
+            min: GridIndex::new(from.row.min(to.row), from.col.min(to.col)),
+            max: GridIndex::new(from.row.max(to.row), from.col.max(to.col)),
+        }
+    }
+
+    pub fn rows(&self) -> isize {
+        self.max.row - self.min.row + 1
+    }
+
+    pub fn columns(&self) -> isize {
+        self.max.col - self.min.col + 1
+    }
+
+    pub fn contains(&self, pos: &GridIndex) -> bool {
+        (self.min.row..=self.max.row).contains(&pos.row)
+            && (self.min.col..=self.max.col).contains(&pos.col)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// GridBounds
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Smallest rectangle holding a set of cells, with both corners inclusive. Safe to use across the
+/// whole index range, negative indices included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Data)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GridBounds {
+    pub min: GridIndex,
+    pub max: GridIndex,
+}
+
+impl GridBounds {
+    /// Bounds spanned by two opposite corners given in any order
+    pub fn new(from: GridIndex, to: GridIndex) -> Self {
+        Self {
+            min: GridIndex::new(from.row.min(to.row), from.col.min(to.col)),
+            max: GridIndex::new(from.row.max(to.row), from.col.max(to.col)),
+        }
+    }
+
+    /// Number of rows and columns spanned, saturating for bounds wider than the index range
+    pub fn extent(&self) -> GridIndex {
+        let length = |min: isize, max: isize| {
+            isize::try_from(max.abs_diff(min))
+                .unwrap_or(isize::MAX)
+                .saturating_add(1)
+        };
+        GridIndex::new(
+            length(self.min.row, self.max.row),
+            length(self.min.col, self.max.col),
+        )
+    }
+
+    pub fn contains(&self, pos: &GridIndex) -> bool {
+        (self.min.row..=self.max.row).contains(&pos.row)
+            && (self.min.col..=self.max.col).contains(&pos.col)
+    }
+
+    /// Middle cell, rounded towards the top left corner
+    pub fn center(&self) -> GridIndex {
+        let middle = |min: isize, max: isize| min.saturating_add_unsigned(max.abs_diff(min) / 2);
+        GridIndex::new(
+            middle(self.min.row, self.max.row),
+            middle(self.min.col, self.max.col),
+        )
+    }
+
+    /// Bounds grown to hold `pos`
+    pub fn include(&self, pos: GridIndex) -> Self {
+        Self {
+            min: GridIndex::new(self.min.row.min(pos.row), self.min.col.min(pos.col)),
+            max: GridIndex::new(self.max.row.max(pos.row), self.max.col.max(pos.col)),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Wire
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Route of a net painted over the cells as a line joining the centres of its cells in order
+#[derive(Debug, Clone, PartialEq, Data)]
+pub struct Wire {
+    pub cells: Vector<GridIndex>,
+    pub color: Color,
+}
+
+impl Wire {
+    pub fn new(cells: impl IntoIterator<Item = GridIndex>, color: Color) -> Self {
+        Self {
+            cells: cells.into_iter().collect(),
+            color,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// GridSelection
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Whole row or column picked from a header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum GridSelection {
+    Row(isize),
+    Column(isize),
+}
+
+impl GridSelection {
+    pub fn contains(&self, pos: &GridIndex) -> bool {
+        match self {
+            GridSelection::Row(row) => pos.row == *row,
+            GridSelection::Column(col) => pos.col == *col,
+        }
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
@@ -109,6 +556,40 @@ pub trait GridItem: Copy + Clone + Hash + Eq {
     fn can_move(&self, other: Option<&Self>) -> bool;
     fn get_color(&self) -> Color;
     fn get_short_text(&self) -> String;
+    /// Name of the item shown to users, e.g. in a [`GridLegend`](crate::legend::GridLegend)
+    fn get_name(&self) -> String {
+        self.get_short_text()
+    }
+    /// Reason why the item can not be added on top of `other`, or None if it can
+    fn can_add_reason(&self, other: Option<&Self>) -> Option<String> {
+        (!self.can_add(other)).then(|| String::from("Can not be added here"))
+    }
+    /// Reason why the item can not be removed, or None if it can
+    fn can_remove_reason(&self) -> Option<String> {
+        (!self.can_remove()).then(|| String::from("Can not be removed"))
+    }
+    /// Reason why the item can not be moved on top of `other`, or None if it can
+    fn can_move_reason(&self, other: Option<&Self>) -> Option<String> {
+        (!self.can_move(other)).then(|| String::from("Can not be moved here"))
+    }
+    /// Number of cells around the item where items of another kind can not be placed
+    fn get_halo(&self) -> usize {
+        0
+    }
+    /// Net the item belongs to, if any
+    fn get_net_id(&self) -> Option<usize> {
+        None
+    }
+    /// Colour of the net of the item from the palette shared by all items, see
+    /// [`net_color`](crate::utils::chroma::net_color)
+    fn get_net_color(&self) -> Option<Color> {
+        self.get_net_id().map(utils::chroma::net_color)
+    }
+    /// Item that takes the place of this one when the cells around it are rotated or flipped,
+    /// e.g. a cell with pins on one side. Most items look the same either way
+    fn transformed(&self, _transform: CellTransform) -> Self {
+        *self
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
@@ -122,6 +603,8 @@ pub enum GridState {
     Idle,
     Running(GridAction),
     Disabled,
+    // Panning and zooming still work but every editing action is rejected
+    ViewOnly,
 }
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
@@ -136,4 +619,120 @@ pub enum GridAction {
     Add,
     Remove,
     Move,
+    Route,
+    // Click two cells to show the distance between them
+    Measure,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{GridIndex, Neighborhood};
+
+    #[test]
+    fn shift_keeps_the_stroke_on_a_line() {
+        let origin = GridIndex::new(2, 2);
+        // Mostly horizontal or vertical drags stay on the row or column of the origin
+        assert_eq!(
+            GridIndex::new(3, 7).snap_to_line(origin),
+            GridIndex::new(2, 7)
+        );
+        assert_eq!(
+            GridIndex::new(-4, 3).snap_to_line(origin),
+            GridIndex::new(-4, 2)
+        );
+        // Anything in between goes on a diagonal
+        assert_eq!(
+            GridIndex::new(5, 6).snap_to_line(origin),
+            GridIndex::new(5, 5)
+        );
+        assert_eq!(
+            GridIndex::new(-1, 6).snap_to_line(origin),
+            GridIndex::new(-1, 5)
+        );
+        assert_eq!(origin.snap_to_line(origin), origin);
+
+        // Cells far apart do not overflow, and diagonals stop at the edge of the grid
+        let far = GridIndex::new(isize::MIN, isize::MIN);
+        assert_eq!(
+            GridIndex::new(isize::MAX, isize::MAX).snap_to_line(far),
+            GridIndex::new(isize::MAX, isize::MAX)
+        );
+        let edge = GridIndex::new(0, isize::MAX - (1 << 61));
+        assert_eq!(
+            GridIndex::new(1 << 62, isize::MAX).snap_to_line(edge),
+            GridIndex::new(3 << 60, isize::MAX)
+        );
+    }
+
+    #[test]
+    fn angle_snapping_picks_the_closest_line() {
+        let origin = GridIndex::new(0, 0);
+        assert_eq!(
+            GridIndex::new(4, 6).snap_to_angle(origin, 45.0),
+            GridIndex::new(5, 5)
+        );
+        assert_eq!(
+            GridIndex::new(4, 6).snap_to_angle(origin, 90.0),
+            GridIndex::new(0, 6)
+        );
+        assert_eq!(
+            GridIndex::new(-6, -1).snap_to_angle(origin, 90.0),
+            GridIndex::new(-6, 0)
+        );
+        // Steps that are not positive leave the cell where it is
+        assert_eq!(
+            GridIndex::new(4, 6).snap_to_angle(origin, 0.0),
+            GridIndex::new(4, 6)
+        );
+        // Cells far apart do not overflow and still land on the line
+        let snapped =
+            GridIndex::new(1, isize::MAX).snap_to_angle(GridIndex::new(0, isize::MIN), 90.0);
+        assert_eq!(snapped.row, 0);
+    }
+
+    #[test]
+    fn neighborhoods_agree_on_adjacency() {
+        let origin = GridIndex::new(0, 0);
+        assert_eq!(origin.neighbors(&Neighborhood::VonNeumann).len(), 4);
+        assert_eq!(origin.neighbors(&Neighborhood::Moore).len(), 8);
+        assert!(Neighborhood::Knight.is_adjacent(origin, GridIndex::new(2, -1)));
+        assert!(!Neighborhood::Knight.is_adjacent(origin, GridIndex::new(1, 1)));
+        // Odd rows are shifted to the right, so the cells above and below an even row lean left
+        assert!(Neighborhood::Hex.is_adjacent(origin, GridIndex::new(1, -1)));
+        assert!(!Neighborhood::Hex.is_adjacent(origin, GridIndex::new(1, 1)));
+        assert!(Neighborhood::Hex.is_adjacent(GridIndex::new(1, 0), GridIndex::new(2, 1)));
+
+        let custom = Neighborhood::Custom(Arc::from([GridIndex::new(0, 3)]));
+        assert_eq!(origin.neighbors(&custom), vec![GridIndex::new(0, 3)]);
+
+        // Every built in neighbourhood is symmetric, including rows of both parities
+        for neighborhood in [
+            Neighborhood::VonNeumann,
+            Neighborhood::Moore,
+            Neighborhood::Knight,
+            Neighborhood::Hex,
+        ] {
+            for row in -1..=1 {
+                let from = GridIndex::new(row, 0);
+                for to in from.neighbors(&neighborhood) {
+                    assert!(neighborhood.is_adjacent(to, from), "{neighborhood:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn min_steps_never_overestimates() {
+        let (from, to) = (GridIndex::new(0, 0), GridIndex::new(3, -4));
+        assert_eq!(Neighborhood::VonNeumann.min_steps(from, to), 7);
+        assert_eq!(Neighborhood::Moore.min_steps(from, to), 4);
+        assert_eq!(Neighborhood::Hex.min_steps(from, to), 4);
+        assert_eq!(
+            Neighborhood::Knight.min_steps(from, GridIndex::new(4, 2)),
+            2
+        );
+        assert_eq!(Neighborhood::Custom(Arc::from([])).min_steps(from, to), 0);
+    }
 }
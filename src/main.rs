@@ -4,17 +4,39 @@ use druid::{
 };
 
 use druid::widget::{
-    Button, ControllerHost, CrossAxisAlignment, Flex, Label, MainAxisAlignment, Switch,
+    Button, ControllerHost, CrossAxisAlignment, Flex, Label, MainAxisAlignment, RadioGroup, Switch,
 };
 
 use druid_color_thesaurus::*;
+use graph_builder::{DirectedCsrGraph, GraphBuilder};
 
-use druid_grid_graph_widget::grid_canvas::{GridCanvas, GridCanvasData};
+use druid_grid_graph_widget::automata::{
+    AutomatonController, LifeRule, AUTOMATON_PAUSE, AUTOMATON_RUN, AUTOMATON_STEP,
+};
+use druid_grid_graph_widget::autosave::{restore_latest, AutosaveController};
+use druid_grid_graph_widget::cell_size::CellSizeController;
+use druid_grid_graph_widget::export::{to_svg, SvgOptions};
+use druid_grid_graph_widget::grid_canvas::{
+    DirtyLens, GestureAction, GestureConfig, GridCanvas, GridCanvasData, ImageExport, ReadOnlyLens,
+    CURSOR_CROSSHAIR, EXPORT_IMAGE,
+};
+use druid_grid_graph_widget::headers::{GridHeader, HEADER_THICKNESS};
+use druid_grid_graph_widget::item_editor::{EditedItem, CLOSE_ITEM_EDITOR};
+use druid_grid_graph_widget::legend::GridLegend;
+use druid_grid_graph_widget::mazes::recursive_backtracker;
+use druid_grid_graph_widget::palette::ItemPalette;
 use druid_grid_graph_widget::panning::{PanController, PanDataAccess};
-use druid_grid_graph_widget::snapping::{GridSnapData, GridSnapDataAccess, GridSnapPainter};
-use druid_grid_graph_widget::utils::cassetta::{CassettePlayer, TapeItem};
+use druid_grid_graph_widget::rotation::{RotationController, RotationDataAccess};
+use druid_grid_graph_widget::session::Session;
+use druid_grid_graph_widget::snapping::{
+    CoordinateSystem, GridSnapData, GridSnapDataAccess, GridSnapPainter, SnapRounding,
+};
+use druid_grid_graph_widget::theme as grid_theme;
+use druid_grid_graph_widget::timeline::{TapeTimeline, TIMELINE_PLAY};
+use druid_grid_graph_widget::utils::cassetta::TapeItem;
+use druid_grid_graph_widget::utils::lokigo::layered_layout;
 use druid_grid_graph_widget::zooming::{ZoomController, ZoomDataAccess};
-use druid_grid_graph_widget::{GridIndex, GridItem};
+use druid_grid_graph_widget::{CellTransform, GridAction, GridIndex, GridItem, GridRegion};
 //////////////////////////////////////////////////////////////////////////////////////
 // Constants
 //////////////////////////////////////////////////////////////////////////////////////
@@ -51,6 +73,38 @@ impl GridNodeType<Net> {
             Self::ChosenPath(net) => net,
         }
     }
+
+    // Same kind of node on another net, walls and boundaries have none
+    fn with_net(&self, net: Net) -> Self {
+        match self {
+            Self::Wall => Self::Wall,
+            Self::Boundary => Self::Boundary,
+            Self::StartNode(_) => Self::StartNode(net),
+            Self::TargetNode(_) => Self::TargetNode(net),
+            Self::UnexploredNode(_) => Self::UnexploredNode(net),
+            Self::ExploredNode(_) => Self::ExploredNode(net),
+            Self::ChosenPath(_) => Self::ChosenPath(net),
+        }
+    }
+
+    // Reads back the text of get_name
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Wall" => return Some(Self::Wall),
+            "Boundary" => return Some(Self::Boundary),
+            _ => {}
+        }
+        let (kind, net) = name.strip_suffix(')')?.split_once('(')?;
+        let net = net.parse().ok()?;
+        match kind {
+            "StartNode" => Some(Self::StartNode(net)),
+            "TargetNode" => Some(Self::TargetNode(net)),
+            "UnexploredNode" => Some(Self::UnexploredNode(net)),
+            "ExploredNode" => Some(Self::ExploredNode(net)),
+            "ChosenPath" => Some(Self::ChosenPath(net)),
+            _ => None,
+        }
+    }
 }
 
 impl GridItem for GridNodeType<Net> {
@@ -72,6 +126,10 @@ impl GridItem for GridNodeType<Net> {
         }
     }
 
+    fn can_move_reason(&self, other: Option<&Self>) -> Option<String> {
+        other.map(|other| format!("Cell is occupied by {}", other.get_short_text()))
+    }
+
     fn get_color(&self) -> Color {
         match self {
             Self::Wall => black::LICORICE,
@@ -80,7 +138,7 @@ impl GridItem for GridNodeType<Net> {
             Self::TargetNode(_) => purple::PURPUREUS,
             Self::UnexploredNode(_) => yellow::YELLOW_AMBER,
             Self::ExploredNode(_) => brown::MAROON,
-            Self::ChosenPath(_) => green::ASH_GRAY,
+            Self::ChosenPath(_) => self.get_net_color().unwrap_or(green::ASH_GRAY),
         }
     }
 
@@ -95,6 +153,24 @@ impl GridItem for GridNodeType<Net> {
             Self::ChosenPath(net) => format!("{:?}", net),
         }
     }
+
+    fn get_halo(&self) -> usize {
+        match self {
+            Self::Boundary => 1,
+            _ => 0,
+        }
+    }
+
+    fn get_name(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn get_net_id(&self) -> Option<usize> {
+        match self {
+            Self::Wall | Self::Boundary => None,
+            _ => usize::try_from(*self.get_net()).ok(),
+        }
+    }
 }
 
 //////////////////////////////////////////////////////////////////////////////////////
@@ -119,21 +195,51 @@ impl AppData {
 
 impl ZoomDataAccess for AppData {
     fn get_zoom_scale(&self) -> f64 {
-        self.grid_data.snap_data.zoom_data.zoom_scale
+        self.grid_data.snap_data.get_zoom_scale()
     }
 
     fn set_zoom_scale(&mut self, scale: f64) {
-        self.grid_data.snap_data.zoom_data.zoom_scale = scale;
+        self.grid_data.snap_data.set_zoom_scale(scale)
     }
 }
 
 impl PanDataAccess for AppData {
     fn get_offset(&self) -> druid::Point {
-        self.grid_data.snap_data.pan_data.offset
+        self.grid_data.snap_data.get_offset()
     }
 
     fn set_offset(&mut self, offset: druid::Point) {
-        self.grid_data.snap_data.pan_data.offset = offset
+        self.grid_data.snap_data.set_offset(offset)
+    }
+
+    fn get_drag_delta(&self) -> druid::Vec2 {
+        self.grid_data.snap_data.get_drag_delta()
+    }
+
+    fn set_drag_delta(&mut self, delta: druid::Vec2) {
+        self.grid_data.snap_data.set_drag_delta(delta)
+    }
+
+    fn get_overscroll(&self) -> druid::Vec2 {
+        self.grid_data.snap_data.get_overscroll()
+    }
+
+    fn set_overscroll(&mut self, overscroll: druid::Vec2) {
+        self.grid_data.snap_data.set_overscroll(overscroll)
+    }
+
+    fn get_content_bounds(&self) -> Option<druid::Rect> {
+        self.grid_data.content_bounds()
+    }
+}
+
+impl RotationDataAccess for AppData {
+    fn get_rotation(&self) -> f64 {
+        self.grid_data.snap_data.get_rotation()
+    }
+
+    fn set_rotation(&mut self, rotation: f64) {
+        self.grid_data.snap_data.set_rotation(rotation)
     }
 }
 
@@ -159,6 +265,30 @@ impl GridSnapDataAccess for AppData {
             .snap_data
             .move_to_grid_position_2(desired_position)
     }
+
+    fn get_snap_enabled(&self) -> bool {
+        self.grid_data.snap_data.snap_enabled
+    }
+
+    fn set_snap_enabled(&mut self, state: bool) {
+        self.grid_data.snap_data.snap_enabled = state;
+    }
+
+    fn get_angle_snap(&self) -> Option<f64> {
+        self.grid_data.snap_data.angle_snap
+    }
+
+    fn set_angle_snap(&mut self, step: Option<f64>) {
+        self.grid_data.snap_data.angle_snap = step;
+    }
+
+    fn get_coordinates(&self) -> CoordinateSystem {
+        self.grid_data.snap_data.coordinates
+    }
+
+    fn set_coordinates(&mut self, coordinates: CoordinateSystem) {
+        self.grid_data.snap_data.coordinates = coordinates;
+    }
 }
 
 //////////////////////////////////////////////////////////////////////////////////////
@@ -287,11 +417,13 @@ fn main() {
 
     AppLauncher::with_window(main_window)
         .configure_env(|env, _| {
+            grid_theme::add_to_env(env);
             env.set(theme::SELECTION_TEXT_COLOR, Color::rgb8(0xA6, 0xCC, 0xFF));
             env.set(theme::WINDOW_BACKGROUND_COLOR, gray::DAVYS_GRAY);
             env.set(theme::CURSOR_COLOR, Color::BLACK);
             env.set(theme::BACKGROUND_LIGHT, Color::rgb8(230, 230, 230));
-            env.set(theme::TEXT_COLOR, white::ALABASTER)
+            env.set(theme::TEXT_COLOR, white::ALABASTER);
+            env.set(CURSOR_CROSSHAIR, true);
         })
         .log_to_console()
         .launch(data)
@@ -306,17 +438,48 @@ fn main() {
 
 fn make_ui() -> impl Widget<AppData> {
     let snap_painter = GridSnapPainter::default();
+    let gestures = GestureConfig {
+        double_click: GestureAction::Edit,
+        long_press: GestureAction::SelectSame,
+        ..Default::default()
+    };
     let grid = GridCanvas::<GridNodeType<Net>>::new()
+        .with_gestures(gestures)
+        .with_item_editor(make_item_editor)
+        .with_child_budget(2000)
+        .controller(AutomatonController::new(LifeRule::conway(
+            GridNodeType::Wall,
+        )))
+        .controller(AutosaveController::csv(
+            autosave_dir(),
+            GridNodeType::get_name,
+        ))
         .with_id(GRID_ID)
         .lens(AppData::grid_data);
 
     let grid_container = grid.background(snap_painter.square_grid());
 
-    let pan_control_host = ControllerHost::new(grid_container, PanController::default());
+    let pan_control_host = ControllerHost::new(
+        grid_container,
+        PanController::default()
+            .with_inertia(0.92)
+            .with_content_bounds(300.0),
+    );
     let zoom_control_host = ControllerHost::new(pan_control_host, ZoomController::default());
+    let rotation_control_host =
+        ControllerHost::new(zoom_control_host, RotationController::default());
+
+    // The headers start where the canvas starts so their indices line up with the cells
+    let column_header = Flex::row()
+        .with_spacer(HEADER_THICKNESS)
+        .with_flex_child(GridHeader::columns().lens(AppData::grid_data), 1.0);
+    let canvas_row = Flex::row()
+        .with_child(GridHeader::rows().lens(AppData::grid_data))
+        .with_flex_child(rotation_control_host, 1.0);
 
     Flex::column()
-        .with_flex_child(zoom_control_host, 1.0) // Grid widget
+        .with_child(column_header)
+        .with_flex_child(canvas_row, 1.0) // Grid widget
         .with_child(make_control_bar())
         .main_axis_alignment(MainAxisAlignment::SpaceAround)
         .cross_axis_alignment(CrossAxisAlignment::Center)
@@ -325,34 +488,76 @@ fn make_ui() -> impl Widget<AppData> {
 fn make_control_bar() -> impl Widget<AppData> {
     Flex::row()
         .with_flex_child(make_grid_options(), 1.0)
+        .with_child(
+            GridLegend::<GridNodeType<Net>>::new()
+                .lens(AppData::grid_data)
+                .padding(5.0),
+        )
         .main_axis_alignment(MainAxisAlignment::SpaceBetween)
         .cross_axis_alignment(CrossAxisAlignment::Start)
         .must_fill_main_axis(true)
         .background(black::ONYX)
 }
 
+// Directory the grid is autosaved to
+fn autosave_dir() -> std::path::PathBuf {
+    std::env::temp_dir().join("druid-grid-autosave")
+}
+
+// Popup opened by double-clicking a node, changes the net it belongs to
+fn make_item_editor(
+    _item: &EditedItem<GridNodeType<Net>>,
+) -> Box<dyn Widget<EditedItem<GridNodeType<Net>>>> {
+    let net_button = |label: &'static str, step: Net| {
+        Button::new(label).on_click(
+            move |_ctx, data: &mut EditedItem<GridNodeType<Net>>, _env| {
+                data.item = data.item.with_net(data.item.get_net() + step);
+            },
+        )
+    };
+    Box::new(
+        Flex::column()
+            .with_child(Label::dynamic(
+                |data: &EditedItem<GridNodeType<Net>>, _env| data.item.get_name(),
+            ))
+            .with_child(
+                Flex::row()
+                    .with_child(net_button("Net -", -1))
+                    .with_child(net_button("Net +", 1))
+                    .with_child(Button::new("Done").on_click(|ctx, _data, _env| {
+                        ctx.submit_command(CLOSE_ITEM_EDITOR.to(GRID_ID))
+                    })),
+            ),
+    )
+}
+
+// Button that rotates or flips the selected cells
+fn transform_button(label: &'static str, transform: CellTransform) -> impl Widget<AppData> {
+    Button::new(label).on_click(move |_ctx, data: &mut AppData, _env| {
+        if let Err(reason) = data.grid_data.transform_selection(transform) {
+            log::warn!("Could not transform the selection: {reason}");
+        }
+    })
+}
+
 fn make_grid_options() -> impl Widget<AppData> {
     Flex::column()
         .with_child(Label::new("Grid Options").with_text_size(20.0))
         .with_child(
             Flex::row()
                 .with_child(Label::new("Playback: "))
-                .with_child(Button::new("Previous").lens(AppData::grid_data).on_click(
-                    |ctx, data, _env| {
-                        let item = data.grid_data.save_data.undo();
-                        if let Some(item) = item {
-                            data.grid_data.grid.rewind(item);
-                        }
-                    },
-                ))
-                .with_child(Button::new("Next").lens(AppData::grid_data).on_click(
+                .with_flex_child(TapeTimeline::new(10.0).lens(AppData::grid_data), 1.0)
+                .with_child(Button::new("Replay all").lens(AppData::grid_data).on_click(
                     |ctx, data, _env| {
-                        let item = data.grid_data.save_data.redo();
-                        if let Some(item) = item {
-                            data.grid_data.grid.advance(item);
-                        }
+                        data.grid_data.seek(0);
+                        ctx.submit_command(TIMELINE_PLAY);
                     },
                 ))
+                .must_fill_main_axis(true),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Edit: "))
                 .with_child(Button::new("Clear").lens(AppData::grid_data).on_click(
                     |ctx, data, _env| {
                         data.grid_data.clear_all();
@@ -418,15 +623,244 @@ fn make_grid_options() -> impl Widget<AppData> {
                             }
                             data.grid_data.submit_to_stack(pattern);
                         }),
+                )
+                .with_child(Button::new("Export").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        ctx.submit_command(EXPORT_IMAGE.with(ImageExport {
+                            scale: 2.0,
+                            path: Some("grid.png".into()),
+                        }));
+                    },
+                ))
+                .with_child(Button::new("Export SVG").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        let svg = to_svg(&data.grid_data, &SvgOptions::default());
+                        match std::fs::write("grid.svg", svg) {
+                            Ok(()) => data.grid_data.mark_saved(),
+                            Err(err) => log::error!("Could not write grid.svg: {err}"),
+                        }
+                    },
+                ))
+                .with_child(Button::new("Restore").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        let restored = restore_latest(autosave_dir()).and_then(|text| {
+                            let text = text.ok_or_else(|| String::from("No autosave found"))?;
+                            data.grid_data
+                                .replace_from_csv(&text, ',', GridNodeType::from_name)
+                        });
+                        if let Err(err) = restored {
+                            log::error!("Could not restore the autosave: {err}");
+                        }
+                    },
+                ))
+                .with_child(
+                    Label::dynamic(|dirty: &bool, _env| {
+                        String::from(if *dirty { "Unsaved changes" } else { "" })
+                    })
+                    .lens(DirtyLens)
+                    .lens(AppData::grid_data),
+                )
+                .with_child(Button::new("Add maze").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        // Carved step by step when played back
+                        let script = recursive_backtracker(
+                            GridIndex::new(0, 50),
+                            GridIndex::new(20, 80),
+                            GridNodeType::Wall,
+                            &mut rand::thread_rng(),
+                        );
+                        data.grid_data.submit_to_stack(script);
+                        ctx.submit_command(TIMELINE_PLAY);
+                    },
+                ))
+                .with_child(Button::new("Add tree").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        let tree: DirectedCsrGraph<usize> = GraphBuilder::new()
+                            .edges(
+                                (1..15)
+                                    .map(|node| ((node - 1) / 2, node))
+                                    .collect::<Vec<_>>(),
+                            )
+                            .build();
+                        data.grid_data.add_layout(
+                            &layered_layout(&tree, 2),
+                            GridIndex::new(20, 5),
+                            GridNodeType::TargetNode(1),
+                        );
+                    },
+                )),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Selection: "))
+                .with_child(transform_button("Rotate ⟳", CellTransform::RotateClockwise))
+                .with_child(transform_button(
+                    "Rotate ⟲",
+                    CellTransform::RotateCounterClockwise,
+                ))
+                .with_child(transform_button("Flip ⇆", CellTransform::FlipHorizontal))
+                .with_child(transform_button("Flip ⇅", CellTransform::FlipVertical)),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Macro: "))
+                .with_child(Button::new("Record").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        data.grid_data.recorder.start();
+                    },
+                ))
+                .with_child(Button::new("Stop").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        data.grid_data.recorder.stop();
+                    },
+                ))
+                .with_child(Button::new("Replay").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        let recording = data.grid_data.recorder.recording.clone();
+                        data.grid_data.replay(recording, GridIndex::new(0, 5));
+                    },
+                )),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Life: "))
+                .with_child(
+                    Button::new("Run").on_click(|ctx, _data: &mut AppData, _env| {
+                        ctx.submit_command(AUTOMATON_RUN);
+                    }),
+                )
+                .with_child(
+                    Button::new("Pause").on_click(|ctx, _data: &mut AppData, _env| {
+                        ctx.submit_command(AUTOMATON_PAUSE);
+                    }),
+                )
+                .with_child(
+                    Button::new("Step").on_click(|ctx, _data: &mut AppData, _env| {
+                        ctx.submit_command(AUTOMATON_STEP);
+                    }),
+                ),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Session: "))
+                .with_child(Button::new("Record").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        data.grid_data.start_session();
+                    },
+                ))
+                .with_child(Button::new("Save").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        if let Some(session) = data.grid_data.stop_session() {
+                            let text = session.to_text(GridNodeType::get_name);
+                            if let Err(err) = std::fs::write("session.txt", text) {
+                                log::error!("Could not write session.txt: {err}");
+                            }
+                        }
+                    },
+                ))
+                .with_child(Button::new("Replay").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        let session = std::fs::read_to_string("session.txt")
+                            .map_err(|err| err.to_string())
+                            .and_then(|text| Session::from_text(&text, GridNodeType::from_name))
+                            .and_then(|session| session.replay(&mut data.grid_data));
+                        if let Err(err) = session {
+                            log::error!("Could not replay session.txt: {err}");
+                        }
+                    },
+                )),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("ECO: "))
+                .with_child(Button::new("Freeze").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        data.grid_data.freeze_baseline();
+                    },
+                ))
+                .with_child(
+                    Button::new("Copy changes")
+                        .lens(AppData::grid_data)
+                        .on_click(|ctx, data, _env| {
+                            let offset = GridIndex::new(0, 5);
+                            let changes = data
+                                .grid_data
+                                .change_list()
+                                .into_iter()
+                                .map(|item| item.map_keys(|pos| pos + offset))
+                                .collect();
+                            data.grid_data.apply_change_list(changes);
+                        }),
                 ),
         )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Regions: "))
+                .with_child(
+                    Button::new("Mark Room A")
+                        .lens(AppData::grid_data)
+                        .on_click(|ctx, data, _env| {
+                            data.grid_data.add_region(GridRegion::new(
+                                "Room A",
+                                GridIndex::new(5, 5),
+                                GridIndex::new(10, 10),
+                            ));
+                        }),
+                )
+                .with_child(
+                    Button::new("Clear Room A")
+                        .lens(AppData::grid_data)
+                        .on_click(|ctx, data, _env| {
+                            data.grid_data.clear_region("Room A");
+                        }),
+                )
+                .with_child(Label::new(|data: &AppData, _: &_| {
+                    format!(
+                        "{} items in Room A",
+                        data.grid_data.cells_in_region("Room A").len()
+                    )
+                })),
+        )
         .with_child(
             Flex::row()
                 .with_child(Label::new("Tool: "))
+                .with_child(Button::new("Draw").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        data.grid_data.set_action(GridAction::Dynamic);
+                    },
+                ))
+                .with_child(Button::new("Route").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        data.grid_data.set_action(GridAction::Route);
+                    },
+                ))
+                .with_child(Button::new("Measure").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        data.grid_data.set_action(GridAction::Measure);
+                    },
+                ))
                 .main_axis_alignment(MainAxisAlignment::SpaceBetween)
                 .cross_axis_alignment(CrossAxisAlignment::Start)
                 .must_fill_main_axis(true),
         )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Item: "))
+                .with_child(
+                    ItemPalette::new(vec![
+                        GridNodeType::Wall,
+                        GridNodeType::Boundary,
+                        GridNodeType::StartNode(0),
+                        GridNodeType::TargetNode(0),
+                        GridNodeType::StartNode(1),
+                        GridNodeType::TargetNode(1),
+                    ])
+                    .lens(AppData::grid_data),
+                )
+                .main_axis_alignment(MainAxisAlignment::SpaceBetween)
+                .cross_axis_alignment(CrossAxisAlignment::Center)
+                .must_fill_main_axis(true),
+        )
         .with_child(
             Flex::row()
                 .with_child(Label::new("Net: "))
@@ -437,6 +871,7 @@ fn make_grid_options() -> impl Widget<AppData> {
                 .cross_axis_alignment(CrossAxisAlignment::Start)
                 .must_fill_main_axis(true),
         )
+        .with_child(CellSizeController::new(5.0, 40.0))
         .with_child(
             Flex::row()
                 .with_child(Label::new("Show Axis: "))
@@ -446,6 +881,66 @@ fn make_grid_options() -> impl Widget<AppData> {
                         .lens(GridCanvasData::snap_data)
                         .lens(AppData::grid_data),
                 )
+                .with_child(
+                    Button::new("Math Axes").on_click(|ctx, data: &mut AppData, _env| {
+                        let coordinates = if data.get_coordinates() == CoordinateSystem::default() {
+                            CoordinateSystem::math(GridIndex::new(20, 5))
+                        } else {
+                            CoordinateSystem::default()
+                        };
+                        data.set_coordinates(coordinates);
+                    }),
+                )
+                .main_axis_alignment(MainAxisAlignment::SpaceBetween)
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .must_fill_main_axis(true),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Snapping: "))
+                .with_child(
+                    RadioGroup::row(vec![
+                        ("Floor", SnapRounding::Floor),
+                        ("Nearest", SnapRounding::Nearest),
+                        ("Hysteresis", SnapRounding::Hysteresis),
+                    ])
+                    .lens(GridSnapData::rounding)
+                    .lens(GridCanvasData::snap_data)
+                    .lens(AppData::grid_data),
+                )
+                .with_child(
+                    Button::new("45° Lines").on_click(|ctx, data: &mut AppData, _env| {
+                        let step = match data.get_angle_snap() {
+                            Some(_) => None,
+                            None => Some(45.0),
+                        };
+                        data.set_angle_snap(step);
+                    }),
+                )
+                .main_axis_alignment(MainAxisAlignment::SpaceBetween)
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .must_fill_main_axis(true),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Finite Board: "))
+                .with_child(Button::new("Toggle").lens(AppData::grid_data).on_click(
+                    |ctx, data, _env| {
+                        let extent = match data.grid_data.extent() {
+                            Some(_) => None,
+                            None => Some(GridIndex::new(30, 60)),
+                        };
+                        data.grid_data.set_extent(extent);
+                    },
+                ))
+                .main_axis_alignment(MainAxisAlignment::SpaceBetween)
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .must_fill_main_axis(true),
+        )
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Read Only: "))
+                .with_child(Switch::new().lens(ReadOnlyLens).lens(AppData::grid_data))
                 .main_axis_alignment(MainAxisAlignment::SpaceBetween)
                 .cross_axis_alignment(CrossAxisAlignment::Start)
                 .must_fill_main_axis(true),
@@ -0,0 +1,340 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::collections::HashSet;
+
+use druid::im::{HashMap, Vector};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{utils::cassetta::TapeItem, GridIndex};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Maze Layout
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+// Rooms of a maze filling the block between two corners. The block starts out as walls and the
+// rooms sit on every other row and column inside its border, so neighbouring rooms are split by
+// a single wall cell
+struct MazeLayout<T> {
+    min: GridIndex,
+    rows: usize,
+    cols: usize,
+    wall: T,
+    // Rooms and walls carved so far
+    open: HashSet<GridIndex>,
+    script: Vector<TapeItem<GridIndex, T>>,
+}
+
+impl<T: Clone> MazeLayout<T> {
+    fn new(from: GridIndex, to: GridIndex, wall: T) -> Self {
+        let min = GridIndex::new(from.row.min(to.row), from.col.min(to.col));
+        let max = GridIndex::new(from.row.max(to.row), from.col.max(to.col));
+        let mut walls = HashMap::new();
+        for row in min.row..=max.row {
+            for col in min.col..=max.col {
+                walls.insert(GridIndex::new(row, col), (wall.clone(), None));
+            }
+        }
+        Self {
+            min,
+            rows: ((max.row - min.row) / 2) as usize,
+            cols: ((max.col - min.col) / 2) as usize,
+            wall,
+            open: HashSet::new(),
+            script: Vector::unit(TapeItem::BatchAdd(walls)),
+        }
+    }
+
+    fn room_count(&self) -> usize {
+        self.rows * self.cols
+    }
+
+    fn cell_of(&self, room: usize) -> GridIndex {
+        GridIndex::new(
+            self.min.row + 1 + 2 * (room / self.cols) as isize,
+            self.min.col + 1 + 2 * (room % self.cols) as isize,
+        )
+    }
+
+    // Rooms sharing a wall with `room`
+    fn neighbors(&self, room: usize) -> Vec<usize> {
+        let (row, col) = (room / self.cols, room % self.cols);
+        let mut rooms = Vec::with_capacity(4);
+        if row > 0 {
+            rooms.push(room - self.cols);
+        }
+        if row + 1 < self.rows {
+            rooms.push(room + self.cols);
+        }
+        if col > 0 {
+            rooms.push(room - 1);
+        }
+        if col + 1 < self.cols {
+            rooms.push(room + 1);
+        }
+        rooms
+    }
+
+    // Every wall between two rooms, once
+    fn walls(&self) -> Vec<(usize, usize)> {
+        (0..self.room_count())
+            .flat_map(|room| {
+                self.neighbors(room)
+                    .into_iter()
+                    .filter(move |other| *other > room)
+                    .map(move |other| (room, other))
+            })
+            .collect()
+    }
+
+    // Opens the cells that are still walls as a single step of the script
+    fn carve(&mut self, cells: &[GridIndex]) {
+        let removed: HashMap<GridIndex, T> = cells
+            .iter()
+            .filter(|cell| self.open.insert(**cell))
+            .map(|cell| (*cell, self.wall.clone()))
+            .collect();
+        if !removed.is_empty() {
+            self.script.push_back(TapeItem::BatchRemove(removed));
+        }
+    }
+
+    fn carve_room(&mut self, room: usize) {
+        let cell = self.cell_of(room);
+        self.carve(&[cell]);
+    }
+
+    // Opens the wall between two neighbouring rooms along with the rooms themselves
+    fn carve_passage(&mut self, from: usize, to: usize) {
+        let (from, to) = (self.cell_of(from), self.cell_of(to));
+        let between = GridIndex::new((from.row + to.row) / 2, (from.col + to.col) / 2);
+        self.carve(&[from, between, to]);
+    }
+
+    fn finish(self) -> Vector<TapeItem<GridIndex, T>> {
+        self.script
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Maze Generators
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+// The generators fill the block between the corners `from` and `to` (inclusive) with `wall`
+// and carve a perfect maze into it, with exactly one path between any two rooms. Rooms sit on
+// the cells an odd number of rows and columns away from the top left corner and the border is
+// left standing. The script starts with a BatchAdd of the whole block followed by a
+// BatchRemove per carving step, so it can be played back one step per tick. Blocks narrower
+// than three cells have no rooms and are left as walls.
+
+/// Walks from a random room to random unvisited neighbours, backing up when stuck. Gives long
+/// winding corridors with few dead ends
+pub fn recursive_backtracker<T: Clone>(
+    from: GridIndex,
+    to: GridIndex,
+    wall: T,
+    rng: &mut impl Rng,
+) -> Vector<TapeItem<GridIndex, T>> {
+    let mut layout = MazeLayout::new(from, to, wall);
+    if layout.room_count() == 0 {
+        return layout.finish();
+    }
+
+    let mut visited = vec![false; layout.room_count()];
+    let start = rng.gen_range(0..layout.room_count());
+    visited[start] = true;
+    layout.carve_room(start);
+    let mut stack = vec![start];
+    while let Some(room) = stack.last().copied() {
+        let unvisited: Vec<usize> = layout
+            .neighbors(room)
+            .into_iter()
+            .filter(|other| !visited[*other])
+            .collect();
+        match unvisited.choose(rng) {
+            Some(next) => {
+                visited[*next] = true;
+                layout.carve_passage(room, *next);
+                stack.push(*next);
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+    layout.finish()
+}
+
+/// Grows the maze from a random room by opening a random wall on its edge at every step. Gives
+/// short branching corridors
+pub fn prim<T: Clone>(
+    from: GridIndex,
+    to: GridIndex,
+    wall: T,
+    rng: &mut impl Rng,
+) -> Vector<TapeItem<GridIndex, T>> {
+    let mut layout = MazeLayout::new(from, to, wall);
+    if layout.room_count() == 0 {
+        return layout.finish();
+    }
+
+    let mut visited = vec![false; layout.room_count()];
+    let start = rng.gen_range(0..layout.room_count());
+    visited[start] = true;
+    layout.carve_room(start);
+    // Walls between a room of the maze and one that may not be in it yet
+    let mut frontier: Vec<(usize, usize)> = layout
+        .neighbors(start)
+        .into_iter()
+        .map(|other| (start, other))
+        .collect();
+    while !frontier.is_empty() {
+        let (room, next) = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+        if visited[next] {
+            continue;
+        }
+        visited[next] = true;
+        layout.carve_passage(room, next);
+        frontier.extend(
+            layout
+                .neighbors(next)
+                .into_iter()
+                .filter(|other| !visited[*other])
+                .map(|other| (next, other)),
+        );
+    }
+    layout.finish()
+}
+
+/// Opens the walls in random order, skipping those between rooms that are already connected.
+/// Corridors grow all over the block at once
+pub fn kruskal<T: Clone>(
+    from: GridIndex,
+    to: GridIndex,
+    wall: T,
+    rng: &mut impl Rng,
+) -> Vector<TapeItem<GridIndex, T>> {
+    let mut layout = MazeLayout::new(from, to, wall);
+    if layout.room_count() == 1 {
+        layout.carve_room(0);
+    }
+
+    let mut sets = DisjointSets::new(layout.room_count());
+    let mut walls = layout.walls();
+    walls.shuffle(rng);
+    for (room, other) in walls {
+        if sets.union(room, other) {
+            layout.carve_passage(room, other);
+        }
+    }
+    layout.finish()
+}
+
+// Union-find over the rooms of a maze
+struct DisjointSets {
+    parents: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, mut item: usize) -> usize {
+        while self.parents[item] != item {
+            self.parents[item] = self.parents[self.parents[item]];
+            item = self.parents[item];
+        }
+        item
+    }
+
+    // Returns false when the two were already in the same set
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a] = b;
+        a != b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashSet, VecDeque};
+
+    use druid::im::Vector;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{kruskal, prim, recursive_backtracker};
+    use crate::{utils::cassetta::TapeItem, GridIndex};
+
+    // Cells left open by a script, checking that it only ever opens walls
+    fn open_cells(script: &Vector<TapeItem<GridIndex, char>>) -> HashSet<GridIndex> {
+        let mut walls = HashSet::new();
+        let mut open = HashSet::new();
+        for (index, item) in script.iter().enumerate() {
+            match item {
+                TapeItem::BatchAdd(map) if index == 0 => walls.extend(map.keys().copied()),
+                TapeItem::BatchRemove(map) => {
+                    for pos in map.keys() {
+                        assert!(walls.remove(pos), "{pos:?} is not a wall");
+                        open.insert(*pos);
+                    }
+                }
+                _ => panic!("unexpected {item:?}"),
+            }
+        }
+        open
+    }
+
+    fn assert_perfect_maze(script: Vector<TapeItem<GridIndex, char>>, from: GridIndex) {
+        let open = open_cells(&script);
+        let rooms: Vec<GridIndex> = (0..4)
+            .flat_map(|row| (0..5).map(move |col| GridIndex::new(1 + 2 * row, 1 + 2 * col)))
+            .map(|pos| pos + from)
+            .collect();
+        // A spanning tree of the rooms, one wall opened per room but the first
+        assert_eq!(open.len(), 2 * rooms.len() - 1);
+        assert!(rooms.iter().all(|room| open.contains(room)));
+
+        let mut reached = HashSet::from([rooms[0]]);
+        let mut queue = VecDeque::from([rooms[0]]);
+        while let Some(pos) = queue.pop_front() {
+            for next in pos.neighbors_rectilinear() {
+                if open.contains(&next) && reached.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        assert_eq!(reached, open);
+    }
+
+    #[test]
+    fn generators_carve_perfect_mazes() {
+        // 9 by 11 cells hold 4 by 5 rooms
+        let from = GridIndex::new(-3, 2);
+        let to = GridIndex::new(5, 12);
+        let mut rng = StdRng::seed_from_u64(7);
+        assert_perfect_maze(recursive_backtracker(from, to, '#', &mut rng), from);
+        assert_perfect_maze(prim(to, from, '#', &mut rng), from);
+        assert_perfect_maze(kruskal(from, to, '#', &mut rng), from);
+    }
+
+    #[test]
+    fn small_blocks() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let corner = GridIndex::new(0, 0);
+        let script = kruskal(corner, GridIndex::new(1, 5), '#', &mut rng);
+        assert_eq!(script.len(), 1);
+        assert!(open_cells(&script).is_empty());
+
+        let script = prim(corner, GridIndex::new(2, 2), '#', &mut rng);
+        assert_eq!(open_cells(&script), HashSet::from([GridIndex::new(1, 1)]));
+        let script = kruskal(corner, GridIndex::new(2, 2), '#', &mut rng);
+        assert_eq!(open_cells(&script), HashSet::from([GridIndex::new(1, 1)]));
+    }
+}
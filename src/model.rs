@@ -0,0 +1,961 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::{fmt::Debug, hash::Hash};
+
+use druid::{
+    im::{HashMap, Vector},
+    Data,
+};
+
+use crate::{
+    chunked_grid::ChunkedGrid,
+    grid_canvas::{CanvasItem, GridCanvasData},
+    session::{Session, SessionEvent, SessionOp},
+    utils::{
+        cassetta::{CassettePlayer, TapeItem},
+        graphema::Lattice2D,
+    },
+    GridIndex, GridItem,
+};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// GridCanvasModel
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Grid with the rules of its items and its undo tape, to edit from code without a widget or a
+/// window, e.g. in server-side tools and tests. Every edit is checked like a gesture on the
+/// widget would be and is a single undo step. Queries go through [`data`](Self::data)
+pub struct GridCanvasModel<T: GridItem + PartialEq + Debug> {
+    data: GridCanvasData<T>,
+}
+
+impl<T: GridItem + PartialEq + Debug> GridCanvasModel<T>
+where
+    GridCanvasData<T>: Data,
+{
+    pub fn new(item_type: T) -> Self {
+        Self {
+            data: GridCanvasData::new(item_type),
+        }
+    }
+
+    pub fn data(&self) -> &GridCanvasData<T> {
+        &self.data
+    }
+
+    /// Data of the grid, for the operations the model does not wrap
+    pub fn data_mut(&mut self) -> &mut GridCanvasData<T> {
+        &mut self.data
+    }
+
+    /// Data to hand to a `GridCanvas` widget
+    pub fn into_data(self) -> GridCanvasData<T> {
+        self.data
+    }
+
+    pub fn get(&self, pos: &GridIndex) -> Option<&T> {
+        self.data.grid.get(pos)
+    }
+
+    // Editing methods
+    pub fn add(&mut self, pos: GridIndex, item: T) -> Result<(), String> {
+        self.check_writable()?;
+        let result = self.data.add_node(&pos, item);
+        self.data.end_stroke();
+        result
+    }
+
+    pub fn remove(&mut self, pos: GridIndex) -> Result<(), String> {
+        self.check_writable()?;
+        self.check_occupied(&pos)?;
+        let result = self.data.remove_node(&pos);
+        self.data.end_stroke();
+        result
+    }
+
+    pub fn move_item(&mut self, from: GridIndex, to: GridIndex) -> Result<(), String> {
+        self.check_writable()?;
+        self.check_occupied(&from)?;
+        self.data.end_stroke();
+        self.data.move_node(&from, &to)
+    }
+
+    /// Finds a route for `item` with [`GridCanvasData::find_route`] and places it, returning
+    /// the cells of the route
+    pub fn route(
+        &mut self,
+        from: GridIndex,
+        to: GridIndex,
+        item: T,
+        max_expansions: usize,
+    ) -> Result<Vec<GridIndex>, String> {
+        self.check_writable()?;
+        let route = self
+            .data
+            .find_route(from, to, &item, max_expansions)
+            .ok_or_else(|| String::from("No route found"))?;
+        self.data.commit_route(&route, item)?;
+        Ok(route)
+    }
+
+    // Playback methods
+    /// Undoes the latest step, returning false if there is none
+    pub fn undo(&mut self) -> bool {
+        let position = self.data.save_data.position();
+        if position == 0 {
+            return false;
+        }
+        self.data.seek(position - 1);
+        true
+    }
+
+    /// Redoes the latest undone step, returning false if there is none
+    pub fn redo(&mut self) -> bool {
+        if self.data.save_data.redo_tape.is_empty() {
+            return false;
+        }
+        self.data.seek(self.data.save_data.position() + 1);
+        true
+    }
+
+    /// Plays a recorded tape at `offset` as a single undo step, see [`GridCanvasData::replay`]
+    pub fn replay(&mut self, tape: Vector<TapeItem<GridIndex, T>>, offset: GridIndex) {
+        self.data.replay(tape, offset);
+    }
+
+    /// Repeats the editing operations of a session, see [`Session::replay`]
+    pub fn replay_session(&mut self, session: &Session<T>) -> Result<(), String> {
+        session.replay(&mut self.data)
+    }
+
+    fn check_writable(&self) -> Result<(), String> {
+        if self.data.is_read_only() {
+            return Err(String::from("Grid is read-only"));
+        }
+        Ok(())
+    }
+
+    fn check_occupied(&self, pos: &GridIndex) -> Result<(), String> {
+        if !self.data.grid.contains_key(pos) {
+            return Err(format!("Cell ({}, {}) is empty", pos.row, pos.col));
+        }
+        Ok(())
+    }
+}
+
+impl<T: GridItem + PartialEq + Debug> From<GridCanvasData<T>> for GridCanvasModel<T> {
+    fn from(data: GridCanvasData<T>) -> Self {
+        Self { data }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Grid Editing
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Outcome of [`GridCanvasData::apply_batch`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchReport<T: Clone + Hash + Eq> {
+    /// Number of cells that were changed
+    pub applied: usize,
+    /// Operations that were left out, one cell at a time, with the reason
+    pub rejected: Vec<(TapeItem<GridIndex, T>, String)>,
+}
+
+impl<T: Clone + Hash + Eq> BatchReport<T> {
+    /// True if every operation of the batch was applied
+    pub fn is_complete(&self) -> bool {
+        self.rejected.is_empty()
+    }
+}
+
+// Editing, validation and undo logic of the grid, kept apart from the widget in grid_canvas.rs
+impl<T: GridItem + PartialEq + Debug> GridCanvasData<T>
+where
+    GridCanvasData<T>: Data,
+{
+    // Basic Grid methods
+    /// Places `item` at `pos` as a single undo step, ending any running stroke first
+    pub fn add_node(&mut self, pos: &GridIndex, item: T) -> Result<(), String> {
+        self.end_stroke();
+        let result = self.stroke_add_node(pos, item);
+        self.end_stroke();
+        result
+    }
+
+    /// Removes the item at `pos` as a single undo step, ending any running stroke first
+    pub fn remove_node(&mut self, pos: &GridIndex) -> Result<(), String> {
+        self.end_stroke();
+        let result = self.stroke_remove_node(pos);
+        self.end_stroke();
+        result
+    }
+
+    // Adds to the running stroke, which the caller ends with end_stroke
+    pub(crate) fn stroke_add_node(&mut self, pos: &GridIndex, item: T) -> Result<(), String> {
+        self.save_data.clear_delta();
+        let previous_item = self.grid.get(pos).copied();
+
+        if let Some(reason) = self.add_rejection(pos, &item) {
+            // Painting over an item of the same kind is not worth reporting
+            if previous_item == Some(item) {
+                return Ok(());
+            }
+            return Err(reason);
+        }
+
+        let command_item = TapeItem::Add(*pos, item, previous_item);
+        self.grid.insert(*pos, item);
+        self.recorder.record(command_item.clone());
+        self.save_data.play(CanvasItem::Grid(command_item));
+        self.sync_derived();
+        self.add_to_stroke(*pos, item, previous_item);
+        self.record_event(SessionEvent::Add(*pos, item));
+        Ok(())
+    }
+
+    pub(crate) fn stroke_remove_node(&mut self, pos: &GridIndex) -> Result<(), String> {
+        self.save_data.clear_delta();
+        if let Some(item) = self.grid.get(pos).copied() {
+            if let Some(reason) = item.can_remove_reason() {
+                return Err(reason);
+            }
+            self.grid.remove(pos);
+            let command_item = TapeItem::Remove(*pos, item);
+            self.recorder.record(command_item.clone());
+            self.save_data.play(CanvasItem::Grid(command_item));
+            self.sync_derived();
+            self.remove_from_stroke(*pos, item);
+            self.record_event(SessionEvent::Remove(*pos));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn move_node(&mut self, from: &GridIndex, to: &GridIndex) -> Result<(), String> {
+        self.save_data.clear_delta();
+        let item = *self.grid.get(from).unwrap();
+        if let Some(reason) = self.move_rejection(to, &item) {
+            return Err(reason);
+        }
+        self.grid.remove(from);
+        self.grid.insert(*to, item);
+        let command_item = TapeItem::Move(*from, *to, item);
+        self.recorder.record(command_item.clone());
+        self.push_change(command_item, Some(SessionEvent::Move(*from, *to)));
+        self.sync_derived();
+        Ok(())
+    }
+
+    /// Reason why `item` can not be placed at `pos`, or None if it can
+    pub fn add_rejection(&self, pos: &GridIndex, item: &T) -> Option<String> {
+        self.board_rejection(pos)
+            .or_else(|| item.can_add_reason(self.grid.get(pos)))
+            .or_else(|| {
+                self.is_in_halo(pos, item)
+                    .then(|| String::from("Inside the halo of another item"))
+            })
+    }
+
+    /// Reason why `item` can not be moved to `pos`, or None if it can
+    pub fn move_rejection(&self, pos: &GridIndex, item: &T) -> Option<String> {
+        self.board_rejection(pos)
+            .or_else(|| item.can_move_reason(self.grid.get(pos)))
+            .or_else(|| {
+                self.is_in_halo(pos, item)
+                    .then(|| String::from("Inside the halo of another item"))
+            })
+    }
+
+    // Board methods
+    /// True if `pos` lies on the board. Every cell is on an unbounded grid
+    pub fn is_on_board(&self, pos: &GridIndex) -> bool {
+        self.extent.is_none_or(|extent| {
+            (0..extent.row).contains(&pos.row) && (0..extent.col).contains(&pos.col)
+        })
+    }
+
+    fn board_rejection(&self, pos: &GridIndex) -> Option<String> {
+        (!self.is_on_board(pos)).then(|| String::from("Outside the board"))
+    }
+
+    // Stroke methods
+    fn add_to_stroke(&mut self, pos: GridIndex, item: T, previous_item: Option<T>) {
+        if let Some(TapeItem::BatchAdd(map)) = &mut self.stroke {
+            map.entry(pos).or_insert((item, previous_item)).0 = item;
+        } else {
+            self.end_stroke();
+            let mut map = HashMap::new();
+            map.insert(pos, (item, previous_item));
+            self.stroke = Some(TapeItem::BatchAdd(map));
+        }
+    }
+
+    fn remove_from_stroke(&mut self, pos: GridIndex, item: T) {
+        if let Some(TapeItem::BatchRemove(map)) = &mut self.stroke {
+            map.entry(pos).or_insert(item);
+        } else {
+            self.end_stroke();
+            let mut map = HashMap::new();
+            map.insert(pos, item);
+            self.stroke = Some(TapeItem::BatchRemove(map));
+        }
+    }
+
+    /// Records the operations of the current stroke as a single undo step
+    pub fn end_stroke(&mut self) {
+        if let Some(item) = self.stroke.take() {
+            self.save_data.commit(CanvasItem::Grid(item));
+            self.record_event(SessionEvent::EndStroke);
+        }
+    }
+
+    /// Reverts the operations of the current stroke without recording them
+    pub fn cancel_stroke(&mut self) {
+        if let Some(item) = self.stroke.take() {
+            self.grid.rewind(item.clone());
+            self.save_data.revert(CanvasItem::Grid(item));
+            self.sync_derived();
+            self.record_event(SessionEvent::CancelStroke);
+        }
+    }
+
+    // Halo methods
+    /// Returns true if placing `item` at `pos` would violate the halo of an item of another kind.
+    /// Only the cells within the widest halo around `pos` are visited
+    pub fn is_in_halo(&self, pos: &GridIndex, item: &T) -> bool {
+        let reach = item.get_halo().max(self.max_halo()) as isize;
+        if reach == 0 {
+            return false;
+        }
+        let min = GridIndex::new(pos.row.saturating_sub(reach), pos.col.saturating_sub(reach));
+        let max = *pos + GridIndex::new(reach, reach);
+        self.grid
+            .iter_area(min, max)
+            .any(|(other_pos, other_item)| {
+                let halo = other_item.get_halo().max(item.get_halo()) as isize;
+                other_pos != *pos
+                    && other_item != item
+                    && (other_pos.row - pos.row).abs() <= halo
+                    && (other_pos.col - pos.col).abs() <= halo
+            })
+    }
+
+    // Widest halo of the items on the grid, found from the distinct items instead of the cells
+    fn max_halo(&self) -> usize {
+        self.grid
+            .item_counts()
+            .map(|(item, _)| item.get_halo())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Empty cells covered by a halo, mapped to the item the halo belongs to
+    pub fn halo_cells(&self) -> HashMap<GridIndex, T> {
+        if self.max_halo() == 0 {
+            return HashMap::new();
+        }
+        self.collect_halo_cells(self.grid.iter())
+    }
+
+    /// Halo cells of the items around the area spanned by two corners. Only the items within
+    /// reach of the widest halo on the grid are visited
+    pub fn halo_cells_in(&self, from: GridIndex, to: GridIndex) -> HashMap<GridIndex, T> {
+        let reach = self.max_halo() as isize;
+        if reach == 0 {
+            return HashMap::new();
+        }
+        let min = GridIndex::new(
+            from.row.min(to.row).saturating_sub(reach),
+            from.col.min(to.col).saturating_sub(reach),
+        );
+        let max = GridIndex::new(
+            from.row.max(to.row).saturating_add(reach),
+            from.col.max(to.col).saturating_add(reach),
+        );
+        self.collect_halo_cells(self.grid.iter_area(min, max))
+    }
+
+    fn collect_halo_cells<'a>(
+        &'a self,
+        items: impl Iterator<Item = (GridIndex, &'a T)>,
+    ) -> HashMap<GridIndex, T> {
+        let mut cells = HashMap::new();
+        for (pos, item) in items {
+            let halo = item.get_halo() as isize;
+            for row in pos.row.saturating_sub(halo)..=pos.row.saturating_add(halo) {
+                for col in pos.col.saturating_sub(halo)..=pos.col.saturating_add(halo) {
+                    let halo_pos = GridIndex::new(row, col);
+                    if !self.grid.contains_key(&halo_pos) {
+                        cells.insert(halo_pos, *item);
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    /// Removes the cells covered by a halo from a routing lattice
+    pub fn block_halos(&self, lattice: &mut Lattice2D) {
+        for pos in self.halo_cells().keys() {
+            if pos.row >= 0 && pos.col >= 0 {
+                lattice.remove_vertex((pos.col as usize, pos.row as usize));
+            }
+        }
+    }
+
+    // Save stack methods
+    fn validate_stack_list(
+        &mut self,
+        list: Vector<TapeItem<GridIndex, T>>,
+    ) -> (HashMap<GridIndex, T>, Vector<TapeItem<GridIndex, T>>) {
+        let mut stack_list = Vector::new();
+        let mut pos_map = HashMap::new();
+
+        for stack_item in list {
+            match stack_item {
+                TapeItem::Add(pos, current_item, _) => {
+                    let other = self.grid.get(&pos);
+                    if current_item.can_add(other) {
+                        stack_list.push_back(stack_item);
+                        pos_map.insert(pos, current_item);
+                    }
+                }
+                TapeItem::Remove(pos, current_item) => {
+                    if current_item.can_remove() {
+                        stack_list.push_back(stack_item);
+                        pos_map.remove(&pos);
+                    }
+                }
+                TapeItem::Move(from_pos, to_pos, current_item) => {
+                    let other = self.grid.get(&to_pos);
+                    if current_item.can_move(other) {
+                        stack_list.push_back(stack_item);
+                        pos_map.remove(&from_pos);
+                        pos_map.insert(to_pos, current_item);
+                    }
+                }
+                TapeItem::BatchAdd(mut map) => {
+                    map.retain(|pos, (current_item, _)| {
+                        let option = self.grid.get(pos);
+                        if current_item.can_add(option) {
+                            pos_map.insert(*pos, *current_item);
+                        }
+                        current_item.can_add(option)
+                    });
+
+                    if !map.is_empty() {
+                        stack_list.push_back(TapeItem::BatchAdd(map));
+                    }
+                }
+                TapeItem::BatchRemove(mut map) => {
+                    map.retain(|pos, current_item| {
+                        if current_item.can_remove() {
+                            pos_map.remove(pos);
+                        }
+                        current_item.can_remove()
+                    });
+                    if !map.is_empty() {
+                        stack_list.push_back(TapeItem::BatchRemove(map));
+                    }
+                }
+                TapeItem::Group(items) => {
+                    let (group_map, group_list) = self.validate_stack_list(items);
+                    pos_map.extend(group_map);
+                    if !group_list.is_empty() {
+                        stack_list.push_back(TapeItem::Group(group_list));
+                    }
+                }
+            }
+        }
+        (pos_map, stack_list)
+    }
+
+    pub fn submit_to_stack(&mut self, list: Vector<TapeItem<GridIndex, T>>) {
+        let (_, save_list) = self.validate_stack_list(list);
+        self.queue(save_list);
+    }
+
+    pub fn submit_to_stack_and_process(&mut self, list: Vector<TapeItem<GridIndex, T>>) {
+        self.process_stack_list(list, None);
+    }
+
+    // Places the items that pass, recording `event` in the session instead of the changes
+    pub(crate) fn process_stack_list(
+        &mut self,
+        list: Vector<TapeItem<GridIndex, T>>,
+        event: Option<SessionEvent<T>>,
+    ) {
+        let (pos_map, save_list) = self.validate_stack_list(list);
+        for (pos, item) in pos_map.iter() {
+            self.grid.insert(*pos, *item);
+        }
+        self.push_changes(save_list, event);
+        self.sync_derived();
+    }
+
+    /// Like [`submit_to_stack_and_process`](Self::submit_to_stack_and_process) but plays every
+    /// operation that passes on the grid in order, so removes and moves take effect as well
+    pub fn submit_to_stack_and_play(&mut self, list: Vector<TapeItem<GridIndex, T>>) {
+        let (_, save_list) = self.validate_stack_list(list);
+        for item in save_list.iter() {
+            self.grid.advance(item.clone());
+        }
+        self.push_changes(save_list, None);
+        self.sync_derived();
+    }
+
+    /// Like [`submit_to_stack_and_play`](Self::submit_to_stack_and_play) but as a single undo
+    /// step
+    pub fn submit_group_and_play(&mut self, list: Vector<TapeItem<GridIndex, T>>) {
+        self.end_stroke();
+        let (_, save_list) = self.validate_stack_list(list);
+        if save_list.is_empty() {
+            return;
+        }
+        for item in save_list.iter() {
+            self.grid.advance(item.clone());
+        }
+        self.push_change(TapeItem::Group(save_list), None);
+        self.sync_derived();
+    }
+
+    // Tape methods
+    // Every change made to the grid outside of a stroke goes on the undo tape through here, so
+    // a session records all of them. The changes are recorded as `event` when the caller has an
+    // operation that replays them, and cell by cell otherwise, one undo step at a time
+    fn push_changes(
+        &mut self,
+        items: Vector<TapeItem<GridIndex, T>>,
+        event: Option<SessionEvent<T>>,
+    ) {
+        if self.is_recording_session() {
+            match event {
+                Some(event) => self.record_event(event),
+                None => {
+                    for item in items.iter() {
+                        self.record_event(SessionEvent::Apply(SessionOp::from_tape(item)));
+                    }
+                }
+            }
+        }
+        self.save_data
+            .append_and_play(items.into_iter().map(CanvasItem::Grid).collect());
+    }
+
+    pub(crate) fn push_change(
+        &mut self,
+        item: TapeItem<GridIndex, T>,
+        event: Option<SessionEvent<T>>,
+    ) {
+        self.push_changes(Vector::unit(item), event);
+    }
+
+    // Puts items on the redo tape without playing them, in place of the steps that were there
+    pub(crate) fn queue(&mut self, items: Vector<TapeItem<GridIndex, T>>) {
+        if self.is_recording_session() {
+            let steps = items.iter().map(SessionOp::from_tape).collect();
+            self.record_event(SessionEvent::Queue(steps));
+        }
+        self.save_data
+            .append(items.into_iter().map(CanvasItem::Grid).collect());
+    }
+
+    /// Validates the operations in order against the grid, so each one sees the effect of the
+    /// ones before it, and applies those that pass as a single undo step. Unlike
+    /// [`submit_to_stack_and_process`](Self::submit_to_stack_and_process) the operations that
+    /// are left out are reported along with the reason. Removes and moves are rejected when the
+    /// cell does not hold the item they name, and every operation is rejected while the grid is
+    /// read-only.
+    pub fn apply_batch(&mut self, ops: Vector<TapeItem<GridIndex, T>>) -> BatchReport<T> {
+        self.play_batch(ops, None)
+    }
+
+    // Applies a batch, recording `event` in the session instead of the changes that pass when the
+    // caller has an operation that made the batch
+    pub(crate) fn play_batch(
+        &mut self,
+        ops: Vector<TapeItem<GridIndex, T>>,
+        event: Option<SessionEvent<T>>,
+    ) -> BatchReport<T> {
+        self.end_stroke();
+        let mut report = BatchReport {
+            applied: 0,
+            rejected: Vec::new(),
+        };
+        let applied = self.apply_batch_items(ops, &mut report);
+        if !applied.is_empty() {
+            self.push_change(TapeItem::Group(applied), event);
+            self.sync_derived();
+        }
+        report
+    }
+
+    /// Operations that turn this grid into the grid of `other`, one per cell that differs, in
+    /// row-major order. Apply them with [`apply_diff`](Self::apply_diff)
+    pub fn diff(&self, other: &Self) -> Vector<TapeItem<GridIndex, T>> {
+        let mut ops = Vec::new();
+        for (pos, item) in self.grid.iter() {
+            if !other.grid.contains_key(&pos) {
+                ops.push((pos, TapeItem::Remove(pos, *item)));
+            }
+        }
+        for (pos, item) in other.grid.iter() {
+            let previous_item = self.grid.get(&pos);
+            if previous_item != Some(item) {
+                ops.push((pos, TapeItem::Add(pos, *item, previous_item.copied())));
+            }
+        }
+        ops.sort_by_key(|(pos, _)| (pos.row, pos.col));
+        ops.into_iter().map(|(_, op)| op).collect()
+    }
+
+    /// Applies operations made by [`diff`](Self::diff) as a single undo step and returns how
+    /// many there were. The rules of the items are not checked, but every cell has to hold what
+    /// the operations expect to find there, otherwise none of them are applied
+    pub fn apply_diff(&mut self, diff: Vector<TapeItem<GridIndex, T>>) -> Result<usize, String> {
+        if self.read_only {
+            return Err(String::from("Grid is read-only"));
+        }
+        self.end_stroke();
+        let mut grid = self.grid.clone();
+        for op in diff.iter() {
+            if let Err(pos) = play_expected(&mut grid, op) {
+                return Err(format!("Cell ({}, {}) has changed", pos.row, pos.col));
+            }
+        }
+        let len = diff.len();
+        if len > 0 {
+            self.grid = grid;
+            self.push_change(TapeItem::Group(diff), None);
+            self.sync_derived();
+        }
+        Ok(len)
+    }
+
+    // Puts the item on the cell, over whatever was there, as a single undo step
+    pub(crate) fn insert_item(&mut self, pos: GridIndex, item: T) {
+        self.end_stroke();
+        let previous_item = self.grid.insert(pos, item);
+        self.push_change(TapeItem::Add(pos, item, previous_item), None);
+        self.sync_derived();
+    }
+
+    pub(crate) fn apply_batch_items(
+        &mut self,
+        ops: Vector<TapeItem<GridIndex, T>>,
+        report: &mut BatchReport<T>,
+    ) -> Vector<TapeItem<GridIndex, T>> {
+        let mut applied = Vector::new();
+        for op in ops {
+            match op {
+                TapeItem::Add(pos, item, _) => match self.try_add(pos, item) {
+                    Ok(previous_item) => {
+                        report.applied += 1;
+                        applied.push_back(TapeItem::Add(pos, item, previous_item));
+                    }
+                    Err(reason) => report.rejected.push((op, reason)),
+                },
+                TapeItem::Remove(pos, item) => match self.try_remove(pos, item) {
+                    Ok(()) => {
+                        report.applied += 1;
+                        applied.push_back(op);
+                    }
+                    Err(reason) => report.rejected.push((op, reason)),
+                },
+                TapeItem::Move(from_pos, to_pos, item) => {
+                    match self.try_move(from_pos, to_pos, item) {
+                        Ok(()) => {
+                            report.applied += 1;
+                            applied.push_back(op);
+                        }
+                        Err(reason) => report.rejected.push((op, reason)),
+                    }
+                }
+                TapeItem::BatchAdd(map) => {
+                    let mut added = HashMap::new();
+                    for (pos, (item, _)) in map {
+                        let previous_item = self.grid.get(&pos).copied();
+                        match self.try_add(pos, item) {
+                            Ok(previous_item) => {
+                                added.insert(pos, (item, previous_item));
+                            }
+                            Err(reason) => report
+                                .rejected
+                                .push((TapeItem::Add(pos, item, previous_item), reason)),
+                        }
+                    }
+                    report.applied += added.len();
+                    if !added.is_empty() {
+                        applied.push_back(TapeItem::BatchAdd(added));
+                    }
+                }
+                TapeItem::BatchRemove(map) => {
+                    let mut removed = HashMap::new();
+                    for (pos, item) in map {
+                        match self.try_remove(pos, item) {
+                            Ok(()) => {
+                                removed.insert(pos, item);
+                            }
+                            Err(reason) => {
+                                report.rejected.push((TapeItem::Remove(pos, item), reason))
+                            }
+                        }
+                    }
+                    report.applied += removed.len();
+                    if !removed.is_empty() {
+                        applied.push_back(TapeItem::BatchRemove(removed));
+                    }
+                }
+                TapeItem::Group(items) => {
+                    let group = self.apply_batch_items(items, report);
+                    if !group.is_empty() {
+                        applied.push_back(TapeItem::Group(group));
+                    }
+                }
+            }
+        }
+        applied
+    }
+
+    // Places the item of a batch and returns the item it replaced
+    fn try_add(&mut self, pos: GridIndex, item: T) -> Result<Option<T>, String> {
+        self.expect_writable()?;
+        match self.add_rejection(&pos, &item) {
+            Some(reason) => Err(reason),
+            None => Ok(self.grid.insert(pos, item)),
+        }
+    }
+
+    fn try_remove(&mut self, pos: GridIndex, item: T) -> Result<(), String> {
+        self.expect_writable()?;
+        self.expect_item(&pos, &item)?;
+        if let Some(reason) = item.can_remove_reason() {
+            return Err(reason);
+        }
+        self.grid.remove(&pos);
+        Ok(())
+    }
+
+    fn try_move(&mut self, from_pos: GridIndex, to_pos: GridIndex, item: T) -> Result<(), String> {
+        self.expect_writable()?;
+        self.expect_item(&from_pos, &item)?;
+        if let Some(reason) = self.move_rejection(&to_pos, &item) {
+            return Err(reason);
+        }
+        self.grid.remove(&from_pos);
+        self.grid.insert(to_pos, item);
+        Ok(())
+    }
+
+    fn expect_writable(&self) -> Result<(), String> {
+        if self.read_only {
+            return Err(String::from("Grid is read-only"));
+        }
+        Ok(())
+    }
+
+    fn expect_item(&self, pos: &GridIndex, item: &T) -> Result<(), String> {
+        match self.grid.get(pos) {
+            Some(current_item) if current_item == item => Ok(()),
+            Some(current_item) => Err(format!(
+                "Cell holds {} instead of {}",
+                current_item.get_short_text(),
+                item.get_short_text()
+            )),
+            None => Err(String::from("Cell is empty")),
+        }
+    }
+
+    // Playback methods
+    /// Moves the tape head to `index` and updates the grid accordingly
+    pub fn seek(&mut self, index: usize) {
+        self.end_stroke();
+        self.save_data.seek(index);
+        self.play_delta();
+        self.sync_derived();
+        self.record_event(SessionEvent::Seek(index));
+    }
+
+    /// Saves the current state of the grid under `name`, see [`Cassetta::checkpoint`](crate::utils::cassetta::Cassetta::checkpoint)
+    pub fn checkpoint(&mut self, name: impl Into<String>) {
+        self.end_stroke();
+        self.save_data.checkpoint(name);
+    }
+
+    /// Brings the grid back, or forward, to the checkpoint called `name`, returning false if
+    /// there is none
+    pub fn seek_checkpoint(&mut self, name: &str) -> bool {
+        self.end_stroke();
+        match self.save_data.checkpoint_position(name) {
+            Some(position) => {
+                self.seek(position);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Rewinds the grid to where the branch at `index` of the undo tree forks off and makes the
+    /// branch the one redo and playback follow, see [`Cassetta::switch_branch`](crate::utils::cassetta::Cassetta::switch_branch). Returns false
+    /// if there is no such branch
+    pub fn switch_branch(&mut self, index: usize) -> bool {
+        self.end_stroke();
+        if !self.save_data.switch_branch(index) {
+            return false;
+        }
+        self.play_delta();
+        self.sync_derived();
+        self.record_event(SessionEvent::SwitchBranch(index));
+        true
+    }
+
+    // Rewinds and plays the items the tape has just moved over on the grid and the annotations
+    fn play_delta(&mut self) {
+        for item in self.save_data.remove_delta.iter() {
+            match item.clone() {
+                CanvasItem::Grid(item) => self.grid.rewind(item),
+                CanvasItem::Annotation(item) => self.annotations.rewind(item),
+            }
+        }
+        for item in self.save_data.add_delta.iter() {
+            match item.clone() {
+                CanvasItem::Grid(item) => self.grid.advance(item),
+                CanvasItem::Annotation(item) => self.annotations.advance(item),
+            }
+        }
+    }
+
+    /// Edits of the grid in the latest change of the tape, the undone ones and the played ones
+    pub(crate) fn grid_delta(
+        &self,
+    ) -> (
+        Vector<TapeItem<GridIndex, T>>,
+        Vector<TapeItem<GridIndex, T>>,
+    ) {
+        let grid_items = |items: &Vector<CanvasItem<T>>| -> Vector<TapeItem<GridIndex, T>> {
+            items
+                .iter()
+                .filter_map(CanvasItem::grid_item)
+                .cloned()
+                .collect()
+        };
+        (
+            grid_items(&self.save_data.remove_delta),
+            grid_items(&self.save_data.add_delta),
+        )
+    }
+}
+
+// Plays `op` on the grid if the cells hold what it expects, otherwise returns the first cell
+// that does not
+pub(crate) fn play_expected<T: Clone + PartialEq>(
+    grid: &mut ChunkedGrid<T>,
+    op: &TapeItem<GridIndex, T>,
+) -> Result<(), GridIndex> {
+    let expect = |grid: &ChunkedGrid<T>, pos: &GridIndex, item: Option<&T>| {
+        if grid.get(pos) == item {
+            Ok(())
+        } else {
+            Err(*pos)
+        }
+    };
+    match op {
+        TapeItem::Add(pos, _, previous_item) => expect(grid, pos, previous_item.as_ref())?,
+        TapeItem::Remove(pos, item) => expect(grid, pos, Some(item))?,
+        TapeItem::Move(from_pos, to_pos, item) => {
+            expect(grid, from_pos, Some(item))?;
+            expect(grid, to_pos, None)?;
+        }
+        TapeItem::BatchAdd(items) => {
+            for (pos, (_, previous_item)) in items.iter() {
+                expect(grid, pos, previous_item.as_ref())?;
+            }
+        }
+        TapeItem::BatchRemove(items) => {
+            for (pos, item) in items.iter() {
+                expect(grid, pos, Some(item))?;
+            }
+        }
+        TapeItem::Group(items) => {
+            for item in items.iter() {
+                play_expected(grid, item)?;
+            }
+            return Ok(());
+        }
+    }
+    grid.advance(op.clone());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridCanvasModel;
+    use crate::{testing::TestItem, GridIndex};
+
+    #[test]
+    fn edits_without_a_widget() {
+        let mut model = GridCanvasModel::new(TestItem::Wall);
+        let (wall, pin) = (GridIndex::new(0, 1), GridIndex::new(2, 2));
+        assert_eq!(model.add(wall, TestItem::Wall), Ok(()));
+        assert_eq!(model.add(pin, TestItem::Pin), Ok(()));
+        assert!(model.add(pin, TestItem::Wall).is_err());
+        assert!(model.remove(pin).is_err());
+        assert_eq!(
+            model.remove(GridIndex::new(5, 5)),
+            Err(String::from("Cell (5, 5) is empty"))
+        );
+
+        let moved = GridIndex::new(1, 1);
+        assert_eq!(model.move_item(wall, moved), Ok(()));
+        assert_eq!(model.get(&moved), Some(&TestItem::Wall));
+        assert_eq!(model.data().save_data.position(), 3);
+
+        assert!(model.undo());
+        assert_eq!(model.get(&wall), Some(&TestItem::Wall));
+        assert!(model.redo());
+        assert!(!model.redo());
+        assert_eq!(model.get(&moved), Some(&TestItem::Wall));
+
+        // Routes go around the items already on the grid
+        let route = model
+            .route(
+                GridIndex::new(1, 0),
+                GridIndex::new(1, 2),
+                TestItem::Weight(1),
+                100,
+            )
+            .unwrap();
+        assert!(!route.contains(&moved));
+        assert!(route
+            .iter()
+            .all(|pos| model.get(pos) == Some(&TestItem::Weight(1))));
+
+        model.data_mut().set_read_only(true);
+        assert!(model.add(GridIndex::new(4, 4), TestItem::Wall).is_err());
+        let data = model.into_data();
+        assert_eq!(data.grid.len(), 2 + route.len());
+    }
+
+    #[test]
+    fn moving_ends_the_running_stroke() {
+        let mut model = GridCanvasModel::new(TestItem::Wall);
+        let (from, to) = (GridIndex::new(0, 0), GridIndex::new(3, 3));
+        model
+            .data_mut()
+            .stroke_add_node(&from, TestItem::Wall)
+            .unwrap();
+        assert_eq!(model.move_item(from, to), Ok(()));
+        assert_eq!(model.data().save_data.position(), 2);
+
+        assert!(model.undo());
+        assert_eq!(model.get(&from), Some(&TestItem::Wall));
+        assert_eq!(model.get(&to), None);
+        assert!(model.undo());
+        assert_eq!(model.get(&from), None);
+    }
+}
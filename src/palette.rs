@@ -0,0 +1,182 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::fmt::Debug;
+
+use crate::{grid_canvas::GridCanvasData, theme, GridItem};
+use druid::{
+    piet::{PietTextLayout, Text, TextLayout, TextLayoutBuilder},
+    BoxConstraints, Cursor, Data, Env, Event, EventCtx, FontFamily, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget,
+};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// ItemPalette Widget
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+const PALETTE_TEXT_SIZE: f64 = 10.0;
+const PALETTE_SWATCH_SIZE: f64 = 28.0;
+const PALETTE_PADDING: f64 = 4.0;
+const PALETTE_SELECTED_WIDTH: f64 = 2.0;
+
+/// Row of swatches, one per item, painted with the colour and short text of the item. Clicking
+/// a swatch makes its item the one the grid draws with.
+pub struct ItemPalette<T> {
+    items: Vec<T>,
+    swatch_size: f64,
+    layouts: Vec<PietTextLayout>,
+}
+
+impl<T: GridItem + PartialEq + Debug> ItemPalette<T>
+where
+    GridCanvasData<T>: Data,
+{
+    pub fn new(items: Vec<T>) -> Self {
+        Self {
+            items,
+            swatch_size: PALETTE_SWATCH_SIZE,
+            layouts: Vec::new(),
+        }
+    }
+
+    pub fn with_swatch_size(mut self, swatch_size: f64) -> Self {
+        self.swatch_size = swatch_size.max(1.0);
+        self
+    }
+
+    fn swatch_rect(&self, index: usize) -> Rect {
+        let x = PALETTE_PADDING + index as f64 * (self.swatch_size + PALETTE_PADDING);
+        Rect::from_origin_size(
+            Point::new(x, PALETTE_PADDING),
+            Size::new(self.swatch_size, self.swatch_size),
+        )
+    }
+
+    /// Index of the item whose swatch is under `point`, if any
+    pub fn item_at(&self, point: Point) -> Option<usize> {
+        (0..self.items.len()).find(|index| self.swatch_rect(*index).contains(point))
+    }
+}
+
+impl<T: GridItem + PartialEq + Debug> Widget<GridCanvasData<T>> for ItemPalette<T>
+where
+    GridCanvasData<T>: Data,
+{
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut GridCanvasData<T>,
+        _env: &Env,
+    ) {
+        match event {
+            Event::MouseMove(e) => {
+                if self.item_at(e.pos).is_some() {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+            }
+            Event::MouseDown(e) if e.button.is_left() => {
+                if let Some(index) = self.item_at(e.pos) {
+                    data.grid_item = self.items[index];
+                    ctx.set_handled();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &GridCanvasData<T>,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &GridCanvasData<T>,
+        data: &GridCanvasData<T>,
+        _env: &Env,
+    ) {
+        if old_data.grid_item != data.grid_item {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &GridCanvasData<T>,
+        env: &Env,
+    ) -> Size {
+        let text_color = theme::color(env, &theme::GRID_LABEL_TEXT);
+        self.layouts = self
+            .items
+            .iter()
+            .map(|item| {
+                ctx.text()
+                    .new_text_layout(item.get_short_text())
+                    .font(FontFamily::SYSTEM_UI, PALETTE_TEXT_SIZE)
+                    .text_color(text_color)
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+
+        let count = self.items.len() as f64;
+        let size = Size::new(
+            count * (self.swatch_size + PALETTE_PADDING) + PALETTE_PADDING,
+            self.swatch_size + 2.0 * PALETTE_PADDING,
+        );
+        bc.constrain(size)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &GridCanvasData<T>, env: &Env) {
+        let border = theme::color(env, &theme::HEADER_BORDER);
+        let selected = theme::color(env, &theme::SELECTION_FILL);
+        for (index, (item, layout)) in self.items.iter().zip(&self.layouts).enumerate() {
+            let swatch = self.swatch_rect(index);
+            ctx.fill(swatch, &item.get_color());
+            if *item == data.grid_item {
+                ctx.stroke(swatch, &selected, PALETTE_SELECTED_WIDTH);
+            } else {
+                ctx.stroke(swatch, &border, 1.0);
+            }
+
+            // Text too wide for the swatch is clipped to it
+            let text_size = layout.size();
+            let text_origin = swatch.center() - text_size.to_vec2() / 2.0;
+            ctx.with_save(|ctx| {
+                ctx.clip(swatch);
+                ctx.draw_text(layout, text_origin);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::Point;
+
+    use super::ItemPalette;
+    use crate::testing::TestItem;
+
+    #[test]
+    fn finds_the_swatch_under_the_pointer() {
+        let palette = ItemPalette::new(vec![TestItem::Wall, TestItem::Pin]).with_swatch_size(20.0);
+        assert_eq!(palette.item_at(Point::new(10.0, 10.0)), Some(0));
+        assert_eq!(palette.item_at(Point::new(35.0, 10.0)), Some(1));
+        // Gaps between the swatches and the space past the last one
+        assert_eq!(palette.item_at(Point::new(26.0, 10.0)), None);
+        assert_eq!(palette.item_at(Point::new(60.0, 10.0)), None);
+    }
+}
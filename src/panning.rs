@@ -3,42 +3,88 @@
 /// Imports
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////////
-use druid::{widget::Controller, Data, Event, Lens, Point, Vec2, Widget};
+use std::time::{Duration, Instant};
+
+use druid::{
+    widget::Controller, Data, Event, KbKey, KeyEvent, Lens, Point, Rect, Size, TimerToken, Vec2,
+    Widget,
+};
 use log::debug;
 
+use crate::{snapping::ViewTransform, zooming::ZoomDataAccess};
+
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 ///
 /// PanningData
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Pan offset of a view, shared by the controllers that move it and the painters that draw it
 pub trait PanDataAccess {
+    /// Position of the grid origin on the widget, including the running drag
     fn get_offset(&self) -> Point;
     fn set_offset(&mut self, offset: Point);
+    /// How far the running pan drag has moved the offset, zero while no drag is running
+    fn get_drag_delta(&self) -> Vec2;
+    fn set_drag_delta(&mut self, delta: Vec2);
+    /// Offset before the running drag started, the offset itself while no drag is running
+    fn get_drag_origin(&self) -> Point {
+        self.get_offset() - self.get_drag_delta()
+    }
+    /// How far the last pan tried to go past the limits, see [`PanData::overscroll`]
+    fn get_overscroll(&self) -> Vec2;
+    fn set_overscroll(&mut self, overscroll: Vec2);
+    /// Area covered by the content at a zoom scale of 1, None if there is nothing to keep in view
+    fn get_content_bounds(&self) -> Option<Rect> {
+        None
+    }
 }
 
+// Largest distance of the grid origin from the widget along either axis. Points of the widget
+// are still a fraction of a pixel apart once an offset this large is subtracted from them
+pub const MAX_OFFSET: f64 = (1u64 << 50) as f64;
+
+/// `offset` with both coordinates kept within [`MAX_OFFSET`], or `previous` if it is not finite
+pub fn guard_offset(offset: Point, previous: Point) -> Point {
+    if !offset.is_finite() {
+        return previous;
+    }
+    Point::new(
+        offset.x.clamp(-MAX_OFFSET, MAX_OFFSET),
+        offset.y.clamp(-MAX_OFFSET, MAX_OFFSET),
+    )
+}
+
+/// Part of the pan offset the running drag added and how far the last pan went past the limits.
+/// The offset itself is kept in the [`ViewTransform`]
 #[derive(Clone, Data, Lens, PartialEq, Debug)]
-pub struct PanData
-where
-    PanData: PanDataAccess,
-{
-    pub offset: Point,
+pub struct PanData {
+    pub drag_delta: Vec2,
+    // How far the last pan tried to move the offset past the limits of the PanController, damped
+    // like a rubber band. It springs back to zero once the pan stops, and is positive along an
+    // axis when the view was pushed against the start of it, i.e. the left or top edge
+    pub overscroll: Vec2,
 }
 
 impl PanData {
     pub fn new() -> Self {
         Self {
-            offset: Point::new(0.0, 0.0),
+            drag_delta: Vec2::ZERO,
+            overscroll: Vec2::ZERO,
         }
     }
-}
 
-impl PanDataAccess for PanData {
-    fn get_offset(&self) -> Point {
-        self.offset
+    /// Sets the drag delta unless it is not finite
+    pub fn set_drag_delta(&mut self, delta: Vec2) {
+        if delta.is_finite() {
+            self.drag_delta = delta;
+        }
     }
 
-    fn set_offset(&mut self, offset: Point) {
-        self.offset = offset;
+    /// Sets the overscroll unless it is not finite
+    pub fn set_overscroll(&mut self, overscroll: Vec2) {
+        if overscroll.is_finite() {
+            self.overscroll = overscroll;
+        }
     }
 }
 
@@ -47,22 +93,157 @@ impl PanDataAccess for PanData {
 /// Panning Controller
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////////
+const INERTIA_TICK: Duration = Duration::from_millis(16);
+// Speed in pixels per millisecond below which inertial scrolling stops
+const INERTIA_MIN_SPEED: f64 = 0.02;
+/// Most pixels the overscroll of a pan stretches to, however far past the limits it goes
+pub const OVERSCROLL_LIMIT: f64 = 48.0;
+// How quickly the rubber band stiffens, and the fraction of the overscroll kept every tick
+// while it springs back
+const OVERSCROLL_STIFFNESS: f64 = 0.55;
+const OVERSCROLL_DECAY: f64 = 0.8;
+// Overscroll in pixels below which it snaps back to zero
+const OVERSCROLL_MIN: f64 = 0.5;
+
+/// Overscroll of a pan that tried to go `excess` pixels past the limits. It grows like `excess`
+/// at first and never reaches [`OVERSCROLL_LIMIT`]
+pub fn rubber_band(excess: f64) -> f64 {
+    let stretch = 1.0 - 1.0 / (excess.abs() * OVERSCROLL_STIFFNESS / OVERSCROLL_LIMIT + 1.0);
+    excess.signum() * stretch * OVERSCROLL_LIMIT
+}
+
 pub struct PanController {
     start_mouse_position: Option<Point>,
     previous_mouse_position: Option<Point>,
-    start_offset: Point,
     min_offset: Point,
     max_offset: Point,
+    // Room left around the content at a zoom scale of 1, None to only use the static bounds
+    content_margin: Option<f64>,
+    // Pixels panned per arrow or WASD key press at a zoom scale of 1
+    key_step: Option<f64>,
+    // Multiplier of the wheel and trackpad scroll deltas, None to leave scrolling to the child
+    scroll_speed: Option<f64>,
+    // Fraction of the speed kept every tick after a drag is released, None for no inertia
+    inertia: Option<f64>,
+    // Drag speed in pixels per millisecond and when it was last measured
+    velocity: Vec2,
+    previous_move: Option<Instant>,
+    inertia_timer: TimerToken,
+    overscroll_timer: TimerToken,
 }
 
 impl PanController {
     pub fn new(min_offset: Point, max_offset: Point) -> Self {
         PanController {
-            start_mouse_position: None,
-            previous_mouse_position: None,
-            start_offset: Point::new(0.0, 0.0),
             min_offset,
             max_offset,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_key_step(mut self, step: f64) -> Self {
+        self.key_step = Some(step);
+        self
+    }
+
+    pub fn without_keys(mut self) -> Self {
+        self.key_step = None;
+        self
+    }
+
+    /// Keeps the content in view, the view can move at most `margin` pixels past its edges. When
+    /// the content is smaller than the view it can not be panned out of it instead
+    pub fn with_content_bounds(mut self, margin: f64) -> Self {
+        self.content_margin = Some(margin);
+        self
+    }
+
+    /// Pans with the wheel or two fingers on a trackpad, holding Shift scrolls horizontally.
+    /// Scrolling with Ctrl held and pinches are left to the
+    /// [`ZoomController`](crate::zooming::ZoomController)
+    pub fn with_scroll_speed(mut self, speed: f64) -> Self {
+        self.scroll_speed = Some(speed);
+        self
+    }
+
+    pub fn without_scroll(mut self) -> Self {
+        self.scroll_speed = None;
+        self
+    }
+
+    /// Keeps the view moving after a drag is released, slowing down by `decay` every tick
+    pub fn with_inertia(mut self, decay: f64) -> Self {
+        self.inertia = Some(decay.clamp(0.0, 0.99));
+        self
+    }
+
+    fn clamp<T: PanDataAccess + ZoomDataAccess>(
+        &self,
+        offset: Point,
+        data: &T,
+        view: Size,
+    ) -> Point {
+        let offset = Point::new(
+            offset.x.clamp(self.min_offset.x, self.max_offset.x),
+            offset.y.clamp(self.min_offset.y, self.max_offset.y),
+        );
+        let (margin, content) = match (self.content_margin, data.get_content_bounds()) {
+            (Some(margin), Some(content)) => (margin, content),
+            _ => return offset,
+        };
+
+        // Where the content is drawn relative to the offset
+        let view = ViewTransform::new(Point::ZERO, data.get_zoom_scale(), 0.0);
+        let content = view.to_screen_rect(content.inflate(margin, margin));
+        // Either the view stays inside the content or the content inside the view, whichever is
+        // smaller
+        let fit = |offset: f64, start: f64, end: f64, length: f64| {
+            let (a, b) = (-start, length - end);
+            offset.clamp(a.min(b), a.max(b))
+        };
+        Point::new(
+            fit(offset.x, content.x0, content.x1, view.width),
+            fit(offset.y, content.y0, content.y1, view.height),
+        )
+    }
+
+    // Moves the view as close to `offset` as the limits allow and returns how far it fell short.
+    // That stretches the overscroll, which springs back once the pan stops
+    fn pan_to<T: PanDataAccess + ZoomDataAccess>(
+        &mut self,
+        ctx: &mut druid::EventCtx,
+        data: &mut T,
+        offset: Point,
+    ) -> Vec2 {
+        let clamped = self.clamp(offset, data, ctx.size());
+        data.set_offset(clamped);
+        let excess = offset - clamped;
+        if excess.is_finite() && excess != Vec2::ZERO {
+            data.set_overscroll(Vec2::new(rubber_band(excess.x), rubber_band(excess.y)));
+            self.overscroll_timer = ctx.request_timer(INERTIA_TICK);
+        }
+        excess
+    }
+
+    // Direction the content moves in for a panning key. Keys pressed with a modifier are left
+    // to shortcuts, e.g. Ctrl+S
+    fn key_direction(key_event: &KeyEvent) -> Option<Vec2> {
+        if !key_event.mods.is_empty() {
+            return None;
+        }
+        match &key_event.key {
+            KbKey::ArrowLeft => Some(Vec2::new(1.0, 0.0)),
+            KbKey::ArrowRight => Some(Vec2::new(-1.0, 0.0)),
+            KbKey::ArrowUp => Some(Vec2::new(0.0, 1.0)),
+            KbKey::ArrowDown => Some(Vec2::new(0.0, -1.0)),
+            KbKey::Character(c) => match c.to_lowercase().as_str() {
+                "a" => Some(Vec2::new(1.0, 0.0)),
+                "d" => Some(Vec2::new(-1.0, 0.0)),
+                "w" => Some(Vec2::new(0.0, 1.0)),
+                "s" => Some(Vec2::new(0.0, -1.0)),
+                _ => None,
+            },
+            _ => None,
         }
     }
 }
@@ -72,14 +253,21 @@ impl Default for PanController {
         Self {
             start_mouse_position: None,
             previous_mouse_position: None,
-            start_offset: Point::new(0.0, 0.0),
             min_offset: Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
             max_offset: Point::new(f64::INFINITY, f64::INFINITY),
+            content_margin: None,
+            key_step: Some(40.0),
+            scroll_speed: Some(1.0),
+            inertia: None,
+            velocity: Vec2::ZERO,
+            previous_move: None,
+            inertia_timer: TimerToken::INVALID,
+            overscroll_timer: TimerToken::INVALID,
         }
     }
 }
 
-impl<T: Data + PanDataAccess, W: Widget<T>> Controller<T, W> for PanController {
+impl<T: Data + PanDataAccess + ZoomDataAccess, W: Widget<T>> Controller<T, W> for PanController {
     fn event(
         &mut self,
         child: &mut W,
@@ -98,12 +286,15 @@ impl<T: Data + PanDataAccess, W: Widget<T>> Controller<T, W> for PanController {
 
         match event {
             Event::MouseDown(mouse_event) => {
+                // Any press stops inertial scrolling
+                self.inertia_timer = TimerToken::INVALID;
                 if mouse_event.button.is_middle() {
+                    self.velocity = Vec2::ZERO;
+                    self.previous_move = Some(Instant::now());
                     self.start_mouse_position = Some(mouse_event.window_pos);
                     self.previous_mouse_position = Some(mouse_event.window_pos);
-                    // self.start_offset = data.absolute_offset;
-                    self.start_offset = data.get_offset();
-                    debug!("Start offset: {:?}", self.start_offset);
+                    data.set_drag_delta(Vec2::ZERO);
+                    debug!("Start offset: {:?}", data.get_offset());
                     ctx.set_active(true);
                     ctx.request_focus();
                 }
@@ -114,26 +305,25 @@ impl<T: Data + PanDataAccess, W: Widget<T>> Controller<T, W> for PanController {
                 {
                     // Calculate delta from current position
                     release_delta = mouse_event.window_pos - start_mouse_position;
-                    let mut offset = self.start_offset + release_delta;
-
-                    self.previous_mouse_position = Some(mouse_event.window_pos);
-
-                    if offset.x > self.max_offset.x {
-                        offset.x = self.max_offset.x;
-                    } else if offset.x < self.min_offset.x {
-                        offset.x = self.min_offset.x;
-                    }
+                    let start_offset = data.get_drag_origin();
 
-                    if offset.y > self.max_offset.y {
-                        offset.y = self.max_offset.y;
-                    } else if offset.y < self.min_offset.y {
-                        offset.y = self.min_offset.y;
+                    // Smoothed so a single jittery move does not decide the inertia
+                    let now = Instant::now();
+                    if let Some(previous_move) = self.previous_move {
+                        let elapsed = now.duration_since(previous_move).as_secs_f64() * 1000.0;
+                        if elapsed > 0.0 {
+                            let velocity =
+                                (mouse_event.window_pos - previous_mouse_position) / elapsed;
+                            self.velocity = self.velocity.lerp(velocity, 0.5);
+                        }
                     }
+                    self.previous_move = Some(now);
+                    self.previous_mouse_position = Some(mouse_event.window_pos);
 
-                    // data.absolute_offset = offset;
-                    data.set_offset(offset);
+                    // The delta is what the drag actually moved the offset, after clamping
+                    self.pan_to(ctx, data, start_offset + release_delta);
+                    data.set_drag_delta(data.get_offset() - start_offset);
                     ctx.set_handled();
-                    // debug!("Current delta: {:?}", data.relative_offset);
                 }
             }
             Event::MouseUp(mouse_event) => {
@@ -141,11 +331,129 @@ impl<T: Data + PanDataAccess, W: Widget<T>> Controller<T, W> for PanController {
                     ctx.set_active(false);
                     ctx.resign_focus();
                     self.start_mouse_position = None;
-                    // debug!("Finish offset: {:?}", data.absolute_offset);
+                    // The drag only keeps going if the mouse was still moving on release
+                    let since_move = self.previous_move.map(|instant| instant.elapsed());
+                    if self.inertia.is_some()
+                        && since_move.is_some_and(|elapsed| elapsed < INERTIA_TICK * 3)
+                        && self.velocity.hypot() > INERTIA_MIN_SPEED
+                    {
+                        self.inertia_timer = ctx.request_timer(INERTIA_TICK);
+                    }
+                    data.set_drag_delta(Vec2::ZERO);
+                    debug!("Finish offset: {:?}", data.get_offset());
                     debug!("Release delta: {:?}\n", release_delta);
                 }
             }
+            Event::Timer(token) if *token == self.inertia_timer => {
+                let step = self.velocity * INERTIA_TICK.as_secs_f64() * 1000.0;
+                let excess = self.pan_to(ctx, data, data.get_offset() + step);
+                // The view stops along the axes it ran into the limits on
+                if excess.x != 0.0 {
+                    self.velocity.x = 0.0;
+                }
+                if excess.y != 0.0 {
+                    self.velocity.y = 0.0;
+                }
+                self.velocity *= self.inertia.unwrap_or(0.0);
+                self.inertia_timer = if self.velocity.hypot() > INERTIA_MIN_SPEED {
+                    ctx.request_timer(INERTIA_TICK)
+                } else {
+                    TimerToken::INVALID
+                };
+                ctx.set_handled();
+            }
+            Event::Timer(token) if *token == self.overscroll_timer => {
+                let overscroll = data.get_overscroll() * OVERSCROLL_DECAY;
+                if overscroll.hypot() < OVERSCROLL_MIN {
+                    data.set_overscroll(Vec2::ZERO);
+                    self.overscroll_timer = TimerToken::INVALID;
+                } else {
+                    data.set_overscroll(overscroll);
+                    self.overscroll_timer = ctx.request_timer(INERTIA_TICK);
+                }
+                ctx.set_handled();
+            }
+            Event::Wheel(wheel) if !wheel.mods.ctrl() => {
+                if let Some(speed) = self.scroll_speed {
+                    self.inertia_timer = TimerToken::INVALID;
+                    let mut delta = wheel.wheel_delta;
+                    // Mouse wheels only scroll vertically
+                    if wheel.mods.shift() && delta.x == 0.0 {
+                        delta = Vec2::new(delta.y, 0.0);
+                    }
+                    self.pan_to(ctx, data, data.get_offset() - delta * speed);
+                    ctx.set_handled();
+                }
+            }
+            Event::KeyDown(key_event) => {
+                if let (Some(step), Some(direction)) =
+                    (self.key_step, Self::key_direction(key_event))
+                {
+                    let step = step * data.get_zoom_scale();
+                    self.pan_to(ctx, data, data.get_offset() + direction * step);
+                    ctx.set_handled();
+                }
+            }
             _ => {}
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use druid::{KbKey, KeyEvent, Modifiers, Point, Vec2};
+
+    use super::{rubber_band, PanController, PanDataAccess, MAX_OFFSET, OVERSCROLL_LIMIT};
+    use crate::snapping::GridSnapData;
+
+    #[test]
+    fn pans_only_on_plain_keys() {
+        let key = |mods, key| PanController::key_direction(&KeyEvent::for_test(mods, key));
+        assert_eq!(
+            key(Modifiers::empty(), KbKey::ArrowLeft),
+            Some(Vec2::new(1.0, 0.0))
+        );
+        assert_eq!(
+            key(Modifiers::empty(), KbKey::Character(String::from("S"))),
+            Some(Vec2::new(0.0, -1.0))
+        );
+        assert_eq!(
+            key(Modifiers::CONTROL, KbKey::Character(String::from("s"))),
+            None
+        );
+        assert_eq!(key(Modifiers::SHIFT, KbKey::ArrowUp), None);
+        assert_eq!(
+            key(Modifiers::empty(), KbKey::Character(String::from("q"))),
+            None
+        );
+    }
+
+    #[test]
+    fn keeps_the_drag_apart_from_the_offset() {
+        let mut snap_data = GridSnapData::new(10.0);
+        snap_data.set_offset(Point::new(10.0, 20.0));
+        assert_eq!(snap_data.get_drag_origin(), Point::new(10.0, 20.0));
+
+        snap_data.set_offset(Point::new(15.0, 5.0));
+        snap_data.set_drag_delta(Vec2::new(5.0, -15.0));
+        assert_eq!(snap_data.get_drag_origin(), Point::new(10.0, 20.0));
+        snap_data.set_drag_delta(Vec2::new(f64::NAN, 0.0));
+        assert_eq!(snap_data.get_drag_delta(), Vec2::new(5.0, -15.0));
+
+        snap_data.set_offset(Point::new(f64::INFINITY, 0.0));
+        assert_eq!(snap_data.get_offset(), Point::new(15.0, 5.0));
+        snap_data.set_offset(Point::new(2.0 * MAX_OFFSET, 0.0));
+        assert_eq!(snap_data.get_offset(), Point::new(MAX_OFFSET, 0.0));
+    }
+
+    #[test]
+    fn overscroll_stretches_like_a_rubber_band() {
+        assert_eq!(rubber_band(0.0), 0.0);
+        // Small pushes past the limits stretch about half as far, large ones never reach the limit
+        assert!((rubber_band(4.0) - 2.0).abs() < 0.25);
+        assert!(rubber_band(1e6) < OVERSCROLL_LIMIT);
+        assert!(rubber_band(1e6) > 0.99 * OVERSCROLL_LIMIT);
+        assert_eq!(rubber_band(-30.0), -rubber_band(30.0));
+        assert!(rubber_band(10.0) < rubber_band(20.0));
+    }
+}
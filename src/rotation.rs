@@ -1,22 +1,108 @@
-pub trait RotationData {
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use druid::{widget::Controller, Affine, Data, Event, KbKey, Point, Widget};
+
+use crate::panning::PanDataAccess;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// RotationData
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+pub trait RotationDataAccess {
+    /// Clockwise angle of the view in radians, kept within [0, 2π)
     fn get_rotation(&self) -> f64;
     fn set_rotation(&mut self, rotation: f64);
 }
 
-#[allow(dead_code)]
+/// Rotation of the view by `rotation` radians around `pivot`, the point of the widget the grid
+/// origin is drawn at. Positions computed from the pan offset and zoom scale alone are mapped to
+/// the widget with it
+pub fn view_rotation(pivot: Point, rotation: f64) -> Affine {
+    Affine::translate(pivot.to_vec2())
+        * Affine::rotate(rotation)
+        * Affine::translate(-pivot.to_vec2())
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// RotationController
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Rotates the view around the centre of the widget with Ctrl+Shift+Wheel, or with Ctrl+R and
+/// Ctrl+Shift+R. Ctrl+0 puts the view back upright
 pub struct RotationController {
     rotation_step: f64,
 }
 
-#[allow(dead_code)]
 impl RotationController {
-    fn new(rotation_step: f64) -> Self {
+    /// `rotation_step` is in radians
+    pub fn new(rotation_step: f64) -> Self {
         Self { rotation_step }
     }
+
+    // Rotates by `delta` keeping the point of the grid under `center` in place
+    fn rotate<T: RotationDataAccess + PanDataAccess>(data: &mut T, center: Point, delta: f64) {
+        let offset = view_rotation(center, delta) * data.get_offset();
+        data.set_offset(offset);
+        data.set_rotation(data.get_rotation() + delta);
+    }
 }
 
 impl Default for RotationController {
     fn default() -> Self {
-        Self { rotation_step: 0.1 }
+        Self {
+            rotation_step: 15.0_f64.to_radians(),
+        }
+    }
+}
+
+impl<T: Data + RotationDataAccess + PanDataAccess, W: Widget<T>> Controller<T, W>
+    for RotationController
+{
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut druid::EventCtx,
+        event: &Event,
+        data: &mut T,
+        env: &druid::Env,
+    ) {
+        let center = ctx.size().to_rect().center();
+        match event {
+            Event::Wheel(wheel) if wheel.mods.ctrl() && wheel.mods.shift() => {
+                // Shift turns vertical wheels into horizontal ones on some platforms
+                let delta = wheel.wheel_delta.y + wheel.wheel_delta.x;
+                if delta != 0.0 {
+                    Self::rotate(data, center, self.rotation_step * delta.signum());
+                }
+                ctx.set_handled();
+                return;
+            }
+            Event::KeyDown(key_event) if key_event.mods.ctrl() => match &key_event.key {
+                KbKey::Character(c) if c.eq_ignore_ascii_case("r") => {
+                    let step = if key_event.mods.shift() {
+                        -self.rotation_step
+                    } else {
+                        self.rotation_step
+                    };
+                    Self::rotate(data, center, step);
+                    ctx.set_handled();
+                    return;
+                }
+                KbKey::Character(c) if c == "0" => {
+                    let rotation = data.get_rotation();
+                    Self::rotate(data, center, -rotation);
+                    ctx.set_handled();
+                    return;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        child.event(ctx, event, data, env);
     }
 }
@@ -0,0 +1,309 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::{cell::RefCell, collections::HashMap as StdHashMap, fmt::Debug, rc::Rc};
+
+use druid::{
+    im::{HashMap, Vector},
+    Data,
+};
+use rhai::{Array, Engine, EvalAltResult, INT};
+
+use crate::{
+    grid_canvas::GridCanvasData,
+    utils::cassetta::{CassettePlayer, TapeItem},
+    GridIndex, GridItem,
+};
+
+type Algorithm<T> = Box<dyn Fn(&HashMap<GridIndex, T>) -> Vector<TapeItem<GridIndex, T>>>;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// ScriptState
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+// Working copy of the grid the script functions operate on
+struct ScriptState<T: GridItem> {
+    grid: HashMap<GridIndex, T>,
+    tape: Vector<TapeItem<GridIndex, T>>,
+    resolver: Box<dyn Fn(&str) -> Option<T>>,
+    algorithms: StdHashMap<String, Algorithm<T>>,
+}
+
+impl<T: GridItem + Debug> ScriptState<T> {
+    fn play(&mut self, item: TapeItem<GridIndex, T>) {
+        self.grid.advance(item.clone());
+        self.tape.push_back(item);
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// ScriptBridge
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Runs rhai scripts against a grid. The following functions are available to scripts:
+///
+/// - `add(row, col, name)`, `remove(row, col)`, `move_cell(from_row, from_col, to_row, to_col)`
+/// - `add_area(row, col, rows, cols, name)`, `remove_area(row, col, rows, cols)`
+/// - `get(row, col)` returns the short text of the item or an empty string
+/// - `select(row, col, rows, cols)` returns the `[row, col]` of every occupied cell in the area
+/// - `run_algorithm(name)` runs an algorithm registered with [`ScriptBridge::register_algorithm`]
+///
+/// The area functions raise an error for areas of more than four million cells.
+///
+/// Item names are converted to grid items with the resolver passed to [`ScriptBridge::new`].
+pub struct ScriptBridge<T: GridItem + 'static> {
+    engine: Engine,
+    state: Rc<RefCell<ScriptState<T>>>,
+}
+
+impl<T: GridItem + PartialEq + Debug + 'static> ScriptBridge<T>
+where
+    GridCanvasData<T>: Data,
+{
+    pub fn new(resolver: impl Fn(&str) -> Option<T> + 'static) -> Self {
+        let state = Rc::new(RefCell::new(ScriptState {
+            grid: HashMap::new(),
+            tape: Vector::new(),
+            resolver: Box::new(resolver),
+            algorithms: StdHashMap::new(),
+        }));
+        let mut engine = Engine::new();
+
+        let add_state = state.clone();
+        engine.register_fn("add", move |row: INT, col: INT, name: &str| -> bool {
+            let mut state = add_state.borrow_mut();
+            let pos = GridIndex::new(row as isize, col as isize);
+            match (state.resolver)(name) {
+                Some(item) if item.can_add(state.grid.get(&pos)) => {
+                    let previous_item = state.grid.get(&pos).copied();
+                    state.play(TapeItem::Add(pos, item, previous_item));
+                    true
+                }
+                _ => false,
+            }
+        });
+
+        let remove_state = state.clone();
+        engine.register_fn("remove", move |row: INT, col: INT| -> bool {
+            let mut state = remove_state.borrow_mut();
+            let pos = GridIndex::new(row as isize, col as isize);
+            match state.grid.get(&pos).copied() {
+                Some(item) if item.can_remove() => {
+                    state.play(TapeItem::Remove(pos, item));
+                    true
+                }
+                _ => false,
+            }
+        });
+
+        let move_state = state.clone();
+        engine.register_fn(
+            "move_cell",
+            move |from_row: INT, from_col: INT, to_row: INT, to_col: INT| -> bool {
+                let mut state = move_state.borrow_mut();
+                let from = GridIndex::new(from_row as isize, from_col as isize);
+                let to = GridIndex::new(to_row as isize, to_col as isize);
+                match state.grid.get(&from).copied() {
+                    Some(item) if from != to && item.can_move(state.grid.get(&to)) => {
+                        state.play(TapeItem::Move(from, to, item));
+                        true
+                    }
+                    _ => false,
+                }
+            },
+        );
+
+        let add_area_state = state.clone();
+        engine.register_fn(
+            "add_area",
+            move |row: INT,
+                  col: INT,
+                  rows: INT,
+                  cols: INT,
+                  name: &str|
+                  -> Result<INT, Box<EvalAltResult>> {
+                let cells = area(row, col, rows, cols)?;
+                let mut state = add_area_state.borrow_mut();
+                let item = match (state.resolver)(name) {
+                    Some(item) => item,
+                    None => return Ok(0),
+                };
+                let mut map = HashMap::new();
+                for pos in cells {
+                    let other = state.grid.get(&pos);
+                    if item.can_add(other) {
+                        map.insert(pos, (item, other.copied()));
+                    }
+                }
+                let count = map.len() as INT;
+                if count > 0 {
+                    state.play(TapeItem::BatchAdd(map));
+                }
+                Ok(count)
+            },
+        );
+
+        let remove_area_state = state.clone();
+        engine.register_fn(
+            "remove_area",
+            move |row: INT, col: INT, rows: INT, cols: INT| -> Result<INT, Box<EvalAltResult>> {
+                let cells = area(row, col, rows, cols)?;
+                let mut state = remove_area_state.borrow_mut();
+                let mut map = HashMap::new();
+                for pos in cells {
+                    if let Some(item) = state.grid.get(&pos) {
+                        if item.can_remove() {
+                            map.insert(pos, *item);
+                        }
+                    }
+                }
+                let count = map.len() as INT;
+                if count > 0 {
+                    state.play(TapeItem::BatchRemove(map));
+                }
+                Ok(count)
+            },
+        );
+
+        let get_state = state.clone();
+        engine.register_fn("get", move |row: INT, col: INT| -> String {
+            let state = get_state.borrow();
+            let pos = GridIndex::new(row as isize, col as isize);
+            state
+                .grid
+                .get(&pos)
+                .map(|item| item.get_short_text())
+                .unwrap_or_default()
+        });
+
+        let select_state = state.clone();
+        engine.register_fn(
+            "select",
+            move |row: INT, col: INT, rows: INT, cols: INT| -> Result<Array, Box<EvalAltResult>> {
+                let state = select_state.borrow();
+                Ok(area(row, col, rows, cols)?
+                    .filter(|pos| state.grid.contains_key(pos))
+                    .map(|pos| {
+                        let cell: Array = vec![(pos.row as INT).into(), (pos.col as INT).into()];
+                        cell.into()
+                    })
+                    .collect())
+            },
+        );
+
+        let algorithm_state = state.clone();
+        engine.register_fn("run_algorithm", move |name: &str| -> bool {
+            let items = {
+                let state = algorithm_state.borrow();
+                match state.algorithms.get(name) {
+                    Some(algorithm) => algorithm(&state.grid),
+                    None => return false,
+                }
+            };
+            let mut state = algorithm_state.borrow_mut();
+            for item in items {
+                state.play(item);
+            }
+            true
+        });
+
+        Self { engine, state }
+    }
+
+    /// Makes an algorithm callable from scripts through `run_algorithm(name)`
+    pub fn register_algorithm(
+        &mut self,
+        name: impl Into<String>,
+        algorithm: impl Fn(&HashMap<GridIndex, T>) -> Vector<TapeItem<GridIndex, T>> + 'static,
+    ) {
+        self.state
+            .borrow_mut()
+            .algorithms
+            .insert(name.into(), Box::new(algorithm));
+    }
+
+    /// Runs the script and submits the resulting operations to the grid as a single undo step.
+    /// Nothing is submitted if the script fails.
+    pub fn run(
+        &mut self,
+        data: &mut GridCanvasData<T>,
+        script: &str,
+    ) -> Result<(), Box<EvalAltResult>> {
+        {
+            let mut state = self.state.borrow_mut();
+            state.grid = data.grid.to_hash_map();
+            state.tape.clear();
+        }
+
+        self.engine.run(script)?;
+
+        let tape = std::mem::take(&mut self.state.borrow_mut().tape);
+        data.submit_group_and_play(tape);
+        Ok(())
+    }
+}
+
+// Most cells a single area function visits, so a runaway size can not stall the script
+const MAX_AREA_CELLS: INT = 1 << 22;
+
+// Cells of the area with its top left cell at (row, col). Areas with no rows or columns are
+// empty, areas reaching past the index range or with too many cells are refused
+fn area(
+    row: INT,
+    col: INT,
+    rows: INT,
+    cols: INT,
+) -> Result<impl Iterator<Item = GridIndex>, Box<EvalAltResult>> {
+    let (rows, cols) = (rows.max(0), cols.max(0));
+    let bounds = row
+        .checked_add(rows)
+        .zip(col.checked_add(cols))
+        .zip(rows.checked_mul(cols));
+    let (end_row, end_col) = match bounds {
+        Some((end, cells)) if cells <= MAX_AREA_CELLS => end,
+        _ => {
+            return Err(
+                format!("Area of {rows} x {cols} cells at ({row}, {col}) is too large").into(),
+            )
+        }
+    };
+    Ok((row..end_row).flat_map(move |row| {
+        (col..end_col).map(move |col| GridIndex::new(row as isize, col as isize))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use rhai::INT;
+
+    use super::{area, MAX_AREA_CELLS};
+    use crate::GridIndex;
+
+    #[test]
+    fn areas_are_checked_before_they_are_visited() {
+        let cells: Vec<GridIndex> = area(1, -1, 2, 2).unwrap().collect();
+        assert_eq!(
+            cells,
+            vec![
+                GridIndex::new(1, -1),
+                GridIndex::new(1, 0),
+                GridIndex::new(2, -1),
+                GridIndex::new(2, 0),
+            ]
+        );
+        assert_eq!(area(0, 0, -3, 5).unwrap().count(), 0);
+
+        assert!(area(INT::MAX, 0, 1, 1).is_err());
+        assert!(area(0, INT::MAX - 1, 1, 2).is_err());
+        assert!(area(0, 0, INT::MAX, INT::MAX).is_err());
+        assert!(area(0, 0, MAX_AREA_CELLS + 1, 1).is_err());
+        assert_eq!(
+            area(0, 0, MAX_AREA_CELLS, 1).unwrap().count() as INT,
+            MAX_AREA_CELLS
+        );
+    }
+}
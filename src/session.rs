@@ -0,0 +1,787 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::{
+    fmt::{Debug, Write},
+    str::FromStr,
+    time::Instant,
+};
+
+use druid::Data;
+
+use crate::{
+    grid_canvas::GridCanvasData, utils::cassetta::TapeItem, CellTransform, GridAction, GridIndex,
+    GridItem,
+};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// SessionEvent
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// An editing operation on a [`GridCanvasData`], named after the method that performed it
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionEvent<T> {
+    SetAction(GridAction),
+    Add(GridIndex, T),
+    Remove(GridIndex),
+    Move(GridIndex, GridIndex),
+    Route(Vec<GridIndex>, T),
+    // Start of the area, item and largest area
+    Fill(GridIndex, T, usize),
+    SelectSame(GridIndex),
+    Transform(CellTransform),
+    EndStroke,
+    CancelStroke,
+    Seek(usize),
+    // Index of the branch of the undo tree
+    SwitchBranch(usize),
+    Clear,
+    // Cell changes applied as a single undo step by a method that does not edit cell by cell,
+    // e.g. `apply_batch`
+    Apply(Vec<SessionOp<T>>),
+    // Steps put on the redo tape without being played, see `submit_to_stack`
+    Queue(Vec<Vec<SessionOp<T>>>),
+    // Cell changes made by another grid, see `apply_remote`
+    Remote(Vec<SessionOp<T>>),
+    SetExtent(Option<GridIndex>),
+    SetReadOnly(bool),
+}
+
+/// A change to a single cell, replayed against whatever the cell holds at the time
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionOp<T> {
+    /// Places an item, replacing the item on the cell if any
+    Put(GridIndex, T),
+    /// Takes the item off the cell
+    Take(GridIndex),
+    /// Moves the item of the first cell onto the second, which has to be empty
+    Shift(GridIndex, GridIndex),
+}
+
+impl<T: Clone> SessionOp<T> {
+    /// Changes made by a tape item, in the order they are played. The cells of a batch are
+    /// sorted so the same item always gives the same changes
+    pub fn from_tape(item: &TapeItem<GridIndex, T>) -> Vec<Self> {
+        let mut ops = Vec::new();
+        push_ops(item, &mut ops);
+        ops
+    }
+}
+
+fn push_ops<T: Clone>(item: &TapeItem<GridIndex, T>, ops: &mut Vec<SessionOp<T>>) {
+    match item {
+        TapeItem::Add(pos, item, _) => ops.push(SessionOp::Put(*pos, item.clone())),
+        TapeItem::Remove(pos, _) => ops.push(SessionOp::Take(*pos)),
+        TapeItem::Move(from, to, _) => ops.push(SessionOp::Shift(*from, *to)),
+        TapeItem::BatchAdd(map) => {
+            let mut cells: Vec<(GridIndex, T)> = map
+                .iter()
+                .map(|(pos, (item, _))| (*pos, item.clone()))
+                .collect();
+            cells.sort_by_key(|(pos, _)| (pos.row, pos.col));
+            ops.extend(
+                cells
+                    .into_iter()
+                    .map(|(pos, item)| SessionOp::Put(pos, item)),
+            );
+        }
+        TapeItem::BatchRemove(map) => {
+            let mut cells: Vec<GridIndex> = map.keys().copied().collect();
+            cells.sort_by_key(|pos| (pos.row, pos.col));
+            ops.extend(cells.into_iter().map(SessionOp::Take));
+        }
+        TapeItem::Group(items) => {
+            for item in items.iter() {
+                push_ops(item, ops);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionEntry<T> {
+    // Milliseconds since the recording started
+    pub time: u64,
+    pub event: SessionEvent<T>,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Session
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+const SESSION_HEADER: &str = "# grid session";
+
+/// The editing operations performed on a grid while a session was recorded, see
+/// [`GridCanvasData::start_session`]. A session saved with [`to_text`](Self::to_text) can be
+/// attached to a bug report and replayed to reproduce it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session<T> {
+    pub entries: Vec<SessionEntry<T>>,
+}
+
+impl<T> Default for Session<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<T: GridItem + PartialEq + Debug> Session<T>
+where
+    GridCanvasData<T>: Data,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Performs every operation of the session on `data`, in order and regardless of the time
+    /// between them. Replaying onto data in the state the recording started from, with the
+    /// same extent and neighbourhood, always gives the grid the recording ended with. Fails on
+    /// the first operation that is rejected, which means the two have diverged.
+    pub fn replay(&self, data: &mut GridCanvasData<T>) -> Result<(), String> {
+        for (index, entry) in self.entries.iter().enumerate() {
+            data.apply_session_event(&entry.event).map_err(|reason| {
+                format!("Event {} at {} ms: {}", index + 1, entry.time, reason)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// One line per operation, starting with the time in milliseconds and the kind of
+    /// operation. Items are turned into text by `encode` and go last on the line, so they may
+    /// contain spaces but not line breaks. Cell changes follow the operation on a line each, the
+    /// steps of a `Queue` with a line giving the number of changes before them.
+    pub fn to_text(&self, encode: impl Fn(&T) -> String) -> String {
+        let mut text = String::from(SESSION_HEADER);
+        text.push('\n');
+        for entry in self.entries.iter() {
+            write!(text, "{} ", entry.time).unwrap();
+            match &entry.event {
+                SessionEvent::SetAction(action) => write!(text, "action {}", action_name(action)),
+                SessionEvent::Add(pos, item) => {
+                    write!(text, "add {} {} {}", pos.row, pos.col, encode(item))
+                }
+                SessionEvent::Remove(pos) => write!(text, "remove {} {}", pos.row, pos.col),
+                SessionEvent::Move(from, to) => {
+                    write!(text, "move {} {} {} {}", from.row, from.col, to.row, to.col)
+                }
+                SessionEvent::Route(route, item) => {
+                    write!(text, "route {}", route.len()).unwrap();
+                    for pos in route {
+                        write!(text, " {} {}", pos.row, pos.col).unwrap();
+                    }
+                    write!(text, " {}", encode(item))
+                }
+                SessionEvent::Fill(pos, item, max_cells) => write!(
+                    text,
+                    "fill {} {} {} {}",
+                    pos.row,
+                    pos.col,
+                    max_cells,
+                    encode(item)
+                ),
+                SessionEvent::SelectSame(pos) => write!(text, "select {} {}", pos.row, pos.col),
+                SessionEvent::Transform(transform) => {
+                    write!(text, "transform {}", transform_name(transform))
+                }
+                SessionEvent::EndStroke => write!(text, "end"),
+                SessionEvent::CancelStroke => write!(text, "cancel"),
+                SessionEvent::Seek(index) => write!(text, "seek {index}"),
+                SessionEvent::SwitchBranch(index) => write!(text, "branch {index}"),
+                SessionEvent::Clear => write!(text, "clear"),
+                SessionEvent::Apply(ops) => {
+                    write!(text, "apply {}", ops.len()).unwrap();
+                    write_ops(&mut text, ops, &encode);
+                    Ok(())
+                }
+                SessionEvent::Queue(steps) => {
+                    write!(text, "queue {}", steps.len()).unwrap();
+                    for ops in steps {
+                        write!(text, "\nstep {}", ops.len()).unwrap();
+                        write_ops(&mut text, ops, &encode);
+                    }
+                    Ok(())
+                }
+                SessionEvent::Remote(ops) => {
+                    write!(text, "remote {}", ops.len()).unwrap();
+                    write_ops(&mut text, ops, &encode);
+                    Ok(())
+                }
+                SessionEvent::SetExtent(Some(extent)) => {
+                    write!(text, "extent {} {}", extent.row, extent.col)
+                }
+                SessionEvent::SetExtent(None) => write!(text, "extent none"),
+                SessionEvent::SetReadOnly(read_only) => {
+                    write!(text, "read-only {}", if *read_only { "on" } else { "off" })
+                }
+            }
+            .unwrap();
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Reads text written by [`to_text`](Self::to_text). Items are read back by `decode`.
+    /// Blank lines and lines starting with `#` are skipped.
+    pub fn from_text(text: &str, decode: impl Fn(&str) -> Option<T>) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        let mut lines = text.lines().enumerate();
+        while let Some((index, line)) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = Fields {
+                rest: line,
+                line: index + 1,
+            };
+            let time = fields.parse("time")?;
+            let kind = fields.next("kind of event")?;
+            let event = match kind {
+                "action" => {
+                    let name = fields.next("action")?;
+                    let action = parse_action(name)
+                        .ok_or_else(|| fields.error(format!("unknown action {name:?}")))?;
+                    SessionEvent::SetAction(action)
+                }
+                "add" => {
+                    let pos = fields.index()?;
+                    SessionEvent::Add(pos, fields.item(&decode)?)
+                }
+                "remove" => SessionEvent::Remove(fields.index()?),
+                "move" => SessionEvent::Move(fields.index()?, fields.index()?),
+                "route" => {
+                    let len: usize = fields.parse("length")?;
+                    let route = (0..len).map(|_| fields.index()).collect::<Result<_, _>>()?;
+                    SessionEvent::Route(route, fields.item(&decode)?)
+                }
+                "fill" => {
+                    let pos = fields.index()?;
+                    let max_cells = fields.parse("cell limit")?;
+                    SessionEvent::Fill(pos, fields.item(&decode)?, max_cells)
+                }
+                "select" => SessionEvent::SelectSame(fields.index()?),
+                "transform" => {
+                    let name = fields.next("transform")?;
+                    let transform = parse_transform(name)
+                        .ok_or_else(|| fields.error(format!("unknown transform {name:?}")))?;
+                    SessionEvent::Transform(transform)
+                }
+                "end" => SessionEvent::EndStroke,
+                "cancel" => SessionEvent::CancelStroke,
+                "seek" => SessionEvent::Seek(fields.parse("position")?),
+                "branch" => SessionEvent::SwitchBranch(fields.parse("branch")?),
+                "clear" => SessionEvent::Clear,
+                "apply" => SessionEvent::Apply(fields.ops(&mut lines, &decode)?),
+                "queue" => {
+                    let len: usize = fields.parse("number of steps")?;
+                    let mut steps = Vec::new();
+                    for _ in 0..len {
+                        let mut step_fields = fields.line(&mut lines, "step")?;
+                        if step_fields.next("step")? != "step" {
+                            return Err(step_fields.error(String::from("expected a step")));
+                        }
+                        steps.push(step_fields.ops(&mut lines, &decode)?);
+                        step_fields.expect_end()?;
+                    }
+                    SessionEvent::Queue(steps)
+                }
+                "remote" => SessionEvent::Remote(fields.ops(&mut lines, &decode)?),
+                "extent" => {
+                    if fields.rest == "none" {
+                        fields.rest = "";
+                        SessionEvent::SetExtent(None)
+                    } else {
+                        SessionEvent::SetExtent(Some(fields.index()?))
+                    }
+                }
+                "read-only" => match fields.next("state")? {
+                    "on" => SessionEvent::SetReadOnly(true),
+                    "off" => SessionEvent::SetReadOnly(false),
+                    state => return Err(fields.error(format!("invalid state {state:?}"))),
+                },
+                _ => return Err(fields.error(format!("unknown event {kind:?}"))),
+            };
+            fields.expect_end()?;
+            entries.push(SessionEntry { time, event });
+        }
+        Ok(Self { entries })
+    }
+}
+
+// Whitespace separated fields of a line of a session
+struct Fields<'a> {
+    rest: &'a str,
+    line: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn error(&self, message: String) -> String {
+        format!("Line {}: {}", self.line, message)
+    }
+
+    fn next(&mut self, what: &str) -> Result<&'a str, String> {
+        if self.rest.is_empty() {
+            return Err(self.error(format!("missing {what}")));
+        }
+        let (field, rest) = self
+            .rest
+            .split_once(char::is_whitespace)
+            .unwrap_or((self.rest, ""));
+        self.rest = rest.trim_start();
+        Ok(field)
+    }
+
+    fn parse<F: FromStr>(&mut self, what: &str) -> Result<F, String> {
+        let field = self.next(what)?;
+        field
+            .parse()
+            .map_err(|_| self.error(format!("invalid {what} {field:?}")))
+    }
+
+    fn index(&mut self) -> Result<GridIndex, String> {
+        Ok(GridIndex::new(self.parse("row")?, self.parse("column")?))
+    }
+
+    fn expect_end(&self) -> Result<(), String> {
+        if self.rest.is_empty() {
+            Ok(())
+        } else {
+            Err(self.error(format!("unexpected {:?}", self.rest)))
+        }
+    }
+
+    // Fields of the next line, for operations that go on over several lines
+    fn line<'b>(
+        &self,
+        lines: &mut impl Iterator<Item = (usize, &'b str)>,
+        what: &str,
+    ) -> Result<Fields<'b>, String> {
+        let (index, line) = lines
+            .next()
+            .ok_or_else(|| self.error(format!("missing {what}")))?;
+        Ok(Fields {
+            rest: line.trim(),
+            line: index + 1,
+        })
+    }
+
+    // Cell changes on the lines that follow, as many as the next field says
+    fn ops<'b, T>(
+        &mut self,
+        lines: &mut impl Iterator<Item = (usize, &'b str)>,
+        decode: impl Fn(&str) -> Option<T>,
+    ) -> Result<Vec<SessionOp<T>>, String> {
+        let len: usize = self.parse("number of changes")?;
+        let mut ops = Vec::new();
+        for _ in 0..len {
+            let mut op_fields = self.line(lines, "change")?;
+            ops.push(op_fields.op(&decode)?);
+            op_fields.expect_end()?;
+        }
+        Ok(ops)
+    }
+
+    fn op<T>(&mut self, decode: impl Fn(&str) -> Option<T>) -> Result<SessionOp<T>, String> {
+        let kind = self.next("kind of change")?;
+        match kind {
+            "put" => {
+                let pos = self.index()?;
+                Ok(SessionOp::Put(pos, self.item(decode)?))
+            }
+            "take" => Ok(SessionOp::Take(self.index()?)),
+            "shift" => Ok(SessionOp::Shift(self.index()?, self.index()?)),
+            _ => Err(self.error(format!("unknown change {kind:?}"))),
+        }
+    }
+
+    // The item takes the rest of the line
+    fn item<T>(&mut self, decode: impl Fn(&str) -> Option<T>) -> Result<T, String> {
+        let text = std::mem::take(&mut self.rest);
+        if text.is_empty() {
+            return Err(self.error(String::from("missing item")));
+        }
+        decode(text).ok_or_else(|| self.error(format!("unknown item {text:?}")))
+    }
+}
+
+// A line per change, each after a line break
+fn write_ops<T>(text: &mut String, ops: &[SessionOp<T>], encode: impl Fn(&T) -> String) {
+    for op in ops {
+        text.push('\n');
+        match op {
+            SessionOp::Put(pos, item) => {
+                write!(text, "put {} {} {}", pos.row, pos.col, encode(item))
+            }
+            SessionOp::Take(pos) => write!(text, "take {} {}", pos.row, pos.col),
+            SessionOp::Shift(from, to) => write!(
+                text,
+                "shift {} {} {} {}",
+                from.row, from.col, to.row, to.col
+            ),
+        }
+        .unwrap();
+    }
+}
+
+fn action_name(action: &GridAction) -> &'static str {
+    match action {
+        GridAction::Dynamic => "dynamic",
+        GridAction::Add => "add",
+        GridAction::Remove => "remove",
+        GridAction::Move => "move",
+        GridAction::Route => "route",
+        GridAction::Measure => "measure",
+    }
+}
+
+fn parse_action(name: &str) -> Option<GridAction> {
+    [
+        GridAction::Dynamic,
+        GridAction::Add,
+        GridAction::Remove,
+        GridAction::Move,
+        GridAction::Route,
+        GridAction::Measure,
+    ]
+    .into_iter()
+    .find(|action| action_name(action) == name)
+}
+
+fn transform_name(transform: &CellTransform) -> &'static str {
+    match transform {
+        CellTransform::RotateClockwise => "rotate-cw",
+        CellTransform::RotateCounterClockwise => "rotate-ccw",
+        CellTransform::FlipHorizontal => "flip-h",
+        CellTransform::FlipVertical => "flip-v",
+    }
+}
+
+fn parse_transform(name: &str) -> Option<CellTransform> {
+    [
+        CellTransform::RotateClockwise,
+        CellTransform::RotateCounterClockwise,
+        CellTransform::FlipHorizontal,
+        CellTransform::FlipVertical,
+    ]
+    .into_iter()
+    .find(|transform| transform_name(transform) == name)
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// SessionRecorder
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Stamps the operations it is given with the time since it was created
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionRecorder<T> {
+    started: Instant,
+    session: Session<T>,
+}
+
+impl<T> SessionRecorder<T> {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            session: Session::default(),
+        }
+    }
+
+    pub fn record(&mut self, event: SessionEvent<T>) {
+        let time = self.started.elapsed().as_millis() as u64;
+        self.session.entries.push(SessionEntry { time, event });
+    }
+
+    pub fn finish(self) -> Session<T> {
+        self.session
+    }
+}
+
+impl<T> Default for SessionRecorder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// SessionPlayer
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Replays a session at the pace it was recorded at, for watching a reproduction unfold
+pub struct SessionPlayer<T> {
+    session: Session<T>,
+    next: usize,
+}
+
+impl<T: GridItem + PartialEq + Debug> SessionPlayer<T>
+where
+    GridCanvasData<T>: Data,
+{
+    pub fn new(session: Session<T>) -> Self {
+        Self { session, next: 0 }
+    }
+
+    /// Performs the operations recorded up to `elapsed` milliseconds into the session that
+    /// have not been performed yet. Returns whether the session is over
+    pub fn advance_to(
+        &mut self,
+        data: &mut GridCanvasData<T>,
+        elapsed: u64,
+    ) -> Result<bool, String> {
+        while let Some(entry) = self.session.entries.get(self.next) {
+            if entry.time > elapsed {
+                return Ok(false);
+            }
+            self.next += 1;
+            data.apply_session_event(&entry.event).map_err(|reason| {
+                format!("Event {} at {} ms: {}", self.next, entry.time, reason)
+            })?;
+        }
+        Ok(true)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next == self.session.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::im::Vector;
+
+    use super::{Session, SessionEntry, SessionEvent, SessionOp, SessionPlayer};
+    use crate::{
+        grid_canvas::GridCanvasData, testing::TestItem, utils::cassetta::TapeItem, CellTransform,
+        GridAction, GridIndex, GridItem,
+    };
+
+    fn encode(item: &TestItem) -> String {
+        item.get_short_text()
+    }
+
+    fn decode(text: &str) -> Option<TestItem> {
+        match text {
+            "W" => Some(TestItem::Wall),
+            "<big pin>" => Some(TestItem::Pin),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let events = vec![
+            SessionEvent::SetAction(GridAction::Route),
+            SessionEvent::Add(GridIndex::new(-2, 3), TestItem::Pin),
+            SessionEvent::Remove(GridIndex::new(0, 0)),
+            SessionEvent::Move(GridIndex::new(1, 2), GridIndex::new(3, 4)),
+            SessionEvent::Route(
+                vec![GridIndex::new(0, 0), GridIndex::new(0, 1)],
+                TestItem::Wall,
+            ),
+            SessionEvent::Fill(GridIndex::new(5, 5), TestItem::Wall, 100),
+            SessionEvent::SelectSame(GridIndex::new(1, 1)),
+            SessionEvent::Transform(CellTransform::RotateCounterClockwise),
+            SessionEvent::EndStroke,
+            SessionEvent::CancelStroke,
+            SessionEvent::Seek(7),
+            SessionEvent::SwitchBranch(1),
+            SessionEvent::Apply(vec![
+                SessionOp::Put(GridIndex::new(0, 1), TestItem::Pin),
+                SessionOp::Take(GridIndex::new(2, 2)),
+                SessionOp::Shift(GridIndex::new(0, 0), GridIndex::new(-1, -1)),
+            ]),
+            SessionEvent::Clear,
+            SessionEvent::Queue(vec![
+                vec![SessionOp::Put(GridIndex::new(0, 0), TestItem::Wall)],
+                vec![
+                    SessionOp::Take(GridIndex::new(1, 1)),
+                    SessionOp::Put(GridIndex::new(1, 1), TestItem::Pin),
+                ],
+            ]),
+            SessionEvent::Remote(vec![SessionOp::Take(GridIndex::new(4, 4))]),
+            SessionEvent::SetExtent(Some(GridIndex::new(10, 20))),
+            SessionEvent::SetExtent(None),
+            SessionEvent::SetReadOnly(true),
+        ];
+        let session = Session {
+            entries: events
+                .into_iter()
+                .enumerate()
+                .map(|(time, event)| SessionEntry {
+                    time: time as u64 * 40,
+                    event,
+                })
+                .collect(),
+        };
+
+        let text = session.to_text(encode);
+        assert!(text.contains("\n40 add -2 3 <big pin>\n"));
+        assert!(text.contains(" apply 3\nput 0 1 <big pin>\ntake 2 2\nshift 0 0 -1 -1\n"));
+        assert!(text.contains(" queue 2\nstep 1\nput 0 0 W\nstep 2\ntake 1 1\nput 1 1 <big pin>\n"));
+        assert_eq!(Session::from_text(&text, decode), Ok(session));
+
+        assert_eq!(
+            Session::from_text("\n# comment\n10 jump 1 2", decode),
+            Err(String::from("Line 3: unknown event \"jump\""))
+        );
+        assert_eq!(
+            Session::from_text("10 add 1", decode),
+            Err(String::from("Line 1: missing column"))
+        );
+        assert_eq!(
+            Session::<TestItem>::from_text("10 end now", decode),
+            Err(String::from("Line 1: unexpected \"now\""))
+        );
+        assert_eq!(
+            Session::from_text("10 apply 2\nput 0 0 W", decode),
+            Err(String::from("Line 1: missing change"))
+        );
+        assert_eq!(
+            Session::from_text("10 apply 1\nswap 0 0", decode),
+            Err(String::from("Line 2: unknown change \"swap\""))
+        );
+        assert_eq!(
+            Session::from_text("10 queue 1\nput 0 0 W", decode),
+            Err(String::from("Line 2: expected a step"))
+        );
+        assert_eq!(
+            Session::<TestItem>::from_text("10 read-only maybe", decode),
+            Err(String::from("Line 1: invalid state \"maybe\""))
+        );
+    }
+
+    #[test]
+    fn replay_reproduces_batches() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.start_session();
+        data.add_node(&GridIndex::new(0, 0), TestItem::Wall)
+            .unwrap();
+        let report = data.apply_batch(Vector::from(vec![
+            TapeItem::Add(GridIndex::new(0, 1), TestItem::Pin, None),
+            TapeItem::Move(GridIndex::new(0, 0), GridIndex::new(1, 0), TestItem::Wall),
+            TapeItem::Remove(GridIndex::new(5, 5), TestItem::Wall),
+        ]));
+        assert_eq!(report.applied, 2);
+
+        let session = data.stop_session().unwrap();
+        let session = Session::from_text(&session.to_text(encode), decode).unwrap();
+        let mut replayed = GridCanvasData::new(TestItem::Wall);
+        session.replay(&mut replayed).unwrap();
+        assert!(replayed.grid == data.grid);
+        assert_eq!(replayed.save_data.position(), data.save_data.position());
+
+        // Changes that no longer fit the grid are not applied at all
+        let mut diverged = GridCanvasData::new(TestItem::Wall);
+        let apply = SessionEvent::Apply(vec![
+            SessionOp::Put(GridIndex::new(0, 0), TestItem::Wall),
+            SessionOp::Take(GridIndex::new(3, 3)),
+        ]);
+        assert_eq!(
+            diverged.apply_session_event(&apply),
+            Err(String::from("Cell (3, 3) is empty"))
+        );
+        assert!(diverged.grid.is_empty());
+        assert_eq!(diverged.save_data.position(), 0);
+    }
+
+    #[test]
+    fn every_change_reaches_the_session() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.start_session();
+        data.set_extent(Some(GridIndex::new(8, 8)));
+        data.add_node_perimeter(GridIndex::new(0, 0), 3, 3, TestItem::Wall);
+        data.add_layout(&[(5, 5), (6, 5)], GridIndex::new(0, 0), TestItem::Pin);
+        data.move_node(&GridIndex::new(5, 5), &GridIndex::new(6, 6))
+            .unwrap();
+        data.edit_item(GridIndex::new(0, 0), TestItem::Pin).unwrap();
+        data.commit_route(
+            &[GridIndex::new(4, 0), GridIndex::new(4, 1)],
+            TestItem::Wall,
+        )
+        .unwrap();
+        data.clear_only([TestItem::Pin].into_iter().collect());
+        let mut other = data.clone();
+        other
+            .add_node(&GridIndex::new(7, 7), TestItem::Pin)
+            .unwrap();
+        data.apply_diff(data.diff(&other)).unwrap();
+        data.apply_remote(TapeItem::Add(GridIndex::new(7, 0), TestItem::Wall, None))
+            .unwrap();
+        data.from_csv("row,col,item\n2,5,W\n", ',', decode).unwrap();
+        data.submit_to_stack(Vector::unit(TapeItem::Add(
+            GridIndex::new(3, 3),
+            TestItem::Pin,
+            None,
+        )));
+        data.seek(data.save_data.position() + 1);
+        data.set_read_only(true);
+        data.set_extent(None);
+
+        let session = data.stop_session().unwrap();
+        let session = Session::from_text(&session.to_text(encode), decode).unwrap();
+        let mut replayed = GridCanvasData::new(TestItem::Wall);
+        session.replay(&mut replayed).unwrap();
+        assert!(replayed.grid == data.grid);
+        // Operations recorded twice would have added undo steps
+        assert_eq!(replayed.save_data.position(), data.save_data.position());
+        assert_eq!(replayed.extent(), None);
+        assert!(replayed.is_read_only());
+    }
+
+    #[test]
+    fn replay_reproduces_the_grid() {
+        let mut data = GridCanvasData::new(TestItem::Wall);
+        data.set_extent(Some(GridIndex::new(4, 4)));
+        data.start_session();
+        assert!(data.is_recording_session());
+
+        for col in 0..4 {
+            data.apply_session_event(&SessionEvent::Add(GridIndex::new(1, col), TestItem::Wall))
+                .unwrap();
+        }
+        data.end_stroke();
+        data.apply_session_event(&SessionEvent::Remove(GridIndex::new(1, 3)))
+            .unwrap();
+        data.cancel_stroke();
+        data.apply_session_event(&SessionEvent::Add(GridIndex::new(3, 0), TestItem::Pin))
+            .unwrap();
+        data.end_stroke();
+        data.fill_area(GridIndex::new(0, 0), TestItem::Pin, 16)
+            .unwrap();
+        data.select_same(&GridIndex::new(3, 0));
+        data.seek(2);
+
+        let session = data.stop_session().unwrap();
+        assert!(!data.is_recording_session());
+        let session = Session::from_text(&session.to_text(encode), decode).unwrap();
+
+        let mut replayed = GridCanvasData::new(TestItem::Wall);
+        replayed.set_extent(Some(GridIndex::new(4, 4)));
+        session.replay(&mut replayed).unwrap();
+        assert!(replayed.grid == data.grid);
+        assert_eq!(replayed.selected_cells, data.selected_cells);
+        assert_eq!(replayed.save_data.position(), data.save_data.position());
+
+        let mut paced = GridCanvasData::new(TestItem::Wall);
+        paced.set_extent(Some(GridIndex::new(4, 4)));
+        let mut player = SessionPlayer::new(session);
+        assert_eq!(player.advance_to(&mut paced, u64::MAX), Ok(true));
+        assert!(player.is_finished());
+        assert!(paced.grid == data.grid);
+    }
+}
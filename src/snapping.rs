@@ -3,32 +3,303 @@
 /// Imports
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::f64::consts::TAU;
+
 use druid::kurbo::Circle;
 use druid::widget::Painter;
-use druid::{theme, Data, Lens, Point, Rect, RenderContext, Size};
-use druid_color_thesaurus::gray;
+use druid::{Affine, Color, Data, Env, Lens, PaintCtx, Point, Rect, RenderContext, Size, Vec2};
 
-use crate::panning::{PanData, PanDataAccess};
-use crate::zooming::{ZoomData, ZoomDataAccess};
+use crate::panning::{guard_offset, PanData, PanDataAccess};
+use crate::rotation::{view_rotation, RotationDataAccess};
+use crate::theme;
+use crate::zooming::ZoomDataAccess;
+use crate::{CellSide, EdgeIndex, GridIndex};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// ViewTransform
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+// Smallest zoom scale positions are divided by, so a zoom scale of 0 does not give infinite
+// positions on the grid
+const MIN_VIEW_SCALE: f64 = 1e-9;
+
+/// Pan offset, zoom scale and rotation of a view onto the grid. The world is the unzoomed grid,
+/// where cell (row, col) starts at (col, row) times the cell size. It is scaled, rotated around
+/// the grid origin and moved to the offset to get the screen, the coordinates of the widget
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+pub struct ViewTransform {
+    pub offset: Point,
+    pub scale: f64,
+    pub rotation: f64,
+}
+
+impl ViewTransform {
+    pub const IDENTITY: ViewTransform = ViewTransform {
+        offset: Point::ZERO,
+        scale: 1.0,
+        rotation: 0.0,
+    };
+
+    pub fn new(offset: Point, scale: f64, rotation: f64) -> Self {
+        Self {
+            offset,
+            scale,
+            rotation,
+        }
+    }
+
+    /// Maps the world to the screen, the transform to paint the grid with
+    pub fn affine(&self) -> Affine {
+        Affine::translate(self.offset.to_vec2())
+            * Affine::rotate(self.rotation)
+            * Affine::scale(self.scale)
+    }
+
+    /// Maps positions computed from the pan offset and zoom scale alone to the screen, see
+    /// [`view_rotation`]
+    pub fn rotation_affine(&self) -> Affine {
+        view_rotation(self.offset, self.rotation)
+    }
+
+    pub fn to_screen(&self, world: Point) -> Point {
+        self.affine() * world
+    }
+
+    pub fn to_world(&self, screen: Point) -> Point {
+        let unrotated = self.rotation_affine().inverse() * screen;
+        ((unrotated - self.offset) / self.scale.max(MIN_VIEW_SCALE)).to_point()
+    }
+
+    /// Part of the world shown in an area of the screen, or its bounding box while the view is
+    /// rotated
+    pub fn to_world_rect(&self, screen: Rect) -> Rect {
+        map_corners(screen, |corner| self.to_world(corner))
+    }
+
+    /// Area of the screen covering an area of the world, or its bounding box while the view is
+    /// rotated
+    pub fn to_screen_rect(&self, world: Rect) -> Rect {
+        map_corners(world, |corner| self.to_screen(corner))
+    }
+
+    /// The same view upright, for positions that are rotated only when they are painted
+    pub fn unrotated(&self) -> ViewTransform {
+        ViewTransform {
+            rotation: 0.0,
+            ..*self
+        }
+    }
+}
+
+// Bounding box of the corners of `rect` mapped by `map`
+fn map_corners(rect: Rect, map: impl Fn(Point) -> Point) -> Rect {
+    let corners = [
+        Point::new(rect.x0, rect.y0),
+        Point::new(rect.x1, rect.y0),
+        Point::new(rect.x0, rect.y1),
+        Point::new(rect.x1, rect.y1),
+    ]
+    .map(map);
+    corners
+        .iter()
+        .skip(1)
+        .fold(Rect::from_points(corners[0], corners[0]), |rect, corner| {
+            rect.union_pt(*corner)
+        })
+}
+
+impl Default for ViewTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 ///
 /// GridSnapData
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////////
-pub trait GridSnapDataAccess: PanDataAccess + ZoomDataAccess {
+pub trait GridSnapDataAccess: PanDataAccess + ZoomDataAccess + RotationDataAccess {
     fn get_cell_size(&self) -> f64;
     fn set_cell_size(&mut self, size: f64);
     fn get_grid_visibility(&self) -> bool;
     fn set_grid_visibility(&mut self, state: bool);
     fn move_to_grid_position(&self, desired_position: Point) -> Point;
+    fn get_snap_enabled(&self) -> bool;
+    fn set_snap_enabled(&mut self, state: bool);
+    fn get_angle_snap(&self) -> Option<f64>;
+    fn set_angle_snap(&mut self, step: Option<f64>);
+    fn get_coordinates(&self) -> CoordinateSystem;
+    fn set_coordinates(&mut self, coordinates: CoordinateSystem);
+
+    /// Pan offset, zoom scale and rotation of the view
+    fn get_view(&self) -> ViewTransform {
+        ViewTransform::new(
+            self.get_offset(),
+            self.get_zoom_scale(),
+            self.get_rotation(),
+        )
+    }
+
+    /// Sets the cell size and moves the pan offset so the point of the grid drawn at `anchor`,
+    /// a position of the widget, stays there. Sizes that are not finite and positive are
+    /// ignored
+    fn rescale_cells(&mut self, size: f64, anchor: Point) {
+        let old_size = self.get_cell_size();
+        if !size.is_finite() || size <= 0.0 || size == old_size {
+            return;
+        }
+        // The rotation of the view turns around the offset, so it moves along with it
+        let ratio = if old_size.is_finite() && old_size > 0.0 {
+            size / old_size
+        } else {
+            1.0
+        };
+        let offset = anchor + (self.get_offset() - anchor) * ratio;
+        self.set_cell_size(size);
+        self.set_offset(offset);
+    }
+}
+
+// Fraction of a cell the pointer has to move past the edge of the current cell before
+// hysteresis snapping picks a neighbour
+const SNAP_HYSTERESIS: f64 = 0.25;
+/// Largest row or column a position of the widget maps to. Cell positions up to here are exact
+/// in an f64 for any sensible cell size and zoom, and indices can be offset from it without
+/// overflowing
+pub const MAX_GRID_COORDINATE: isize = 1 << 40;
+// Fraction of a cell on either side of an edge where the pointer picks the edge
+const EDGE_HIT_DISTANCE: f64 = 0.25;
+// Smallest cell size positions are divided by, so a cell size of 0 does not give infinite
+// indices
+const MIN_CELL_SIZE: f64 = 1e-6;
+// Width in pixels of the axes through the origin
+const ORIGIN_AXIS_WIDTH: f64 = 2.0;
+const ORIGIN_RADIUS: f64 = 5.0;
+
+/// How a pointer position is turned into a grid index
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug)]
+pub enum SnapRounding {
+    /// Cell under the pointer
+    Floor,
+    /// Cell under the pointer, while positions snap to the grid intersection nearest to them,
+    /// for children that sit on the grid lines
+    Nearest,
+    /// Cell under the pointer, but the previous cell is kept until the pointer is well past its
+    /// edge so drags along a boundary do not flicker between two cells
+    Hysteresis,
+}
+
+/// Direction the rows count in, as shown to the user
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug, Default)]
+pub enum YAxis {
+    /// Rows count down the screen, as in images and spreadsheets
+    #[default]
+    Down,
+    /// Rows count up the screen, as in plots and math tools
+    Up,
+}
+
+/// Order of the two numbers of a coordinate shown to the user
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug, Default)]
+pub enum AxisOrder {
+    /// (row, col)
+    #[default]
+    RowMajor,
+    /// (x, y), i.e. (col, row)
+    ColumnMajor,
+}
+
+/// Coordinates the user sees for the cells of the grid, on the headers and in CSV. Items are
+/// always stored by their [`GridIndex`], this only changes how indices are shown and read.
+/// Sessions and undo tapes keep the indices, so they replay the same under any coordinates
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug)]
+pub struct CoordinateSystem {
+    // Cell shown as (0, 0)
+    pub origin: GridIndex,
+    pub y_axis: YAxis,
+    pub order: AxisOrder,
+}
+
+impl Default for CoordinateSystem {
+    fn default() -> Self {
+        Self {
+            origin: GridIndex::new(0, 0),
+            y_axis: YAxis::Down,
+            order: AxisOrder::RowMajor,
+        }
+    }
+}
+
+impl CoordinateSystem {
+    /// Coordinates of plots and math tools, (x, y) with y counting up from `origin`
+    pub fn math(origin: GridIndex) -> Self {
+        Self {
+            origin,
+            y_axis: YAxis::Up,
+            order: AxisOrder::ColumnMajor,
+        }
+    }
+
+    /// Row shown to the user for a row of the grid
+    pub fn user_row(&self, row: isize) -> isize {
+        let row = row.saturating_sub(self.origin.row);
+        match self.y_axis {
+            YAxis::Down => row,
+            YAxis::Up => row.saturating_neg(),
+        }
+    }
+
+    /// Column shown to the user for a column of the grid
+    pub fn user_col(&self, col: isize) -> isize {
+        col.saturating_sub(self.origin.col)
+    }
+
+    /// Coordinate shown to the user for a cell, in the order of the axes
+    pub fn to_user(&self, index: GridIndex) -> (isize, isize) {
+        let (row, col) = (self.user_row(index.row), self.user_col(index.col));
+        match self.order {
+            AxisOrder::RowMajor => (row, col),
+            AxisOrder::ColumnMajor => (col, row),
+        }
+    }
+
+    /// Cell of a coordinate given by the user, the inverse of [`CoordinateSystem::to_user`]
+    pub fn from_user(&self, coordinate: (isize, isize)) -> GridIndex {
+        let (row, col) = match self.order {
+            AxisOrder::RowMajor => coordinate,
+            AxisOrder::ColumnMajor => (coordinate.1, coordinate.0),
+        };
+        let row = match self.y_axis {
+            YAxis::Down => row,
+            YAxis::Up => row.saturating_neg(),
+        };
+        GridIndex::new(
+            row.saturating_add(self.origin.row),
+            col.saturating_add(self.origin.col),
+        )
+    }
 }
 
 #[derive(Clone, Data, Lens, PartialEq, Debug)]
 pub struct GridSnapData {
     pub cell_size: f64,
+    pub rounding: SnapRounding,
+    // Steps per cell along each axis that positions snap to, 1 for the cell corners only and 2
+    // for half cells
+    pub snap_divisions: usize,
+    // Positions are left as they are while off, so canvas children can be placed freely. Grid
+    // items still take up whole cells
+    pub snap_enabled: bool,
+    // Keeps drags of the editing tools on lines at multiples of this many degrees from where
+    // they started, e.g. 45.0 for wires. Needs snapping to be on
+    pub angle_snap: Option<f64>,
     pub grid_visibility: bool,
-    pub zoom_data: ZoomData,
+    // Origin and axes of the coordinates shown to the user
+    pub coordinates: CoordinateSystem,
+    // Pan offset, zoom scale and rotation the grid is shown with
+    pub view: ViewTransform,
     pub pan_data: PanData,
 }
 
@@ -36,39 +307,147 @@ impl GridSnapData {
     pub fn new(cell_size: f64) -> Self {
         Self {
             cell_size,
+            rounding: SnapRounding::Floor,
+            snap_divisions: 1,
+            snap_enabled: true,
+            angle_snap: None,
             grid_visibility: true,
-            zoom_data: ZoomData::new(),
+            coordinates: CoordinateSystem::default(),
+            view: ViewTransform::IDENTITY,
             pan_data: PanData::new(),
         }
     }
+
+    /// Maps positions computed from the pan offset and zoom scale to the widget, see
+    /// [`ViewTransform::rotation_affine`]
+    pub fn rotation_transform(&self) -> Affine {
+        self.view.rotation_affine()
+    }
+
+    /// Size of a cell on the widget
+    pub fn scaled_cell_size(&self) -> f64 {
+        self.cell_size * self.view.scale
+    }
+
+    /// Top left and bottom right (row, col) of the cells a view of `size` can show
+    pub fn get_visible_indices(&self, size: Size) -> ((isize, isize), (isize, isize)) {
+        // The corners of the view land anywhere on the grid once it is rotated
+        let view = size.to_rect();
+        let corners = [
+            Point::new(view.x0, view.y0),
+            Point::new(view.x1, view.y0),
+            Point::new(view.x0, view.y1),
+            Point::new(view.x1, view.y1),
+        ]
+        .map(|corner| self.get_grid_index(corner));
+        corners.iter().skip(1).fold(
+            (corners[0], corners[0]),
+            |((top, left), (bottom, right)), &(row, col)| {
+                (
+                    (top.min(row), left.min(col)),
+                    (bottom.max(row), right.max(col)),
+                )
+            },
+        )
+    }
+
     pub fn move_to_grid_position_2(&self, desired_position: Point) -> Point {
-        let (row, col) = self.get_grid_index(desired_position);
-        self.get_opt_grid_position(row, col)
+        if !self.snap_enabled {
+            return desired_position;
+        }
+        // Positions are snapped before the rotation of the view, like the cells
+        let view = self.view.unrotated();
+        let step = self.cell_size / self.snap_divisions.max(1) as f64;
+        let snap = |value: f64| match self.rounding {
+            SnapRounding::Nearest => (value / step).round() * step,
+            SnapRounding::Floor | SnapRounding::Hysteresis => (value / step).floor() * step,
+        };
+        let world = view.to_world(desired_position);
+        view.to_screen(Point::new(snap(world.x), snap(world.y)))
     }
 
     pub fn get_grid_index(&self, position: Point) -> (isize, isize) {
-        // Normalise translation offset
-        let mut position_norm = position;
-        position_norm.x -= self.pan_data.offset.x;
-        position_norm.y -= self.pan_data.offset.y;
+        let cells = self.to_cells(position);
+        (
+            to_grid_coordinate(cells.y.floor()),
+            to_grid_coordinate(cells.x.floor()),
+        )
+    }
+
+    /// Edge closest to a position of the widget, or None if the position is further than a
+    /// quarter of a cell from every edge
+    pub fn get_edge_index(&self, position: Point) -> Option<EdgeIndex> {
+        let Point { x: col, y: row } = self.to_cells(position);
+        let cell = GridIndex::new(
+            to_grid_coordinate(row.floor()),
+            to_grid_coordinate(col.floor()),
+        );
+        let (row, col) = (row - row.floor(), col - col.floor());
+        [
+            (row, CellSide::Top),
+            (1.0 - col, CellSide::Right),
+            (1.0 - row, CellSide::Bottom),
+            (col, CellSide::Left),
+        ]
+        .into_iter()
+        .filter(|(distance, _)| *distance <= EDGE_HIT_DISTANCE)
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, side)| EdgeIndex::new(cell, side))
+    }
 
-        let scaled_cell_size = self.cell_size * self.zoom_data.zoom_scale;
+    /// Grid index of the cell holding a pointer position according to the rounding mode.
+    /// `previous` is the index picked for the last pointer position and is only used by
+    /// hysteresis.
+    pub fn snap_grid_index(
+        &self,
+        position: Point,
+        previous: Option<(isize, isize)>,
+    ) -> (isize, isize) {
+        let Point { x: col, y: row } = self.to_cells(position);
 
-        let row = (position_norm.y / scaled_cell_size).floor() as isize;
-        let col = (position_norm.x / scaled_cell_size).floor() as isize;
+        match (self.rounding, previous) {
+            (SnapRounding::Hysteresis, Some((previous_row, previous_col)))
+                if within_hysteresis(row, previous_row) && within_hysteresis(col, previous_col) =>
+            {
+                (previous_row, previous_col)
+            }
+            _ => (
+                to_grid_coordinate(row.floor()),
+                to_grid_coordinate(col.floor()),
+            ),
+        }
+    }
 
-        (row, col)
+    // Position of a point of the widget on the grid, in cells
+    fn to_cells(&self, position: Point) -> Point {
+        let world = self.view.to_world(position);
+        (world.to_vec2() / self.cell_size.max(MIN_CELL_SIZE)).to_point()
     }
 
     pub fn get_opt_grid_position(&self, row: isize, col: isize) -> Point {
-        let scaled_cell_size = self.cell_size * self.zoom_data.zoom_scale;
+        self.view
+            .unrotated()
+            .to_screen(self.get_grid_position(row, col))
+    }
 
-        Point {
-            x: col as f64 * scaled_cell_size + self.pan_data.offset.x,
-            y: row as f64 * scaled_cell_size + self.pan_data.offset.y,
-        }
+    /// Area of the widget covered by a cell, after zoom and pan are applied but before the
+    /// rotation of the view
+    pub fn get_cell_rect(&self, row: isize, col: isize) -> Rect {
+        let scaled_cell_size = self.scaled_cell_size();
+        Rect::from_origin_size(
+            self.get_opt_grid_position(row, col),
+            Size::new(scaled_cell_size, scaled_cell_size),
+        )
+    }
+
+    /// Area of the widget covered by a cell, or its bounding box while the view is rotated
+    pub fn get_screen_cell_rect(&self, row: isize, col: isize) -> Rect {
+        let size = Size::new(self.cell_size, self.cell_size);
+        let world = Rect::from_origin_size(self.get_grid_position(row, col), size);
+        self.view.to_screen_rect(world)
     }
 
+    /// Position of a cell on the grid before the view is applied, its world position
     pub fn get_grid_position(&self, row: isize, col: isize) -> Point {
         Point {
             x: col as f64 * self.cell_size,
@@ -77,6 +456,75 @@ impl GridSnapData {
     }
 }
 
+// First and last row and column lines crossing `area`, given in the coordinates of the widget
+// before the rotation of the view
+fn visible_lines(
+    area: Rect,
+    offset: Point,
+    scaled_cell_size: f64,
+) -> ((isize, isize), (isize, isize)) {
+    let first = |start: f64, offset: f64| ((start - offset) / scaled_cell_size).floor() as isize;
+    let last = |end: f64, offset: f64| ((end - offset) / scaled_cell_size).ceil() as isize;
+    (
+        (first(area.y0, offset.y), first(area.x0, offset.x)),
+        (last(area.y1, offset.y), last(area.x1, offset.x)),
+    )
+}
+
+// Row or column of a position already divided by the cell size. NaN, e.g. from a position at
+// infinity on an infinite offset, maps to 0
+fn to_grid_coordinate(value: f64) -> isize {
+    if value.is_nan() {
+        0
+    } else {
+        value.clamp(-MAX_GRID_COORDINATE as f64, MAX_GRID_COORDINATE as f64) as isize
+    }
+}
+
+fn within_hysteresis(position: f64, index: isize) -> bool {
+    let start = index as f64 - SNAP_HYSTERESIS;
+    (start..start + 1.0 + 2.0 * SNAP_HYSTERESIS).contains(&position)
+}
+
+// Axes through the origin of the user coordinates across `area`, given in the coordinates of
+// the widget before the rotation of the view. They meet at the corner of the origin cell that
+// is (0, 0) when the cell spans one unit along each axis
+fn paint_origin_axes(
+    ctx: &mut PaintCtx,
+    area: Rect,
+    offset: Point,
+    scaled_cell_size: f64,
+    coordinates: CoordinateSystem,
+    env: &Env,
+) {
+    let origin = coordinates.origin;
+    let row = match coordinates.y_axis {
+        YAxis::Down => origin.row,
+        YAxis::Up => origin.row.saturating_add(1),
+    };
+    let center = Point::new(
+        offset.x + scaled_cell_size * origin.col as f64,
+        offset.y + scaled_cell_size * row as f64,
+    );
+    let half_width = ORIGIN_AXIS_WIDTH / 2.0;
+    let x_axis = Rect::new(
+        area.x0,
+        center.y - half_width,
+        area.x1,
+        center.y + half_width,
+    );
+    let y_axis = Rect::new(
+        center.x - half_width,
+        area.y0,
+        center.x + half_width,
+        area.y1,
+    );
+    let x_color = theme::color(env, &theme::GRID_X_AXIS);
+    ctx.fill(x_axis, &x_color);
+    ctx.fill(y_axis, &theme::color(env, &theme::GRID_Y_AXIS));
+    ctx.fill(Circle::new(center, ORIGIN_RADIUS), &x_color);
+}
+
 impl GridSnapDataAccess for GridSnapData {
     fn get_cell_size(&self) -> f64 {
         self.cell_size
@@ -97,25 +545,79 @@ impl GridSnapDataAccess for GridSnapData {
     fn move_to_grid_position(&self, desired_position: Point) -> Point {
         self.move_to_grid_position_2(desired_position)
     }
+
+    fn get_snap_enabled(&self) -> bool {
+        self.snap_enabled
+    }
+
+    fn set_snap_enabled(&mut self, state: bool) {
+        self.snap_enabled = state;
+    }
+
+    fn get_angle_snap(&self) -> Option<f64> {
+        self.angle_snap
+    }
+
+    fn set_angle_snap(&mut self, step: Option<f64>) {
+        self.angle_snap = step;
+    }
+
+    fn get_coordinates(&self) -> CoordinateSystem {
+        self.coordinates
+    }
+
+    fn set_coordinates(&mut self, coordinates: CoordinateSystem) {
+        self.coordinates = coordinates;
+    }
+
+    fn get_view(&self) -> ViewTransform {
+        self.view
+    }
 }
 
 impl ZoomDataAccess for GridSnapData {
     fn get_zoom_scale(&self) -> f64 {
-        self.zoom_data.zoom_scale
+        self.view.scale
     }
 
     fn set_zoom_scale(&mut self, scale: f64) {
-        self.zoom_data.zoom_scale = scale;
+        self.view.scale = scale;
+    }
+}
+
+impl RotationDataAccess for GridSnapData {
+    fn get_rotation(&self) -> f64 {
+        self.view.rotation
+    }
+
+    fn set_rotation(&mut self, rotation: f64) {
+        self.view.rotation = rotation.rem_euclid(TAU);
     }
 }
 
 impl PanDataAccess for GridSnapData {
     fn get_offset(&self) -> Point {
-        self.pan_data.offset
+        self.view.offset
     }
 
     fn set_offset(&mut self, offset: Point) {
-        self.pan_data.offset = offset;
+        self.view.offset = guard_offset(offset, self.view.offset);
+    }
+
+    fn get_drag_delta(&self) -> Vec2 {
+        self.pan_data.drag_delta
+    }
+
+    fn set_drag_delta(&mut self, delta: Vec2) {
+        self.pan_data.set_drag_delta(delta);
+    }
+
+    fn get_overscroll(&self) -> Vec2 {
+        self.pan_data.overscroll
+    }
+
+    fn set_overscroll(&mut self, overscroll: Vec2) {
+        self.pan_data.set_overscroll(overscroll);
     }
 }
 
@@ -124,10 +626,106 @@ impl PanDataAccess for GridSnapData {
 /// GridSnapPainter
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////////
+// Major lines and dots are this many times wider than minor ones
+const MAJOR_LINE_SCALE: f64 = 2.0;
+// Spaces in pixels between lines at which the lines fade out as the view zooms out, the cell
+// size for minor lines and the major interval times the cell size for major lines
+const LINE_FADE_START: f64 = 12.0;
+const LINE_FADE_END: f64 = 4.0;
+// How far minor and major lines derived from the window background are moved towards black or
+// white
+const MINOR_LINE_CONTRAST: f64 = 0.25;
+const MAJOR_LINE_CONTRAST: f64 = 0.5;
+// How far tinted cells of a patterned background are moved towards the line color
+const BACKGROUND_TINT: f64 = 0.12;
+
+/// Pattern of tinted cells painted over the background, to help follow rows and columns across
+/// large grids
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug, Default)]
+pub enum BackgroundStyle {
+    #[default]
+    Plain,
+    /// Cells alternate like a chess board
+    Checkerboard,
+    /// Every other row is tinted
+    RowBands,
+    /// Every other column is tinted
+    ColumnBands,
+}
+
+impl BackgroundStyle {
+    /// Whether the cell is painted with the tint
+    pub fn is_tinted(&self, row: isize, col: isize) -> bool {
+        match self {
+            BackgroundStyle::Plain => false,
+            BackgroundStyle::Checkerboard => (row + col).rem_euclid(2) == 1,
+            BackgroundStyle::RowBands => row.rem_euclid(2) == 1,
+            BackgroundStyle::ColumnBands => col.rem_euclid(2) == 1,
+        }
+    }
+}
+
+// Tinted cells of a background style across `area`, given in the coordinates of the widget
+// before the rotation of the view. Bands are painted whole
+fn paint_background_style(
+    ctx: &mut PaintCtx,
+    style: BackgroundStyle,
+    area: Rect,
+    offset: Point,
+    scaled_cell_size: f64,
+    tint: Color,
+) {
+    let ((from_row, from_col), (to_row, to_col)) = visible_lines(area, offset, scaled_cell_size);
+    let row_span = |row: isize| {
+        let y = offset.y + scaled_cell_size * row as f64;
+        (y, y + scaled_cell_size)
+    };
+    let col_span = |col: isize| {
+        let x = offset.x + scaled_cell_size * col as f64;
+        (x, x + scaled_cell_size)
+    };
+    match style {
+        BackgroundStyle::Plain => {}
+        BackgroundStyle::RowBands => {
+            for row in (from_row..=to_row).filter(|row| style.is_tinted(*row, 0)) {
+                let (y0, y1) = row_span(row);
+                ctx.fill(Rect::new(area.x0, y0, area.x1, y1), &tint);
+            }
+        }
+        BackgroundStyle::ColumnBands => {
+            for col in (from_col..=to_col).filter(|col| style.is_tinted(0, *col)) {
+                let (x0, x1) = col_span(col);
+                ctx.fill(Rect::new(x0, area.y0, x1, area.y1), &tint);
+            }
+        }
+        BackgroundStyle::Checkerboard => {
+            for row in from_row..=to_row {
+                let (y0, y1) = row_span(row);
+                for col in (from_col..=to_col).filter(|col| style.is_tinted(row, *col)) {
+                    let (x0, x1) = col_span(col);
+                    ctx.fill(Rect::new(x0, y0, x1, y1), &tint);
+                }
+            }
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct GridSnapPainter {
     show_origin: bool,
     debug_offset: bool,
+    // Rows and columns between major lines, 0 to draw only minor lines
+    major_interval: usize,
+    background_style: BackgroundStyle,
+    // Override the GRID_BACKGROUND, GRID_LINE and GRID_MAJOR_LINE env keys
+    background: Option<Color>,
+    line_color: Option<Color>,
+    major_line_color: Option<Color>,
+    // Colors that are not overridden follow the window background instead of the env keys
+    from_window: bool,
+    // Painting the dot grid, which reads the DOT_GRID_* keys instead of GRID_BACKGROUND and
+    // GRID_LINE
+    dots: bool,
 }
 
 impl Default for GridSnapPainter {
@@ -135,18 +733,216 @@ impl Default for GridSnapPainter {
         Self {
             show_origin: true,
             debug_offset: true,
+            major_interval: 10,
+            background_style: BackgroundStyle::Plain,
+            background: None,
+            line_color: None,
+            major_line_color: None,
+            from_window: false,
+            dots: false,
         }
     }
 }
 
+// `amount` of the way from one color to another, keeping the opacity of the first
+fn mix(from: Color, to: Color, amount: f64) -> Color {
+    let (r0, g0, b0, a) = from.as_rgba();
+    let (r1, g1, b1, _) = to.as_rgba();
+    let lerp = |start: f64, end: f64| start + (end - start) * amount;
+    Color::rgba(lerp(r0, r1), lerp(g0, g1), lerp(b0, b1), a)
+}
+
+fn is_dark(color: Color) -> bool {
+    let (r, g, b, _) = color.as_rgba();
+    0.2126 * r + 0.7152 * g + 0.0722 * b < 0.5
+}
+
+// Minor and major line colors standing out from `background` as much as the default theme
+fn lines_for_background(background: Color) -> (Color, Color) {
+    let contrast = if is_dark(background) {
+        Color::WHITE
+    } else {
+        Color::BLACK
+    };
+    (
+        mix(background, contrast, MINOR_LINE_CONTRAST),
+        mix(background, contrast, MAJOR_LINE_CONTRAST),
+    )
+}
+
+// Opacity of lines `spacing` pixels apart, fading to nothing so that a zoomed out grid is not
+// covered in lines
+fn line_alpha(spacing: f64) -> f64 {
+    ((spacing - LINE_FADE_END) / (LINE_FADE_START - LINE_FADE_END)).clamp(0.0, 1.0)
+}
+
 impl GridSnapPainter {
+    /// Dark lines on a light background
+    pub fn light() -> Self {
+        Self::default()
+            .with_background(Color::rgb8(0xfa, 0xfa, 0xfa))
+            .with_line_color(Color::rgb8(0xd8, 0xd8, 0xd8))
+            .with_major_line_color(Color::rgb8(0x9e, 0x9e, 0x9e))
+    }
+
+    /// Light lines on a dark background, the colors of the default theme
+    pub fn dark() -> Self {
+        Self::default()
+            .with_background(theme::default_color(&theme::GRID_BACKGROUND))
+            .with_line_color(theme::default_color(&theme::GRID_LINE))
+            .with_major_line_color(theme::default_color(&theme::GRID_MAJOR_LINE))
+    }
+
+    /// Paints the background in the window background color of the env and derives the lines
+    /// from it, so the grid follows the app when it switches between light and dark themes.
+    /// Colors set with the builder methods still win
+    pub fn from_env() -> Self {
+        Self {
+            from_window: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_background(mut self, color: Color) -> Self {
+        self.background = Some(color);
+        self
+    }
+
+    pub fn with_background_style(mut self, style: BackgroundStyle) -> Self {
+        self.background_style = style;
+        self
+    }
+
+    /// Draws every `interval` rows and columns as a major line, 0 for no major lines
+    pub fn with_major_interval(mut self, interval: usize) -> Self {
+        self.major_interval = interval;
+        self
+    }
+
+    pub fn with_line_color(mut self, color: Color) -> Self {
+        self.line_color = Some(color);
+        self
+    }
+
+    pub fn with_major_line_color(mut self, color: Color) -> Self {
+        self.major_line_color = Some(color);
+        self
+    }
+
+    fn is_major(&self, index: isize) -> bool {
+        let interval = self.major_interval as isize;
+        interval > 0 && index.rem_euclid(interval) == 0
+    }
+
+    // Opacity of the minor and the major lines for a scaled cell size
+    fn line_alphas(&self, scaled_cell_size: f64) -> (f64, f64) {
+        let major = match self.major_interval {
+            0 => 0.0,
+            interval => line_alpha(scaled_cell_size * interval as f64),
+        };
+        (line_alpha(scaled_cell_size), major)
+    }
+
+    // Rows or columns between `from` and `to` that are drawn: every one while the minor lines
+    // show, then only the major ones until they fade out too
+    fn drawn_lines(
+        &self,
+        from: isize,
+        to: isize,
+        (minor_alpha, major_alpha): (f64, f64),
+    ) -> impl Iterator<Item = isize> {
+        let lines = if minor_alpha > 0.0 {
+            Some((from..=to).step_by(1))
+        } else if major_alpha > 0.0 {
+            let interval = self.major_interval as isize;
+            let first = from + (interval - from.rem_euclid(interval)) % interval;
+            Some((first..=to).step_by(self.major_interval))
+        } else {
+            None
+        };
+        lines.into_iter().flatten()
+    }
+
+    fn background(&self, env: &Env) -> Color {
+        self.background.unwrap_or_else(|| {
+            if self.from_window {
+                env.get(druid::theme::WINDOW_BACKGROUND_COLOR)
+            } else if self.dots {
+                theme::color(env, &theme::DOT_GRID_BACKGROUND)
+            } else {
+                theme::color(env, &theme::GRID_BACKGROUND)
+            }
+        })
+    }
+
+    // Colors of the minor and major lines
+    fn line_colors(&self, env: &Env) -> (Color, Color) {
+        let (minor, major) = if self.from_window {
+            lines_for_background(self.background(env))
+        } else if self.dots {
+            (
+                theme::dot_color(env),
+                theme::color(env, &theme::GRID_MAJOR_LINE),
+            )
+        } else {
+            (
+                theme::color(env, &theme::GRID_LINE),
+                theme::color(env, &theme::GRID_MAJOR_LINE),
+            )
+        };
+        (
+            self.line_color.unwrap_or(minor),
+            self.major_line_color.unwrap_or(major),
+        )
+    }
+
+    // Tinted cells of the background style, fading out with the minor lines
+    fn paint_background_pattern(
+        &self,
+        ctx: &mut PaintCtx,
+        env: &Env,
+        rotation: Affine,
+        area: Rect,
+        offset: Point,
+        scaled_cell_size: f64,
+    ) {
+        let alpha = line_alpha(scaled_cell_size);
+        if self.background_style == BackgroundStyle::Plain || alpha == 0.0 {
+            return;
+        }
+        let (line, _) = self.line_colors(env);
+        let tint = mix(self.background(env), line, BACKGROUND_TINT * alpha);
+        ctx.with_save(|ctx| {
+            ctx.transform(rotation);
+            let style = self.background_style;
+            paint_background_style(ctx, style, area, offset, scaled_cell_size, tint);
+        });
+    }
+
+    // Color and width of the line of a row or column, None for a line that has faded out
+    fn line_style(
+        &self,
+        index: isize,
+        line_width: f64,
+        (minor_alpha, major_alpha): (f64, f64),
+        (minor, major): (Color, Color),
+    ) -> Option<(Color, f64)> {
+        let (color, alpha, width) = if self.is_major(index) {
+            (major, major_alpha, line_width * MAJOR_LINE_SCALE)
+        } else {
+            (minor, minor_alpha, line_width)
+        };
+        (alpha > 0.0).then(|| (color.with_alpha(color.as_rgba().3 * alpha), width))
+    }
+
     pub fn square_grid<T: Data + GridSnapDataAccess>(&self) -> Painter<T> {
+        let painter = *self;
         let origin_visibility = self.show_origin;
         let debug_visibility = self.debug_offset;
 
-        Painter::new(move |ctx, data: &T, _env| {
-            // let scaled_cell_size = data.cell_size * data.zoom_data.zoom_scale;
-            let scaled_cell_size = data.get_cell_size() * data.get_zoom_scale();
+        Painter::new(move |ctx, data: &T, env| {
+            let view = data.get_view();
+            let scaled_cell_size = data.get_cell_size() * view.scale;
             let line_width = scaled_cell_size * 0.05;
 
             // Partial Paint Setup
@@ -156,70 +952,78 @@ impl GridSnapPainter {
 
             // Background Painting Logic
             let rect = screen_space.to_rect();
-            ctx.fill(rect, &gray::OUTER_SPACE);
+            ctx.fill(rect, &painter.background(env));
+
+            // Lines are drawn before the rotation of the view, across the part of the grid the
+            // damaged area shows
+            let offset = view.offset;
+            let rotation = view.rotation_affine();
+            let area = rotation.inverse().transform_rect_bbox(invalidation_rect);
+            painter.paint_background_pattern(ctx, env, rotation, area, offset, scaled_cell_size);
 
             // Axes Painting Logic
             if data.get_grid_visibility() {
-                let start_point = data.move_to_grid_position(invalidation_rect.origin());
-                let end_point = data.move_to_grid_position(Point {
-                    x: invalidation_rect.max_x(),
-                    y: invalidation_rect.max_y(),
-                });
+                let ((from_row, from_col), (to_row, to_col)) =
+                    visible_lines(area, offset, scaled_cell_size);
 
-                let from_row = (start_point.y / scaled_cell_size).floor() as usize;
-                let from_col = (start_point.x / scaled_cell_size).floor() as usize;
-
-                let to_row = (end_point.y / scaled_cell_size).ceil() as usize + 1;
-                let to_col = (end_point.x / scaled_cell_size).ceil() as usize + 1;
-
-                for row in from_row..=to_row {
-                    let mut from_point =
-                        Point::new(0.0, scaled_cell_size * row as f64 - line_width / 2.0);
-                    // Integrate translation data to line rendering
-                    // from_point.y += data.pan_data.absolute_offset.y % scaled_cell_size;
-                    from_point.y += data.get_offset().y % scaled_cell_size;
-                    let size = Size::new(ctx.size().width, line_width);
-                    let rect = Rect::from_origin_size(from_point, size);
-                    ctx.fill(rect, &gray::GAINSBORO)
-                }
+                let alphas = painter.line_alphas(scaled_cell_size);
+                let colors = painter.line_colors(env);
 
-                for col in from_col..=to_col {
-                    let mut from_point =
-                        Point::new(scaled_cell_size * col as f64 - line_width / 2.0, 0.0);
-                    // Integrate translation data to line rendering
-                    // from_point.x += data.pan_data.absolute_offset.x % scaled_cell_size;
-                    from_point.x += data.get_offset().x % scaled_cell_size;
-                    let size = Size::new(line_width, ctx.size().width);
-                    let rect = Rect::from_origin_size(from_point, size);
-                    ctx.fill(rect, &gray::GAINSBORO)
-                }
+                ctx.with_save(|ctx| {
+                    ctx.transform(rotation);
+                    for row in painter.drawn_lines(from_row, to_row, alphas) {
+                        let Some((color, width)) =
+                            painter.line_style(row, line_width, alphas, colors)
+                        else {
+                            continue;
+                        };
+                        let y = offset.y + scaled_cell_size * row as f64;
+                        let rect = Rect::new(area.x0, y - width / 2.0, area.x1, y + width / 2.0);
+                        ctx.fill(rect, &color)
+                    }
+
+                    for col in painter.drawn_lines(from_col, to_col, alphas) {
+                        let Some((color, width)) =
+                            painter.line_style(col, line_width, alphas, colors)
+                        else {
+                            continue;
+                        };
+                        let x = offset.x + scaled_cell_size * col as f64;
+                        let rect = Rect::new(x - width / 2.0, area.y0, x + width / 2.0, area.y1);
+                        ctx.fill(rect, &color)
+                    }
+                });
             }
 
             if origin_visibility {
-                // let center = Point::new(data.pan_data.absolute_offset.x, data.pan_data.absolute_offset.y);
-                let center = Point::new(data.get_offset().x, data.get_offset().y);
-                let circle = Circle::new(center, 5.0);
-                ctx.fill(circle, &druid_color_thesaurus::red::CARMINE);
+                let coordinates = data.get_coordinates();
+                ctx.with_save(|ctx| {
+                    ctx.transform(rotation);
+                    paint_origin_axes(ctx, area, offset, scaled_cell_size, coordinates, env);
+                });
             }
 
             if debug_visibility {
-                // let center = Point::new(data.pan_data.absolute_offset.x % scaled_cell_size,data.pan_data.absolute_offset.y % scaled_cell_size);
-                let center = Point::new(
-                    data.get_offset().x % scaled_cell_size,
-                    data.get_offset().y % scaled_cell_size,
-                );
+                let center = Point::new(offset.x % scaled_cell_size, offset.y % scaled_cell_size);
                 let circle = Circle::new(center, 5.0);
                 ctx.fill(circle, &druid_color_thesaurus::pink::CORAL_PINK);
             }
         })
     }
 
+    /// Dots where the lines would cross. Unless overridden the colors come from the
+    /// `DOT_GRID_BACKGROUND` and `DOT_GRID_DOT` keys
     pub fn dot_grid(&self) -> Painter<GridSnapData> {
+        let painter = Self {
+            dots: true,
+            ..*self
+        };
         let origin_visibility = self.show_origin;
         let debug_visibility = self.debug_offset;
 
         Painter::new(move |ctx, data: &GridSnapData, env| {
-            let scaled_cell_size = data.cell_size * data.zoom_data.zoom_scale;
+            let view = data.view;
+            let scaled_cell_size = data.scaled_cell_size();
             let line_width = scaled_cell_size * 0.05;
 
             // Partial Paint Setup
@@ -229,52 +1033,366 @@ impl GridSnapPainter {
 
             // Background Painting Logic
             let rect = screen_space.to_rect();
-            ctx.fill(rect, &gray::MARENGO);
-
-            if data.grid_visibility {
-                let start_point = data.move_to_grid_position(invalidation_rect.origin());
-                let end_point = data.move_to_grid_position(Point {
-                    x: invalidation_rect.max_x(),
-                    y: invalidation_rect.max_y(),
-                });
+            ctx.fill(rect, &painter.background(env));
 
-                let from_row = (start_point.y / scaled_cell_size).floor() as usize;
-                let from_col = (start_point.x / scaled_cell_size).floor() as usize;
+            let offset = view.offset;
+            let rotation = view.rotation_affine();
+            let area = rotation.inverse().transform_rect_bbox(invalidation_rect);
+            painter.paint_background_pattern(ctx, env, rotation, area, offset, scaled_cell_size);
 
-                let to_row = (end_point.y / scaled_cell_size).ceil() as usize + 1;
-                let to_col = (end_point.x / scaled_cell_size).ceil() as usize + 1;
-
-                for row in from_row..=to_row {
-                    for col in from_col..=to_col {
-                        let mut center = Point::new(
-                            scaled_cell_size * col as f64,
-                            scaled_cell_size * row as f64,
-                        );
+            if data.grid_visibility {
+                let ((from_row, from_col), (to_row, to_col)) =
+                    visible_lines(area, offset, scaled_cell_size);
 
-                        // Zoom UI functionality
-                        center.x += data.pan_data.offset.x % scaled_cell_size;
-                        center.y += data.pan_data.offset.y % scaled_cell_size;
+                let alphas = painter.line_alphas(scaled_cell_size);
+                let colors = painter.line_colors(env);
 
-                        let circle = Circle::new(center, line_width);
-                        ctx.fill(circle, &env.get(theme::BORDER_LIGHT));
+                ctx.with_save(|ctx| {
+                    ctx.transform(rotation);
+                    for row in painter.drawn_lines(from_row, to_row, alphas) {
+                        for col in painter.drawn_lines(from_col, to_col, alphas) {
+                            // Dots where two major lines cross are major
+                            let index = if painter.is_major(row) { col } else { row };
+                            let Some((color, radius)) =
+                                painter.line_style(index, line_width, alphas, colors)
+                            else {
+                                continue;
+                            };
+                            let center = Point::new(
+                                offset.x + scaled_cell_size * col as f64,
+                                offset.y + scaled_cell_size * row as f64,
+                            );
+                            ctx.fill(Circle::new(center, radius), &color);
+                        }
                     }
-                }
+                });
             }
 
             if origin_visibility {
-                let center = Point::new(data.pan_data.offset.x, data.pan_data.offset.y);
-                let circle = Circle::new(center, 5.0);
-                ctx.fill(circle, &druid_color_thesaurus::red::CARMINE);
+                ctx.with_save(|ctx| {
+                    ctx.transform(rotation);
+                    let coordinates = data.coordinates;
+                    paint_origin_axes(ctx, area, offset, scaled_cell_size, coordinates, env);
+                });
             }
 
             if debug_visibility {
-                let center = Point::new(
-                    data.pan_data.offset.x % scaled_cell_size,
-                    data.pan_data.offset.y % scaled_cell_size,
-                );
+                let center = Point::new(offset.x % scaled_cell_size, offset.y % scaled_cell_size);
                 let circle = Circle::new(center, 5.0);
                 ctx.fill(circle, &druid_color_thesaurus::pink::CORAL_PINK);
             }
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use druid::{Color, Env, Point};
+
+    use super::{
+        line_alpha, lines_for_background, AxisOrder, BackgroundStyle, CoordinateSystem,
+        GridSnapData, GridSnapDataAccess, GridSnapPainter, SnapRounding, ViewTransform, YAxis,
+        MAX_GRID_COORDINATE,
+    };
+    use crate::{
+        panning::{PanDataAccess, MAX_OFFSET},
+        theme, CellSide, EdgeIndex, GridIndex,
+    };
+
+    #[test]
+    fn view_transform_matches_the_snap_data() {
+        let mut snap_data = GridSnapData::new(10.0);
+        snap_data.view = ViewTransform::new(Point::new(40.0, -15.0), 1.5, -0.7);
+        let view = snap_data.view;
+
+        let world = snap_data.get_grid_position(3, -2);
+        let screen = view.to_screen(world);
+        let expected = snap_data.rotation_transform() * snap_data.get_opt_grid_position(3, -2);
+        assert!((screen - expected).hypot() < 1e-9);
+        assert!((view.to_world(screen) - world).hypot() < 1e-9);
+        assert_eq!(snap_data.get_grid_index(screen + (1.0, 1.0)), (3, -2));
+
+        let shown = ViewTransform::IDENTITY.to_world_rect(druid::Rect::new(0.0, 0.0, 8.0, 6.0));
+        assert_eq!(shown, druid::Rect::new(0.0, 0.0, 8.0, 6.0));
+        // A zoom scale of 0 still maps back to finite positions
+        let flat = ViewTransform::new(Point::ZERO, 0.0, 0.0);
+        assert!(flat.to_world(Point::new(3.0, 4.0)).is_finite());
+    }
+
+    #[test]
+    fn rescales_around_the_anchor() {
+        let mut snap_data = GridSnapData::new(10.0);
+        snap_data.view = ViewTransform::new(Point::new(5.0, -20.0), 2.0, 0.3);
+        let anchor = Point::new(123.0, 77.0);
+        // Position of the anchor on the grid, in cells
+        let on_grid = |snap_data: &GridSnapData| {
+            (snap_data.rotation_transform().inverse() * anchor - snap_data.view.offset)
+                / snap_data.scaled_cell_size()
+        };
+        let before = on_grid(&snap_data);
+
+        snap_data.rescale_cells(25.0, anchor);
+        assert_eq!(snap_data.cell_size, 25.0);
+        assert!((on_grid(&snap_data) - before).hypot() < 1e-9);
+
+        for size in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            snap_data.rescale_cells(size, anchor);
+            assert_eq!(snap_data.cell_size, 25.0);
+        }
+    }
+
+    #[test]
+    fn clamps_far_away_positions() {
+        let mut snap_data = GridSnapData::new(15.0);
+        assert_eq!(
+            snap_data.get_grid_index(Point::new(1.5e300, -1.5e300)),
+            (-MAX_GRID_COORDINATE, MAX_GRID_COORDINATE)
+        );
+        // Infinity turns into NaN on its way through the rotation of the view
+        assert_eq!(
+            snap_data.get_grid_index(Point::new(f64::INFINITY, f64::NAN)),
+            (0, 0)
+        );
+
+        // A zoom scale of 0 shrinks the cells to nothing
+        snap_data.view.scale = 0.0;
+        assert_eq!(snap_data.get_grid_index(Point::ORIGIN), (0, 0));
+        assert_eq!(
+            snap_data.get_grid_index(Point::new(1e9, -1e9)),
+            (-MAX_GRID_COORDINATE, MAX_GRID_COORDINATE)
+        );
+        snap_data.rounding = SnapRounding::Nearest;
+        assert_eq!(
+            snap_data.snap_grid_index(Point::new(-1e9, 1e9), None),
+            (MAX_GRID_COORDINATE, -MAX_GRID_COORDINATE)
+        );
+    }
+
+    #[test]
+    fn picks_the_nearest_edge() {
+        let snap_data = GridSnapData::new(10.0);
+        let cell = GridIndex::new(-2, 3);
+        let origin = snap_data.get_opt_grid_position(cell.row, cell.col);
+        assert_eq!(snap_data.get_edge_index(origin + (5.0, 5.0)), None);
+        assert_eq!(
+            snap_data.get_edge_index(origin + (5.0, 1.0)),
+            Some(EdgeIndex::new(cell, CellSide::Top))
+        );
+        // The bottom of a cell is the top of the one below it
+        assert_eq!(
+            snap_data.get_edge_index(origin + (4.0, 8.0)),
+            Some(EdgeIndex::new(cell.below(), CellSide::Top))
+        );
+        assert_eq!(
+            snap_data.get_edge_index(origin + (9.0, 6.0)),
+            Some(EdgeIndex::new(cell, CellSide::Right))
+        );
+        assert_eq!(
+            EdgeIndex::new(cell, CellSide::Right).cells(),
+            (cell, cell.right())
+        );
+    }
+
+    #[test]
+    fn keeps_far_away_cells_apart() {
+        let snap_data = GridSnapData::new(15.0);
+        let row = 1_000_000_000;
+        let position = snap_data.get_opt_grid_position(row, -row) + (7.5, 7.5);
+        assert_eq!(snap_data.get_grid_index(position), (row, -row));
+    }
+
+    #[test]
+    fn guards_the_offset() {
+        let mut snap_data = GridSnapData::new(15.0);
+        snap_data.set_offset(Point::new(20.0, -5.0));
+        snap_data.set_offset(Point::new(f64::NAN, 0.0));
+        assert_eq!(snap_data.get_offset(), Point::new(20.0, -5.0));
+        snap_data.set_offset(Point::new(1e20, -1e20));
+        assert_eq!(snap_data.get_offset(), Point::new(MAX_OFFSET, -MAX_OFFSET));
+    }
+
+    #[test]
+    fn converts_user_coordinates() {
+        let math = CoordinateSystem::math(GridIndex::new(10, -3));
+        assert_eq!(math.to_user(GridIndex::new(10, -3)), (0, 0));
+        // One row up and two columns right is (2, 1)
+        assert_eq!(math.to_user(GridIndex::new(9, -1)), (2, 1));
+        assert_eq!(math.from_user((2, 1)), GridIndex::new(9, -1));
+
+        let flipped = CoordinateSystem {
+            origin: GridIndex::new(0, 0),
+            y_axis: YAxis::Down,
+            order: AxisOrder::ColumnMajor,
+        };
+        assert_eq!(flipped.to_user(GridIndex::new(4, 7)), (7, 4));
+        let default = CoordinateSystem::default();
+        for index in [
+            GridIndex::new(-5, 3),
+            GridIndex::new(isize::MAX, isize::MIN),
+        ] {
+            assert_eq!(default.to_user(index), (index.row, index.col));
+            assert_eq!(default.from_user(default.to_user(index)), index);
+        }
+    }
+
+    #[test]
+    fn fades_minor_lines_when_zoomed_out() {
+        assert_eq!(line_alpha(15.0), 1.0);
+        assert_eq!(line_alpha(8.0), 0.5);
+        assert_eq!(line_alpha(2.0), 0.0);
+
+        let painter = GridSnapPainter::default().with_major_interval(5);
+        let colors = (Color::WHITE, Color::BLACK);
+        assert_eq!(
+            painter.line_style(-10, 1.0, (0.0, 1.0), colors),
+            Some((Color::BLACK, 2.0))
+        );
+        assert!(painter.line_style(-9, 1.0, (0.0, 1.0), colors).is_none());
+        let (color, width) = painter.line_style(3, 1.0, (0.5, 1.0), colors).unwrap();
+        assert!((color.as_rgba().3 - 0.5).abs() < 0.01);
+        assert_eq!(width, 1.0);
+        assert!(!painter.with_major_interval(0).is_major(0));
+    }
+
+    #[test]
+    fn fades_major_lines_by_their_spacing() {
+        let painter = GridSnapPainter::default().with_major_interval(4);
+        assert_eq!(painter.line_alphas(3.0), (0.0, 1.0));
+        assert_eq!(painter.line_alphas(2.0), (0.0, 0.5));
+        assert_eq!(painter.line_alphas(0.5), (0.0, 0.0));
+        assert_eq!(painter.with_major_interval(0).line_alphas(20.0), (1.0, 0.0));
+        let colors = (Color::WHITE, Color::BLACK);
+        assert!(painter.line_style(8, 1.0, (0.0, 0.0), colors).is_none());
+    }
+
+    #[test]
+    fn visits_only_major_lines_when_zoomed_out() {
+        let painter = GridSnapPainter::default().with_major_interval(5);
+        let lines = |from, to, alphas| painter.drawn_lines(from, to, alphas).collect::<Vec<_>>();
+        assert_eq!(lines(-7, 12, (0.0, 1.0)), vec![-5, 0, 5, 10]);
+        assert_eq!(lines(5, 5, (0.0, 1.0)), vec![5]);
+        assert_eq!(lines(6, 9, (0.0, 1.0)), Vec::<isize>::new());
+        assert_eq!(lines(-2, 1, (0.5, 1.0)), vec![-2, -1, 0, 1]);
+        assert!(lines(-100, 100, (0.0, 0.0)).is_empty());
+    }
+
+    #[test]
+    fn dot_grid_keeps_its_own_colors() {
+        let dots = GridSnapPainter {
+            dots: true,
+            ..GridSnapPainter::default()
+        };
+        let env = Env::empty();
+        assert_eq!(
+            dots.background(&env),
+            theme::default_color(&theme::DOT_GRID_BACKGROUND)
+        );
+        assert_eq!(dots.line_colors(&env).0, Color::rgb8(0xa1, 0xa1, 0xa1));
+        let env = env.adding(druid::theme::BORDER_LIGHT, Color::WHITE);
+        assert_eq!(dots.line_colors(&env).0, Color::WHITE);
+        let env = env.adding(theme::DOT_GRID_DOT, Color::BLACK);
+        assert_eq!(dots.line_colors(&env).0, Color::BLACK);
+        assert_eq!(
+            dots.with_line_color(Color::GREEN).line_colors(&env).0,
+            Color::GREEN
+        );
+        assert_eq!(
+            GridSnapPainter::default().background(&env),
+            theme::default_color(&theme::GRID_BACKGROUND)
+        );
+    }
+
+    #[test]
+    fn derives_lines_from_the_background() {
+        let (minor, major) = lines_for_background(Color::BLACK);
+        assert_eq!(minor.as_rgba8(), (0x40, 0x40, 0x40, 0xff));
+        assert_eq!(major.as_rgba8(), (0x80, 0x80, 0x80, 0xff));
+        let (minor, _) = lines_for_background(Color::WHITE);
+        assert_eq!(minor.as_rgba8(), (0xbf, 0xbf, 0xbf, 0xff));
+    }
+
+    #[test]
+    fn tints_alternate_cells() {
+        let checkerboard = BackgroundStyle::Checkerboard;
+        assert!(!checkerboard.is_tinted(0, 0));
+        assert!(checkerboard.is_tinted(-1, 0));
+        assert!(checkerboard.is_tinted(2, 3));
+        assert!(BackgroundStyle::RowBands.is_tinted(-3, 8));
+        assert!(!BackgroundStyle::ColumnBands.is_tinted(-3, 8));
+        assert!(!BackgroundStyle::Plain.is_tinted(1, 1));
+    }
+
+    #[test]
+    fn snaps_to_subdivisions_of_a_cell() {
+        let mut snap_data = GridSnapData::new(10.0);
+        snap_data.view.offset = Point::new(1.0, 2.0);
+        let desired = Point::new(13.0, 27.0);
+        assert_eq!(
+            snap_data.move_to_grid_position(desired),
+            Point::new(11.0, 22.0)
+        );
+        snap_data.snap_divisions = 2;
+        assert_eq!(
+            snap_data.move_to_grid_position(desired),
+            Point::new(11.0, 27.0)
+        );
+        snap_data.view.scale = 2.0;
+        assert_eq!(
+            snap_data.move_to_grid_position(desired),
+            Point::new(11.0, 22.0)
+        );
+        // No divisions behaves like a single one
+        snap_data.snap_divisions = 0;
+        assert_eq!(
+            snap_data.move_to_grid_position(desired),
+            Point::new(1.0, 22.0)
+        );
+    }
+
+    #[test]
+    fn snapping_can_be_turned_off() {
+        let mut snap_data = GridSnapData::new(10.0);
+        let desired = Point::new(13.5, 27.25);
+        assert_eq!(
+            snap_data.move_to_grid_position(desired),
+            Point::new(10.0, 20.0)
+        );
+        snap_data.set_snap_enabled(false);
+        assert!(!snap_data.get_snap_enabled());
+        assert_eq!(snap_data.move_to_grid_position(desired), desired);
+
+        snap_data.set_angle_snap(Some(45.0));
+        assert_eq!(snap_data.get_angle_snap(), Some(45.0));
+    }
+
+    #[test]
+    fn rounding_modes() {
+        let mut snap_data = GridSnapData::new(10.0);
+        let position = Point::new(16.0, 4.0);
+        assert_eq!(snap_data.snap_grid_index(position, None), (0, 1));
+        assert_eq!(
+            snap_data.move_to_grid_position(position),
+            Point::new(10.0, 0.0)
+        );
+
+        // Nearest still picks the cell under the pointer but snaps positions to the closest
+        // intersection
+        snap_data.rounding = SnapRounding::Nearest;
+        assert_eq!(snap_data.snap_grid_index(position, None), (0, 1));
+        assert_eq!(
+            snap_data.move_to_grid_position(position),
+            Point::new(20.0, 0.0)
+        );
+
+        // Hysteresis keeps the previous cell until the pointer is well past its edge
+        snap_data.rounding = SnapRounding::Hysteresis;
+        let near_edge = Point::new(11.0, 4.0);
+        assert_eq!(snap_data.snap_grid_index(near_edge, Some((0, 0))), (0, 0));
+        assert_eq!(snap_data.snap_grid_index(near_edge, None), (0, 1));
+        assert_eq!(snap_data.snap_grid_index(position, Some((0, 0))), (0, 1));
+        assert_eq!(
+            snap_data.move_to_grid_position(position),
+            Point::new(10.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,384 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::{fmt::Debug, hash::Hash};
+
+use druid::{
+    im::{HashMap, Vector},
+    Color, Data,
+};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    chunked_grid::ChunkedGrid,
+    grid_canvas::{GridCanvas, GridCanvasData, RenderMode},
+    model::GridCanvasModel,
+    utils::cassetta::{CassettePlayer, TapeItem},
+    GridIndex, GridItem,
+};
+
+// Cells touched by a single batch item
+const MAX_BATCH_CELLS: usize = 4;
+// Items in a single group
+const MAX_GROUP_ITEMS: usize = 3;
+// Groups nested in groups
+const MAX_GROUP_DEPTH: usize = 2;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Generators
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Grid of `rows` × `cols` cells from the origin in which every cell holds a random one of
+/// `items` with a chance of `density`
+pub fn arbitrary_grid<T: Clone>(
+    rng: &mut impl Rng,
+    rows: isize,
+    cols: isize,
+    items: &[T],
+    density: f64,
+) -> HashMap<GridIndex, T> {
+    let mut grid = HashMap::new();
+    for row in 0..rows {
+        for col in 0..cols {
+            if rng.gen_bool(density.clamp(0.0, 1.0)) {
+                if let Some(item) = items.choose(rng) {
+                    grid.insert(GridIndex::new(row, col), item.clone());
+                }
+            }
+        }
+    }
+    grid
+}
+
+/// `len` random tape items of every kind, on cells of a `rows` × `cols` grid from the origin.
+/// The items are consistent with `grid` when played on it in order: adds name the item they
+/// replace and removes and moves the item they take
+pub fn arbitrary_tape<T: Clone>(
+    rng: &mut impl Rng,
+    grid: &HashMap<GridIndex, T>,
+    rows: isize,
+    cols: isize,
+    items: &[T],
+    len: usize,
+) -> Vector<TapeItem<GridIndex, T>> {
+    assert!(rows > 0 && cols > 0 && !items.is_empty());
+    let mut generator = TapeGenerator {
+        grid: grid.clone(),
+        rows,
+        cols,
+        items,
+    };
+    (0..len)
+        .map(|_| generator.item(rng, MAX_GROUP_DEPTH))
+        .collect()
+}
+
+// Plays the items it makes on its copy of the grid, so the next ones see their effect
+struct TapeGenerator<'a, T> {
+    grid: HashMap<GridIndex, T>,
+    rows: isize,
+    cols: isize,
+    items: &'a [T],
+}
+
+impl<T: Clone> TapeGenerator<'_, T> {
+    fn item(&mut self, rng: &mut impl Rng, depth: usize) -> TapeItem<GridIndex, T> {
+        let kinds = if depth > 0 { 6 } else { 5 };
+        let item = match rng.gen_range(0..kinds) {
+            0 => self.add(rng),
+            1 => match self.occupied_cell(rng) {
+                Some((pos, item)) => TapeItem::Remove(pos, item),
+                None => self.add(rng),
+            },
+            2 => {
+                let to = self.cell(rng);
+                match self.occupied_cell(rng) {
+                    Some((from, item)) if !self.grid.contains_key(&to) => {
+                        TapeItem::Move(from, to, item)
+                    }
+                    _ => self.add(rng),
+                }
+            }
+            3 => {
+                let mut map = HashMap::new();
+                for _ in 0..rng.gen_range(1..=MAX_BATCH_CELLS) {
+                    let pos = self.cell(rng);
+                    map.insert(pos, (self.random_item(rng), self.grid.get(&pos).cloned()));
+                }
+                TapeItem::BatchAdd(map)
+            }
+            4 => {
+                let mut map = HashMap::new();
+                for _ in 0..rng.gen_range(1..=MAX_BATCH_CELLS) {
+                    if let Some((pos, item)) = self.occupied_cell(rng) {
+                        map.insert(pos, item);
+                    }
+                }
+                if map.is_empty() {
+                    self.add(rng)
+                } else {
+                    TapeItem::BatchRemove(map)
+                }
+            }
+            _ => {
+                // The grouped items are played as they are made
+                let len = rng.gen_range(1..=MAX_GROUP_ITEMS);
+                let items = (0..len).map(|_| self.item(rng, depth - 1)).collect();
+                return TapeItem::Group(items);
+            }
+        };
+        self.grid.advance(item.clone());
+        item
+    }
+
+    fn add(&self, rng: &mut impl Rng) -> TapeItem<GridIndex, T> {
+        let pos = self.cell(rng);
+        TapeItem::Add(pos, self.random_item(rng), self.grid.get(&pos).cloned())
+    }
+
+    fn cell(&self, rng: &mut impl Rng) -> GridIndex {
+        random_cell(rng, self.rows, self.cols)
+    }
+
+    // Cells are sorted so the same seed makes the same tape
+    fn occupied_cell(&self, rng: &mut impl Rng) -> Option<(GridIndex, T)> {
+        let mut cells: Vec<GridIndex> = self.grid.keys().copied().collect();
+        cells.sort_by_key(|pos| (pos.row, pos.col));
+        let pos = *cells.choose(rng)?;
+        Some((pos, self.grid[&pos].clone()))
+    }
+
+    fn random_item(&self, rng: &mut impl Rng) -> T {
+        self.items.choose(rng).unwrap().clone()
+    }
+}
+
+fn random_cell(rng: &mut impl Rng, rows: isize, cols: isize) -> GridIndex {
+    GridIndex::new(rng.gen_range(0..rows), rng.gen_range(0..cols))
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Checkers
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Plays `tape` on a copy of `grid` and checks that rewinding every item, or playing its
+/// inverse, brings back the grid from before it, that `ChunkedGrid` ends up with the same cells
+/// and that rewinding the whole tape brings back `grid`
+pub fn check_tape_round_trip<T: Clone + Hash + Eq + Debug>(
+    grid: &HashMap<GridIndex, T>,
+    tape: &Vector<TapeItem<GridIndex, T>>,
+) -> Result<(), String> {
+    let mut played = grid.clone();
+    for (index, item) in tape.iter().enumerate() {
+        let before = played.clone();
+        played.advance(item.clone());
+        let mut rewound = played.clone();
+        rewound.rewind(item.clone());
+        if rewound != before {
+            return Err(format!("Rewinding item {index} changes the grid: {item:?}"));
+        }
+        let mut inverted = played.clone();
+        inverted.advance(item.inverse());
+        if inverted != before {
+            return Err(format!(
+                "The inverse of item {index} does not undo it: {item:?}"
+            ));
+        }
+    }
+
+    let mut chunked: ChunkedGrid<T> = grid.clone().into_iter().collect();
+    for item in tape.iter() {
+        chunked.advance(item.clone());
+    }
+    if chunked.to_hash_map() != played {
+        return Err(String::from("ChunkedGrid ends up with different cells"));
+    }
+
+    for item in tape.iter().rev() {
+        played.rewind(item.clone());
+    }
+    if played != *grid {
+        return Err(String::from(
+            "Rewinding the tape does not bring back the grid",
+        ));
+    }
+    Ok(())
+}
+
+/// Makes `steps` random edits with `items` on a `rows` × `cols` grid from the origin, the way
+/// gestures on the widget would, then checks that undoing every edit empties the grid and
+/// redoing them brings it back. Edits the items reject are skipped. Meant for implementers of
+/// [`GridItem`] to check their rules with
+pub fn check_random_edits<T: GridItem + PartialEq + Debug>(
+    rng: &mut impl Rng,
+    items: &[T],
+    rows: isize,
+    cols: isize,
+    steps: usize,
+) -> Result<(), String>
+where
+    GridCanvasData<T>: Data,
+{
+    assert!(rows > 0 && cols > 0 && !items.is_empty());
+    let mut model = GridCanvasModel::new(items[0]);
+    for _ in 0..steps {
+        let pos = random_cell(rng, rows, cols);
+        match rng.gen_range(0..3) {
+            0 => {
+                let item = *items.choose(rng).unwrap();
+                if model.add(pos, item).is_ok() && model.get(&pos) != Some(&item) {
+                    return Err(format!(
+                        "Cell ({}, {}) does not hold the item added to it",
+                        pos.row, pos.col
+                    ));
+                }
+            }
+            1 => {
+                if model.remove(pos).is_ok() && model.get(&pos).is_some() {
+                    return Err(format!(
+                        "Cell ({}, {}) is not empty after a remove",
+                        pos.row, pos.col
+                    ));
+                }
+            }
+            _ => {
+                let _ = model.move_item(pos, random_cell(rng, rows, cols));
+            }
+        }
+    }
+
+    let edited = model.data().grid.to_hash_map();
+    while model.undo() {}
+    if !model.data().grid.is_empty() {
+        return Err(String::from("Undoing every edit does not empty the grid"));
+    }
+    while model.redo() {}
+    if model.data().grid.to_hash_map() != edited {
+        return Err(String::from(
+            "Redoing every edit does not bring back the grid",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that the child widgets of a grid widget match the grid: every child is keyed by the
+/// cell it belongs to and there is one child per occupied cell. Only meaningful once the widget
+/// has been added and the children have caught up with the grid, see
+/// `GridCanvas::with_child_budget`. Lightweight widgets have no children
+pub fn check_children<T: GridItem + PartialEq + Debug>(
+    widget: &GridCanvas<T>,
+    data: &GridCanvasData<T>,
+) -> Result<(), String>
+where
+    GridCanvasData<T>: Data,
+{
+    if let Some(reason) = widget.child_inconsistency() {
+        return Err(reason);
+    }
+
+    let canvas = widget.canvas();
+    if widget.render_mode() == RenderMode::Lightweight {
+        if !canvas.children().is_empty() {
+            return Err(String::from("Lightweight grid has children"));
+        }
+        return Ok(());
+    }
+    if widget.has_pending_children() {
+        return Ok(());
+    }
+    if canvas.children().len() != data.grid.len() {
+        return Err(format!(
+            "{} children for {} items",
+            canvas.children().len(),
+            data.grid.len()
+        ));
+    }
+    for pos in data.grid.keys() {
+        if !widget.has_child(pos) {
+            return Err(format!("Cell ({}, {}) has no child", pos.row, pos.col));
+        }
+    }
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Fixtures
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Item with one kind for each rule a [`GridItem`] can have, shared by the tests
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Data)]
+pub enum TestItem {
+    /// Goes on any cell but a pin's
+    Wall,
+    /// Can not be removed or painted over
+    Pin,
+    /// Keeps the items of other kinds two cells away
+    Pad,
+    /// Number to edit, a weight of 0 can not be placed
+    Weight(u8),
+}
+
+impl GridItem for TestItem {
+    fn can_add(&self, other: Option<&Self>) -> bool {
+        *self != TestItem::Weight(0) && other != Some(&TestItem::Pin)
+    }
+
+    fn can_remove(&self) -> bool {
+        *self != TestItem::Pin
+    }
+
+    fn can_move(&self, other: Option<&Self>) -> bool {
+        other.is_none()
+    }
+
+    fn get_color(&self) -> Color {
+        match self {
+            TestItem::Pin => Color::rgba8(0xff, 0x00, 0x00, 0x80),
+            _ => Color::BLACK,
+        }
+    }
+
+    // Pins need quoting and escaping wherever the text is written out
+    fn get_short_text(&self) -> String {
+        match self {
+            TestItem::Wall => String::from("W"),
+            TestItem::Pin => String::from("<big pin>"),
+            TestItem::Pad => String::from("Pad"),
+            TestItem::Weight(weight) => weight.to_string(),
+        }
+    }
+
+    fn get_halo(&self) -> usize {
+        match self {
+            TestItem::Pad => 2,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{
+        arbitrary_grid, arbitrary_tape, check_random_edits, check_tape_round_trip, TestItem,
+    };
+
+    #[test]
+    fn random_tapes_round_trip() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let items = [TestItem::Wall, TestItem::Pin];
+        for _ in 0..20 {
+            let grid = arbitrary_grid(&mut rng, 6, 6, &items, 0.3);
+            let tape = arbitrary_tape(&mut rng, &grid, 6, 6, &items, 30);
+            assert_eq!(tape.len(), 30);
+            assert_eq!(check_tape_round_trip(&grid, &tape), Ok(()));
+        }
+        assert_eq!(check_random_edits(&mut rng, &items, 5, 5, 200), Ok(()));
+    }
+}
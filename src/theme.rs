@@ -0,0 +1,116 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use druid::{theme::BORDER_LIGHT, Color, Env, Key};
+use druid_color_thesaurus::{black, gray, red, white};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Color Keys
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+// Every key is optional, the widgets fall back to the default theme for keys the env does not
+// set
+pub const GRID_BACKGROUND: Key<Color> = Key::new("grid-canvas.grid-background");
+pub const GRID_LINE: Key<Color> = Key::new("grid-canvas.grid-line");
+/// Lines every few rows and columns, see `GridSnapPainter::with_major_interval`
+pub const GRID_MAJOR_LINE: Key<Color> = Key::new("grid-canvas.grid-major-line");
+/// Axis through the origin along the rows, also the origin marker
+pub const GRID_X_AXIS: Key<Color> = Key::new("grid-canvas.grid-x-axis");
+/// Axis through the origin along the columns
+pub const GRID_Y_AXIS: Key<Color> = Key::new("grid-canvas.grid-y-axis");
+/// Text of the cells, the headers and the search costs
+pub const GRID_LABEL_TEXT: Key<Color> = Key::new("grid-canvas.grid-label-text");
+/// Selected rows, columns and cells, and the progress bar
+pub const SELECTION_FILL: Key<Color> = Key::new("grid-canvas.selection-fill");
+/// Outline of a finite board and the handle that resizes it
+pub const BOARD_EDGE: Key<Color> = Key::new("grid-canvas.board-edge");
+/// Flash over cells an edit was rejected on
+pub const REJECTED_FILL: Key<Color> = Key::new("grid-canvas.rejected-fill");
+pub const MEASURE_COLOR: Key<Color> = Key::new("grid-canvas.measure-color");
+pub const SEARCH_OPEN_FILL: Key<Color> = Key::new("grid-canvas.search-open-fill");
+pub const SEARCH_CLOSED_FILL: Key<Color> = Key::new("grid-canvas.search-closed-fill");
+/// Outline of the node a debugged search expands next
+pub const SEARCH_CURRENT: Key<Color> = Key::new("grid-canvas.search-current");
+/// Backdrop of the badges and indicators drawn over the grid
+pub const OVERLAY_BACKGROUND: Key<Color> = Key::new("grid-canvas.overlay-background");
+pub const OVERLAY_FOREGROUND: Key<Color> = Key::new("grid-canvas.overlay-foreground");
+pub const HEADER_BACKGROUND: Key<Color> = Key::new("grid-canvas.header-background");
+/// Borders of the header cells and the indices outside a finite board
+pub const HEADER_BORDER: Key<Color> = Key::new("grid-canvas.header-border");
+/// Glow on the edges of the grid the view was panned past the content against
+pub const OVERSCROLL_GLOW: Key<Color> = Key::new("grid-canvas.overscroll-glow");
+/// Background of `GridSnapPainter::dot_grid`
+pub const DOT_GRID_BACKGROUND: Key<Color> = Key::new("grid-canvas.dot-grid-background");
+/// Dots of `GridSnapPainter::dot_grid`. Falls back to druid's `BORDER_LIGHT`, see [`dot_color`]
+pub const DOT_GRID_DOT: Key<Color> = Key::new("grid-canvas.dot-grid-dot");
+/// Outline of the cell under the pointer and the crosshair
+pub const CURSOR_COLOR: Key<Color> = Key::new("grid-canvas.cursor-color");
+/// Colors the regions take in turn, in the order they were added
+pub const REGION_COLORS: [Key<Color>; 4] = [
+    Key::new("grid-canvas.region-color-1"),
+    Key::new("grid-canvas.region-color-2"),
+    Key::new("grid-canvas.region-color-3"),
+    Key::new("grid-canvas.region-color-4"),
+];
+
+/// Default color of every key
+pub const DEFAULT_COLORS: [(Key<Color>, Color); 25] = [
+    (GRID_BACKGROUND, gray::OUTER_SPACE),
+    (GRID_LINE, gray::GAINSBORO),
+    (GRID_MAJOR_LINE, Color::WHITE),
+    (GRID_X_AXIS, red::CARMINE),
+    (GRID_Y_AXIS, Color::rgb8(0x6a, 0xbf, 0x69)),
+    (GRID_LABEL_TEXT, white::ALABASTER),
+    (SELECTION_FILL, Color::rgb8(0x4a, 0x90, 0xd9)),
+    (BOARD_EDGE, Color::rgb8(0xd0, 0x20, 0x20)),
+    (REJECTED_FILL, Color::rgb8(0xd0, 0x20, 0x20)),
+    (MEASURE_COLOR, Color::rgb8(0xf5, 0xd0, 0x3b)),
+    (SEARCH_OPEN_FILL, Color::rgb8(0x3c, 0xb3, 0x71)),
+    (SEARCH_CLOSED_FILL, Color::rgb8(0xd0, 0x60, 0x40)),
+    (SEARCH_CURRENT, Color::rgb8(0xff, 0xb0, 0x00)),
+    (OVERLAY_BACKGROUND, Color::rgba8(0x00, 0x00, 0x00, 0x80)),
+    (OVERLAY_FOREGROUND, white::ALABASTER),
+    (HEADER_BACKGROUND, black::ONYX),
+    (HEADER_BORDER, gray::DAVYS_GRAY),
+    (OVERSCROLL_GLOW, Color::rgb8(0x4a, 0x90, 0xd9)),
+    (DOT_GRID_BACKGROUND, gray::MARENGO),
+    // Default of druid's BORDER_LIGHT
+    (DOT_GRID_DOT, Color::rgb8(0xa1, 0xa1, 0xa1)),
+    (CURSOR_COLOR, Color::rgba8(0xff, 0xff, 0xff, 0x80)),
+    (REGION_COLORS[0], Color::rgb8(0x4a, 0x90, 0xd9)),
+    (REGION_COLORS[1], Color::rgb8(0xe0, 0x9f, 0x3e)),
+    (REGION_COLORS[2], Color::rgb8(0x6a, 0xbf, 0x69)),
+    (REGION_COLORS[3], Color::rgb8(0xb5, 0x6f, 0xc4)),
+];
+
+/// Sets every key to its default, e.g. from `AppLauncher::configure_env` before restyling some
+/// of them
+pub fn add_to_env(env: &mut Env) {
+    for (key, color) in DEFAULT_COLORS {
+        env.set(key, color);
+    }
+}
+
+/// Color of `key` in the default theme
+pub fn default_color(key: &Key<Color>) -> Color {
+    DEFAULT_COLORS
+        .iter()
+        .find(|(default, _)| default.raw() == key.raw())
+        .map_or(Color::BLACK, |(_, color)| *color)
+}
+
+/// Color of `key` in `env`, or its default if the env does not set it
+pub fn color(env: &Env, key: &Key<Color>) -> Color {
+    env.try_get(key).unwrap_or_else(|_| default_color(key))
+}
+
+/// Color of the dots of the dot grid: [`DOT_GRID_DOT`], then druid's `BORDER_LIGHT`, then the
+/// default of `BORDER_LIGHT`
+pub fn dot_color(env: &Env) -> Color {
+    env.try_get(DOT_GRID_DOT)
+        .or_else(|_| env.try_get(BORDER_LIGHT))
+        .unwrap_or_else(|_| default_color(&DOT_GRID_DOT))
+}
@@ -0,0 +1,171 @@
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Imports
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+use std::time::Duration;
+
+use druid::{
+    lens,
+    widget::{Button, CrossAxisAlignment, Flex, Label, Slider},
+    BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx,
+    Point, Selector, Size, TimerToken, UpdateCtx, Widget, WidgetExt, WidgetPod,
+};
+
+use crate::utils::cassetta::PlaybackDataAccess;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// Notification Selectors
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+pub const TIMELINE_PLAY: Selector = Selector::new("timeline-play");
+pub const TIMELINE_PAUSE: Selector = Selector::new("timeline-pause");
+pub const TIMELINE_STEP_FORWARD: Selector = Selector::new("timeline-step-forward");
+pub const TIMELINE_STEP_BACKWARD: Selector = Selector::new("timeline-step-backward");
+// Sent when playback reaches the end of the tape
+pub const TIMELINE_FINISHED: Selector = Selector::new("timeline-finished");
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// TapeTimeline Widget
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Slider and play/pause/step controls that scrub through a tape. The controls can also be sent
+/// as commands, e.g. to start playback from a menu or a button elsewhere in the app
+pub struct TapeTimeline<T> {
+    inner: WidgetPod<T, Box<dyn Widget<T>>>,
+    updates_per_second: f64,
+    is_playing: bool,
+    timer_id: TimerToken,
+}
+
+impl<T: Data + PlaybackDataAccess> TapeTimeline<T> {
+    pub fn new(updates_per_second: f64) -> Self {
+        // The slider works on the fraction of the tape that has been played
+        let progress = lens::Map::new(
+            |data: &T| {
+                if data.get_len() == 0 {
+                    0.0
+                } else {
+                    data.get_position() as f64 / data.get_len() as f64
+                }
+            },
+            |data: &mut T, progress: f64| {
+                let index = (progress * data.get_len() as f64).round() as usize;
+                if index != data.get_position() {
+                    data.set_position(index);
+                }
+            },
+        );
+
+        let inner = Flex::row()
+            .with_child(Button::new("<").on_click(|ctx, _data: &mut T, _env| {
+                ctx.submit_notification(TIMELINE_STEP_BACKWARD)
+            }))
+            .with_child(
+                Button::new("Play")
+                    .on_click(|ctx, _data: &mut T, _env| ctx.submit_notification(TIMELINE_PLAY)),
+            )
+            .with_child(
+                Button::new("Pause")
+                    .on_click(|ctx, _data: &mut T, _env| ctx.submit_notification(TIMELINE_PAUSE)),
+            )
+            .with_child(Button::new(">").on_click(|ctx, _data: &mut T, _env| {
+                ctx.submit_notification(TIMELINE_STEP_FORWARD)
+            }))
+            .with_flex_child(Slider::new().lens(progress).expand_width(), 1.0)
+            .with_child(Label::new(|data: &T, _: &Env| {
+                format!("{}/{}", data.get_position(), data.get_len())
+            }))
+            .cross_axis_alignment(CrossAxisAlignment::Center);
+
+        Self {
+            inner: WidgetPod::new(Box::new(inner)),
+            updates_per_second,
+            is_playing: false,
+            timer_id: TimerToken::INVALID,
+        }
+    }
+
+    pub fn set_updates_per_second(&mut self, updates_per_second: f64) {
+        self.updates_per_second = updates_per_second;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.is_playing
+    }
+
+    fn period(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.updates_per_second.max(f64::EPSILON))
+    }
+
+    // Applies a control selector. Returns false when the selector is not one of the controls
+    fn control(&mut self, ctx: &mut EventCtx, data: &mut T, is: impl Fn(Selector) -> bool) -> bool {
+        if is(TIMELINE_PLAY) {
+            self.is_playing = true;
+            self.timer_id = ctx.request_timer(self.period());
+        } else if is(TIMELINE_PAUSE) {
+            self.is_playing = false;
+            self.timer_id = TimerToken::INVALID;
+        } else if is(TIMELINE_STEP_FORWARD) {
+            data.set_position(data.get_position() + 1);
+        } else if is(TIMELINE_STEP_BACKWARD) {
+            data.set_position(data.get_position().saturating_sub(1));
+        } else {
+            return false;
+        }
+        true
+    }
+}
+
+impl<T: Data + PlaybackDataAccess> Widget<T> for TapeTimeline<T> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        match event {
+            Event::Notification(notification) => {
+                if self.control(ctx, data, |selector| notification.is(selector)) {
+                    ctx.set_handled();
+                }
+                return;
+            }
+            Event::Command(cmd) => {
+                if self.control(ctx, data, |selector| cmd.is(selector)) {
+                    ctx.set_handled();
+                    return;
+                }
+            }
+            Event::Timer(token) if *token == self.timer_id => {
+                if self.is_playing && data.get_position() < data.get_len() {
+                    data.set_position(data.get_position() + 1);
+                    self.timer_id = ctx.request_timer(self.period());
+                } else {
+                    self.is_playing = false;
+                    self.timer_id = TimerToken::INVALID;
+                    ctx.submit_notification(TIMELINE_FINISHED);
+                }
+                ctx.set_handled();
+                return;
+            }
+            _ => {}
+        }
+        self.inner.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        self.inner.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &T, data: &T, env: &Env) {
+        self.inner.update(ctx, data, env);
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        let size = self.inner.layout(ctx, bc, data, env);
+        self.inner.set_origin(ctx, Point::ORIGIN);
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        self.inner.paint(ctx, data, env);
+    }
+}
@@ -7,7 +7,20 @@ use druid::{
     im::{HashMap, Vector},
     Data, Lens,
 };
-use std::{fmt::Debug, hash::Hash};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, hash::Hash, mem::size_of};
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// PlaybackDataAccess
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+pub trait PlaybackDataAccess {
+    fn get_position(&self) -> usize;
+    fn get_len(&self) -> usize;
+    fn set_position(&mut self, index: usize);
+}
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 ///
@@ -20,6 +33,50 @@ pub struct Cassetta<T: Clone + Debug> {
     pub redo_tape: Vector<T>,
     pub add_delta: Vector<T>,
     pub remove_delta: Vector<T>,
+    /// Maximum number of items kept on the undo tape. The oldest items are evicted first
+    pub max_len: Option<usize>,
+    group_depth: usize,
+    group_start: usize,
+    // Named tape positions, in the order they were made
+    checkpoints: Vector<Checkpoint>,
+    // Keeps the redo tape as a branch instead of dropping it when a new item is recorded
+    undo_tree: bool,
+    // Branches forking off the current tape, in the order they were made
+    branches: Vector<Branch<T>>,
+    // Position marked by mark_saved, None once the items up to it are overwritten or evicted
+    saved: Option<usize>,
+}
+
+/// Tape position saved under a name, see [`Cassetta::checkpoint`]
+#[derive(Clone, Data, PartialEq, Debug)]
+pub struct Checkpoint {
+    pub name: String,
+    pub position: usize,
+}
+
+/// Items that were undone and then replaced by new ones, kept when the undo tree is on. See
+/// [`Cassetta::with_undo_tree`]
+#[derive(Clone, Data, PartialEq, Debug)]
+pub struct Branch<T: Clone + Debug> {
+    /// Position of the tape the branch forks off at
+    pub fork: usize,
+    pub tape: Vector<T>,
+    // Checkpoints and branches that were on the tape when it was cut off
+    checkpoints: Vector<Checkpoint>,
+    branches: Vector<Branch<T>>,
+}
+
+impl<T: Clone + Debug> Branch<T> {
+    // Shifts the branch after the first item of the undo tape is evicted
+    fn shift(&mut self) {
+        self.fork -= 1;
+        for checkpoint in self.checkpoints.iter_mut() {
+            checkpoint.position -= 1;
+        }
+        for branch in self.branches.iter_mut() {
+            branch.shift();
+        }
+    }
 }
 
 impl<T: Clone + Debug> Cassetta<T> {
@@ -29,33 +86,246 @@ impl<T: Clone + Debug> Cassetta<T> {
             redo_tape: Vector::new(),
             add_delta: Vector::new(),
             remove_delta: Vector::new(),
+            max_len: None,
+            group_depth: 0,
+            group_start: 0,
+            checkpoints: Vector::new(),
+            undo_tree: false,
+            branches: Vector::new(),
+            saved: Some(0),
         }
     }
 
+    /// Keeps the items that were undone as a branch when a new item is recorded, see
+    /// [`branches`](Self::branches)
+    pub fn with_undo_tree(mut self) -> Self {
+        self.set_undo_tree(true);
+        self
+    }
+
+    /// Turning the undo tree off drops the branches
+    pub fn set_undo_tree(&mut self, undo_tree: bool) {
+        self.undo_tree = undo_tree;
+        if !undo_tree {
+            self.branches.clear();
+        }
+    }
+
+    pub fn has_undo_tree(&self) -> bool {
+        self.undo_tree
+    }
+
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.set_max_len(Some(max_len));
+        self
+    }
+
+    pub fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.max_len = max_len;
+        self.evict();
+    }
+
+    /// Number of items on both tapes
+    pub fn len(&self) -> usize {
+        self.undo_tape.len() + self.redo_tape.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.undo_tape.is_empty() && self.redo_tape.is_empty()
+    }
+
+    /// Approximate number of bytes held by the tapes. Heap data owned by the items is not included
+    pub fn approximate_memory(&self) -> usize {
+        (self.len() + self.add_delta.len() + self.remove_delta.len()) * size_of::<T>()
+    }
+
+    fn evict(&mut self) {
+        // Evicting while a group is open would invalidate its start index
+        if self.group_depth > 0 {
+            return;
+        }
+        if let Some(max_len) = self.max_len {
+            while self.undo_tape.len() > max_len {
+                self.undo_tape.pop_front();
+                // Checkpoints before the evicted item can no longer be reached
+                self.checkpoints
+                    .retain(|checkpoint| checkpoint.position > 0);
+                for checkpoint in self.checkpoints.iter_mut() {
+                    checkpoint.position -= 1;
+                }
+                self.branches.retain(|branch| branch.fork > 0);
+                for branch in self.branches.iter_mut() {
+                    branch.shift();
+                }
+                self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+            }
+        }
+    }
+
+    /// Drops the redo tape along with the checkpoints and branches on it
+    pub fn clear_redo(&mut self) {
+        self.cut();
+    }
+
+    // Takes the redo tape along with the checkpoints and branches on it
+    fn cut(&mut self) -> Option<Branch<T>> {
+        let fork = self.position();
+        let branch = Branch {
+            fork,
+            tape: std::mem::take(&mut self.redo_tape),
+            checkpoints: self
+                .checkpoints
+                .iter()
+                .filter(|checkpoint| checkpoint.position > fork)
+                .cloned()
+                .collect(),
+            branches: self
+                .branches
+                .iter()
+                .filter(|branch| branch.fork > fork)
+                .cloned()
+                .collect(),
+        };
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.position <= fork);
+        self.branches.retain(|branch| branch.fork <= fork);
+        self.saved = self.saved.filter(|saved| *saved <= fork);
+        (!branch.tape.is_empty()).then_some(branch)
+    }
+
+    // Called before a new item is recorded at the tape head
+    fn overwrite_redo(&mut self) {
+        if let Some(branch) = self.cut() {
+            if self.undo_tree {
+                self.branches.push_back(branch);
+            }
+        }
+    }
+
+    /// Branches forking off the current tape, e.g. to list in a menu
+    pub fn branches(&self) -> &Vector<Branch<T>> {
+        &self.branches
+    }
+
+    /// Seeks back to where the branch at `index` forks off and makes it the redo tape. The items
+    /// after the fork become a branch in turn, so switching never loses any. Returns false if
+    /// there is no such branch or a group is open
+    pub fn switch_branch(&mut self, index: usize) -> bool {
+        if index >= self.branches.len() || self.group_depth > 0 {
+            return false;
+        }
+        let branch = self.branches.remove(index);
+        self.seek(branch.fork);
+        if let Some(current) = self.cut() {
+            self.branches.push_back(current);
+        }
+        self.redo_tape = branch.tape;
+        self.checkpoints.append(branch.checkpoints);
+        self.branches.append(branch.branches);
+        true
+    }
+
+    /// Saves the position of the tape head under `name`, replacing a checkpoint of the same name.
+    /// Checkpoints are dropped once the items after them are overwritten or evicted
+    pub fn checkpoint(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.name != name);
+        self.checkpoints.push_back(Checkpoint {
+            name,
+            position: self.position(),
+        });
+    }
+
+    /// Checkpoints in the order they were made, e.g. for a dropdown
+    pub fn checkpoints(&self) -> &Vector<Checkpoint> {
+        &self.checkpoints
+    }
+
+    /// Position saved under `name`, if the checkpoint is still there
+    pub fn checkpoint_position(&self, name: &str) -> Option<usize> {
+        self.checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.name == name)
+            .map(|checkpoint| checkpoint.position)
+    }
+
+    pub fn remove_checkpoint(&mut self, name: &str) -> bool {
+        let len = self.checkpoints.len();
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.name != name);
+        self.checkpoints.len() != len
+    }
+
+    /// Seeks to the checkpoint called `name`, returning false if there is none
+    pub fn seek_checkpoint(&mut self, name: &str) -> bool {
+        match self.checkpoint_position(name) {
+            Some(position) => {
+                self.seek(position);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Marks the position of the tape head as the saved one, e.g. once the data has been
+    /// written to a file
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.position());
+    }
+
+    /// True if the tape head is back at the position marked by [`mark_saved`](Self::mark_saved).
+    /// A new tape counts as saved at position 0
+    pub fn is_at_saved(&self) -> bool {
+        self.saved == Some(self.position())
+    }
+
     pub fn insert(&mut self, item: T) {
         self.clear_delta();
-        self.redo_tape.clear();
+        self.overwrite_redo();
         self.redo_tape.push_back(item);
     }
 
     pub fn insert_and_play(&mut self, item: T) {
         self.clear_delta();
-        self.redo_tape.clear();
+        self.overwrite_redo();
         self.undo_tape.push_back(item.clone());
         self.add_delta.push_back(item);
+        self.evict();
+    }
+
+    /// Plays an item without recording it on the undo tape. Pair with `commit`
+    pub fn play(&mut self, item: T) {
+        self.clear_delta();
+        self.add_delta.push_back(item);
+    }
+
+    /// Rewinds an item that has been played but never committed. Pair with `play`
+    pub fn revert(&mut self, item: T) {
+        self.clear_delta();
+        self.remove_delta.push_back(item);
+    }
+
+    /// Records an item that has already been played on the undo tape
+    pub fn commit(&mut self, item: T) {
+        self.clear_delta();
+        self.overwrite_redo();
+        self.undo_tape.push_back(item);
+        self.evict();
     }
 
     pub fn append(&mut self, other: Vector<T>) {
         self.clear_delta();
-        self.redo_tape.clear();
+        self.overwrite_redo();
         self.redo_tape.append(other);
     }
 
     pub fn append_and_play(&mut self, other: Vector<T>) {
         self.clear_delta();
-        self.redo_tape.clear();
+        self.overwrite_redo();
         self.undo_tape.append(other.clone());
-        self.add_delta.append(other)
+        self.add_delta.append(other);
+        self.evict();
     }
 
     pub fn clear_delta(&mut self) {
@@ -63,8 +333,12 @@ impl<T: Clone + Debug> Cassetta<T> {
         self.remove_delta.clear();
     }
 
+    /// Undoes the latest item. Nothing is undone while a group is open, see `begin_group`
     pub fn undo(&mut self) -> Option<T> {
         self.clear_delta();
+        if self.group_depth > 0 {
+            return None;
+        }
         let item = self.undo_tape.pop_back();
         if let Some(item) = item.clone() {
             self.redo_tape.push_front(item.clone());
@@ -73,18 +347,167 @@ impl<T: Clone + Debug> Cassetta<T> {
         item
     }
 
+    /// Number of items that have been played, i.e. the position of the tape head
+    pub fn position(&self) -> usize {
+        self.undo_tape.len()
+    }
+
+    /// Advances or rewinds the tape until the head is at `index`. Every item that is played is
+    /// collected in `add_delta` and every item that is reverted in `remove_delta`, in the order
+    /// they should be applied. The tape stays where it is while a group is open.
+    pub fn seek(&mut self, index: usize) {
+        self.clear_delta();
+        if self.group_depth > 0 {
+            return;
+        }
+        let index = index.min(self.len());
+        while self.undo_tape.len() < index {
+            match self.redo_tape.pop_front() {
+                Some(item) => {
+                    self.undo_tape.push_back(item.clone());
+                    self.add_delta.push_back(item);
+                }
+                None => break,
+            }
+        }
+        while self.undo_tape.len() > index {
+            match self.undo_tape.pop_back() {
+                Some(item) => {
+                    self.redo_tape.push_front(item.clone());
+                    self.remove_delta.push_back(item);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Redoes the latest undone item. Nothing is redone while a group is open
     pub fn redo(&mut self) -> Option<T> {
         self.clear_delta();
+        if self.group_depth > 0 {
+            return None;
+        }
         let item = self.redo_tape.pop_front();
         if let Some(item) = item.clone() {
             self.undo_tape.push_back(item.clone());
             self.add_delta.push_back(item);
+            self.evict();
         }
         item
     }
 }
 
+impl<T: Clone + Debug> PlaybackDataAccess for Cassetta<T> {
+    fn get_position(&self) -> usize {
+        self.position()
+    }
+
+    fn get_len(&self) -> usize {
+        self.len()
+    }
+
+    fn set_position(&mut self, index: usize) {
+        self.seek(index);
+    }
+}
+
+impl<K, V> Cassetta<TapeItem<K, V>>
+where
+    K: Clone + Debug + Hash + Eq,
+    V: Clone + Debug,
+{
+    /// Starts collecting the items added to the undo tape into a single undo step.
+    /// Groups can be nested, in which case only the outermost group is recorded.
+    /// Undo, redo, seeking and switching branches are refused until the group is closed, and the
+    /// oldest items are only evicted once it is.
+    pub fn begin_group(&mut self) {
+        if self.group_depth == 0 {
+            self.group_start = self.undo_tape.len();
+        }
+        self.group_depth += 1;
+    }
+
+    /// Collapses the items added since the matching `begin_group` into a `TapeItem::Group`
+    pub fn end_group(&mut self) {
+        if self.group_depth == 0 {
+            return;
+        }
+        self.group_depth -= 1;
+        if self.group_depth == 0 {
+            let items = self.undo_tape.split_off(self.group_start);
+            if !items.is_empty() {
+                let group_end = self.group_start + items.len();
+                self.undo_tape.push_back(TapeItem::Group(items));
+                // Checkpoints made inside the group end up after it, branches forking off
+                // inside it are dropped
+                let (start, end) = (self.group_start, self.undo_tape.len());
+                for checkpoint in self.checkpoints.iter_mut() {
+                    if checkpoint.position > start {
+                        checkpoint.position = checkpoint.position.min(end);
+                    }
+                }
+                self.branches.retain(|branch| branch.fork <= start);
+                // A save made inside the group can only be got back to if nothing came after it
+                self.saved = match self.saved {
+                    Some(saved) if saved > start && saved == group_end => Some(end),
+                    Some(saved) if saved > start => None,
+                    saved => saved,
+                };
+            }
+            self.evict();
+        }
+    }
+
+    pub fn is_grouping(&self) -> bool {
+        self.group_depth > 0
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// TapeRecorder
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
+/// Captures the items submitted while recording so they can be serialized and replayed later
+#[derive(Clone, Data, Lens, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TapeRecorder<T: Clone + Debug> {
+    pub is_recording: bool,
+    pub recording: Vector<T>,
+}
+
+impl<T: Clone + Debug> TapeRecorder<T> {
+    pub fn new() -> Self {
+        Self {
+            is_recording: false,
+            recording: Vector::new(),
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.is_recording = true;
+        self.recording.clear();
+    }
+
+    pub fn stop(&mut self) -> Vector<T> {
+        self.is_recording = false;
+        self.recording.clone()
+    }
+
+    pub fn record(&mut self, item: T) {
+        if self.is_recording {
+            self.recording.push_back(item);
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+///
+/// TapeItem
+///
+///////////////////////////////////////////////////////////////////////////////////////////////////
 #[derive(Clone, Debug, PartialEq, Data)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TapeItem<K, V>
 where
     K: Clone + Debug + Hash + Eq,
@@ -95,6 +518,134 @@ where
     BatchAdd(HashMap<K, (V, Option<V>)>),
     BatchRemove(HashMap<K, V>),
     // BatchMove(HashMap<K, (K, V)>)
+    /// Items that are played in order and rewound in reverse order as a single step
+    Group(Vector<TapeItem<K, V>>),
+}
+
+impl<K, V> TapeItem<K, V>
+where
+    K: Clone + Debug + Hash + Eq,
+    V: Clone,
+{
+    /// Returns the same item with every key passed through `f`. Used to replay recordings at an offset
+    pub fn map_keys(self, f: impl Fn(K) -> K) -> Self {
+        self.map_keys_dyn(&f)
+    }
+
+    /// Item that undoes this one when it is played, e.g. to send an undo to another grid
+    pub fn inverse(&self) -> Self {
+        match self {
+            TapeItem::Add(key, current_item, Some(previous_item)) => TapeItem::Add(
+                key.clone(),
+                previous_item.clone(),
+                Some(current_item.clone()),
+            ),
+            TapeItem::Add(key, current_item, None) => {
+                TapeItem::Remove(key.clone(), current_item.clone())
+            }
+            TapeItem::Remove(key, item) => TapeItem::Add(key.clone(), item.clone(), None),
+            TapeItem::Move(from_key, to_key, item) => {
+                TapeItem::Move(to_key.clone(), from_key.clone(), item.clone())
+            }
+            TapeItem::BatchAdd(items) => {
+                let mut replaced = HashMap::new();
+                let mut added = HashMap::new();
+                for (key, (current_item, previous_item)) in items.iter() {
+                    match previous_item {
+                        Some(previous_item) => {
+                            replaced.insert(
+                                key.clone(),
+                                (previous_item.clone(), Some(current_item.clone())),
+                            );
+                        }
+                        None => {
+                            added.insert(key.clone(), current_item.clone());
+                        }
+                    }
+                }
+                match (replaced.is_empty(), added.is_empty()) {
+                    (_, true) => TapeItem::BatchAdd(replaced),
+                    (true, false) => TapeItem::BatchRemove(added),
+                    (false, false) => TapeItem::Group(Vector::from(vec![
+                        TapeItem::BatchRemove(added),
+                        TapeItem::BatchAdd(replaced),
+                    ])),
+                }
+            }
+            TapeItem::BatchRemove(items) => TapeItem::BatchAdd(
+                items
+                    .iter()
+                    .map(|(key, item)| (key.clone(), (item.clone(), None)))
+                    .collect(),
+            ),
+            TapeItem::Group(items) => {
+                TapeItem::Group(items.iter().rev().map(TapeItem::inverse).collect())
+            }
+        }
+    }
+
+    /// Every key the item touches, with the keys of grouped items in play order
+    pub fn keys(&self) -> Vec<K> {
+        match self {
+            TapeItem::Add(key, _, _) | TapeItem::Remove(key, _) => vec![key.clone()],
+            TapeItem::Move(from_key, to_key, _) => vec![from_key.clone(), to_key.clone()],
+            TapeItem::BatchAdd(items) => items.keys().cloned().collect(),
+            TapeItem::BatchRemove(items) => items.keys().cloned().collect(),
+            TapeItem::Group(items) => items.iter().flat_map(TapeItem::keys).collect(),
+        }
+    }
+
+    fn map_keys_dyn(self, f: &dyn Fn(K) -> K) -> Self {
+        match self {
+            TapeItem::Add(key, current_item, previous_item) => {
+                TapeItem::Add(f(key), current_item, previous_item)
+            }
+            TapeItem::Remove(key, item) => TapeItem::Remove(f(key), item),
+            TapeItem::Move(from_key, to_key, item) => TapeItem::Move(f(from_key), f(to_key), item),
+            TapeItem::BatchAdd(items) => TapeItem::BatchAdd(
+                items
+                    .into_iter()
+                    .map(|(key, value)| (f(key), value))
+                    .collect(),
+            ),
+            TapeItem::BatchRemove(items) => TapeItem::BatchRemove(
+                items
+                    .into_iter()
+                    .map(|(key, value)| (f(key), value))
+                    .collect(),
+            ),
+            TapeItem::Group(items) => {
+                TapeItem::Group(items.into_iter().map(|item| item.map_keys_dyn(f)).collect())
+            }
+        }
+    }
+}
+
+impl<K, V> TapeItem<K, V>
+where
+    K: Clone + Debug + Hash + Eq,
+    V: Clone + PartialEq,
+{
+    /// Returns the items that turn `from` into `to`, one per key that differs
+    pub fn diff(from: &HashMap<K, V>, to: &HashMap<K, V>) -> Vector<Self> {
+        let mut items = Vector::new();
+        for (key, value) in from.iter() {
+            if !to.contains_key(key) {
+                items.push_back(TapeItem::Remove(key.clone(), value.clone()));
+            }
+        }
+        for (key, value) in to.iter() {
+            match from.get(key) {
+                Some(previous_value) if previous_value == value => {}
+                previous_value => items.push_back(TapeItem::Add(
+                    key.clone(),
+                    value.clone(),
+                    previous_value.cloned(),
+                )),
+            }
+        }
+        items
+    }
 }
 
 pub trait CassettePlayer<K, V>
@@ -128,6 +679,11 @@ impl<K: Eq + Clone + Hash + Debug, V: Clone> CassettePlayer<K, V> for HashMap<K,
                     self.remove(&grid_index);
                 }
             }
+            TapeItem::Group(items) => {
+                for item in items {
+                    self.advance(item);
+                }
+            }
         }
     }
 
@@ -159,6 +715,11 @@ impl<K: Eq + Clone + Hash + Debug, V: Clone> CassettePlayer<K, V> for HashMap<K,
                     self.insert(grid_index, previous_item);
                 }
             }
+            TapeItem::Group(items) => {
+                for item in items.into_iter().rev() {
+                    self.rewind(item);
+                }
+            }
         }
     }
 }
@@ -186,6 +747,11 @@ impl<V: Clone> CassettePlayer<usize, V> for Vector<V> {
                     self.remove(grid_index);
                 }
             }
+            TapeItem::Group(items) => {
+                for item in items {
+                    self.advance(item);
+                }
+            }
         }
     }
 
@@ -217,6 +783,294 @@ impl<V: Clone> CassettePlayer<usize, V> for Vector<V> {
                     self.insert(grid_index, previous_item);
                 }
             }
+            TapeItem::Group(items) => {
+                for item in items.into_iter().rev() {
+                    self.rewind(item);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::im::{HashMap, Vector};
+
+    use super::{Cassetta, CassettePlayer, TapeItem};
+
+    #[test]
+    fn evicts_oldest_items() {
+        let mut cassetta = Cassetta::new().with_max_len(3);
+        for item in 0..5 {
+            cassetta.insert_and_play(item);
+        }
+        assert_eq!(cassetta.len(), 3);
+        assert_eq!(cassetta.undo_tape.front(), Some(&2));
+
+        cassetta.undo();
+        assert_eq!(cassetta.len(), 3);
+        cassetta.set_max_len(Some(1));
+        assert_eq!(cassetta.undo_tape.len(), 1);
+        assert_eq!(cassetta.redo_tape.len(), 1);
+    }
+
+    #[test]
+    fn seek_collects_deltas() {
+        let mut cassetta = Cassetta::new();
+        cassetta.append_and_play((0..5).collect());
+        cassetta.seek(1);
+        assert_eq!(cassetta.position(), 1);
+        assert_eq!(cassetta.remove_delta, (1..5).rev().collect::<Vector<_>>());
+
+        cassetta.seek(10);
+        assert_eq!(cassetta.position(), 5);
+        assert_eq!(cassetta.add_delta, (1..5).collect::<Vector<_>>());
+    }
+
+    #[test]
+    fn group_is_undone_as_one_step() {
+        let mut grid: HashMap<usize, char> = HashMap::new();
+        let mut cassetta = Cassetta::new();
+        cassetta.begin_group();
+        for (key, value) in [(0, 'a'), (1, 'b')] {
+            let item = TapeItem::Add(key, value, None);
+            grid.advance(item.clone());
+            cassetta.insert_and_play(item);
+        }
+        let item = TapeItem::Move(0, 2, 'a');
+        grid.advance(item.clone());
+        cassetta.insert_and_play(item);
+        cassetta.end_group();
+        assert_eq!(cassetta.undo_tape.len(), 1);
+
+        if let Some(item) = cassetta.undo() {
+            grid.rewind(item);
+        }
+        assert!(grid.is_empty());
+    }
+
+    #[test]
+    fn tape_stays_put_while_grouping() {
+        let mut cassetta = Cassetta::new();
+        cassetta.insert_and_play(TapeItem::Add(0, 'a', None));
+        cassetta.begin_group();
+        cassetta.insert_and_play(TapeItem::Add(1, 'b', None));
+        assert_eq!(cassetta.undo(), None);
+        cassetta.seek(0);
+        assert_eq!(cassetta.position(), 2);
+        assert!(cassetta.add_delta.is_empty() && cassetta.remove_delta.is_empty());
+        assert_eq!(cassetta.redo(), None);
+
+        cassetta.insert_and_play(TapeItem::Add(2, 'c', None));
+        cassetta.end_group();
+        assert_eq!(cassetta.position(), 2);
+        assert!(matches!(cassetta.undo(), Some(TapeItem::Group(items)) if items.len() == 2));
+        assert_eq!(cassetta.position(), 1);
+    }
+
+    #[test]
+    fn saves_inside_a_group_move_with_it() {
+        let mut cassetta = Cassetta::new();
+        cassetta.insert_and_play(TapeItem::Add(0, 'a', None));
+        cassetta.begin_group();
+        cassetta.insert_and_play(TapeItem::Add(1, 'b', None));
+        cassetta.insert_and_play(TapeItem::Add(2, 'c', None));
+        cassetta.mark_saved();
+        cassetta.end_group();
+        assert!(cassetta.is_at_saved());
+        cassetta.insert_and_play(TapeItem::Add(3, 'd', None));
+        assert!(!cassetta.is_at_saved());
+        cassetta.undo();
+        assert!(cassetta.is_at_saved());
+
+        // Items added to the group after the save leave nothing to get back to
+        cassetta.begin_group();
+        cassetta.insert_and_play(TapeItem::Add(4, 'e', None));
+        cassetta.mark_saved();
+        cassetta.insert_and_play(TapeItem::Add(5, 'f', None));
+        cassetta.end_group();
+        cassetta.insert_and_play(TapeItem::Add(6, 'g', None));
+        assert_eq!(cassetta.position(), 4);
+        assert!(!cassetta.is_at_saved());
+    }
+
+    #[test]
+    fn evicts_once_the_group_is_closed() {
+        let mut cassetta = Cassetta::new().with_max_len(2);
+        for key in 0..2 {
+            cassetta.insert_and_play(TapeItem::Add(key, 'a', None));
         }
+        cassetta.begin_group();
+        for key in 2..5 {
+            cassetta.insert_and_play(TapeItem::Add(key, 'b', None));
+        }
+        // The items of the open group are kept however many there are
+        assert_eq!(cassetta.undo_tape.len(), 5);
+
+        cassetta.end_group();
+        assert_eq!(cassetta.undo_tape.len(), 2);
+        assert_eq!(
+            cassetta.undo_tape.front(),
+            Some(&TapeItem::Add(1, 'a', None))
+        );
+        assert!(
+            matches!(cassetta.undo_tape.back(), Some(TapeItem::Group(items)) if items.len() == 3)
+        );
+    }
+
+    #[test]
+    fn diff_turns_one_map_into_the_other() {
+        let from: HashMap<usize, char> = [(0, 'a'), (1, 'b'), (2, 'c')].into_iter().collect();
+        let to: HashMap<usize, char> = [(1, 'b'), (2, 'x'), (3, 'd')].into_iter().collect();
+
+        let items = TapeItem::diff(&from, &to);
+        assert_eq!(items.len(), 3);
+
+        let mut grid = from.clone();
+        for item in items.iter() {
+            grid.advance(item.clone());
+        }
+        assert_eq!(grid, to);
+
+        for item in items.into_iter().rev() {
+            grid.rewind(item);
+        }
+        assert_eq!(grid, from);
+    }
+
+    #[test]
+    fn checkpoints_follow_the_tape() {
+        let mut cassetta = Cassetta::new().with_max_len(4);
+        cassetta.insert_and_play(1);
+        cassetta.checkpoint("one");
+        cassetta.insert_and_play(2);
+        cassetta.insert_and_play(3);
+        cassetta.checkpoint("three");
+        let names: Vec<&str> = cassetta
+            .checkpoints()
+            .iter()
+            .map(|checkpoint| checkpoint.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["one", "three"]);
+
+        assert!(cassetta.seek_checkpoint("one"));
+        assert_eq!(cassetta.position(), 1);
+        assert_eq!(cassetta.remove_delta, Vector::from(vec![3, 2]));
+        assert!(cassetta.seek_checkpoint("three"));
+        assert_eq!(cassetta.position(), 3);
+        assert!(!cassetta.seek_checkpoint("missing"));
+
+        // Overwriting the redo tape drops the checkpoints on it
+        cassetta.seek(2);
+        cassetta.insert_and_play(4);
+        assert_eq!(cassetta.checkpoint_position("three"), None);
+        assert_eq!(cassetta.checkpoint_position("one"), Some(1));
+
+        // Evicting shifts the checkpoints and drops those before the evicted items
+        cassetta.insert_and_play(5);
+        cassetta.insert_and_play(6);
+        assert_eq!(cassetta.checkpoint_position("one"), Some(0));
+        cassetta.checkpoint("six");
+        cassetta.insert_and_play(7);
+        assert_eq!(cassetta.checkpoint_position("one"), None);
+        assert_eq!(cassetta.checkpoint_position("six"), Some(3));
+        assert!(cassetta.remove_checkpoint("six"));
+        assert!(cassetta.checkpoints().is_empty());
+    }
+
+    #[test]
+    fn saved_position_follows_the_tape() {
+        let mut cassetta = Cassetta::new().with_max_len(3);
+        assert!(cassetta.is_at_saved());
+        cassetta.append_and_play(Vector::from(vec![1, 2]));
+        assert!(!cassetta.is_at_saved());
+        cassetta.mark_saved();
+        cassetta.undo();
+        assert!(!cassetta.is_at_saved());
+        cassetta.redo();
+        assert!(cassetta.is_at_saved());
+
+        // Evicting shifts the saved position
+        cassetta.insert_and_play(3);
+        cassetta.insert_and_play(4);
+        cassetta.seek(1);
+        assert!(cassetta.is_at_saved());
+
+        // Overwriting the saved position leaves nothing to get back to
+        cassetta.seek(0);
+        cassetta.insert_and_play(5);
+        cassetta.seek(1);
+        assert!(!cassetta.is_at_saved());
+        cassetta.seek(0);
+        assert!(!cassetta.is_at_saved());
+    }
+
+    #[test]
+    fn undo_tree_keeps_branches() {
+        let mut cassetta = Cassetta::new().with_undo_tree();
+        cassetta.append_and_play(Vector::from(vec![1, 2, 3]));
+        cassetta.checkpoint("three");
+        cassetta.seek(1);
+        cassetta.insert_and_play(4);
+        assert_eq!(cassetta.undo_tape, Vector::from(vec![1, 4]));
+        assert_eq!(cassetta.checkpoint_position("three"), None);
+        assert_eq!(cassetta.branches().len(), 1);
+        assert_eq!(cassetta.branches()[0].fork, 1);
+        assert_eq!(cassetta.branches()[0].tape, Vector::from(vec![2, 3]));
+
+        // Every edit at the same position adds a branch
+        cassetta.seek(1);
+        cassetta.insert_and_play(5);
+        cassetta.undo();
+        cassetta.insert_and_play(6);
+        assert_eq!(cassetta.branches().len(), 3);
+
+        assert!(cassetta.switch_branch(0));
+        assert_eq!(cassetta.position(), 1);
+        assert_eq!(cassetta.remove_delta, Vector::from(vec![6]));
+        assert_eq!(cassetta.redo_tape, Vector::from(vec![2, 3]));
+        assert_eq!(cassetta.checkpoint_position("three"), Some(3));
+        assert!(cassetta.seek_checkpoint("three"));
+        assert_eq!(cassetta.undo_tape, Vector::from(vec![1, 2, 3]));
+        assert!(!cassetta.switch_branch(3));
+
+        // Branches forking off later are kept inside the branch that is cut off
+        cassetta.seek(0);
+        cassetta.insert_and_play(7);
+        assert_eq!(cassetta.branches().len(), 1);
+        assert!(cassetta.switch_branch(0));
+        assert_eq!(cassetta.branches().len(), 4);
+        cassetta.seek(cassetta.len());
+        assert_eq!(cassetta.undo_tape, Vector::from(vec![1, 2, 3]));
+        assert_eq!(cassetta.checkpoint_position("three"), Some(3));
+
+        // Without the undo tree the redo tape is dropped
+        cassetta.set_undo_tree(false);
+        assert!(cassetta.branches().is_empty());
+        cassetta.undo();
+        cassetta.insert_and_play(8);
+        assert!(cassetta.redo_tape.is_empty() && cassetta.branches().is_empty());
+    }
+
+    #[test]
+    fn inverse_undoes_the_item() {
+        let mut grid: HashMap<i32, char> = HashMap::new();
+        grid.insert(0, 'a');
+        grid.insert(1, 'b');
+        let mut batch = HashMap::new();
+        batch.insert(1, ('c', Some('b')));
+        batch.insert(2, ('d', None));
+        let item = TapeItem::Group(Vector::from(vec![
+            TapeItem::Move(0, 3, 'a'),
+            TapeItem::BatchAdd(batch),
+            TapeItem::Remove(3, 'a'),
+            TapeItem::Add(4, 'e', None),
+        ]));
+
+        let from = grid.clone();
+        grid.advance(item.clone());
+        assert_ne!(grid, from);
+        grid.advance(item.inverse());
+        assert_eq!(grid, from);
     }
 }
@@ -0,0 +1,133 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use druid::Color;
+
+/**
+ *  Net colours
+ * */
+const GOLDEN_RATIO_CONJUGATE: f64 = 0.618_033_988_749_895;
+/// Smallest hue difference, as a fraction of the colour wheel, between two nets of a palette
+const MIN_HUE_GAP: f64 = 0.04;
+/// Hues tried before the gap is halved to make room for more nets
+const PROBES_PER_GAP: usize = 16;
+
+/// Visually distinct colours for net ids. The hue of a net is picked by stepping around the
+/// colour wheel by the golden ratio, starting from a hue derived from the id, so the same net
+/// gets the same colour across runs. A hue that is too close to one already handed out is
+/// skipped, which makes the colours of later nets depend on the order they were first asked for.
+#[derive(Debug, Clone)]
+pub struct NetPalette {
+    lightness: f64,
+    chroma: f64,
+    colors: HashMap<usize, Color>,
+    hues: Vec<f64>,
+}
+
+impl Default for NetPalette {
+    fn default() -> Self {
+        Self::new(60.0, 55.0)
+    }
+}
+
+impl NetPalette {
+    /// Palette with the given CIE LCh lightness (0-100) and chroma
+    pub fn new(lightness: f64, chroma: f64) -> Self {
+        Self {
+            lightness,
+            chroma,
+            colors: HashMap::new(),
+            hues: Vec::new(),
+        }
+    }
+
+    pub fn color(&mut self, net: usize) -> Color {
+        if let Some(color) = self.colors.get(&net) {
+            return color.clone();
+        }
+
+        let hue = self.free_hue((net as f64 * GOLDEN_RATIO_CONJUGATE).fract());
+        let color = Color::hlc(hue * 360.0, self.lightness, self.chroma);
+        self.hues.push(hue);
+        self.colors.insert(net, color.clone());
+        color
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.colors.clear();
+        self.hues.clear();
+    }
+
+    // First hue from `hue` onwards that keeps the gap to every hue handed out so far
+    fn free_hue(&self, mut hue: f64) -> f64 {
+        let mut gap = MIN_HUE_GAP;
+        let mut probes = 0;
+        while gap > f64::EPSILON
+            && self
+                .hues
+                .iter()
+                .any(|other| hue_distance(hue, *other) < gap)
+        {
+            hue = (hue + GOLDEN_RATIO_CONJUGATE).fract();
+            probes += 1;
+            if probes == PROBES_PER_GAP {
+                probes = 0;
+                gap /= 2.0;
+            }
+        }
+        hue
+    }
+}
+
+fn hue_distance(a: f64, b: f64) -> f64 {
+    let distance = (a - b).abs();
+    distance.min(1.0 - distance)
+}
+
+thread_local! {
+    static NET_PALETTE: RefCell<NetPalette> = RefCell::new(NetPalette::default());
+}
+
+/// Colour of a net from a palette shared by the whole UI thread, for use in
+/// [`GridItem::get_color`](crate::GridItem::get_color)
+pub fn net_color(net: usize) -> Color {
+    NET_PALETTE.with(|palette| palette.borrow_mut().color(net))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hue_distance, NetPalette, MIN_HUE_GAP};
+
+    #[test]
+    fn colors_are_stable_and_distinct() {
+        let mut palette = NetPalette::default();
+        let colors: Vec<_> = (0..12).map(|net| palette.color(net)).collect();
+        assert_eq!(palette.color(3), colors[3]);
+        assert_eq!(palette.len(), 12);
+        for (index, hue) in palette.hues.iter().enumerate() {
+            for other in palette.hues[index + 1..].iter() {
+                assert!(hue_distance(*hue, *other) >= MIN_HUE_GAP);
+            }
+        }
+
+        // The same nets asked for in another order start from the same hues
+        let mut other = NetPalette::default();
+        assert_eq!(other.color(5), colors[5]);
+    }
+
+    #[test]
+    fn makes_room_for_many_nets() {
+        let mut palette = NetPalette::default();
+        for net in 0..200 {
+            palette.color(net);
+        }
+        assert_eq!(palette.len(), 200);
+    }
+}
@@ -6,7 +6,7 @@
 // Slotmap vs arena
 
 use std::{
-    collections::{hash_set::IntoIter, HashSet},
+    collections::{hash_set::IntoIter, HashMap, HashSet, VecDeque},
     fmt::Display,
     iter::FusedIterator,
     vec,
@@ -14,10 +14,55 @@ use std::{
 
 use bitvec::prelude::*;
 use graph_builder::{DirectedCsrGraph, GraphBuilder, UndirectedCsrGraph};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Transforms from the first octant to each of the eight octants, as (xx, xy, yx, yy)
+const OCTANTS: [(isize, isize, isize, isize); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+// Offsets of the neighbours of a vertex in the order they are listed, with whether they are
+// diagonal
+const NEIGHBOUR_OFFSETS: [(isize, isize, bool); 8] = [
+    (-1, 0, false),
+    (-1, -1, true),
+    (-1, 1, true),
+    (1, 0, false),
+    (1, -1, true),
+    (1, 1, true),
+    (0, -1, false),
+    (0, 1, false),
+];
+
+/// When a diagonal edge may pass between the two cells it cuts the corner of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CornerCutting {
+    /// Always, even between two missing vertices
+    #[default]
+    Allowed,
+    /// When at least one of the two cells is a vertex
+    IfOneOpen,
+    /// Only when both cells are vertices
+    Never,
+}
 
 // Used for physical design
 // See pathfinding
 #[derive(Debug, Clone, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(into = "LatticeBits", try_from = "LatticeBits")
+)]
 pub struct Lattice2D {
     /// Columns
     pub columns: usize,
@@ -25,6 +70,8 @@ pub struct Lattice2D {
     pub rows: usize,
     /// Rectilinear vs Octilinear
     diagonal_mode: bool,
+    /// Diagonal edges kept next to missing vertices
+    corner_cutting: CornerCutting,
     /// represents gaps in the graph if dense is true and nodes otherwise
     dense: bool,
     /// Tracks present or absent vertices in the graph
@@ -38,16 +85,38 @@ impl Lattice2D {
             columns,
             rows,
             diagonal_mode: false,
+            corner_cutting: CornerCutting::Allowed,
             dense: false,
             exclusions: HashSet::new(),
         }
     }
+
+    /// Lattice with the vertices set in `vertices`, in vertex index order, see
+    /// [`Lattice2D::as_bitvec`]
+    pub fn from_bitvec(columns: usize, rows: usize, vertices: BitVec) -> Result<Self, String> {
+        let mut lattice = Self::new(columns, rows);
+        if vertices.len() != lattice.size() {
+            return Err(format!(
+                "Expected {} bits for a {columns}x{rows} lattice, got {}",
+                lattice.size(),
+                vertices.len()
+            ));
+        }
+        lattice.add_vertex_vector(vertices);
+        lattice.rebalance();
+        Ok(lattice)
+    }
     // Builders
     pub fn with_diagonal(mut self) -> Self {
         self.diagonal_mode = true;
         self
     }
 
+    pub fn with_corner_cutting(mut self, corner_cutting: CornerCutting) -> Self {
+        self.corner_cutting = corner_cutting;
+        self
+    }
+
     // Setters
     pub fn invert(&mut self) {
         self.dense = !self.dense
@@ -61,8 +130,16 @@ impl Lattice2D {
         self.diagonal_mode = false;
     }
 
+    pub fn set_corner_cutting(&mut self, corner_cutting: CornerCutting) {
+        self.corner_cutting = corner_cutting;
+    }
+
     // Queries
     #[must_use]
+    pub fn corner_cutting(&self) -> CornerCutting {
+        self.corner_cutting
+    }
+    #[must_use]
     pub fn size(&self) -> usize {
         self.columns * self.rows
     }
@@ -120,7 +197,7 @@ impl Lattice2D {
         }
         let x = v1.0.abs_diff(v2.0);
         let y = v1.1.abs_diff(v2.1);
-        x + y == 1 || (x == 1 && y == 1 && self.diagonal_mode)
+        x + y == 1 || (x == 1 && y == 1 && self.diagonal_mode && self.can_cut_corner(v1, v2))
     }
     #[must_use]
     pub fn to_vertex_index(&self, column: usize, row: usize) -> usize {
@@ -165,6 +242,43 @@ impl Lattice2D {
             )
     }
 
+    /// Cells crossed by the segment between the centers of two cells. Where the segment passes
+    /// exactly through a corner both cells next to the corner are included.
+    pub fn supercover_line(
+        &self,
+        from_vertex: (usize, usize),
+        to_vertex: (usize, usize),
+    ) -> Vec<(usize, usize)> {
+        let dx = to_vertex.0 as isize - from_vertex.0 as isize;
+        let dy = to_vertex.1 as isize - from_vertex.1 as isize;
+        let (nx, ny) = (dx.abs(), dy.abs());
+        let (sign_x, sign_y) = (dx.signum(), dy.signum());
+
+        let (mut x, mut y) = (from_vertex.0 as isize, from_vertex.1 as isize);
+        let mut cells = vec![from_vertex];
+        let (mut ix, mut iy) = (0, 0);
+        while ix < nx || iy < ny {
+            let decision = (1 + 2 * ix) * ny - (1 + 2 * iy) * nx;
+            if decision == 0 {
+                // Through a corner
+                cells.push(((x + sign_x) as usize, y as usize));
+                cells.push((x as usize, (y + sign_y) as usize));
+                x += sign_x;
+                y += sign_y;
+                ix += 1;
+                iy += 1;
+            } else if decision < 0 {
+                x += sign_x;
+                ix += 1;
+            } else {
+                y += sign_y;
+                iy += 1;
+            }
+            cells.push((x as usize, y as usize));
+        }
+        cells
+    }
+
     pub fn resize(&mut self, column: usize, row: usize) -> bool {
         let mut truncated = false;
         if column < self.columns {
@@ -205,53 +319,267 @@ impl Lattice2D {
     }
     #[must_use]
     pub fn neighbours(&self, vertex: (usize, usize)) -> Vec<(usize, usize)> {
-        if !self.has_vertex(vertex) {
-            return vec![];
-        }
-        let (x, y) = vertex;
-        let mut candidates = Vec::with_capacity(8);
-        if x > 0 {
-            // Left Neighbour
-            candidates.push((x - 1, y));
-            if self.diagonal_mode {
-                if y > 0 {
-                    // Top-Left Neighbour
-                    candidates.push((x - 1, y - 1));
+        self.neighbours_iter(vertex).collect()
+    }
+
+    /// Same vertices in the same order as [`Lattice2D::neighbours`] without allocating
+    pub fn neighbours_iter(
+        &self,
+        vertex: (usize, usize),
+    ) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let present = self.has_vertex(vertex);
+        NEIGHBOUR_OFFSETS
+            .iter()
+            .filter(move |(_, _, diagonal)| present && (!diagonal || self.diagonal_mode))
+            .filter_map(move |(dx, dy, _)| {
+                Some((
+                    vertex.0.checked_add_signed(*dx)?,
+                    vertex.1.checked_add_signed(*dy)?,
+                ))
+            })
+            .filter(move |neighbour| {
+                self.has_vertex(*neighbour)
+                    && (neighbour.0 == vertex.0
+                        || neighbour.1 == vertex.1
+                        || self.can_cut_corner(vertex, *neighbour))
+            })
+    }
+
+    // Whether the corner cutting policy lets a diagonal edge join the two vertices
+    fn can_cut_corner(&self, from: (usize, usize), to: (usize, usize)) -> bool {
+        let open = [(to.0, from.1), (from.0, to.1)]
+            .into_iter()
+            .filter(|corner| self.has_vertex(*corner))
+            .count();
+        match self.corner_cutting {
+            CornerCutting::Allowed => true,
+            CornerCutting::IfOneOpen => open >= 1,
+            CornerCutting::Never => open == 2,
+        }
+    }
+
+    /// Every edge of the lattice once, as (vertex, neighbour) with the vertex coming first in
+    /// vertex index order
+    pub fn edges(&self) -> impl Iterator<Item = ((usize, usize), (usize, usize))> + '_ {
+        (0..self.size())
+            .map(|index| self.to_vertex_coords(index))
+            .flat_map(move |vertex| {
+                let index = self.to_vertex_index(vertex.0, vertex.1);
+                self.neighbours_iter(vertex)
+                    .filter(move |neighbour| self.to_vertex_index(neighbour.0, neighbour.1) > index)
+                    .map(move |neighbour| (vertex, neighbour))
+            })
+    }
+
+    // Visibility
+    /// Returns true if every cell crossed by the segment between the two vertices is a vertex
+    #[must_use]
+    pub fn has_line_of_sight(
+        &self,
+        from_vertex: (usize, usize),
+        to_vertex: (usize, usize),
+    ) -> bool {
+        self.supercover_line(from_vertex, to_vertex)
+            .into_iter()
+            .all(|vertex| self.has_vertex(vertex))
+    }
+
+    /// Cells visible from `origin` within `radius` cells, computed with recursive shadow casting.
+    /// Missing vertices block the view but are visible themselves.
+    #[must_use]
+    pub fn field_of_view(&self, origin: (usize, usize), radius: usize) -> HashSet<(usize, usize)> {
+        let mut visible = HashSet::new();
+        if !self.is_inside(origin) {
+            return visible;
+        }
+        visible.insert(origin);
+        for octant in OCTANTS {
+            self.cast_light(&mut visible, origin, radius as isize, 1, 1.0, 0.0, octant);
+        }
+        visible
+    }
+
+    // Scans one octant row by row, recursing whenever an obstacle splits the light cone.
+    // Slopes are measured from the origin, `start` is the upper and `end` the lower edge of the cone
+    #[allow(clippy::too_many_arguments)]
+    fn cast_light(
+        &self,
+        visible: &mut HashSet<(usize, usize)>,
+        origin: (usize, usize),
+        radius: isize,
+        row: isize,
+        mut start: f64,
+        end: f64,
+        (xx, xy, yx, yy): (isize, isize, isize, isize),
+    ) {
+        if start < end {
+            return;
+        }
+        let mut next_start = start;
+        for distance in row..=radius {
+            let dy = -distance;
+            let mut blocked = false;
+            for dx in -distance..=0 {
+                let left_slope = (dx as f64 - 0.5) / (dy as f64 + 0.5);
+                let right_slope = (dx as f64 + 0.5) / (dy as f64 - 0.5);
+                if start < right_slope {
+                    continue;
+                } else if end > left_slope {
+                    break;
                 }
-                if y + 1 < self.rows {
-                    // Bottom-Left Neightbour
-                    candidates.push((x - 1, y + 1));
+
+                let x = origin.0 as isize + dx * xx + dy * xy;
+                let y = origin.1 as isize + dx * yx + dy * yy;
+                let vertex = (x as usize, y as usize);
+                let is_inside = x >= 0 && y >= 0 && self.is_inside(vertex);
+                if is_inside && dx * dx + dy * dy <= radius * radius {
+                    visible.insert(vertex);
                 }
+
+                let is_opaque = !is_inside || !self.has_vertex(vertex);
+                if blocked {
+                    if is_opaque {
+                        next_start = right_slope;
+                    } else {
+                        blocked = false;
+                        start = next_start;
+                    }
+                } else if is_opaque && distance < radius {
+                    blocked = true;
+                    self.cast_light(
+                        visible,
+                        origin,
+                        radius,
+                        distance + 1,
+                        start,
+                        left_slope,
+                        (xx, xy, yx, yy),
+                    );
+                    next_start = right_slope;
+                }
+            }
+            if blocked {
+                break;
             }
         }
+    }
 
-        if x + 1 < self.columns {
-            // Right Neighbour
-            candidates.push((x + 1, y));
-            if self.diagonal_mode {
-                if y > 0 {
-                    // Top-Right Neighbour
-                    candidates.push((x + 1, y - 1));
-                }
-                if y + 1 < self.rows {
-                    // Bottom-Right Neighbour
-                    candidates.push((x + 1, y + 1));
-                }
+    // Connectivity
+    /// Labels the groups of vertices connected by edges of the lattice, diagonal ones included
+    /// in diagonal mode
+    #[must_use]
+    pub fn connected_components(&self) -> Components {
+        let mut sets = UnionFind::new(self.size());
+        for vertex in self {
+            let index = self.to_vertex_index(vertex.0, vertex.1);
+            for (column, row) in self.neighbours_iter(vertex) {
+                sets.union(index, self.to_vertex_index(column, row));
             }
         }
 
-        if y > 0 {
-            // Top Neighbour
-            candidates.push((x, y - 1));
+        let mut labels = vec![None; self.size()];
+        let mut sizes = Vec::new();
+        let mut root_labels = HashMap::new();
+        for (index, label) in labels.iter_mut().enumerate() {
+            if !self.has_vertex(self.to_vertex_coords(index)) {
+                continue;
+            }
+            let component = *root_labels.entry(sets.find(index)).or_insert_with(|| {
+                sizes.push(0);
+                sizes.len() - 1
+            });
+            sizes[component] += 1;
+            *label = Some(component);
+        }
+        Components {
+            columns: self.columns,
+            labels,
+            sizes,
         }
+    }
+
+    /// Vertices reachable from `from` along the edges of the lattice, itself included. Empty if
+    /// `from` is not a vertex
+    #[must_use]
+    pub fn flood_fill(&self, from: (usize, usize)) -> HashSet<(usize, usize)> {
+        let mut reached = HashSet::new();
+        self.search(from, |_| false, &mut reached);
+        reached
+    }
+
+    /// Returns true if a path along the edges of the lattice joins the two vertices
+    #[must_use]
+    pub fn is_connected(&self, a: (usize, usize), b: (usize, usize)) -> bool {
+        self.has_vertex(b) && self.search(a, |vertex| vertex == b, &mut HashSet::new())
+    }
 
-        if y + 1 < self.rows {
-            // Bottom Neighbour
-            candidates.push((x, y + 1));
+    // Breadth-first search from `from` that stops early once `is_goal` holds for a vertex.
+    // Returns true if it did
+    fn search(
+        &self,
+        from: (usize, usize),
+        is_goal: impl Fn((usize, usize)) -> bool,
+        reached: &mut HashSet<(usize, usize)>,
+    ) -> bool {
+        if !self.has_vertex(from) {
+            return false;
+        }
+        reached.insert(from);
+        let mut queue = VecDeque::from([from]);
+        while let Some(vertex) = queue.pop_front() {
+            if is_goal(vertex) {
+                return true;
+            }
+            for neighbour in self.neighbours_iter(vertex) {
+                if reached.insert(neighbour) {
+                    queue.push_back(neighbour);
+                }
+            }
         }
+        false
+    }
 
-        candidates.retain(|&vertex| self.has_vertex(vertex));
-        candidates
+    /// Corners of the largest rectangle without a single vertex. On a tie the one whose top left
+    /// corner comes first row by row wins, then the flattest. None if every cell is a vertex
+    #[must_use]
+    pub fn largest_empty_rectangle(&self) -> Option<((usize, usize), (usize, usize))> {
+        // Height of the empty run ending at each column of the current row
+        let mut heights = vec![0; self.columns];
+        let mut best: Option<(usize, ((usize, usize), (usize, usize)))> = None;
+        for row in 0..self.rows {
+            for (column, height) in heights.iter_mut().enumerate() {
+                *height = if self.has_vertex((column, row)) {
+                    0
+                } else {
+                    *height + 1
+                };
+            }
+            // Largest rectangle under the histogram, with a stack of increasing heights
+            let mut stack: Vec<usize> = Vec::new();
+            for column in 0..=self.columns {
+                let height = heights.get(column).copied().unwrap_or(0);
+                while let Some(top) = stack.last().copied().filter(|top| heights[*top] >= height) {
+                    stack.pop();
+                    let top_height = heights[top];
+                    if top_height == 0 {
+                        continue;
+                    }
+                    let left = stack.last().map_or(0, |left| left + 1);
+                    let area = top_height * (column - left);
+                    let corners = ((left, row + 1 - top_height), (column - 1, row));
+                    if best.is_none_or(|(best_area, best_corners)| {
+                        area > best_area
+                            || (area == best_area
+                                && (corners.0 .1, corners.0 .0)
+                                    < (best_corners.0 .1, best_corners.0 .0))
+                    }) {
+                        best = Some((area, corners));
+                    }
+                }
+                stack.push(column);
+            }
+        }
+        best.map(|(_, corners)| corners)
     }
 
     // Manipulators
@@ -459,14 +787,76 @@ impl Lattice2D {
         result
     }
 
+    /// One bit per cell in vertex index order, set for the cells that are vertices
     pub fn as_bitvec(&self) -> BitVec {
-        (0..self.columns)
-            .flat_map(move |column| (0..self.rows).map(move |row| (column, row)))
-            .map(|vertex| self.has_vertex(vertex))
+        (0..self.size())
+            .map(|index| self.has_vertex(self.to_vertex_coords(index)))
             .collect()
     }
 }
 
+/// Compact form of a [`Lattice2D`] used to save it, with its cells packed eight to a byte
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LatticeBits {
+    pub columns: usize,
+    pub rows: usize,
+    pub diagonal: bool,
+    pub corner_cutting: CornerCutting,
+    /// Bits of [`Lattice2D::as_bitvec`], least significant bit first
+    pub vertices: Vec<u8>,
+}
+
+impl From<&Lattice2D> for LatticeBits {
+    fn from(lattice: &Lattice2D) -> Self {
+        let vertices: BitVec<u8, Lsb0> = lattice.as_bitvec().into_iter().collect();
+        Self {
+            columns: lattice.columns,
+            rows: lattice.rows,
+            diagonal: lattice.diagonal_mode,
+            corner_cutting: lattice.corner_cutting,
+            vertices: vertices.into_vec(),
+        }
+    }
+}
+
+impl From<Lattice2D> for LatticeBits {
+    fn from(lattice: Lattice2D) -> Self {
+        Self::from(&lattice)
+    }
+}
+
+impl TryFrom<LatticeBits> for Lattice2D {
+    type Error = String;
+
+    /// Fails on a lattice too large to address and on a number of bytes that does not match
+    /// its size, which deserializing reports as an error of the format
+    fn try_from(bits: LatticeBits) -> Result<Self, Self::Error> {
+        let size = bits.columns.checked_mul(bits.rows).ok_or_else(|| {
+            format!(
+                "A {}x{} lattice has too many cells",
+                bits.columns, bits.rows
+            )
+        })?;
+        if bits.vertices.len() != size.div_ceil(8) {
+            return Err(format!(
+                "Expected {} bytes for a {}x{} lattice, got {}",
+                size.div_ceil(8),
+                bits.columns,
+                bits.rows,
+                bits.vertices.len()
+            ));
+        }
+        let mut vertices = BitVec::<u8, Lsb0>::from_vec(bits.vertices);
+        vertices.truncate(size);
+        let mut lattice =
+            Self::from_bitvec(bits.columns, bits.rows, vertices.into_iter().collect())?;
+        lattice.diagonal_mode = bits.diagonal;
+        lattice.corner_cutting = bits.corner_cutting;
+        Ok(lattice)
+    }
+}
+
 impl IntoIterator for Lattice2D {
     type Item = (usize, usize);
 
@@ -518,23 +908,15 @@ impl PartialEq for Lattice2D {
 
 impl Into<UndirectedCsrGraph<usize, usize>> for Lattice2D {
     fn into(self) -> UndirectedCsrGraph<usize, usize> {
-        let mut edges: HashSet<(usize, usize)> = HashSet::new();
-        for column in 0..self.columns {
-            // Columns
-            for row in 0..self.rows {
-                if self.has_vertex((column, row)) {
-                    let self_index = self.to_vertex_index(column, row);
-
-                    for (neighbour_col, neighbour_row) in self.neighbours((column, row)) {
-                        let neighbour_index = self.to_vertex_index(neighbour_col, neighbour_row);
-                        // For DirectedCsrGraph this check should be removed
-                        if !edges.contains(&(neighbour_index, self_index)) {
-                            edges.insert((self_index, neighbour_index));
-                        }
-                    }
-                }
-            }
-        }
+        let edges: Vec<(usize, usize)> = self
+            .edges()
+            .map(|(vertex, neighbour)| {
+                (
+                    self.to_vertex_index(vertex.0, vertex.1),
+                    self.to_vertex_index(neighbour.0, neighbour.1),
+                )
+            })
+            .collect();
 
         GraphBuilder::new()
             .csr_layout(graph_builder::CsrLayout::Sorted)
@@ -657,8 +1039,233 @@ impl Into<DirectedCsrGraph<usize>> for Lattice2D {
     }
 }
 
-// Connected Components (UnionFind)
-// See graph, path-finding-lib-rust or petgraph
+/// Lattice2D together with an adjacency list that is updated in O(degree) when a vertex is added
+/// or removed, so graph algorithms can run after every edit without rebuilding a CSR graph.
+/// Vertices are identified by their vertex index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicLatticeGraph {
+    lattice: Lattice2D,
+    adjacency: Vec<Vec<usize>>,
+    edge_count: usize,
+}
+
+impl DynamicLatticeGraph {
+    // Constructors
+    pub fn new(lattice: Lattice2D) -> Self {
+        let mut adjacency = vec![Vec::new(); lattice.size()];
+        let mut edge_count = 0;
+        for (column, row) in &lattice {
+            let neighbours: Vec<usize> = lattice
+                .neighbours_iter((column, row))
+                .map(|(neighbour_col, neighbour_row)| {
+                    lattice.to_vertex_index(neighbour_col, neighbour_row)
+                })
+                .collect();
+            edge_count += neighbours.len();
+            adjacency[lattice.to_vertex_index(column, row)] = neighbours;
+        }
+        Self {
+            lattice,
+            adjacency,
+            edge_count: edge_count / 2,
+        }
+    }
+
+    // Queries
+    pub fn lattice(&self) -> &Lattice2D {
+        &self.lattice
+    }
+
+    #[must_use]
+    pub fn node_count(&self) -> usize {
+        self.lattice.size()
+    }
+
+    #[must_use]
+    pub fn edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    #[must_use]
+    pub fn neighbours(&self, index: usize) -> &[usize] {
+        self.adjacency
+            .get(index)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    // Manipulators
+    pub fn add_vertex(&mut self, vertex: (usize, usize)) -> bool {
+        if !self.lattice.add_vertex(vertex) {
+            return false;
+        }
+        let index = self.lattice.to_vertex_index(vertex.0, vertex.1);
+        let neighbours: Vec<usize> = self
+            .lattice
+            .neighbours_iter(vertex)
+            .map(|(column, row)| self.lattice.to_vertex_index(column, row))
+            .collect();
+        for neighbour in neighbours.iter() {
+            self.adjacency[*neighbour].push(index);
+        }
+        self.edge_count += neighbours.len();
+        self.adjacency[index] = neighbours;
+        self.refresh_corners(vertex);
+        true
+    }
+
+    pub fn remove_vertex(&mut self, vertex: (usize, usize)) -> bool {
+        if !self.lattice.remove_vertex(vertex) {
+            return false;
+        }
+        let index = self.lattice.to_vertex_index(vertex.0, vertex.1);
+        let neighbours = std::mem::take(&mut self.adjacency[index]);
+        for neighbour in neighbours.iter() {
+            self.adjacency[*neighbour].retain(|other| *other != index);
+        }
+        self.edge_count -= neighbours.len();
+        self.refresh_corners(vertex);
+        true
+    }
+
+    // Diagonal edges that cut the corner at `vertex` depend on it unless corner cutting is
+    // allowed, brings the ones between its orthogonal neighbours up to date
+    fn refresh_corners(&mut self, (column, row): (usize, usize)) {
+        if self.lattice.corner_cutting() == CornerCutting::Allowed {
+            return;
+        }
+        let (column, row) = (column as isize, row as isize);
+        let orthogonal = [(-1, 0), (0, -1), (1, 0), (0, 1), (-1, 0)];
+        for pair in orthogonal.windows(2) {
+            let a = (column + pair[0].0, row + pair[0].1);
+            let b = (column + pair[1].0, row + pair[1].1);
+            if a.0 < 0 || a.1 < 0 || b.0 < 0 || b.1 < 0 {
+                continue;
+            }
+            let a = (a.0 as usize, a.1 as usize);
+            let b = (b.0 as usize, b.1 as usize);
+            if !self.lattice.has_vertex(a) || !self.lattice.has_vertex(b) {
+                continue;
+            }
+            let a_index = self.lattice.to_vertex_index(a.0, a.1);
+            let b_index = self.lattice.to_vertex_index(b.0, b.1);
+            let linked = self.adjacency[a_index].contains(&b_index);
+            if self.lattice.has_edge(a, b) && !linked {
+                self.adjacency[a_index].push(b_index);
+                self.adjacency[b_index].push(a_index);
+                self.edge_count += 1;
+            } else if !self.lattice.has_edge(a, b) && linked {
+                self.adjacency[a_index].retain(|other| *other != b_index);
+                self.adjacency[b_index].retain(|other| *other != a_index);
+                self.edge_count -= 1;
+            }
+        }
+    }
+}
+
+impl From<&DynamicLatticeGraph> for UndirectedCsrGraph<usize, usize> {
+    fn from(graph: &DynamicLatticeGraph) -> Self {
+        let edges: Vec<(usize, usize)> = graph
+            .adjacency
+            .iter()
+            .enumerate()
+            .flat_map(|(index, neighbours)| {
+                neighbours
+                    .iter()
+                    .filter(move |neighbour| index < **neighbour)
+                    .map(move |neighbour| (index, *neighbour))
+            })
+            .collect();
+
+        GraphBuilder::new()
+            .csr_layout(graph_builder::CsrLayout::Sorted)
+            .edges(edges)
+            .node_values(0..graph.node_count())
+            .build()
+    }
+}
+
+/// Connected components of a [`Lattice2D`], see [`Lattice2D::connected_components`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Components {
+    columns: usize,
+    /// Component of every vertex index, None where the lattice has no vertex. Components are
+    /// labelled from 0 in the order their first vertex comes up row by row
+    pub labels: Vec<Option<usize>>,
+    /// Number of vertices of every component, by label
+    pub sizes: Vec<usize>,
+}
+
+impl Components {
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    #[must_use]
+    pub fn label(&self, vertex: (usize, usize)) -> Option<usize> {
+        if vertex.0 >= self.columns {
+            return None;
+        }
+        self.labels
+            .get(vertex.0 + vertex.1 * self.columns)
+            .copied()
+            .flatten()
+    }
+
+    /// Label of the component with the most vertices, the first one on a tie
+    #[must_use]
+    pub fn largest(&self) -> Option<usize> {
+        self.sizes
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, size)| **size)
+            .map(|(label, _)| label)
+    }
+
+    /// Every vertex with its label, row by row
+    pub fn vertices(&self) -> impl Iterator<Item = ((usize, usize), usize)> + '_ {
+        self.labels.iter().enumerate().filter_map(|(index, label)| {
+            label.map(|label| ((index % self.columns, index / self.columns), label))
+        })
+    }
+}
+
+// Union-find over vertex indices with union by size and path halving
+struct UnionFind {
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+            sizes: vec![1; len],
+        }
+    }
+
+    fn find(&mut self, mut item: usize) -> usize {
+        while self.parents[item] != item {
+            self.parents[item] = self.parents[self.parents[item]];
+            item = self.parents[item];
+        }
+        item
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.sizes[a] < self.sizes[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parents[b] = a;
+        self.sizes[a] += self.sizes[b];
+    }
+}
 
 // Used by netlist (Might be unecessary here)
 // See grapes
@@ -671,7 +1278,7 @@ mod tests {
 
     use graph_builder::{Graph, GraphBuilder, UndirectedCsrGraph, UndirectedNeighbors};
 
-    use super::Lattice2D;
+    use super::{Components, CornerCutting, DynamicLatticeGraph, Lattice2D, LatticeBits};
 
     #[test]
     fn to_vertex_index_3x3() {
@@ -1286,6 +1893,78 @@ mod tests {
         assert_eq!(expected_bitvec, result_bitvec);
     }
     #[test]
+    fn neighbours_iter_3x3() {
+        let mut lattice = Lattice2D::new(3, 3).with_diagonal();
+        lattice.fill();
+        assert_eq!(
+            lattice.neighbours_iter((0, 0)).collect::<Vec<_>>(),
+            vec![(1, 0), (1, 1), (0, 1)]
+        );
+        assert_eq!(lattice.neighbours_iter((1, 1)).count(), 8);
+        assert_eq!(lattice.edges().count(), 20);
+
+        lattice.remove_vertex((1, 1));
+        assert_eq!(lattice.neighbours_iter((1, 1)).next(), None);
+        assert_eq!(lattice.edges().count(), 12);
+        assert!(lattice
+            .edges()
+            .all(|(vertex, neighbour)| lattice.has_edge(vertex, neighbour)
+                && lattice.to_vertex_index(vertex.0, vertex.1)
+                    < lattice.to_vertex_index(neighbour.0, neighbour.1)));
+    }
+    #[test]
+    fn from_bitvec_4x2() {
+        // Vertices at (0, 0), (3, 0), (1, 1) and (2, 1)
+        let bits = bitvec![1, 0, 0, 1, 0, 1, 1, 0];
+        let lattice = Lattice2D::from_bitvec(4, 2, bits.clone()).unwrap();
+        assert!(lattice.has_vertex((3, 0)));
+        assert!(lattice.has_vertex((1, 1)));
+        assert!(!lattice.has_vertex((0, 1)));
+        assert_eq!(lattice.vertices_len(), 4);
+        assert_eq!(lattice.as_bitvec(), bits);
+
+        assert!(Lattice2D::from_bitvec(4, 3, bits).is_err());
+    }
+    #[test]
+    fn lattice_bits_5x3() {
+        let mut lattice = Lattice2D::new(5, 3).with_diagonal();
+        lattice.fill();
+        lattice.remove_vertex((4, 0));
+        lattice.remove_vertex((2, 1));
+        let bits = LatticeBits::from(&lattice);
+        assert_eq!(bits.vertices, vec![0b0110_1111, 0b0111_1111]);
+
+        let restored = Lattice2D::try_from(bits.clone()).unwrap();
+        assert_eq!(restored.as_bitvec(), lattice.as_bitvec());
+        assert_eq!(restored.neighbours((1, 0)).len(), 4);
+
+        let mut truncated = bits.clone();
+        truncated.vertices.pop();
+        assert!(Lattice2D::try_from(truncated).is_err());
+        let mut padded = bits.clone();
+        padded.vertices.push(0);
+        assert_eq!(
+            Lattice2D::try_from(padded).err(),
+            Some(String::from("Expected 2 bytes for a 5x3 lattice, got 3"))
+        );
+        let huge = LatticeBits {
+            columns: usize::MAX,
+            rows: 2,
+            ..bits.clone()
+        };
+        assert_eq!(
+            Lattice2D::try_from(huge).err(),
+            Some(format!("A {}x2 lattice has too many cells", usize::MAX))
+        );
+        let empty = LatticeBits {
+            columns: 0,
+            rows: 0,
+            vertices: Vec::new(),
+            ..bits
+        };
+        assert_eq!(Lattice2D::try_from(empty).unwrap().size(), 0);
+    }
+    #[test]
     fn add_border() {
         let mut lattice = Lattice2D::new(5, 5);
         let size = lattice.size();
@@ -1362,4 +2041,155 @@ mod tests {
         assert_eq!(result_graph.node_count(), expected_graph.node_count());
         assert_eq!(result_graph.edge_count(), expected_graph.edge_count());
     }
+
+    #[test]
+    fn dynamic_lattice_graph_matches_csr() {
+        let mut lattice = Lattice2D::new(4, 3);
+        lattice.add_vertex_area((0, 0), (3, 1));
+        let mut graph = DynamicLatticeGraph::new(lattice.clone());
+
+        for vertex in [(1, 1), (2, 0), (1, 2)] {
+            assert_eq!(lattice.remove_vertex(vertex), graph.remove_vertex(vertex));
+        }
+        for vertex in [(1, 1), (3, 2), (3, 2)] {
+            assert_eq!(lattice.add_vertex(vertex), graph.add_vertex(vertex));
+        }
+
+        let expected_graph: UndirectedCsrGraph<usize, usize> = lattice.clone().into();
+        assert_eq!(graph.node_count(), expected_graph.node_count());
+        assert_eq!(graph.edge_count(), expected_graph.edge_count());
+        for node in 0..expected_graph.node_count() {
+            let mut result_neighbours = graph.neighbours(node).to_vec();
+            result_neighbours.sort();
+            let expected_neighbours: Vec<usize> = expected_graph.neighbors(node).copied().collect();
+            assert_eq!(
+                result_neighbours, expected_neighbours,
+                "node: {node}{lattice}"
+            );
+        }
+    }
+
+    #[test]
+    fn dynamic_lattice_graph_cuts_no_corners() {
+        let mut lattice = Lattice2D::new(4, 3)
+            .with_diagonal()
+            .with_corner_cutting(CornerCutting::Never);
+        lattice.fill();
+        let mut graph = DynamicLatticeGraph::new(lattice.clone());
+
+        for vertex in [(1, 1), (2, 0), (0, 2)] {
+            assert_eq!(lattice.remove_vertex(vertex), graph.remove_vertex(vertex));
+        }
+        for vertex in [(1, 1), (0, 2)] {
+            assert_eq!(lattice.add_vertex(vertex), graph.add_vertex(vertex));
+        }
+
+        let expected_graph: UndirectedCsrGraph<usize, usize> = lattice.clone().into();
+        assert_eq!(graph.edge_count(), expected_graph.edge_count());
+        for node in 0..expected_graph.node_count() {
+            let mut result_neighbours = graph.neighbours(node).to_vec();
+            result_neighbours.sort();
+            let expected_neighbours: Vec<usize> = expected_graph.neighbors(node).copied().collect();
+            assert_eq!(
+                result_neighbours, expected_neighbours,
+                "node: {node}{lattice}"
+            );
+        }
+    }
+
+    #[test]
+    fn corner_cutting_2x2() {
+        let mut lattice = Lattice2D::new(2, 2).with_diagonal();
+        lattice.fill();
+        lattice.remove_vertex((1, 0));
+        assert!(lattice.has_edge((0, 0), (1, 1)));
+        lattice.set_corner_cutting(CornerCutting::IfOneOpen);
+        assert!(lattice.has_edge((0, 0), (1, 1)));
+        lattice.set_corner_cutting(CornerCutting::Never);
+        assert!(!lattice.has_edge((0, 0), (1, 1)));
+        assert_eq!(lattice.neighbours((0, 0)), vec![(0, 1)]);
+
+        lattice.remove_vertex((0, 1));
+        lattice.set_corner_cutting(CornerCutting::IfOneOpen);
+        assert!(!lattice.has_edge((0, 0), (1, 1)));
+        lattice.set_corner_cutting(CornerCutting::Allowed);
+        assert_eq!(lattice.neighbours((0, 0)), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn line_of_sight_5x5() {
+        let mut lattice = Lattice2D::new(5, 5);
+        lattice.fill();
+        lattice.remove_vertex((2, 1));
+
+        assert!(lattice.has_line_of_sight((0, 0), (0, 4)));
+        assert!(!lattice.has_line_of_sight((0, 1), (4, 1)));
+        // The segment passes through the corner of the missing vertex
+        assert!(!lattice.has_line_of_sight((1, 0), (3, 2)));
+        assert!(lattice.has_line_of_sight((0, 2), (4, 3)));
+    }
+
+    #[test]
+    fn field_of_view_9x9() {
+        let mut lattice = Lattice2D::new(9, 9);
+        lattice.fill();
+        assert_eq!(lattice.field_of_view((4, 4), 3).len(), 29, "{lattice}");
+
+        lattice.remove_vertex_area((6, 0), (6, 8));
+        let visible = lattice.field_of_view((4, 4), 8);
+        assert!(visible.contains(&(6, 4)), "{lattice}");
+        assert!(!visible.iter().any(|vertex| vertex.0 > 6), "{lattice}");
+    }
+
+    #[test]
+    fn connected_components_5x5() {
+        let mut lattice = Lattice2D::new(5, 5);
+        lattice.add_vertex_area((0, 0), (1, 1));
+        lattice.add_vertex((2, 2));
+        lattice.add_vertex_area((3, 3), (4, 4));
+        lattice.add_vertex((4, 0));
+
+        let components = lattice.connected_components();
+        assert_eq!(components.count(), 4, "{lattice}");
+        assert_eq!(components.sizes, vec![4, 1, 1, 4]);
+        assert_eq!(components.label((4, 0)), Some(1));
+        assert_eq!(components.label((2, 2)), Some(2));
+        assert_eq!(components.label((3, 0)), None);
+        assert_eq!(components.largest(), Some(0));
+        assert_eq!(components.vertices().count(), 10);
+
+        // The diagonal joins the two squares through the middle vertex
+        lattice.enable_diagonal();
+        let components: Components = lattice.connected_components();
+        assert_eq!(components.sizes, vec![9, 1]);
+        assert_eq!(components.label((4, 4)), Some(0));
+    }
+
+    #[test]
+    fn regions_5x5() {
+        let mut lattice = Lattice2D::new(5, 5);
+        lattice.add_vertex_area((0, 0), (4, 0));
+        lattice.add_vertex_area((0, 0), (0, 4));
+        lattice.add_vertex((2, 2));
+        lattice.add_vertex((3, 3));
+
+        assert_eq!(lattice.flood_fill((4, 0)).len(), 9, "{lattice}");
+        assert!(lattice.flood_fill((1, 1)).is_empty());
+        assert!(lattice.is_connected((4, 0), (0, 4)));
+        assert!(!lattice.is_connected((0, 0), (2, 2)));
+        assert!(!lattice.is_connected((0, 0), (1, 1)));
+        lattice.enable_diagonal();
+        assert!(lattice.is_connected((2, 2), (3, 3)));
+        assert_eq!(lattice.flood_fill((3, 3)).len(), 2);
+
+        // The empty cells form a 4 by 4 block with two holes on its diagonal, leaving a few
+        // rectangles of 4 cells
+        assert_eq!(lattice.largest_empty_rectangle(), Some(((1, 1), (4, 1))));
+        lattice.add_vertex((4, 1));
+        assert_eq!(lattice.largest_empty_rectangle(), Some(((1, 1), (1, 4))));
+        lattice.fill();
+        assert_eq!(lattice.largest_empty_rectangle(), None);
+        lattice.clear();
+        assert_eq!(lattice.largest_empty_rectangle(), Some(((0, 0), (4, 4))));
+    }
 }
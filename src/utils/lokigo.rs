@@ -1,4 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+
+use graph_builder::{DirectedNeighbors, Graph};
 
 use crate::utils::soma::{
     cell_library::Net,
@@ -33,3 +35,164 @@ pub struct RoutingProblem {
     // This is populated during global routing and it used by the detailed router to reduce the scope of the problem.
     pub routing_guides: (),
 }
+
+/**
+ *  Layered Layout
+ * */
+// Number of barycenter sweeps used to reduce edge crossings between layers
+const ORDERING_SWEEPS: usize = 4;
+
+/// Places the nodes of a tree or DAG in layers (Sugiyama-style) and returns the (column, row) of
+/// every node. Every edge points at least one layer down, layers are `spacing` rows apart and the
+/// nodes of a layer are `spacing` columns apart, centred on the widest layer.
+/// Edges that close a cycle are ignored.
+pub fn layered_layout<G>(graph: &G, spacing: usize) -> Vec<(usize, usize)>
+where
+    G: Graph<usize> + DirectedNeighbors<usize>,
+{
+    let successors: Vec<Vec<usize>> = (0..graph.node_count())
+        .map(|node| graph.out_neighbors(node).copied().collect())
+        .collect();
+    let layers = order_layers(&successors, &assign_layers(&successors));
+
+    let spacing = spacing.max(1);
+    let widest = layers.iter().map(Vec::len).max().unwrap_or(0);
+    let mut positions = vec![(0, 0); successors.len()];
+    for (row, layer) in layers.iter().enumerate() {
+        let offset = (widest - layer.len()) * spacing / 2;
+        for (col, node) in layer.iter().enumerate() {
+            positions[*node] = (offset + col * spacing, row * spacing);
+        }
+    }
+    positions
+}
+
+// Longest path layering in topological order
+fn assign_layers(successors: &[Vec<usize>]) -> Vec<usize> {
+    let node_count = successors.len();
+    let mut in_degree = vec![0; node_count];
+    for next in successors.iter().flatten() {
+        in_degree[*next] += 1;
+    }
+
+    let mut layer = vec![0; node_count];
+    let mut visited = vec![false; node_count];
+    let mut queue: VecDeque<usize> = (0..node_count)
+        .filter(|node| in_degree[*node] == 0)
+        .collect();
+    // Only cycles are left when the queue runs out, break them at the lowest unvisited node
+    while let Some(node) = queue
+        .pop_front()
+        .or_else(|| (0..node_count).find(|node| !visited[*node]))
+    {
+        if visited[node] {
+            continue;
+        }
+        visited[node] = true;
+
+        for next in successors[node].iter().copied() {
+            if visited[next] {
+                continue;
+            }
+            layer[next] = layer[next].max(layer[node] + 1);
+            in_degree[next] -= 1;
+            if in_degree[next] == 0 {
+                queue.push_back(next);
+            }
+        }
+    }
+    layer
+}
+
+// Orders the nodes of every layer by the barycenter of their neighbours in the adjacent layer,
+// sweeping down and up alternately
+fn order_layers(successors: &[Vec<usize>], layer: &[usize]) -> Vec<Vec<usize>> {
+    let depth = layer.iter().max().map_or(0, |depth| depth + 1);
+    let mut layers = vec![Vec::new(); depth];
+    let mut predecessors = vec![Vec::new(); successors.len()];
+    for (node, nexts) in successors.iter().enumerate() {
+        layers[layer[node]].push(node);
+        for next in nexts.iter().copied() {
+            if layer[node] < layer[next] {
+                predecessors[next].push(node);
+            }
+        }
+    }
+
+    let mut index = vec![0.0; successors.len()];
+    for nodes in layers.iter() {
+        for (i, node) in nodes.iter().enumerate() {
+            index[*node] = i as f64;
+        }
+    }
+
+    for sweep in 0..ORDERING_SWEEPS {
+        let (order, neighbours): (Vec<usize>, &[Vec<usize>]) = if sweep % 2 == 0 {
+            ((1..depth).collect(), &predecessors)
+        } else {
+            ((0..depth.saturating_sub(1)).rev().collect(), successors)
+        };
+        for l in order {
+            let mut keyed: Vec<(f64, usize)> = layers[l]
+                .iter()
+                .map(|node| {
+                    let others = &neighbours[*node];
+                    let barycenter = if others.is_empty() {
+                        index[*node]
+                    } else {
+                        others.iter().map(|other| index[*other]).sum::<f64>() / others.len() as f64
+                    };
+                    (barycenter, *node)
+                })
+                .collect();
+            keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+            layers[l] = keyed.into_iter().map(|(_, node)| node).collect();
+            for (i, node) in layers[l].iter().enumerate() {
+                index[*node] = i as f64;
+            }
+        }
+    }
+    layers
+}
+
+#[cfg(test)]
+mod tests {
+    use graph_builder::{DirectedCsrGraph, GraphBuilder};
+
+    use super::layered_layout;
+
+    #[test]
+    fn layered_layout_of_binary_tree() {
+        let edges = vec![(0, 1), (0, 2), (1, 3), (1, 4), (2, 5), (2, 6)];
+        let graph: DirectedCsrGraph<usize> = GraphBuilder::new().edges(edges.clone()).build();
+        let positions = layered_layout(&graph, 2);
+
+        assert_eq!(positions[0], (3, 0));
+        for (from, to) in edges {
+            assert_eq!(positions[from].1 + 2, positions[to].1, "{positions:?}");
+        }
+        let mut unique = positions.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), positions.len(), "{positions:?}");
+    }
+
+    #[test]
+    fn layered_layout_removes_crossings() {
+        let graph: DirectedCsrGraph<usize> = GraphBuilder::new()
+            .edges(vec![(0, 4), (1, 3), (2, 5)])
+            .build();
+        let positions = layered_layout(&graph, 1);
+        assert!(positions[4].0 < positions[3].0, "{positions:?}");
+        assert!(positions[3].0 < positions[5].0, "{positions:?}");
+    }
+
+    #[test]
+    fn layered_layout_breaks_cycles() {
+        let graph: DirectedCsrGraph<usize> = GraphBuilder::new()
+            .edges(vec![(0, 1), (1, 2), (2, 0)])
+            .build();
+        let positions = layered_layout(&graph, 1);
+        assert_eq!(positions, vec![(0, 0), (0, 1), (0, 2)]);
+    }
+}
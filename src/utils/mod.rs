@@ -1,4 +1,5 @@
 pub mod cassetta;
+pub mod chroma;
 pub mod graphema;
 pub mod lokigo;
 pub mod soma;
@@ -11,15 +11,15 @@ pub enum Orientation {
 }
 
 impl Orientation {
-    pub fn get_direction(from: (usize, usize), to: (usize, usize)) -> Self {
-        if from.0 == to.0 + 1 || from.0 == to.0 - 1 {
-            Self::Horizontal
-        } else {
-            Self::Vertical
-        }
+    /// Orientation of the line through two (column, row) positions or None if they are the same.
+    /// See [`Direction::between`] for how displacements off the eight axes are classified
+    pub fn get_direction(from: (usize, usize), to: (usize, usize)) -> Option<Self> {
+        Direction::between(from, to).map(Direction::orientation)
     }
 }
 
+/// Compass directions on the grid with rows growing southwards
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Direction {
     North,
     South,
@@ -30,6 +30,51 @@ pub enum Direction {
     SouthEast,
     SouthWest,
 }
+
+impl Direction {
+    /// Direction from one (column, row) position to another or None if they are the same.
+    /// Displacements are rounded to the closest of the eight directions, so anything within
+    /// 22.5 degrees of an axis or a diagonal falls on it
+    pub fn between(from: (usize, usize), to: (usize, usize)) -> Option<Self> {
+        let dx = to.0 as f64 - from.0 as f64;
+        let dy = to.1 as f64 - from.1 as f64;
+        let tan_22_5 = std::f64::consts::SQRT_2 - 1.0;
+
+        let direction = if dx == 0.0 && dy == 0.0 {
+            return None;
+        } else if dy.abs() < dx.abs() * tan_22_5 {
+            if dx > 0.0 {
+                Self::East
+            } else {
+                Self::West
+            }
+        } else if dx.abs() < dy.abs() * tan_22_5 {
+            if dy > 0.0 {
+                Self::South
+            } else {
+                Self::North
+            }
+        } else {
+            match (dx > 0.0, dy > 0.0) {
+                (true, false) => Self::NorthEast,
+                (false, false) => Self::NorthWest,
+                (true, true) => Self::SouthEast,
+                (false, true) => Self::SouthWest,
+            }
+        };
+        Some(direction)
+    }
+
+    pub fn orientation(self) -> Orientation {
+        match self {
+            Self::North | Self::South => Orientation::Vertical,
+            Self::East | Self::West => Orientation::Horizontal,
+            Self::NorthEast | Self::SouthWest => Orientation::Diag45,
+            Self::NorthWest | Self::SouthEast => Orientation::Diag135,
+        }
+    }
+}
+
 #[derive(PartialEq)]
 pub enum SignalDirection {
     None,
@@ -96,3 +141,71 @@ impl From<Rect> for Polygon {
         Polygon(vec![point_top_left, point_top_right, point_bottom_right])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Direction, Orientation};
+
+    #[test]
+    fn classifies_adjacent_cells() {
+        let center = (5, 5);
+        let cases = [
+            ((5, 4), Direction::North),
+            ((5, 6), Direction::South),
+            ((6, 5), Direction::East),
+            ((4, 5), Direction::West),
+            ((6, 4), Direction::NorthEast),
+            ((4, 4), Direction::NorthWest),
+            ((6, 6), Direction::SouthEast),
+            ((4, 6), Direction::SouthWest),
+        ];
+        for (to, direction) in cases {
+            assert_eq!(Direction::between(center, to), Some(direction), "{to:?}");
+        }
+        assert_eq!(Direction::between(center, center), None);
+    }
+
+    #[test]
+    fn rounds_to_the_closest_direction() {
+        // Up to 22.5 degrees off an axis
+        assert_eq!(Direction::between((0, 0), (10, 4)), Some(Direction::East));
+        assert_eq!(Direction::between((10, 10), (6, 0)), Some(Direction::North));
+        // Closer to the diagonal
+        assert_eq!(
+            Direction::between((0, 0), (10, 5)),
+            Some(Direction::SouthEast)
+        );
+        assert_eq!(
+            Direction::between((10, 0), (0, 7)),
+            Some(Direction::SouthWest)
+        );
+        // Far displacements and the origin
+        assert_eq!(Direction::between((0, 100), (0, 0)), Some(Direction::North));
+        assert_eq!(
+            Direction::between((0, 9), (9, 0)),
+            Some(Direction::NorthEast)
+        );
+    }
+
+    #[test]
+    fn orientation_ignores_the_sense() {
+        let cases = [
+            ((3, 3), (3, 0), Orientation::Vertical),
+            ((3, 0), (3, 3), Orientation::Vertical),
+            ((0, 3), (3, 3), Orientation::Horizontal),
+            ((3, 3), (0, 3), Orientation::Horizontal),
+            ((0, 3), (3, 0), Orientation::Diag45),
+            ((3, 0), (0, 3), Orientation::Diag45),
+            ((0, 0), (3, 3), Orientation::Diag135),
+            ((3, 3), (0, 0), Orientation::Diag135),
+        ];
+        for (from, to, orientation) in cases {
+            assert_eq!(
+                Orientation::get_direction(from, to),
+                Some(orientation),
+                "{from:?} -> {to:?}"
+            );
+        }
+        assert_eq!(Orientation::get_direction((1, 1), (1, 1)), None);
+    }
+}
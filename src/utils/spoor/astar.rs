@@ -1,115 +1,278 @@
-use std::collections::{BTreeSet, HashSet};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
 
-use graph_builder::UndirectedNeighborsWithValues;
+use crate::utils::{cassetta::TapeItem, soma::common::Orientation};
 
-use crate::utils::{cassetta::TapeItem, graphema::Lattice2D, soma::common::Orientation};
-
-use super::core::{Net, NodeType, PathHeuristic, PathNode, ShortestPath};
+use super::core::{
+    Cost, Net, NodeType, PathHeuristic, PathNode, SearchLog, SearchProgress, SearchTree,
+    ShortestPath, ShortestPathConfig,
+};
 
+/// Settles the vertices in order of their cost from the source plus the estimated cost to the
+/// goal. The cost of a step comes from [`ShortestPathConfig::step_cost`], so the direction
+/// preference decides between routes of the same length. Of two routes with the same cost the
+/// one with fewer turns is found. Without a goal the whole graph is explored
 pub struct Astar {
-    unresolved_nodes: BTreeSet<PathNode>,
-    resolved_nodes: HashSet<PathNode>,
-    path_nodes: HashSet<PathNode>,
+    tree: Option<SearchTree>,
+    // Estimated total cost, turns, cost from the source and vertex of every reached vertex
+    unresolved_nodes: BinaryHeap<Reverse<(Cost, usize, Cost, usize)>>,
+    // Turns on the way from the source to every reached vertex
+    turns: HashMap<usize, usize>,
     distance_heuristic: PathHeuristic,
-    previous_orientation: Option<Orientation>,
-    previous_position: Option<(usize, usize)>,
+    // Steps of the last search, None while debugging is off
+    debug_steps: Option<SearchLog>,
 }
 
 impl Astar {
     pub fn new() -> Self {
         Self {
-            unresolved_nodes: BTreeSet::new(),
-            resolved_nodes: HashSet::new(),
-            path_nodes: HashSet::new(),
+            tree: None,
+            unresolved_nodes: BinaryHeap::new(),
+            turns: HashMap::new(),
             distance_heuristic: PathHeuristic::Manhattan,
-            previous_orientation: None,
-            previous_position: None,
+            debug_steps: None,
         }
     }
+
+    fn estimate(&self, tree: &SearchTree, vertex: usize, goal: Option<usize>) -> Cost {
+        goal.map_or(0, |goal| {
+            self.distance_heuristic
+                .cost_estimate(tree.position(vertex), tree.position(goal))
+        })
+    }
+
+    // Turns taken to reach `next` through `vertex`
+    fn turns_to(&self, tree: &SearchTree, vertex: usize, next: usize) -> usize {
+        let turns = self.turns.get(&vertex).copied().unwrap_or(0);
+        let turned = tree.parent(vertex).is_some_and(|parent| {
+            Orientation::get_direction(tree.position(parent), tree.position(vertex))
+                != Orientation::get_direction(tree.position(vertex), tree.position(next))
+        });
+        turns + turned as usize
+    }
+}
+
+impl Default for Astar {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ShortestPath for Astar {
     fn compute(
         &mut self,
-        config: super::core::ShortestPathConfig,
+        config: &ShortestPathConfig,
+        source: usize,
+    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        self.compute_streamed(config, source, &mut |_| true)
+    }
+
+    fn compute_streamed(
+        &mut self,
+        config: &ShortestPathConfig,
         source: usize,
-    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> where {
+        progress: SearchProgress<'_>,
+    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
         // Reset state
         self.unresolved_nodes.clear();
-        self.resolved_nodes.clear();
-        self.path_nodes.clear();
-        let lattice = Lattice2D::new(config.boundary.0, config.boundary.1);
-        let tape = Vec::new();
-        if let Some(target_index) = config.goal {
-            let from = lattice.to_vertex_coords(source);
-            let to = lattice.to_vertex_coords(target_index);
-
-            let path_node = PathNode::new(from, 0, to, self.distance_heuristic, 0);
-            self.unresolved_nodes.insert(path_node); // Add source node to set
-                                                     // While there are values in the unresolved set get the node with the lowest cost
-            while let Some(node) = self.get_next_unresolved() {
-                // Move the node from the unresolved to the resolved set
-                let node_index = lattice.to_vertex_index(node.position.0, node.position.1);
-                self.resolved_nodes.insert(node);
-                for neighbour in config.graph.neighbors_with_values(node_index) {
-                    let neighbour_pos = lattice.to_vertex_coords(neighbour.target);
-                    // If the neighbour orientation from its parent is the same as the previous opientation don't increase the cost
-                    let mut orientation_cost = node.orientation_cost;
-                    if let Some(orientation) = self.previous_orientation {
-                        if Orientation::get_direction(node.position, neighbour_pos) != orientation {
-                            orientation_cost += 1;
-                        }
-                    }
-                    let neighbour_node = PathNode::new(
-                        neighbour_pos,
-                        node.cost_from_start,
-                        to,
-                        self.distance_heuristic,
-                        orientation_cost,
-                    );
-                    let other_node = self.resolved_nodes.remove(&neighbour_node);
-                    // If a neighbour is the target node stop
-                    if neighbour_pos == to {
-                        self.resolved_nodes.insert(neighbour_node);
-                        return tape;
-                    }
-                    // Record each neighbour that has not been encountered before or that has a lower cost than any previous one
-                }
+        self.turns.clear();
+        if let Some(steps) = &mut self.debug_steps {
+            steps.clear();
+        }
+        let tree = SearchTree::new(config, source).with_steps(self.debug_steps.is_some());
+        let estimate = self.estimate(&tree, source, config.goal);
+        self.tree = Some(tree);
+        self.unresolved_nodes
+            .push(Reverse((estimate, 0, 0, source)));
 
-                return tape;
+        let mut tape = Vec::new();
+        while let Some(node) = self.get_next_unresolved() {
+            let mut tree = self.tree.take().unwrap();
+            let vertex = tree.vertex(node.position);
+            tree.resolve(vertex);
+            if let Some(steps) = &mut self.debug_steps {
+                steps.push(tree.step(Some(node.position)));
             }
 
-            return Vec::new();
+            if tree.is_goal(vertex) {
+                let items = tree.take_tape();
+                progress(&items);
+                tape.extend(items);
+                self.tree = Some(tree);
+                break;
+            }
+            for next in config.graph.neighbours(vertex) {
+                if tree.is_resolved(next) {
+                    continue;
+                }
+                if let Some(step) = config.step_cost(node.position, tree.position(next)) {
+                    let cost = node.cost_from_start + step;
+                    let turns = self.turns_to(&tree, vertex, next);
+                    if tree.reach(next, vertex, cost) {
+                        self.turns.insert(next, turns);
+                        let estimate = cost + self.estimate(&tree, next, config.goal);
+                        self.unresolved_nodes
+                            .push(Reverse((estimate, turns, cost, next)));
+                    }
+                }
+            }
+            let items = tree.take_tape();
+            self.tree = Some(tree);
+            let proceed = progress(&items);
+            tape.extend(items);
+            if !proceed {
+                break;
+            }
         }
-
-        Vec::new()
+        tape
     }
 
     fn reconstruct_path(&mut self) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
-        todo!()
+        self.tree
+            .as_ref()
+            .map(SearchTree::path_tape)
+            .unwrap_or_default()
     }
 
     fn get_next_unresolved(&mut self) -> Option<PathNode> {
-        let node = self.unresolved_nodes.pop_first();
-        if let Some(to) = node {
-            if let Some(from) = self.previous_position {
-                // This is executed the second time the function is called.
-                self.previous_orientation = Some(Orientation::get_direction(from, to.position));
+        let tree = self.tree.as_ref()?;
+        // The heap keeps the entries of vertices that were reached again at a lower cost
+        while let Some(Reverse((estimate, turns, cost, vertex))) = self.unresolved_nodes.pop() {
+            if !tree.is_resolved(vertex) && tree.cost(vertex) == Some(cost) {
+                let mut node =
+                    PathNode::uninformed(tree.position(vertex), cost).with_orientation_cost(turns);
+                node.cost_to_target = Some(estimate - cost);
+                node.cost_total = estimate;
+                return Some(node);
             }
-            // This is obviously executed the first time the function is called (with the source node)
-            self.previous_position = Some(to.position);
         }
-        node
+        None
     }
 
+    /// The goal, once the search has reached it
     fn get_next_path_node(&self) -> Option<PathNode> {
-        todo!()
+        let tree = self.tree.as_ref()?;
+        tree.path().last().and_then(|goal| tree.node(*goal))
+    }
+
+    fn set_debugging(&mut self, enabled: bool) {
+        self.debug_steps = enabled.then(SearchLog::default);
+    }
+
+    fn debug_steps(&self) -> Option<&SearchLog> {
+        self.debug_steps.as_ref()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use bitvec::prelude::*;
+    use graph_builder::UndirectedCsrGraph;
+
+    use super::Astar;
+    use crate::utils::{
+        cassetta::TapeItem,
+        graphema::Lattice2D,
+        soma::common::Orientation,
+        spoor::core::{
+            DirectionPreference, DirectionRule, NodeType, ShortestPath, ShortestPathConfig,
+        },
+    };
+
+    // 5 by 5 lattice with a way around the blocked cells on either side, from (1, 0) to (3, 2)
+    fn config(direction_preference: Option<DirectionPreference>) -> ShortestPathConfig {
+        let mut lattice = Lattice2D::new(5, 5);
+        lattice.fill();
+        for blocked in [(2, 2), (2, 4), (3, 1)] {
+            lattice.remove_vertex(blocked);
+        }
+        let graph: UndirectedCsrGraph<usize, usize> = lattice.into();
+        ShortestPathConfig {
+            graph: graph.into(),
+            goal: Some(13),
+            boundary: (5, 5),
+            direction_preference,
+            net: 4,
+        }
+    }
+
+    fn route(tape: &[TapeItem<(usize, usize), NodeType<usize>>]) -> Vec<(usize, usize)> {
+        tape.iter()
+            .map(|item| match item {
+                TapeItem::Add(pos, NodeType::Route(4, _), _) => *pos,
+                _ => panic!("unexpected {item:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn direction_preference_picks_the_route() {
+        let mut astar = Astar::new();
+        let vertical = DirectionPreference::new(Orientation::Vertical, DirectionRule::Soft(2));
+        astar.compute(&config(Some(vertical)), 1);
+        assert_eq!(
+            route(&astar.reconstruct_path()),
+            vec![(1, 1), (1, 2), (1, 3), (2, 3), (3, 3)]
+        );
+        // Two horizontal steps cost 3 each
+        assert_eq!(astar.get_next_path_node().unwrap().cost_from_start, 10);
+
+        let horizontal = DirectionPreference::new(Orientation::Horizontal, DirectionRule::Soft(2));
+        astar.compute(&config(Some(horizontal)), 1);
+        assert_eq!(
+            route(&astar.reconstruct_path()),
+            vec![(2, 0), (3, 0), (4, 0), (4, 1), (4, 2)]
+        );
+        assert_eq!(astar.get_next_path_node().unwrap().cost_from_start, 10);
+
+        let hard = DirectionPreference::new(Orientation::Vertical, DirectionRule::Hard);
+        astar.compute(&config(Some(hard)), 1);
+        assert!(astar.get_next_path_node().is_none());
+    }
+
+    #[test]
+    fn streams_every_expansion() {
+        let mut astar = Astar::new();
+        let mut streamed = Vec::new();
+        let tape = astar.compute_streamed(&config(None), 1, &mut |items| {
+            streamed.extend_from_slice(items);
+            true
+        });
+        assert_eq!(streamed, tape);
+        assert_eq!(tape, Astar::new().compute(&config(None), 1));
+
+        // Stopped after two expansions, without reaching the goal
+        let mut expansions = 0;
+        let partial = astar.compute_streamed(&config(None), 1, &mut |_| {
+            expansions += 1;
+            expansions < 2
+        });
+        assert_eq!(expansions, 2);
+        assert!(partial.len() < tape.len());
+        assert!(astar.get_next_path_node().is_none());
+    }
+
+    #[test]
+    fn prefers_fewer_turns() {
+        let mut lattice = Lattice2D::new(3, 3);
+        lattice.fill();
+        let graph: UndirectedCsrGraph<usize, usize> = lattice.into();
+        let mut config = config(None);
+        config.graph = graph.into();
+        config.goal = Some(8);
+        config.boundary = (3, 3);
+
+        // Every monotone route takes four steps, only the two along the edges turn once
+        let mut astar = Astar::new();
+        astar.compute(&config, 0);
+        let route = route(&astar.reconstruct_path());
+        assert!(
+            route == vec![(1, 0), (2, 0), (2, 1)] || route == vec![(0, 1), (0, 2), (1, 2)],
+            "{route:?}"
+        );
+    }
 
     #[test]
     fn orientation_bias() {
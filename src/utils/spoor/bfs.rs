@@ -0,0 +1,211 @@
+use std::collections::VecDeque;
+
+use crate::utils::cassetta::TapeItem;
+
+use super::core::{
+    Net, NodeType, PathNode, SearchLog, SearchProgress, SearchTree, ShortestPath,
+    ShortestPathConfig,
+};
+
+/// Visits the vertices in rings around the source and stops as soon as the goal is reached,
+/// which gives the path with the fewest steps. Step costs are ignored, the direction preference
+/// only rules out the steps it does not allow. Without a goal the whole graph is explored
+pub struct BreadthFirst {
+    tree: Option<SearchTree>,
+    unresolved_nodes: VecDeque<usize>,
+    // Steps of the last search, None while debugging is off
+    debug_steps: Option<SearchLog>,
+}
+
+impl BreadthFirst {
+    pub fn new() -> Self {
+        Self {
+            tree: None,
+            unresolved_nodes: VecDeque::new(),
+            debug_steps: None,
+        }
+    }
+
+    fn record_step(&mut self, current: (usize, usize)) {
+        if let (Some(steps), Some(tree)) = (&mut self.debug_steps, &mut self.tree) {
+            steps.push(tree.step(Some(current)));
+        }
+    }
+}
+
+impl Default for BreadthFirst {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShortestPath for BreadthFirst {
+    fn compute(
+        &mut self,
+        config: &ShortestPathConfig,
+        source: usize,
+    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        self.compute_streamed(config, source, &mut |_| true)
+    }
+
+    fn compute_streamed(
+        &mut self,
+        config: &ShortestPathConfig,
+        source: usize,
+        progress: SearchProgress<'_>,
+    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        // Reset state
+        self.unresolved_nodes.clear();
+        if let Some(steps) = &mut self.debug_steps {
+            steps.clear();
+        }
+        self.tree = Some(SearchTree::new(config, source).with_steps(self.debug_steps.is_some()));
+        self.unresolved_nodes.push_back(source);
+
+        let mut tape = Vec::new();
+        while let Some(node) = self.get_next_unresolved() {
+            let tree = self.tree.as_mut().unwrap();
+            let vertex = tree.vertex(node.position);
+            tree.resolve(vertex);
+            self.record_step(node.position);
+
+            let tree = self.tree.as_mut().unwrap();
+            for next in config.graph.neighbours(vertex) {
+                if tree.is_reached(next)
+                    || config
+                        .step_cost(node.position, tree.position(next))
+                        .is_none()
+                {
+                    continue;
+                }
+                tree.reach(next, vertex, node.cost_from_start + 1);
+                if tree.is_goal(next) {
+                    self.unresolved_nodes.clear();
+                    break;
+                }
+                self.unresolved_nodes.push_back(next);
+            }
+            let items = tree.take_tape();
+            let proceed = progress(&items);
+            tape.extend(items);
+            if !proceed {
+                break;
+            }
+        }
+        tape
+    }
+
+    fn reconstruct_path(&mut self) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        self.tree
+            .as_ref()
+            .map(SearchTree::path_tape)
+            .unwrap_or_default()
+    }
+
+    fn get_next_unresolved(&mut self) -> Option<PathNode> {
+        let tree = self.tree.as_ref()?;
+        self.unresolved_nodes
+            .pop_front()
+            .and_then(|vertex| tree.node(vertex))
+    }
+
+    /// The goal, once the search has reached it
+    fn get_next_path_node(&self) -> Option<PathNode> {
+        let tree = self.tree.as_ref()?;
+        tree.path().last().and_then(|goal| tree.node(*goal))
+    }
+
+    fn set_debugging(&mut self, enabled: bool) {
+        self.debug_steps = enabled.then(SearchLog::default);
+    }
+
+    fn debug_steps(&self) -> Option<&SearchLog> {
+        self.debug_steps.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graph_builder::UndirectedCsrGraph;
+
+    use super::BreadthFirst;
+    use crate::utils::{
+        graphema::Lattice2D,
+        spoor::{
+            core::{ShortestPath, ShortestPathAlgo, ShortestPathConfig},
+            dijkstra::Dijkstra,
+        },
+    };
+
+    fn config(goal: Option<usize>) -> ShortestPathConfig {
+        let mut lattice = Lattice2D::new(4, 4);
+        lattice.fill();
+        lattice.remove_vertex((1, 1));
+        lattice.remove_vertex((2, 1));
+        lattice.remove_vertex((1, 2));
+        let graph: UndirectedCsrGraph<usize, usize> = lattice.into();
+        ShortestPathConfig {
+            graph: graph.into(),
+            goal,
+            boundary: (4, 4),
+            direction_preference: None,
+            net: 1,
+        }
+    }
+
+    #[test]
+    fn finds_as_short_a_path_as_dijkstra() {
+        // From (0, 0) to (2, 2)
+        let config = config(Some(10));
+        let mut bfs = BreadthFirst::new();
+        let mut dijkstra = Dijkstra::new();
+        bfs.compute(&config, 0);
+        dijkstra.compute(&config, 0);
+        assert_eq!(bfs.reconstruct_path().len(), 5);
+        assert_eq!(
+            bfs.get_next_path_node().unwrap().cost_from_start,
+            dijkstra.get_next_path_node().unwrap().cost_from_start
+        );
+    }
+
+    #[test]
+    fn explores_everything_without_a_goal() {
+        let mut bfs = BreadthFirst::new();
+        bfs.set_debugging(true);
+        let tape = bfs.compute(&config(None), 0);
+        // Every vertex but the source is reached and resolved
+        assert_eq!(tape.len(), 2 * 12);
+        let steps = bfs.debug_steps().unwrap();
+        assert_eq!(steps.len(), 13);
+        // Steps keep only what they changed, snapshots put the sets back together
+        assert!(steps
+            .steps()
+            .iter()
+            .all(|step| step.opened.len() + step.closed.len() <= 5));
+        let first = steps.snapshot(0).unwrap();
+        assert_eq!((first.open.len(), first.closed.len()), (0, 1));
+        let last = steps.last().unwrap();
+        assert_eq!((last.open.len(), last.closed.len()), (0, 13));
+        assert!(steps.snapshot(13).is_none());
+        assert!(bfs.reconstruct_path().is_empty());
+    }
+
+    #[test]
+    fn algorithms_are_selected_by_name() {
+        let mut algo = ShortestPathAlgo::new(config(Some(10)));
+        assert_eq!(
+            algo.algo_names(),
+            vec!["astar", "bfs", "bidirectional", "dijkstra", "jps"]
+        );
+        assert!(algo.select("physarum").is_err());
+        algo.select("bfs").unwrap();
+        assert_eq!(algo.selected(), "bfs");
+        assert!(!algo.compute(0).is_empty());
+        assert_eq!(algo.reconstruct_path().len(), 5);
+        algo.select("dijkstra").unwrap();
+        assert_eq!(
+            algo.algo_names(),
+            vec!["astar", "bfs", "bidirectional", "dijkstra", "jps"]
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::utils::cassetta::TapeItem;
+
+use super::core::{
+    Cost, Net, NodeType, PathHeuristic, PathNode, SearchLog, SearchProgress, SearchTree,
+    ShortestPath, ShortestPathConfig,
+};
+
+/// Runs A* from the source towards the goal and from the goal towards the source at the same
+/// time, always expanding the side with the lowest estimate, and stops once neither side can
+/// improve on the cheapest path crossing between them. The heuristic has to be admissible for
+/// the lattice, Manhattan for rectilinear ones and Chebyshev when diagonal steps are allowed.
+/// Without a goal there is nothing to search from and the tape is empty
+pub struct BidirectionalAstar {
+    distance_heuristic: PathHeuristic,
+    // Searches from the source and from the goal, None before the first search
+    frontiers: Option<[Frontier; 2]>,
+    // Vertex where the cheapest path found so far crosses between the trees, with its cost
+    meeting: Option<(usize, Cost)>,
+    // Last item the tape put on every cell, both searches write to the same cells
+    shown: HashMap<(usize, usize), NodeType<Net>>,
+    // Steps of the last search, None while debugging is off
+    debug_steps: Option<SearchLog>,
+}
+
+// One side of the search
+struct Frontier {
+    tree: SearchTree,
+    source: usize,
+    target: (usize, usize),
+    // (estimated total cost, cost from the source, vertex)
+    unresolved_nodes: BinaryHeap<Reverse<(Cost, Cost, usize)>>,
+}
+
+impl Frontier {
+    fn new(tree: SearchTree, source: usize, target: usize, heuristic: PathHeuristic) -> Self {
+        let target = tree.position(target);
+        let mut frontier = Self {
+            tree,
+            source,
+            target,
+            unresolved_nodes: BinaryHeap::new(),
+        };
+        frontier.push(source, 0, heuristic);
+        frontier
+    }
+
+    fn push(&mut self, vertex: usize, cost: Cost, heuristic: PathHeuristic) {
+        let estimate = heuristic.cost_estimate(self.tree.position(vertex), self.target);
+        self.unresolved_nodes
+            .push(Reverse((cost + estimate, cost, vertex)));
+    }
+
+    // Lowest estimate among the vertices waiting to be resolved. The heap keeps the entries of
+    // vertices that were reached again at a lower cost, they are dropped on the way
+    fn peek(&mut self) -> Option<Cost> {
+        while let Some(Reverse((estimate, cost, vertex))) = self.unresolved_nodes.peek().copied() {
+            if !self.tree.is_resolved(vertex) && self.tree.cost(vertex) == Some(cost) {
+                return Some(estimate);
+            }
+            self.unresolved_nodes.pop();
+        }
+        None
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        self.peek()?;
+        self.unresolved_nodes
+            .pop()
+            .map(|Reverse((_, _, vertex))| vertex)
+    }
+}
+
+impl BidirectionalAstar {
+    pub fn new() -> Self {
+        Self {
+            distance_heuristic: PathHeuristic::Manhattan,
+            frontiers: None,
+            meeting: None,
+            shown: HashMap::new(),
+            debug_steps: None,
+        }
+    }
+
+    pub fn with_heuristic(mut self, distance_heuristic: PathHeuristic) -> Self {
+        self.distance_heuristic = distance_heuristic;
+        self
+    }
+
+    // Both trees in one step, each node with its cost from the side that reached it
+    fn record_step(&mut self, current: (usize, usize)) {
+        if let (Some(steps), Some([forward, backward])) =
+            (&mut self.debug_steps, &mut self.frontiers)
+        {
+            let mut step = forward.tree.step(Some(current));
+            let other = backward.tree.step(None);
+            step.opened.extend(other.opened);
+            step.closed.extend(other.closed);
+            steps.push(step);
+        }
+    }
+
+    // Links every item of a tree to what the tape showed on its cell before, which may have
+    // come from the other tree
+    fn show(
+        &mut self,
+        items: Vec<TapeItem<(usize, usize), NodeType<Net>>>,
+    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        items
+            .into_iter()
+            .map(|item| match item {
+                TapeItem::Add(pos, node, _) => {
+                    let previous = self.shown.insert(pos, node);
+                    TapeItem::Add(pos, node, previous)
+                }
+                item => item,
+            })
+            .collect()
+    }
+}
+
+impl Default for BidirectionalAstar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShortestPath for BidirectionalAstar {
+    fn compute(
+        &mut self,
+        config: &ShortestPathConfig,
+        source: usize,
+    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        self.compute_streamed(config, source, &mut |_| true)
+    }
+
+    fn compute_streamed(
+        &mut self,
+        config: &ShortestPathConfig,
+        source: usize,
+        progress: SearchProgress<'_>,
+    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        // Reset state
+        self.frontiers = None;
+        self.meeting = None;
+        self.shown.clear();
+        if let Some(steps) = &mut self.debug_steps {
+            steps.clear();
+        }
+        let Some(goal) = config.goal else {
+            return Vec::new();
+        };
+        let forward = SearchTree::new(config, source).with_steps(self.debug_steps.is_some());
+        let backward = SearchTree::new(config, goal)
+            .with_steps(self.debug_steps.is_some())
+            .with_goal(Some(source));
+        self.frontiers = Some([
+            Frontier::new(forward, source, goal, self.distance_heuristic),
+            Frontier::new(backward, goal, source, self.distance_heuristic),
+        ]);
+        if source == goal {
+            self.meeting = Some((source, 0));
+        }
+
+        let mut tape = Vec::new();
+        loop {
+            let [forward, backward] = self.frontiers.as_mut().unwrap();
+            let (Some(forward_estimate), Some(backward_estimate)) =
+                (forward.peek(), backward.peek())
+            else {
+                break;
+            };
+            // No path through the vertices left on either side can be cheaper
+            if self
+                .meeting
+                .is_some_and(|(_, cost)| forward_estimate >= cost || backward_estimate >= cost)
+            {
+                break;
+            }
+            let is_backward = backward_estimate < forward_estimate;
+            let (frontier, other) = if is_backward {
+                (backward, &*forward)
+            } else {
+                (forward, &*backward)
+            };
+
+            let vertex = frontier.pop().unwrap();
+            let cost = frontier.tree.cost(vertex).unwrap();
+            let position = frontier.tree.position(vertex);
+            frontier.tree.resolve(vertex);
+            for next in config.graph.neighbours(vertex) {
+                if frontier.tree.is_resolved(next) {
+                    continue;
+                }
+                // The backward search walks the steps of the path in reverse
+                let next_position = frontier.tree.position(next);
+                let step = if is_backward {
+                    config.step_cost(next_position, position)
+                } else {
+                    config.step_cost(position, next_position)
+                };
+                let Some(step) = step else {
+                    continue;
+                };
+                if frontier.tree.reach(next, vertex, cost + step) {
+                    frontier.push(next, cost + step, self.distance_heuristic);
+                }
+                if let (Some(reached), Some(other_reached)) =
+                    (frontier.tree.cost(next), other.tree.cost(next))
+                {
+                    let total = reached + other_reached;
+                    if self.meeting.is_none_or(|(_, best)| total < best) {
+                        self.meeting = Some((next, total));
+                    }
+                }
+            }
+            let items = frontier.tree.take_tape();
+            self.record_step(position);
+            let items = self.show(items);
+            let proceed = progress(&items);
+            tape.extend(items);
+            if !proceed {
+                break;
+            }
+        }
+        tape
+    }
+
+    fn reconstruct_path(&mut self) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        let (Some([forward, backward]), Some((meeting, total))) = (&self.frontiers, self.meeting)
+        else {
+            return Vec::new();
+        };
+        // Costs on the backward half are counted from the goal
+        let mut path: Vec<(usize, Cost)> = forward
+            .tree
+            .path_to(meeting)
+            .into_iter()
+            .map(|vertex| (vertex, forward.tree.cost(vertex).unwrap()))
+            .collect();
+        let mut rest = backward.tree.path_to(meeting);
+        rest.pop();
+        path.extend(
+            rest.into_iter()
+                .rev()
+                .map(|vertex| (vertex, total - backward.tree.cost(vertex).unwrap())),
+        );
+        let inner = path.len().saturating_sub(2);
+        path.into_iter()
+            .skip(1)
+            .take(inner)
+            .map(|(vertex, cost)| {
+                let pos = forward.tree.position(vertex);
+                TapeItem::Add(
+                    pos,
+                    NodeType::Route(forward.tree.net(), cost),
+                    self.shown.get(&pos).copied(),
+                )
+            })
+            .collect()
+    }
+
+    fn get_next_unresolved(&mut self) -> Option<PathNode> {
+        let [forward, backward] = self.frontiers.as_mut()?;
+        let frontier = match (forward.peek(), backward.peek()) {
+            (Some(forward_estimate), Some(backward_estimate))
+                if backward_estimate < forward_estimate =>
+            {
+                backward
+            }
+            (Some(_), _) => forward,
+            (None, Some(_)) => backward,
+            (None, None) => return None,
+        };
+        let vertex = frontier.pop()?;
+        frontier.tree.node(vertex)
+    }
+
+    /// The goal with the cost of the path, once the two searches have met
+    fn get_next_path_node(&self) -> Option<PathNode> {
+        let [_, backward] = self.frontiers.as_ref()?;
+        let (_, cost) = self.meeting?;
+        Some(PathNode::uninformed(
+            backward.tree.position(backward.source),
+            cost,
+        ))
+    }
+
+    fn set_debugging(&mut self, enabled: bool) {
+        self.debug_steps = enabled.then(SearchLog::default);
+    }
+
+    fn debug_steps(&self) -> Option<&SearchLog> {
+        self.debug_steps.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use graph_builder::UndirectedCsrGraph;
+
+    use super::BidirectionalAstar;
+    use crate::utils::{
+        cassetta::TapeItem,
+        graphema::Lattice2D,
+        soma::common::Orientation,
+        spoor::{
+            core::{DirectionPreference, DirectionRule, ShortestPath, ShortestPathConfig},
+            dijkstra::Dijkstra,
+        },
+    };
+
+    // 4 by 4 lattice with an L shaped obstacle next to (2, 2)
+    fn config(
+        goal: Option<usize>,
+        direction_preference: Option<DirectionPreference>,
+    ) -> ShortestPathConfig {
+        let mut lattice = Lattice2D::new(4, 4);
+        lattice.fill();
+        lattice.remove_vertex((1, 1));
+        lattice.remove_vertex((2, 1));
+        lattice.remove_vertex((1, 2));
+        let graph: UndirectedCsrGraph<usize, usize> = lattice.into();
+        ShortestPathConfig {
+            graph: graph.into(),
+            goal,
+            boundary: (4, 4),
+            direction_preference,
+            net: 1,
+        }
+    }
+
+    #[test]
+    fn finds_as_cheap_a_path_as_dijkstra() {
+        let preference = DirectionPreference::new(Orientation::Vertical, DirectionRule::Soft(2));
+        for direction_preference in [None, Some(preference)] {
+            // From (0, 0) to (2, 2)
+            let config = config(Some(10), direction_preference);
+            let mut search = BidirectionalAstar::new();
+            let mut dijkstra = Dijkstra::new();
+            let tape = search.compute(&config, 0);
+            dijkstra.compute(&config, 0);
+            let cost = search.get_next_path_node().unwrap().cost_from_start;
+            assert_eq!(cost, dijkstra.get_next_path_node().unwrap().cost_from_start);
+
+            // Every step undoes to what the tape showed before, pins left alone
+            let mut shown = HashMap::new();
+            for item in tape.iter().chain(search.reconstruct_path().iter()) {
+                let TapeItem::Add(pos, node, previous) = item else {
+                    panic!("unexpected {item:?}");
+                };
+                assert!(*pos != (0, 0) && *pos != (2, 2));
+                assert_eq!(shown.insert(*pos, *node), *previous);
+            }
+            let route = search.reconstruct_path();
+            assert_eq!(route.len(), 5);
+            let TapeItem::Add(_, last, _) = route.last().unwrap() else {
+                unreachable!()
+            };
+            assert!(last.get_cost().is_some_and(|last| *last < cost));
+        }
+    }
+
+    #[test]
+    fn needs_a_goal_and_a_way_there() {
+        let mut search = BidirectionalAstar::new();
+        search.set_debugging(true);
+        assert!(search.compute(&config(None, None), 0).is_empty());
+        assert!(search.get_next_path_node().is_none());
+
+        let preference = DirectionPreference::new(Orientation::Vertical, DirectionRule::Hard);
+        search.compute(&config(Some(10), Some(preference)), 0);
+        assert!(search.get_next_path_node().is_none());
+        assert!(search.reconstruct_path().is_empty());
+        assert!(!search.debug_steps().unwrap().is_empty());
+    }
+}
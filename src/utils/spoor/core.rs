@@ -1,13 +1,23 @@
 use std::{
     cmp::Ordering,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Debug,
     hash::{Hash, Hasher},
+    sync::Arc,
 };
 
-use graph_builder::{index::Idx, UndirectedCsrGraph};
+use graph_builder::{index::Idx, Graph, UndirectedCsrGraph, UndirectedNeighborsWithValues};
 
-use crate::utils::cassetta::TapeItem;
+use crate::utils::{
+    cassetta::TapeItem,
+    graphema::{DynamicLatticeGraph, Lattice2D},
+    soma::{common::Orientation, technology::Layer},
+};
+
+use super::{
+    astar::Astar, bfs::BreadthFirst, bidirectional::BidirectionalAstar, dijkstra::Dijkstra,
+    jps::JumpPointSearch,
+};
 
 /// Routing
 /// Convert to builder pattern
@@ -15,24 +25,288 @@ use crate::utils::cassetta::TapeItem;
 pub trait ShortestPath {
     fn compute(
         &mut self,
-        config: ShortestPathConfig,
+        config: &ShortestPathConfig,
         source: usize,
     ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>>;
+    /// Like `compute`, handing what every expansion recorded to `progress` as soon as it is
+    /// recorded. The search stops early, keeping what it found so far, when `progress` returns
+    /// false. Searches that do not stream hand over the whole tape at the end
+    fn compute_streamed(
+        &mut self,
+        config: &ShortestPathConfig,
+        source: usize,
+        progress: SearchProgress<'_>,
+    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        let tape = self.compute(config, source);
+        progress(&tape);
+        tape
+    }
     fn reconstruct_path(&mut self) -> Vec<TapeItem<(usize, usize), NodeType<Net>>>;
     fn get_next_unresolved(&mut self) -> Option<PathNode>;
     fn get_next_path_node(&self) -> Option<PathNode>;
+    /// Records a [`SearchStep`] after every step of `compute` while enabled. Searches that do
+    /// not support debugging ignore it
+    fn set_debugging(&mut self, _enabled: bool) {}
+    /// Steps recorded by the last `compute`, None while debugging is off
+    fn debug_steps(&self) -> Option<&SearchLog> {
+        None
+    }
+}
+
+/// Receives the tape items recorded by one expansion of a search, returns false to stop it
+pub type SearchProgress<'a> = &'a mut dyn FnMut(&[TapeItem<(usize, usize), NodeType<Net>>]) -> bool;
+
+/// Open and closed sets of a search after one of its steps, with the costs of every node
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchSnapshot {
+    /// Node resolved by the step
+    pub current: Option<(usize, usize)>,
+    /// Nodes waiting to be resolved
+    pub open: Vec<PathNode>,
+    /// Nodes already resolved
+    pub closed: Vec<PathNode>,
+}
+
+impl SearchSnapshot {
+    pub fn new<'a>(
+        current: Option<(usize, usize)>,
+        open: impl IntoIterator<Item = &'a PathNode>,
+        closed: impl IntoIterator<Item = &'a PathNode>,
+    ) -> Self {
+        Self {
+            current,
+            open: open.into_iter().copied().collect(),
+            closed: closed.into_iter().copied().collect(),
+        }
+    }
+}
+
+/// Nodes one step of a search opened or closed, or reached again at a lower cost
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchStep {
+    /// Node resolved by the step
+    pub current: Option<(usize, usize)>,
+    pub opened: Vec<PathNode>,
+    pub closed: Vec<PathNode>,
+}
+
+/// Steps of a search, each holding only what it changed so that a long search does not copy
+/// its open and closed sets at every step. [`SearchLog::snapshot`] puts the sets back together
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchLog {
+    steps: Vec<SearchStep>,
+}
+
+impl SearchLog {
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn push(&mut self, step: SearchStep) {
+        self.steps.push(step);
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+
+    pub fn steps(&self) -> &[SearchStep] {
+        &self.steps
+    }
+
+    /// Open and closed sets after `step`, None past the last one
+    pub fn snapshot(&self, step: usize) -> Option<SearchSnapshot> {
+        let current = self.steps.get(step)?.current;
+        let mut nodes = HashMap::new();
+        for step in &self.steps[..=step] {
+            for node in &step.opened {
+                nodes.insert(node.position, (*node, false));
+            }
+            for node in &step.closed {
+                nodes.insert(node.position, (*node, true));
+            }
+        }
+        let mut snapshot = SearchSnapshot {
+            current,
+            ..SearchSnapshot::default()
+        };
+        for (node, closed) in nodes.into_values() {
+            if closed {
+                snapshot.closed.push(node);
+            } else {
+                snapshot.open.push(node);
+            }
+        }
+        Some(snapshot)
+    }
+
+    /// Open and closed sets at the end of the search
+    pub fn last(&self) -> Option<SearchSnapshot> {
+        self.snapshot(self.len().checked_sub(1)?)
+    }
 }
 
 pub struct ShortestPathConfig {
-    pub graph: UndirectedCsrGraph<usize, usize>,
+    pub graph: SearchGraph,
     pub goal: Option<usize>,
     pub boundary: (usize, usize),
+    /// Preferred routing direction of the layer being routed
+    pub direction_preference: Option<DirectionPreference>,
+    /// Net the route is recorded for
+    pub net: Net,
+}
+
+impl ShortestPathConfig {
+    /// Cost of a step between two adjacent vertices, None when the direction preference does not
+    /// allow it
+    pub fn step_cost(&self, from: (usize, usize), to: (usize, usize)) -> Option<Cost> {
+        match self.direction_preference {
+            Some(preference) => preference.segment_cost(from, to).map(|cost| cost + 1),
+            None => Some(1),
+        }
+    }
 }
 
+/// Graph a search runs on. A CSR graph is built once and never changes, a lattice graph is
+/// kept up to date edit by edit, see [`DynamicLatticeGraph`], and is shared with the grid
+/// that edits it instead of being rebuilt for every search
+#[derive(Clone, Debug)]
+pub enum SearchGraph {
+    Csr(UndirectedCsrGraph<usize, usize>),
+    Lattice(Arc<DynamicLatticeGraph>),
+}
+
+impl SearchGraph {
+    pub fn node_count(&self) -> usize {
+        match self {
+            Self::Csr(graph) => graph.node_count(),
+            Self::Lattice(graph) => graph.node_count(),
+        }
+    }
+
+    pub fn neighbours(&self, vertex: usize) -> impl Iterator<Item = usize> + '_ {
+        let (csr, lattice) = match self {
+            Self::Csr(graph) => (Some(graph.neighbors_with_values(vertex)), None),
+            Self::Lattice(graph) => (None, Some(graph.neighbours(vertex))),
+        };
+        csr.into_iter()
+            .flatten()
+            .map(|neighbour| neighbour.target)
+            .chain(lattice.into_iter().flatten().copied())
+    }
+}
+
+impl From<UndirectedCsrGraph<usize, usize>> for SearchGraph {
+    fn from(graph: UndirectedCsrGraph<usize, usize>) -> Self {
+        Self::Csr(graph)
+    }
+}
+
+impl From<Arc<DynamicLatticeGraph>> for SearchGraph {
+    fn from(graph: Arc<DynamicLatticeGraph>) -> Self {
+        Self::Lattice(graph)
+    }
+}
+
+impl From<DynamicLatticeGraph> for SearchGraph {
+    fn from(graph: DynamicLatticeGraph) -> Self {
+        Self::Lattice(Arc::new(graph))
+    }
+}
+
+/// The shortest path searches available to a router, by name. One of them is selected at a
+/// time and runs every search
 pub struct ShortestPathAlgo {
     config: ShortestPathConfig,
     algo_map: HashMap<String, Box<dyn ShortestPath>>,
     algo: Box<dyn ShortestPath>,
+    algo_name: String,
+}
+
+impl ShortestPathAlgo {
+    /// Registers A*, Dijkstra, breadth-first search, bidirectional A* and jump point search as
+    /// `astar`, `dijkstra`, `bfs`, `bidirectional` and `jps`, with A* selected
+    pub fn new(config: ShortestPathConfig) -> Self {
+        let mut algo_map: HashMap<String, Box<dyn ShortestPath>> = HashMap::new();
+        algo_map.insert(String::from("dijkstra"), Box::new(Dijkstra::new()));
+        algo_map.insert(String::from("bfs"), Box::new(BreadthFirst::new()));
+        algo_map.insert(
+            String::from("bidirectional"),
+            Box::new(BidirectionalAstar::new()),
+        );
+        algo_map.insert(String::from("jps"), Box::new(JumpPointSearch::new()));
+        Self {
+            config,
+            algo_map,
+            algo: Box::new(Astar::new()),
+            algo_name: String::from("astar"),
+        }
+    }
+
+    /// Adds a search under `name`, replacing any search of that name
+    pub fn register(&mut self, name: impl Into<String>, algo: Box<dyn ShortestPath>) {
+        let name = name.into();
+        if name == self.algo_name {
+            self.algo = algo;
+        } else {
+            self.algo_map.insert(name, algo);
+        }
+    }
+
+    /// Names of the registered searches, sorted
+    pub fn algo_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .algo_map
+            .keys()
+            .map(String::as_str)
+            .chain(std::iter::once(self.algo_name.as_str()))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn selected(&self) -> &str {
+        &self.algo_name
+    }
+
+    pub fn select(&mut self, name: &str) -> Result<(), String> {
+        if name == self.algo_name {
+            return Ok(());
+        }
+        let algo = self
+            .algo_map
+            .remove(name)
+            .ok_or_else(|| format!("Unknown search {name:?}"))?;
+        let previous = std::mem::replace(&mut self.algo, algo);
+        let previous_name = std::mem::replace(&mut self.algo_name, String::from(name));
+        self.algo_map.insert(previous_name, previous);
+        Ok(())
+    }
+
+    pub fn config(&self) -> &ShortestPathConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: ShortestPathConfig) {
+        self.config = config;
+    }
+
+    /// Runs the selected search from `source` and returns its exploration tape
+    pub fn compute(&mut self, source: usize) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        self.algo.compute(&self.config, source)
+    }
+
+    pub fn reconstruct_path(&mut self) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        self.algo.reconstruct_path()
+    }
+
+    pub fn algo_mut(&mut self) -> &mut dyn ShortestPath {
+        self.algo.as_mut()
+    }
 }
 
 // A*
@@ -58,6 +332,50 @@ where
 // Physarum
 //
 
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// DirectionPreference
+//
+//////////////////////////////////////////////////////////////////////////////////////
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DirectionRule {
+    /// Segments against the preferred direction are not allowed
+    Hard,
+    /// Segments against the preferred direction are allowed with an extra cost
+    Soft(Cost),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DirectionPreference {
+    pub orientation: Orientation,
+    pub rule: DirectionRule,
+}
+
+impl DirectionPreference {
+    pub fn new(orientation: Orientation, rule: DirectionRule) -> Self {
+        Self { orientation, rule }
+    }
+
+    /// Returns None if the layer has no preferred direction
+    pub fn from_layer(layer: &Layer, rule: DirectionRule) -> Option<Self> {
+        layer
+            .orientation
+            .map(|orientation| Self::new(orientation, rule))
+    }
+
+    /// Extra cost of routing a segment between two adjacent vertices or None if it is not allowed
+    pub fn segment_cost(&self, from: (usize, usize), to: (usize, usize)) -> Option<Cost> {
+        if Orientation::get_direction(from, to) == Some(self.orientation) {
+            Some(0)
+        } else {
+            match self.rule {
+                DirectionRule::Hard => None,
+                DirectionRule::Soft(cost) => Some(cost),
+            }
+        }
+    }
+}
+
 //////////////////////////////////////////////////////////////////////////////////////
 //
 // DistanceHeuristic
@@ -140,6 +458,17 @@ impl PathNode {
         }
     }
 
+    /// Node of a search without a heuristic, where the total cost is the cost from the start
+    pub fn uninformed(position: (usize, usize), cost_from_start: usize) -> Self {
+        Self {
+            position,
+            cost_from_start,
+            cost_to_target: None,
+            cost_total: cost_from_start,
+            orientation_cost: 0,
+        }
+    }
+
     pub fn with_start_cost(mut self, cost_start: usize) -> Self {
         self.cost_from_start = cost_start;
         self
@@ -160,6 +489,12 @@ impl PathNode {
         self.orientation_cost = orientation_cost;
         self
     }
+
+    /// Cost from the start, estimated cost to the target and their sum, better known as g, h
+    /// and f
+    pub fn costs(&self) -> (usize, Option<usize>, usize) {
+        (self.cost_from_start, self.cost_to_target, self.cost_total)
+    }
 }
 
 impl PartialEq for PathNode {
@@ -223,7 +558,7 @@ impl Ord for PathNode {
 pub type Net = usize;
 pub type Cost = usize;
 //type Weight = i32;
-#[derive(Copy, Clone, Debug, Hash)]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum NodeType<Net> {
     Obstacle,
     Boundary,
@@ -254,3 +589,214 @@ impl NodeType<Net> {
         }
     }
 }
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// SearchTree
+//
+//////////////////////////////////////////////////////////////////////////////////////
+/// Vertices reached by a search from a single source, with their cost and the vertex they were
+/// reached from. Every change is recorded on an exploration tape: vertices are added as
+/// `Unresolved` when reached, become `Resolved` when the search settles them and `Route` when
+/// they are on the path. The source and the goal are left out, they hold the pins of the net
+#[derive(Clone, Debug)]
+pub struct SearchTree {
+    lattice: Lattice2D,
+    source: usize,
+    goal: Option<usize>,
+    net: Net,
+    costs: HashMap<usize, Cost>,
+    parents: HashMap<usize, usize>,
+    resolved: HashSet<usize>,
+    tape: Vec<TapeItem<(usize, usize), NodeType<Net>>>,
+    // Vertices changed since the last step, None unless the steps are recorded
+    changed: Option<Vec<usize>>,
+}
+
+impl SearchTree {
+    pub fn new(config: &ShortestPathConfig, source: usize) -> Self {
+        let mut tree = Self {
+            lattice: Lattice2D::new(config.boundary.0, config.boundary.1),
+            source,
+            goal: config.goal,
+            net: config.net,
+            costs: HashMap::new(),
+            parents: HashMap::new(),
+            resolved: HashSet::new(),
+            tape: Vec::new(),
+            changed: None,
+        };
+        tree.costs.insert(source, 0);
+        tree
+    }
+
+    /// Keeps the vertices every step changes for [`SearchTree::step`]
+    pub fn with_steps(mut self, enabled: bool) -> Self {
+        self.changed = enabled.then(|| vec![self.source]);
+        self
+    }
+
+    /// Replaces the goal of the configuration, for searches that run backwards from it
+    pub fn with_goal(mut self, goal: Option<usize>) -> Self {
+        self.goal = goal;
+        self
+    }
+
+    pub fn position(&self, vertex: usize) -> (usize, usize) {
+        self.lattice.to_vertex_coords(vertex)
+    }
+
+    pub fn vertex(&self, position: (usize, usize)) -> usize {
+        self.lattice.to_vertex_index(position.0, position.1)
+    }
+
+    pub fn net(&self) -> Net {
+        self.net
+    }
+
+    pub fn cost(&self, vertex: usize) -> Option<Cost> {
+        self.costs.get(&vertex).copied()
+    }
+
+    /// Vertex `vertex` was last reached from, None for the source and unreached vertices
+    pub fn parent(&self, vertex: usize) -> Option<usize> {
+        self.parents.get(&vertex).copied()
+    }
+
+    pub fn is_reached(&self, vertex: usize) -> bool {
+        self.costs.contains_key(&vertex)
+    }
+
+    pub fn is_resolved(&self, vertex: usize) -> bool {
+        self.resolved.contains(&vertex)
+    }
+
+    pub fn is_goal(&self, vertex: usize) -> bool {
+        self.goal == Some(vertex)
+    }
+
+    pub fn node(&self, vertex: usize) -> Option<PathNode> {
+        self.cost(vertex)
+            .map(|cost| PathNode::uninformed(self.position(vertex), cost))
+    }
+
+    /// Reaches `vertex` from `parent` at `cost`. Returns false, changing nothing, when it was
+    /// already reached at that cost or less
+    pub fn reach(&mut self, vertex: usize, parent: usize, cost: Cost) -> bool {
+        let previous = self.cost(vertex);
+        if previous.is_some_and(|previous| previous <= cost) {
+            return false;
+        }
+        self.costs.insert(vertex, cost);
+        self.parents.insert(vertex, parent);
+        if let Some(changed) = &mut self.changed {
+            changed.push(vertex);
+        }
+        self.record(
+            vertex,
+            NodeType::Unresolved(cost),
+            previous.map(NodeType::Unresolved),
+        );
+        true
+    }
+
+    pub fn resolve(&mut self, vertex: usize) {
+        if let Some(cost) = self.cost(vertex) {
+            if self.resolved.insert(vertex) {
+                if let Some(changed) = &mut self.changed {
+                    changed.push(vertex);
+                }
+                self.record(
+                    vertex,
+                    NodeType::Resolved(cost),
+                    Some(NodeType::Unresolved(cost)),
+                );
+            }
+        }
+    }
+
+    fn record(&mut self, vertex: usize, item: NodeType<Net>, previous: Option<NodeType<Net>>) {
+        if vertex != self.source && !self.is_goal(vertex) {
+            self.tape
+                .push(TapeItem::Add(self.position(vertex), item, previous));
+        }
+    }
+
+    /// Exploration recorded since the last call
+    pub fn take_tape(&mut self) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        std::mem::take(&mut self.tape)
+    }
+
+    /// Vertices from the source to the goal, empty if the goal was not reached
+    pub fn path(&self) -> Vec<usize> {
+        self.goal.map(|goal| self.path_to(goal)).unwrap_or_default()
+    }
+
+    /// Vertices from the source to `vertex`, empty if it was not reached
+    pub fn path_to(&self, vertex: usize) -> Vec<usize> {
+        if !self.is_reached(vertex) {
+            return Vec::new();
+        }
+        let mut path = vec![vertex];
+        let mut current = vertex;
+        while let Some(parent) = self.parent(current) {
+            path.push(parent);
+            current = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Marks the vertices between the source and the goal as the route of the net
+    pub fn path_tape(&self) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        let path = self.path();
+        path.iter()
+            .skip(1)
+            .take(path.len().saturating_sub(2))
+            .map(|vertex| self.route_item(*vertex, self.costs[vertex]))
+            .collect()
+    }
+
+    /// Marks `vertex` as part of the route at `cost`, over whatever the tree recorded for it.
+    /// Searches that skip over vertices use it for the ones they never reached
+    pub fn route_item(&self, vertex: usize, cost: Cost) -> TapeItem<(usize, usize), NodeType<Net>> {
+        let previous = self.cost(vertex).map(|reached| {
+            if self.is_resolved(vertex) {
+                NodeType::Resolved(reached)
+            } else {
+                NodeType::Unresolved(reached)
+            }
+        });
+        TapeItem::Add(
+            self.position(vertex),
+            NodeType::Route(self.net, cost),
+            previous,
+        )
+    }
+
+    /// Nodes changed since the last step, see [`ShortestPath::debug_steps`]. Empty unless the
+    /// tree was built with [`SearchTree::with_steps`]
+    pub fn step(&mut self, current: Option<(usize, usize)>) -> SearchStep {
+        let mut step = SearchStep {
+            current,
+            ..SearchStep::default()
+        };
+        for vertex in self
+            .changed
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+        {
+            let Some(cost) = self.cost(vertex) else {
+                continue;
+            };
+            let node = PathNode::uninformed(self.position(vertex), cost);
+            if self.is_resolved(vertex) {
+                step.closed.push(node);
+            } else {
+                step.opened.push(node);
+            }
+        }
+        step
+    }
+}
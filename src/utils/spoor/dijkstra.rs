@@ -0,0 +1,228 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::utils::cassetta::TapeItem;
+
+use super::core::{
+    Cost, Net, NodeType, PathNode, SearchLog, SearchProgress, SearchTree, ShortestPath,
+    ShortestPathConfig,
+};
+
+/// Settles the vertices in order of their cost from the source, which makes the first path to
+/// the goal the cheapest one. The cost of a step comes from
+/// [`ShortestPathConfig::step_cost`]. Without a goal the whole graph is explored
+pub struct Dijkstra {
+    tree: Option<SearchTree>,
+    unresolved_nodes: BinaryHeap<Reverse<(Cost, usize)>>,
+    // Steps of the last search, None while debugging is off
+    debug_steps: Option<SearchLog>,
+}
+
+impl Dijkstra {
+    pub fn new() -> Self {
+        Self {
+            tree: None,
+            unresolved_nodes: BinaryHeap::new(),
+            debug_steps: None,
+        }
+    }
+
+    fn record_step(&mut self, current: (usize, usize)) {
+        if let (Some(steps), Some(tree)) = (&mut self.debug_steps, &mut self.tree) {
+            steps.push(tree.step(Some(current)));
+        }
+    }
+}
+
+impl Default for Dijkstra {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShortestPath for Dijkstra {
+    fn compute(
+        &mut self,
+        config: &ShortestPathConfig,
+        source: usize,
+    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        self.compute_streamed(config, source, &mut |_| true)
+    }
+
+    fn compute_streamed(
+        &mut self,
+        config: &ShortestPathConfig,
+        source: usize,
+        progress: SearchProgress<'_>,
+    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        // Reset state
+        self.unresolved_nodes.clear();
+        if let Some(steps) = &mut self.debug_steps {
+            steps.clear();
+        }
+        self.tree = Some(SearchTree::new(config, source).with_steps(self.debug_steps.is_some()));
+        self.unresolved_nodes.push(Reverse((0, source)));
+
+        let mut tape = Vec::new();
+        while let Some(node) = self.get_next_unresolved() {
+            let tree = self.tree.as_mut().unwrap();
+            let vertex = tree.vertex(node.position);
+            tree.resolve(vertex);
+            self.record_step(node.position);
+
+            let tree = self.tree.as_mut().unwrap();
+            if tree.is_goal(vertex) {
+                break;
+            }
+            for next in config.graph.neighbours(vertex) {
+                if tree.is_resolved(next) {
+                    continue;
+                }
+                if let Some(step) = config.step_cost(node.position, tree.position(next)) {
+                    let cost = node.cost_from_start + step;
+                    if tree.reach(next, vertex, cost) {
+                        self.unresolved_nodes.push(Reverse((cost, next)));
+                    }
+                }
+            }
+            let items = tree.take_tape();
+            let proceed = progress(&items);
+            tape.extend(items);
+            if !proceed {
+                break;
+            }
+        }
+        tape
+    }
+
+    fn reconstruct_path(&mut self) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        self.tree
+            .as_ref()
+            .map(SearchTree::path_tape)
+            .unwrap_or_default()
+    }
+
+    fn get_next_unresolved(&mut self) -> Option<PathNode> {
+        let tree = self.tree.as_ref()?;
+        // The heap keeps the entries of vertices that were reached again at a lower cost
+        while let Some(Reverse((cost, vertex))) = self.unresolved_nodes.pop() {
+            if !tree.is_resolved(vertex) && tree.cost(vertex) == Some(cost) {
+                return tree.node(vertex);
+            }
+        }
+        None
+    }
+
+    /// The goal, once the search has reached it
+    fn get_next_path_node(&self) -> Option<PathNode> {
+        let tree = self.tree.as_ref()?;
+        tree.path().last().and_then(|goal| tree.node(*goal))
+    }
+
+    fn set_debugging(&mut self, enabled: bool) {
+        self.debug_steps = enabled.then(SearchLog::default);
+    }
+
+    fn debug_steps(&self) -> Option<&SearchLog> {
+        self.debug_steps.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graph_builder::UndirectedCsrGraph;
+
+    use super::Dijkstra;
+    use crate::utils::{
+        cassetta::TapeItem,
+        graphema::{DynamicLatticeGraph, Lattice2D},
+        soma::common::Orientation,
+        spoor::core::{
+            DirectionPreference, DirectionRule, NodeType, ShortestPath, ShortestPathConfig,
+        },
+    };
+
+    // 3 by 3 lattice with the top two cells of the middle column blocked
+    fn config(direction_preference: Option<DirectionPreference>) -> ShortestPathConfig {
+        let mut lattice = Lattice2D::new(3, 3);
+        lattice.fill();
+        lattice.remove_vertex((1, 0));
+        lattice.remove_vertex((1, 1));
+        let graph: UndirectedCsrGraph<usize, usize> = lattice.into();
+        ShortestPathConfig {
+            graph: graph.into(),
+            goal: Some(2),
+            boundary: (3, 3),
+            direction_preference,
+            net: 4,
+        }
+    }
+
+    fn route(tape: &[TapeItem<(usize, usize), NodeType<usize>>]) -> Vec<((usize, usize), usize)> {
+        tape.iter()
+            .map(|item| match item {
+                TapeItem::Add(pos, NodeType::Route(4, cost), _) => (*pos, *cost),
+                _ => panic!("unexpected {item:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn goes_around_obstacles() {
+        let mut dijkstra = Dijkstra::new();
+        dijkstra.set_debugging(true);
+        let tape = dijkstra.compute(&config(None), 0);
+        assert!(!tape.is_empty());
+        assert!(tape.iter().all(|item| matches!(
+            item,
+            TapeItem::Add(pos, _, _) if *pos != (0, 0) && *pos != (2, 0)
+        )));
+        assert_eq!(
+            route(&dijkstra.reconstruct_path()),
+            vec![
+                ((0, 1), 1),
+                ((0, 2), 2),
+                ((1, 2), 3),
+                ((2, 2), 4),
+                ((2, 1), 5)
+            ]
+        );
+        assert_eq!(dijkstra.get_next_path_node().unwrap().cost_from_start, 6);
+        assert_eq!(
+            dijkstra.debug_steps().unwrap().last().unwrap().current,
+            Some((2, 0))
+        );
+    }
+
+    #[test]
+    fn pays_for_soft_direction_preference() {
+        let preference = DirectionPreference::new(Orientation::Vertical, DirectionRule::Soft(2));
+        let mut dijkstra = Dijkstra::new();
+        dijkstra.compute(&config(Some(preference)), 0);
+        // Two horizontal steps cost 3 each
+        assert_eq!(dijkstra.get_next_path_node().unwrap().cost_from_start, 10);
+
+        let preference = DirectionPreference::new(Orientation::Vertical, DirectionRule::Hard);
+        dijkstra.compute(&config(Some(preference)), 0);
+        assert!(dijkstra.get_next_path_node().is_none());
+        assert!(dijkstra.reconstruct_path().is_empty());
+    }
+
+    #[test]
+    fn searches_an_edited_lattice_graph() {
+        let mut lattice = Lattice2D::new(3, 3);
+        lattice.fill();
+        let mut graph = DynamicLatticeGraph::new(lattice);
+        graph.remove_vertex((1, 0));
+        graph.remove_vertex((1, 1));
+        let mut edited = config(None);
+        edited.graph = graph.into();
+
+        // Same search as on the CSR graph built from the same lattice
+        let mut dijkstra = Dijkstra::new();
+        dijkstra.compute(&config(None), 0);
+        let route = dijkstra.reconstruct_path();
+        dijkstra.compute(&edited, 0);
+        assert_eq!(dijkstra.reconstruct_path(), route);
+        assert_eq!(dijkstra.get_next_path_node().unwrap().cost_from_start, 6);
+    }
+}
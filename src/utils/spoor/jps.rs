@@ -0,0 +1,317 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::utils::{cassetta::TapeItem, graphema::Lattice2D};
+
+use super::core::{
+    Cost, Net, NodeType, PathHeuristic, PathNode, SearchLog, SearchProgress, SearchTree,
+    ShortestPath, ShortestPathConfig,
+};
+
+/// Jump point search for rectilinear lattices where every step costs the same. Instead of
+/// adding every neighbour it jumps in a straight line until it hits a wall, the goal or a cell
+/// where a wall ends and a turn could be needed, so only those jump points show up on the
+/// exploration tape. The graph is read back into a [`Lattice2D`], diagonal edges and the
+/// direction preference are ignored. Without a goal every jump point is explored
+pub struct JumpPointSearch {
+    tree: Option<SearchTree>,
+    lattice: Lattice2D,
+    // (estimated total cost, cost from the source, vertex)
+    unresolved_nodes: BinaryHeap<Reverse<(Cost, Cost, usize)>>,
+    // Steps of the last search, None while debugging is off
+    debug_steps: Option<SearchLog>,
+}
+
+impl JumpPointSearch {
+    pub fn new() -> Self {
+        Self {
+            tree: None,
+            lattice: Lattice2D::new(0, 0),
+            unresolved_nodes: BinaryHeap::new(),
+            debug_steps: None,
+        }
+    }
+
+    fn record_step(&mut self, current: (usize, usize)) {
+        if let (Some(steps), Some(tree)) = (&mut self.debug_steps, &mut self.tree) {
+            steps.push(tree.step(Some(current)));
+        }
+    }
+
+    fn is_walkable(&self, (column, row): (isize, isize)) -> bool {
+        column >= 0 && row >= 0 && self.lattice.has_vertex((column as usize, row as usize))
+    }
+
+    // First jump point met walking from `from` in `direction`, None if a wall comes first
+    fn jump(
+        &self,
+        from: (usize, usize),
+        (dx, dy): (isize, isize),
+        goal: Option<(usize, usize)>,
+    ) -> Option<(usize, usize)> {
+        let walkable = |column, row| self.is_walkable((column, row));
+        let (mut x, mut y) = (from.0 as isize, from.1 as isize);
+        loop {
+            x += dx;
+            y += dy;
+            if !walkable(x, y) {
+                return None;
+            }
+            let position = (x as usize, y as usize);
+            if Some(position) == goal {
+                return Some(position);
+            }
+            if dx != 0 {
+                // A wall above or below ends here, the cell behind it can only be reached by
+                // turning
+                if (walkable(x, y - 1) && !walkable(x - dx, y - 1))
+                    || (walkable(x, y + 1) && !walkable(x - dx, y + 1))
+                {
+                    return Some(position);
+                }
+            } else {
+                if (walkable(x - 1, y) && !walkable(x - 1, y - dy))
+                    || (walkable(x + 1, y) && !walkable(x + 1, y - dy))
+                {
+                    return Some(position);
+                }
+                // Vertical jumps stop where a horizontal one would find something
+                if self.jump(position, (1, 0), goal).is_some()
+                    || self.jump(position, (-1, 0), goal).is_some()
+                {
+                    return Some(position);
+                }
+            }
+        }
+    }
+
+    // Directions worth jumping in from a jump point, given the direction it was reached from
+    fn directions(position: (usize, usize), parent: Option<(usize, usize)>) -> Vec<(isize, isize)> {
+        let Some(parent) = parent else {
+            return vec![(1, 0), (-1, 0), (0, 1), (0, -1)];
+        };
+        let dx = (position.0 as isize - parent.0 as isize).signum();
+        let dy = (position.1 as isize - parent.1 as isize).signum();
+        if dx != 0 {
+            vec![(dx, 0), (0, 1), (0, -1)]
+        } else {
+            vec![(0, dy), (1, 0), (-1, 0)]
+        }
+    }
+}
+
+impl Default for JumpPointSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShortestPath for JumpPointSearch {
+    fn compute(
+        &mut self,
+        config: &ShortestPathConfig,
+        source: usize,
+    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        self.compute_streamed(config, source, &mut |_| true)
+    }
+
+    fn compute_streamed(
+        &mut self,
+        config: &ShortestPathConfig,
+        source: usize,
+        progress: SearchProgress<'_>,
+    ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        // Reset state
+        self.unresolved_nodes.clear();
+        if let Some(steps) = &mut self.debug_steps {
+            steps.clear();
+        }
+        let (columns, rows) = config.boundary;
+        self.lattice = Lattice2D::new(columns, rows);
+        for vertex in 0..config.graph.node_count().min(columns * rows) {
+            if config.graph.neighbours(vertex).next().is_some() {
+                self.lattice
+                    .add_vertex(self.lattice.to_vertex_coords(vertex));
+            }
+        }
+        let tree = SearchTree::new(config, source).with_steps(self.debug_steps.is_some());
+        let goal = config.goal.map(|goal| tree.position(goal));
+        let estimate = |position| {
+            goal.map_or(0, |goal| {
+                PathHeuristic::Manhattan.cost_estimate(position, goal)
+            })
+        };
+        self.unresolved_nodes
+            .push(Reverse((estimate(tree.position(source)), 0, source)));
+        self.tree = Some(tree);
+
+        let mut tape = Vec::new();
+        while let Some(node) = self.get_next_unresolved() {
+            let tree = self.tree.as_mut().unwrap();
+            let vertex = tree.vertex(node.position);
+            tree.resolve(vertex);
+            self.record_step(node.position);
+
+            let tree = self.tree.as_ref().unwrap();
+            if tree.is_goal(vertex) {
+                break;
+            }
+            let parent = tree.parent(vertex).map(|parent| tree.position(parent));
+            let jump_points: Vec<(usize, usize)> = Self::directions(node.position, parent)
+                .into_iter()
+                .filter_map(|direction| self.jump(node.position, direction, goal))
+                .collect();
+
+            let tree = self.tree.as_mut().unwrap();
+            for jump_point in jump_points {
+                let next = tree.vertex(jump_point);
+                if tree.is_resolved(next) {
+                    continue;
+                }
+                let cost = node.cost_from_start
+                    + node.position.0.abs_diff(jump_point.0)
+                    + node.position.1.abs_diff(jump_point.1);
+                if tree.reach(next, vertex, cost) {
+                    self.unresolved_nodes
+                        .push(Reverse((cost + estimate(jump_point), cost, next)));
+                }
+            }
+            let items = tree.take_tape();
+            let proceed = progress(&items);
+            tape.extend(items);
+            if !proceed {
+                break;
+            }
+        }
+        tape
+    }
+
+    /// Fills in the cells between the jump points of the path
+    fn reconstruct_path(&mut self) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        let Some(tree) = &self.tree else {
+            return Vec::new();
+        };
+        let path = tree.path();
+        let mut route = Vec::new();
+        for segment in path.windows(2) {
+            let from = tree.position(segment[0]);
+            let to = tree.position(segment[1]);
+            let cost = tree.cost(segment[0]).unwrap();
+            let dx = (to.0 as isize - from.0 as isize).signum();
+            let dy = (to.1 as isize - from.1 as isize).signum();
+            let steps = from.0.abs_diff(to.0) + from.1.abs_diff(to.1);
+            for step in 1..=steps {
+                let position = (
+                    (from.0 as isize + dx * step as isize) as usize,
+                    (from.1 as isize + dy * step as isize) as usize,
+                );
+                route.push(tree.route_item(tree.vertex(position), cost + step));
+            }
+        }
+        // The goal keeps its pin
+        route.pop();
+        route
+    }
+
+    fn get_next_unresolved(&mut self) -> Option<PathNode> {
+        let tree = self.tree.as_ref()?;
+        // The heap keeps the entries of vertices that were reached again at a lower cost
+        while let Some(Reverse((_, cost, vertex))) = self.unresolved_nodes.pop() {
+            if !tree.is_resolved(vertex) && tree.cost(vertex) == Some(cost) {
+                return tree.node(vertex);
+            }
+        }
+        None
+    }
+
+    /// The goal, once the search has reached it
+    fn get_next_path_node(&self) -> Option<PathNode> {
+        let tree = self.tree.as_ref()?;
+        tree.path().last().and_then(|goal| tree.node(*goal))
+    }
+
+    fn set_debugging(&mut self, enabled: bool) {
+        self.debug_steps = enabled.then(SearchLog::default);
+    }
+
+    fn debug_steps(&self) -> Option<&SearchLog> {
+        self.debug_steps.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graph_builder::UndirectedCsrGraph;
+
+    use super::JumpPointSearch;
+    use crate::utils::{
+        cassetta::TapeItem,
+        graphema::Lattice2D,
+        spoor::{
+            bfs::BreadthFirst,
+            core::{NodeType, ShortestPath, ShortestPathConfig},
+        },
+    };
+
+    // 7 by 5 lattice split by a wall down the middle column, open at the bottom
+    fn config(goal: Option<usize>, open: bool) -> ShortestPathConfig {
+        let mut lattice = Lattice2D::new(7, 5);
+        lattice.fill();
+        for row in 0..4 {
+            lattice.remove_vertex((3, row));
+        }
+        if !open {
+            lattice.remove_vertex((3, 4));
+        }
+        let graph: UndirectedCsrGraph<usize, usize> = lattice.into();
+        ShortestPathConfig {
+            graph: graph.into(),
+            goal,
+            boundary: (7, 5),
+            direction_preference: None,
+            net: 2,
+        }
+    }
+
+    #[test]
+    fn jumps_to_the_goal() {
+        // From (0, 1) to (6, 1)
+        let config = config(Some(13), true);
+        let mut jps = JumpPointSearch::new();
+        let mut bfs = BreadthFirst::new();
+        let jps_tape = jps.compute(&config, 7);
+        let bfs_tape = bfs.compute(&config, 7);
+        let cost = jps.get_next_path_node().unwrap().cost_from_start;
+        assert_eq!(cost, bfs.get_next_path_node().unwrap().cost_from_start);
+        assert!(jps_tape.len() < bfs_tape.len());
+
+        // The route steps from cell to cell between the pins
+        let route: Vec<((usize, usize), usize)> = jps
+            .reconstruct_path()
+            .into_iter()
+            .map(|item| match item {
+                TapeItem::Add(pos, NodeType::Route(2, cost), _) => (pos, cost),
+                item => panic!("unexpected {item:?}"),
+            })
+            .collect();
+        assert_eq!(route.len(), cost - 1);
+        let mut previous = ((0, 1), 0);
+        for (pos, step) in route.iter().copied().chain([((6, 1), cost)]) {
+            assert_eq!(
+                previous.0 .0.abs_diff(pos.0) + previous.0 .1.abs_diff(pos.1),
+                1
+            );
+            assert_eq!(step, previous.1 + 1);
+            previous = (pos, step);
+        }
+    }
+
+    #[test]
+    fn walls_stop_the_search() {
+        let mut jps = JumpPointSearch::new();
+        jps.set_debugging(true);
+        jps.compute(&config(Some(13), false), 7);
+        assert!(jps.get_next_path_node().is_none());
+        assert!(jps.reconstruct_path().is_empty());
+        assert!(!jps.debug_steps().unwrap().is_empty());
+    }
+}
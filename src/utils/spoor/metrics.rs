@@ -0,0 +1,148 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+use super::core::{Net, NodeType};
+
+/// A single routing layer of a routed grid, keyed by (column, row)
+pub type RoutingLayer = HashMap<(usize, usize), NodeType<Net>>;
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// RoutingMetrics
+//
+//////////////////////////////////////////////////////////////////////////////////////
+/// Quality metrics of a routed grid used to compare routing algorithms quantitatively
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RoutingMetrics {
+    /// Sum of the wirelength of all nets measured in grid edges
+    pub total_wirelength: usize,
+    /// Number of cells where a wire changes direction
+    pub bends: usize,
+    /// Number of same-net connections between adjacent layers
+    pub vias: usize,
+    /// Highest ratio of occupied to available cells over all gcells
+    pub max_congestion: f64,
+    /// Wirelength of each net measured in grid edges
+    pub net_wirelength: BTreeMap<Net, usize>,
+}
+
+impl RoutingMetrics {
+    /// Computes the metrics of the given layers (ordered bottom to top).
+    /// Congestion is measured over square gcells of `gcell_size` cells per side.
+    pub fn new(layers: &[RoutingLayer], gcell_size: usize) -> Self {
+        let mut metrics = Self::default();
+        let gcell_size = gcell_size.max(1);
+        let mut gcell_usage: HashMap<(usize, usize), usize> = HashMap::new();
+
+        for layer in layers {
+            for (&(col, row), node) in layer.iter() {
+                let net = match node.get_net() {
+                    Some(net) => *net,
+                    None => continue,
+                };
+                let is_same_net = |vertex: (usize, usize)| {
+                    layer.get(&vertex).and_then(|other| other.get_net()) == Some(&net)
+                };
+
+                // Count every edge once by only looking right and below
+                let mut length = 0;
+                if is_same_net((col + 1, row)) {
+                    length += 1;
+                }
+                if is_same_net((col, row + 1)) {
+                    length += 1;
+                }
+                *metrics.net_wirelength.entry(net).or_insert(0) += length;
+                metrics.total_wirelength += length;
+
+                let horizontal =
+                    is_same_net((col + 1, row)) || (col > 0 && is_same_net((col - 1, row)));
+                let vertical =
+                    is_same_net((col, row + 1)) || (row > 0 && is_same_net((col, row - 1)));
+                if horizontal && vertical {
+                    metrics.bends += 1;
+                }
+
+                *gcell_usage
+                    .entry((col / gcell_size, row / gcell_size))
+                    .or_insert(0) += 1;
+            }
+        }
+
+        for (lower, upper) in layers.iter().zip(layers.iter().skip(1)) {
+            metrics.vias += lower
+                .iter()
+                .filter(|(vertex, node)| {
+                    node.get_net().is_some()
+                        && upper.get(*vertex).and_then(|other| other.get_net()) == node.get_net()
+                })
+                .count();
+        }
+
+        let capacity = (gcell_size * gcell_size * layers.len().max(1)) as f64;
+        metrics.max_congestion = gcell_usage
+            .values()
+            .map(|usage| *usage as f64 / capacity)
+            .fold(0.0, f64::max);
+
+        metrics
+    }
+}
+
+impl Display for RoutingMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Routing Metrics")?;
+        writeln!(f, "  Total wirelength: {}", self.total_wirelength)?;
+        writeln!(f, "  Bends: {}", self.bends)?;
+        writeln!(f, "  Vias: {}", self.vias)?;
+        writeln!(f, "  Max congestion: {:.2}", self.max_congestion)?;
+        writeln!(f, "  Net wirelength:")?;
+        for (net, length) in self.net_wirelength.iter() {
+            writeln!(f, "    Net {net}: {length}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RoutingLayer, RoutingMetrics};
+    use crate::utils::spoor::core::NodeType;
+
+    #[test]
+    fn l_shaped_route() {
+        let mut layer = RoutingLayer::new();
+        layer.insert((0, 0), NodeType::Start(0));
+        layer.insert((1, 0), NodeType::Route(0, 1));
+        layer.insert((2, 0), NodeType::Route(0, 2));
+        layer.insert((2, 1), NodeType::Route(0, 3));
+        layer.insert((2, 2), NodeType::Target(0));
+        layer.insert((0, 2), NodeType::Obstacle);
+
+        let metrics = RoutingMetrics::new(&[layer], 4);
+        assert_eq!(metrics.total_wirelength, 4, "{metrics}");
+        assert_eq!(metrics.bends, 1, "{metrics}");
+        assert_eq!(metrics.vias, 0, "{metrics}");
+        assert_eq!(metrics.net_wirelength.get(&0), Some(&4), "{metrics}");
+        assert_eq!(metrics.max_congestion, 5.0 / 16.0, "{metrics}");
+    }
+
+    #[test]
+    fn vias_between_layers() {
+        let mut lower = RoutingLayer::new();
+        lower.insert((0, 0), NodeType::Start(0));
+        lower.insert((1, 0), NodeType::Route(0, 1));
+        lower.insert((3, 3), NodeType::Start(1));
+        let mut upper = RoutingLayer::new();
+        upper.insert((1, 0), NodeType::Route(0, 2));
+        upper.insert((1, 1), NodeType::Target(0));
+        upper.insert((3, 3), NodeType::Route(2, 1));
+
+        let metrics = RoutingMetrics::new(&[lower, upper], 2);
+        assert_eq!(metrics.vias, 1, "{metrics}");
+        assert_eq!(metrics.total_wirelength, 2, "{metrics}");
+        assert_eq!(metrics.net_wirelength.get(&1), Some(&0), "{metrics}");
+    }
+}
@@ -1,2 +1,9 @@
 pub mod astar;
+pub mod bfs;
+pub mod bidirectional;
 pub mod core;
+pub mod dijkstra;
+pub mod jps;
+pub mod metrics;
+pub mod negotiation;
+pub mod worker;
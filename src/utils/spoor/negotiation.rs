@@ -0,0 +1,481 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet},
+};
+
+use crate::utils::{cassetta::TapeItem, graphema::Lattice2D};
+
+use super::{
+    core::{Cost, Net, NodeType},
+    metrics::RoutingLayer,
+};
+
+/// Tape of one iteration of the negotiation
+pub type IterationTape = Vec<TapeItem<(usize, usize), NodeType<Net>>>;
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// NegotiationConfig
+//
+//////////////////////////////////////////////////////////////////////////////////////
+/// Tuning of the negotiation between nets. Entering a cell costs
+/// `(1 + history) * (1 + present_factor * other_nets)`, where `other_nets` is the number of
+/// other nets already using it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NegotiationConfig {
+    /// Iterations after which the router gives up on removing the overflow
+    pub max_iterations: usize,
+    /// History cost added to a cell for every net too many it holds after an iteration
+    pub history_increment: Cost,
+    /// Present congestion factor of the second iteration, the first one ignores congestion
+    pub present_factor: Cost,
+    /// Factor the present congestion factor is multiplied by after every later iteration
+    pub present_growth: Cost,
+}
+
+impl Default for NegotiationConfig {
+    fn default() -> Self {
+        Self {
+            max_iterations: 30,
+            history_increment: 1,
+            present_factor: 1,
+            present_growth: 2,
+        }
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// NegotiationStats
+//
+//////////////////////////////////////////////////////////////////////////////////////
+/// State of the routing after one iteration of the negotiation
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IterationStats {
+    /// Nets ripped up and routed again, or routed for the first time
+    pub rerouted: usize,
+    /// Cells used by more than one net
+    pub overused_cells: usize,
+    /// Nets too many summed over the overused cells
+    pub overflow: usize,
+    /// Sum of the wirelength of all nets measured in grid edges
+    pub wirelength: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NegotiationStats {
+    /// Oldest first
+    pub iterations: Vec<IterationStats>,
+    /// Nets with a pin that cannot be reached at any cost
+    pub unroutable: BTreeSet<Net>,
+}
+
+impl NegotiationStats {
+    pub fn iteration_count(&self) -> usize {
+        self.iterations.len()
+    }
+
+    /// True once an iteration left no cell used by more than one net
+    pub fn is_converged(&self) -> bool {
+        self.iterations
+            .last()
+            .is_some_and(|iteration| iteration.overflow == 0)
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// NegotiatedRouter
+//
+//////////////////////////////////////////////////////////////////////////////////////
+/// Routes several nets on one lattice, PathFinder style. The first iteration routes every net
+/// on its own as if the others were not there. Every later iteration rips up the nets sharing
+/// a cell and routes them again one at a time, with the cells in use by other nets getting more
+/// expensive from one iteration to the next and the cells that were overused before staying
+/// expensive, until no cell is shared or the iterations run out. Nets with more than two pins
+/// are connected pin by pin to what is already routed.
+///
+/// Every iteration is recorded as a tape that rips up and lays down `Route` cells, pins are
+/// left out, they are placed by the caller
+#[derive(Clone, Debug)]
+pub struct NegotiatedRouter {
+    lattice: Lattice2D,
+    config: NegotiationConfig,
+    nets: BTreeMap<Net, Vec<(usize, usize)>>,
+    pin_nets: HashMap<(usize, usize), Net>,
+    // Cells of every routed net besides its pins, with their cost from the cell they branch off
+    routes: BTreeMap<Net, BTreeMap<(usize, usize), Cost>>,
+    occupancy: HashMap<(usize, usize), BTreeSet<Net>>,
+    history: HashMap<(usize, usize), Cost>,
+    present_factor: Cost,
+    // Last item the tapes put on every cell, overused cells show one of their nets
+    shown: HashMap<(usize, usize), NodeType<Net>>,
+    stats: NegotiationStats,
+}
+
+impl NegotiatedRouter {
+    pub fn new(lattice: Lattice2D, config: NegotiationConfig) -> Self {
+        Self {
+            lattice,
+            config,
+            nets: BTreeMap::new(),
+            pin_nets: HashMap::new(),
+            routes: BTreeMap::new(),
+            occupancy: HashMap::new(),
+            history: HashMap::new(),
+            present_factor: 0,
+            shown: HashMap::new(),
+            stats: NegotiationStats::default(),
+        }
+    }
+
+    /// Adds a net to be routed by the next iteration. Pins must be vertices of the lattice that
+    /// no other net uses
+    pub fn add_net(&mut self, net: Net, pins: Vec<(usize, usize)>) -> Result<(), String> {
+        if self.nets.contains_key(&net) {
+            return Err(format!("Net {net} was already added"));
+        }
+        if pins.len() < 2 {
+            return Err(format!("Net {net} needs at least two pins"));
+        }
+        for pin in pins.iter() {
+            if !self.lattice.has_vertex(*pin) {
+                return Err(format!("Pin {pin:?} of net {net} is not on the lattice"));
+            }
+            if let Some(other) = self.pin_nets.get(pin).filter(|other| **other != net) {
+                return Err(format!("Pin {pin:?} of net {net} belongs to net {other}"));
+            }
+        }
+        for pin in pins.iter() {
+            self.pin_nets.insert(*pin, net);
+        }
+        self.nets.insert(net, pins);
+        Ok(())
+    }
+
+    pub fn config(&self) -> &NegotiationConfig {
+        &self.config
+    }
+
+    pub fn stats(&self) -> &NegotiationStats {
+        &self.stats
+    }
+
+    /// Cells of the route of `net` besides its pins
+    pub fn route_of(&self, net: Net) -> Option<&BTreeMap<(usize, usize), Cost>> {
+        self.routes.get(&net)
+    }
+
+    /// True once every net is routed and no cell is shared
+    pub fn is_converged(&self) -> bool {
+        self.pending_nets().is_empty() && self.stats.is_converged()
+    }
+
+    /// The routed layer as the tapes show it with the pins, for [`RoutingMetrics`]
+    ///
+    /// [`RoutingMetrics`]: super::metrics::RoutingMetrics
+    pub fn routing_layer(&self) -> RoutingLayer {
+        let mut layer: RoutingLayer = self.shown.clone();
+        for (net, pins) in self.nets.iter() {
+            for (index, pin) in pins.iter().enumerate() {
+                let pin_type = if index == 0 {
+                    NodeType::Start(*net)
+                } else {
+                    NodeType::Target(*net)
+                };
+                layer.insert(*pin, pin_type);
+            }
+        }
+        layer
+    }
+
+    /// Runs iterations until the routing converges or the iterations run out, returning the
+    /// tape of every iteration
+    pub fn route(&mut self) -> Vec<IterationTape> {
+        std::iter::from_fn(|| self.step()).collect()
+    }
+
+    /// Runs a single iteration and returns its tape, None once the routing has converged or
+    /// the iterations have run out
+    pub fn step(&mut self) -> Option<IterationTape> {
+        if self.is_converged() || self.stats.iteration_count() >= self.config.max_iterations {
+            return None;
+        }
+        let mut nets = self.pending_nets();
+        nets.extend(self.overflowing_nets());
+
+        let mut tape = Vec::new();
+        for net in nets.iter() {
+            tape.extend(self.rip_up(*net));
+            tape.extend(self.route_net(*net));
+        }
+
+        let overused: Vec<((usize, usize), usize)> = self
+            .occupancy
+            .iter()
+            .filter(|(_, nets)| nets.len() > 1)
+            .map(|(cell, nets)| (*cell, nets.len() - 1))
+            .collect();
+        for (cell, overflow) in overused.iter() {
+            *self.history.entry(*cell).or_insert(0) += self.config.history_increment * overflow;
+        }
+        self.present_factor = if self.present_factor == 0 {
+            self.config.present_factor
+        } else {
+            self.present_factor
+                .saturating_mul(self.config.present_growth)
+        };
+
+        let wirelength = self
+            .routes
+            .iter()
+            .map(|(net, cells)| cells.len() + self.nets[net].len() - 1)
+            .sum();
+        self.stats.iterations.push(IterationStats {
+            rerouted: nets.len(),
+            overused_cells: overused.len(),
+            overflow: overused.iter().map(|(_, overflow)| overflow).sum(),
+            wirelength,
+        });
+        Some(tape)
+    }
+
+    // Nets that have not been routed yet
+    fn pending_nets(&self) -> BTreeSet<Net> {
+        self.nets
+            .keys()
+            .filter(|net| !self.routes.contains_key(net))
+            .copied()
+            .collect()
+    }
+
+    // Nets using a cell together with another net
+    fn overflowing_nets(&self) -> BTreeSet<Net> {
+        self.occupancy
+            .values()
+            .filter(|nets| nets.len() > 1)
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    fn rip_up(&mut self, net: Net) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        let mut tape = Vec::new();
+        for (cell, cost) in self.routes.remove(&net).unwrap_or_default() {
+            let nets = self.occupancy.entry(cell).or_default();
+            nets.remove(&net);
+            let remaining = nets.iter().next().copied();
+            if nets.is_empty() {
+                self.occupancy.remove(&cell);
+            }
+            // Only cells showing this net change on the tape
+            let current = NodeType::Route(net, cost);
+            if self.shown.get(&cell) != Some(&current) {
+                continue;
+            }
+            match remaining {
+                Some(other) => {
+                    let shown = NodeType::Route(other, self.routes[&other][&cell]);
+                    self.shown.insert(cell, shown);
+                    tape.push(TapeItem::Add(cell, shown, Some(current)));
+                }
+                None => {
+                    self.shown.remove(&cell);
+                    tape.push(TapeItem::Remove(cell, current));
+                }
+            }
+        }
+        tape
+    }
+
+    fn route_net(&mut self, net: Net) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+        let pins = self.nets[&net].clone();
+        let mut tree: HashSet<(usize, usize)> = HashSet::from([pins[0]]);
+        let mut route = BTreeMap::new();
+        for pin in pins.iter().skip(1) {
+            if tree.contains(pin) {
+                continue;
+            }
+            match self.connect(net, &tree, *pin) {
+                Some(cells) => {
+                    for (cell, cost) in cells {
+                        tree.insert(cell);
+                        if self.pin_nets.get(&cell) != Some(&net) {
+                            route.insert(cell, cost);
+                        }
+                    }
+                }
+                None => {
+                    self.stats.unroutable.insert(net);
+                }
+            }
+        }
+
+        let mut tape = Vec::new();
+        for (cell, cost) in route.iter() {
+            self.occupancy.entry(*cell).or_default().insert(net);
+            let shown = NodeType::Route(net, *cost);
+            let previous = self.shown.insert(*cell, shown);
+            tape.push(TapeItem::Add(*cell, shown, previous));
+        }
+        self.routes.insert(net, route);
+        tape
+    }
+
+    // Cost for `net` of entering `cell`, None if it cannot be entered at all
+    fn cell_cost(&self, net: Net, cell: (usize, usize)) -> Option<Cost> {
+        if self.pin_nets.get(&cell).is_some_and(|owner| *owner != net) {
+            return None;
+        }
+        let others = self
+            .occupancy
+            .get(&cell)
+            .map_or(0, |nets| nets.iter().filter(|other| **other != net).count());
+        let history = self.history.get(&cell).copied().unwrap_or(0);
+        Some((1 + history).saturating_mul(1 + self.present_factor.saturating_mul(others)))
+    }
+
+    // Cheapest cells leading from any cell of `tree` to `pin`, each with its cost from the
+    // tree, None if the pin cannot be reached
+    fn connect(
+        &self,
+        net: Net,
+        tree: &HashSet<(usize, usize)>,
+        pin: (usize, usize),
+    ) -> Option<Vec<((usize, usize), Cost)>> {
+        let mut costs: HashMap<(usize, usize), Cost> = HashMap::new();
+        let mut parents: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        let mut unresolved_nodes = BinaryHeap::new();
+        for cell in tree.iter() {
+            costs.insert(*cell, 0);
+            unresolved_nodes.push(Reverse((0, *cell)));
+        }
+        while let Some(Reverse((cost, cell))) = unresolved_nodes.pop() {
+            if costs.get(&cell) != Some(&cost) {
+                continue;
+            }
+            if cell == pin {
+                let mut path = vec![(cell, cost)];
+                let mut current = cell;
+                while let Some(parent) = parents
+                    .get(&current)
+                    .filter(|parent| !tree.contains(parent))
+                {
+                    path.push((*parent, costs[parent]));
+                    current = *parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for next in self.lattice.neighbours_iter(cell) {
+                let Some(step) = self.cell_cost(net, next) else {
+                    continue;
+                };
+                let next_cost = cost + step;
+                if costs.get(&next).is_none_or(|known| next_cost < *known) {
+                    costs.insert(next, next_cost);
+                    parents.insert(next, cell);
+                    unresolved_nodes.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{NegotiatedRouter, NegotiationConfig};
+    use crate::utils::{cassetta::TapeItem, graphema::Lattice2D};
+
+    // 5 by 5 lattice with a wall down the middle column, open on rows 1 and 4
+    fn router(config: NegotiationConfig) -> NegotiatedRouter {
+        let mut lattice = Lattice2D::new(5, 5);
+        lattice.fill();
+        for row in [0, 2, 3] {
+            lattice.remove_vertex((2, row));
+        }
+        NegotiatedRouter::new(lattice, config)
+    }
+
+    #[test]
+    fn negotiates_the_narrow_passage() {
+        let mut router = router(NegotiationConfig::default());
+        router.add_net(1, vec![(0, 1), (4, 1)]).unwrap();
+        router.add_net(2, vec![(1, 0), (3, 0)]).unwrap();
+        assert!(router.add_net(3, vec![(3, 0), (4, 4)]).is_err());
+        assert!(router.add_net(3, vec![(2, 0), (4, 4)]).is_err());
+
+        let tapes = router.route();
+        assert!(router.is_converged());
+        let stats = router.stats();
+        assert_eq!(stats.iteration_count(), 2);
+        assert_eq!(tapes.len(), 2);
+        // Both nets take the passage on row 1 at first
+        assert_eq!(stats.iterations[0].overused_cells, 3);
+        assert_eq!(stats.iterations[0].wirelength, 8);
+        // Then net 1 goes around through row 4
+        assert_eq!(stats.iterations[1].rerouted, 2);
+        assert_eq!(stats.iterations[1].wirelength, 14);
+        assert_eq!(router.route_of(1).unwrap().len(), 9);
+        assert!(router
+            .route_of(1)
+            .unwrap()
+            .keys()
+            .all(|cell| !router.route_of(2).unwrap().contains_key(cell)));
+        assert!(router.step().is_none());
+
+        // The tapes replay to the routed layer
+        let mut shown = HashMap::new();
+        for item in tapes.iter().flatten() {
+            match item {
+                TapeItem::Add(pos, node, previous) => {
+                    assert_eq!(shown.insert(*pos, *node), *previous)
+                }
+                TapeItem::Remove(pos, node) => assert_eq!(shown.remove(pos), Some(*node)),
+                _ => panic!("unexpected {item:?}"),
+            }
+        }
+        let layer = router.routing_layer();
+        assert_eq!(layer.len(), shown.len() + 4);
+        assert!(shown.iter().all(|(pos, node)| layer.get(pos) == Some(node)));
+    }
+
+    #[test]
+    fn gives_up_on_crossing_nets() {
+        let config = NegotiationConfig {
+            max_iterations: 4,
+            ..Default::default()
+        };
+        let mut lattice = Lattice2D::new(5, 3);
+        lattice.fill();
+        let mut router = NegotiatedRouter::new(lattice, config);
+        // Net 2 splits the left half of the lattice from the right one
+        router.add_net(1, vec![(0, 1), (4, 1)]).unwrap();
+        router.add_net(2, vec![(2, 0), (2, 2)]).unwrap();
+        let tapes = router.route();
+        assert_eq!(tapes.len(), 4);
+        assert!(!router.is_converged());
+        assert!(router
+            .stats()
+            .iterations
+            .iter()
+            .all(|iteration| iteration.overflow > 0));
+    }
+
+    #[test]
+    fn reports_unreachable_pins() {
+        let mut router = router(NegotiationConfig::default());
+        router.add_net(1, vec![(0, 0), (1, 0), (4, 0)]).unwrap();
+        router.add_net(2, vec![(3, 0), (3, 1)]).unwrap();
+        router.add_net(3, vec![(4, 1), (4, 2)]).unwrap();
+        router.route();
+        assert!(router.is_converged());
+        assert_eq!(
+            router.stats().unroutable.iter().collect::<Vec<_>>(),
+            vec![&1]
+        );
+    }
+}
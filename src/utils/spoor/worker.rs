@@ -0,0 +1,257 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use druid::{ExtEventSink, Selector, Target};
+
+use crate::utils::cassetta::TapeItem;
+
+use super::core::{Net, NodeType, ShortestPath, ShortestPathConfig};
+
+// Sent by a PathfindingWorker while its search runs, see AlgoProgress
+pub const GRID_ALGO_PROGRESS: Selector<AlgoProgress> = Selector::new("grid-algo-progress");
+
+// Exploration is collected for this long before it is sent, so a fast search does not flood
+// the event loop with a command per expansion
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Update from a search running on a worker thread
+#[derive(Clone, Debug, PartialEq)]
+pub enum AlgoProgress {
+    /// Exploration recorded since the last update, in order
+    Explored(Vec<TapeItem<(usize, usize), NodeType<Net>>>),
+    /// The search ran to its end, with the route it found or nothing if it found none
+    Finished(Vec<TapeItem<(usize, usize), NodeType<Net>>>),
+    /// The search was cancelled and sends nothing more
+    Cancelled,
+}
+
+/// Runs a [`ShortestPath`] search on its own thread and streams its exploration back, so large
+/// searches do not block the event loop. The search checks for cancellation between
+/// expansions, dropping the worker cancels it
+pub struct PathfindingWorker {
+    cancelled: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Box<dyn ShortestPath + Send>>>,
+}
+
+impl PathfindingWorker {
+    /// Sends the progress of the search to `target` as [`GRID_ALGO_PROGRESS`] commands
+    pub fn spawn(
+        algo: Box<dyn ShortestPath + Send>,
+        config: ShortestPathConfig,
+        source: usize,
+        sink: ExtEventSink,
+        target: impl Into<Target>,
+    ) -> Self {
+        let target = target.into();
+        Self::spawn_with(algo, config, source, move |progress| {
+            // The only error is a closed application, nobody is left to tell
+            let _ = sink.submit_command(GRID_ALGO_PROGRESS, progress, target);
+        })
+    }
+
+    /// Hands the progress of the search to `send` on the worker thread
+    pub fn spawn_with(
+        mut algo: Box<dyn ShortestPath + Send>,
+        config: ShortestPathConfig,
+        source: usize,
+        mut send: impl FnMut(AlgoProgress) + Send + 'static,
+    ) -> Self {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let is_cancelled = cancelled.clone();
+        let handle = thread::spawn(move || {
+            let mut explored = Vec::new();
+            let mut last_sent = Instant::now();
+            // Only a search stopped by the flag counts as cancelled, one cancelled after its
+            // last expansion has finished
+            let mut stopped = false;
+            algo.compute_streamed(&config, source, &mut |items| {
+                if is_cancelled.load(Ordering::Relaxed) {
+                    stopped = true;
+                    return false;
+                }
+                explored.extend_from_slice(items);
+                if !explored.is_empty() && last_sent.elapsed() >= PROGRESS_INTERVAL {
+                    send(AlgoProgress::Explored(std::mem::take(&mut explored)));
+                    last_sent = Instant::now();
+                }
+                true
+            });
+            if stopped {
+                send(AlgoProgress::Cancelled);
+            } else {
+                if !explored.is_empty() {
+                    send(AlgoProgress::Explored(explored));
+                }
+                send(AlgoProgress::Finished(algo.reconstruct_path()));
+            }
+            algo
+        });
+        Self {
+            cancelled,
+            handle: Some(handle),
+        }
+    }
+
+    /// Stops the search at its next expansion
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle
+            .as_ref()
+            .is_none_or(|handle| handle.is_finished())
+    }
+
+    /// Waits for the search to end and hands the search back, with the state of its last run.
+    /// None if the search panicked
+    pub fn join(mut self) -> Option<Box<dyn ShortestPath + Send>> {
+        self.handle.take()?.join().ok()
+    }
+}
+
+impl Drop for PathfindingWorker {
+    fn drop(&mut self) {
+        if self.handle.is_some() {
+            self.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use graph_builder::UndirectedCsrGraph;
+
+    use super::{AlgoProgress, PathfindingWorker};
+    use crate::utils::{
+        cassetta::TapeItem,
+        graphema::Lattice2D,
+        spoor::{
+            bfs::BreadthFirst,
+            core::{Net, NodeType, PathNode, SearchProgress, ShortestPath, ShortestPathConfig},
+        },
+    };
+
+    // Expands nothing until it is told to stop
+    struct Endless;
+
+    impl ShortestPath for Endless {
+        fn compute(
+            &mut self,
+            config: &ShortestPathConfig,
+            source: usize,
+        ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+            self.compute_streamed(config, source, &mut |_| true)
+        }
+
+        fn compute_streamed(
+            &mut self,
+            _config: &ShortestPathConfig,
+            _source: usize,
+            progress: SearchProgress<'_>,
+        ) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+            while progress(&[]) {}
+            Vec::new()
+        }
+
+        fn reconstruct_path(&mut self) -> Vec<TapeItem<(usize, usize), NodeType<Net>>> {
+            Vec::new()
+        }
+
+        fn get_next_unresolved(&mut self) -> Option<PathNode> {
+            None
+        }
+
+        fn get_next_path_node(&self) -> Option<PathNode> {
+            None
+        }
+    }
+
+    fn config(size: usize) -> ShortestPathConfig {
+        let mut lattice = Lattice2D::new(size, size);
+        lattice.fill();
+        let graph: UndirectedCsrGraph<usize, usize> = lattice.into();
+        ShortestPathConfig {
+            graph: graph.into(),
+            goal: Some(size * size - 1),
+            boundary: (size, size),
+            direction_preference: None,
+            net: 3,
+        }
+    }
+
+    #[test]
+    fn streams_the_whole_search() {
+        let (sender, receiver) = mpsc::channel();
+        let worker = PathfindingWorker::spawn_with(
+            Box::new(BreadthFirst::new()),
+            config(8),
+            0,
+            move |progress| sender.send(progress).unwrap(),
+        );
+        let algo = worker.join().unwrap();
+        let updates: Vec<AlgoProgress> = receiver.try_iter().collect();
+
+        let mut explored = Vec::new();
+        let mut route = None;
+        for update in updates {
+            match update {
+                AlgoProgress::Explored(items) => {
+                    assert!(route.is_none());
+                    explored.extend(items);
+                }
+                AlgoProgress::Finished(items) => route = Some(items),
+                AlgoProgress::Cancelled => panic!("not cancelled"),
+            }
+        }
+        let mut bfs = BreadthFirst::new();
+        assert_eq!(explored, bfs.compute(&config(8), 0));
+        assert_eq!(route.unwrap().len(), 13);
+        assert!(algo.get_next_path_node().is_some());
+    }
+
+    #[test]
+    fn cancels_between_expansions() {
+        let (sender, receiver) = mpsc::channel();
+        let worker =
+            PathfindingWorker::spawn_with(Box::new(Endless), config(2), 0, move |progress| {
+                sender.send(progress).unwrap()
+            });
+        assert!(!worker.is_finished());
+        worker.cancel();
+        assert!(worker.is_cancelled());
+        worker.join().unwrap();
+        let updates: Vec<AlgoProgress> = receiver.try_iter().collect();
+        assert_eq!(updates, vec![AlgoProgress::Cancelled]);
+    }
+
+    #[test]
+    fn cancelling_a_finished_search_keeps_its_route() {
+        let (sender, receiver) = mpsc::channel();
+        let worker = PathfindingWorker::spawn_with(
+            Box::new(BreadthFirst::new()),
+            config(4),
+            0,
+            move |progress| sender.send(progress).unwrap(),
+        );
+        while !worker.is_finished() {
+            std::thread::yield_now();
+        }
+        worker.cancel();
+        worker.join().unwrap();
+        let updates: Vec<AlgoProgress> = receiver.try_iter().collect();
+        assert!(matches!(updates.last(), Some(AlgoProgress::Finished(route)) if !route.is_empty()));
+    }
+}
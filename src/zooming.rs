@@ -3,7 +3,9 @@
 /// Imports
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////////
-use druid::{widget::Controller, Data, Event, Lens, Widget};
+use druid::{widget::Controller, Data, Event, Point, Widget};
+
+use crate::{panning::PanDataAccess, snapping::ViewTransform};
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 ///
@@ -15,36 +17,24 @@ pub trait ZoomDataAccess {
     fn set_zoom_scale(&mut self, scale: f64);
 }
 
-#[derive(Clone, Data, Lens, PartialEq, Debug)]
-pub struct ZoomData {
-    pub zoom_scale: f64,
-}
-
-impl ZoomData {
-    pub fn new() -> Self {
-        Self { zoom_scale: 1.0 }
-    }
-}
-
-impl ZoomDataAccess for ZoomData {
-    fn get_zoom_scale(&self) -> f64 {
-        self.zoom_scale
-    }
-
-    fn set_zoom_scale(&mut self, scale: f64) {
-        self.zoom_scale = scale;
-    }
-}
-
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 ///
 /// ZoomController
 ///
 ///////////////////////////////////////////////////////////////////////////////////////////////////
+// Wheel delta of one notch of a mouse wheel
+const WHEEL_NOTCH: f64 = 120.0;
+
+/// Zooms the view around the pointer with Ctrl+Wheel, and around the point between the fingers
+/// with a trackpad pinch. Platforms without pinch events send pinches as Ctrl+Wheel. Two-finger
+/// panning is left to the [`PanController`](crate::panning::PanController)
 pub struct ZoomController {
     min_zoom_scale: f64,
     max_zoom_scale: f64,
+    // Fraction the zoom scale changes by for each notch of a mouse wheel
     zoom_step: f64,
+    // Last pointer position over the widget, pinch events do not carry one
+    pointer: Option<Point>,
 }
 
 impl ZoomController {
@@ -53,6 +43,23 @@ impl ZoomController {
             min_zoom_scale,
             max_zoom_scale,
             zoom_step,
+            pointer: None,
+        }
+    }
+
+    fn clamp(&self, zoom_scale: f64) -> f64 {
+        zoom_scale.clamp(self.min_zoom_scale, self.max_zoom_scale)
+    }
+
+    // Zoom scale after a Ctrl+Wheel of `delta`. A notch of a mouse wheel zooms by a whole step,
+    // the many small deltas of a trackpad pinch by a fraction of one each
+    fn wheel_zoom_scale(&self, zoom_scale: f64, delta: f64) -> f64 {
+        let factor = (1.0 + self.zoom_step).powf(-delta / WHEEL_NOTCH);
+        match zoom_scale {
+            zoom_scale if zoom_scale > 0.0 => self.clamp(zoom_scale * factor),
+            // A view zoomed out to nothing grows back from a single step
+            _ if factor > 1.0 => self.clamp(self.zoom_step * factor),
+            zoom_scale => zoom_scale,
         }
     }
 }
@@ -63,11 +70,27 @@ impl Default for ZoomController {
             min_zoom_scale: 0.,
             max_zoom_scale: 4.,
             zoom_step: 0.05,
+            pointer: None,
         }
     }
 }
 
-impl<T: Data + ZoomDataAccess, W: Widget<T>> Controller<T, W> for ZoomController {
+/// Sets the zoom scale keeping the point of the grid under `anchor`, a position of the widget,
+/// in place. A view zoomed out to nothing has no point there and only gets the new scale
+pub fn zoom_around<T: ZoomDataAccess + PanDataAccess>(data: &mut T, anchor: Point, scale: f64) {
+    let old_scale = data.get_zoom_scale();
+    if old_scale > 0.0 && old_scale.is_finite() {
+        // The rotation of the view turns around the offset and moves with it, so the upright view
+        // moves the offset the same
+        let before = ViewTransform::new(data.get_offset(), old_scale, 0.0);
+        let after = ViewTransform { scale, ..before };
+        let world = before.to_world(anchor);
+        data.set_offset(data.get_offset() + (anchor - after.to_screen(world)));
+    }
+    data.set_zoom_scale(scale);
+}
+
+impl<T: Data + ZoomDataAccess + PanDataAccess, W: Widget<T>> Controller<T, W> for ZoomController {
     fn event(
         &mut self,
         child: &mut W,
@@ -77,28 +100,78 @@ impl<T: Data + ZoomDataAccess, W: Widget<T>> Controller<T, W> for ZoomController
         env: &druid::Env,
     ) {
         match event {
-            Event::Wheel(wheel) if wheel.mods.ctrl() => {
-                // let mut current_zoom_scale = data.zoom_scale;
-                let mut current_zoom_scale = data.get_zoom_scale();
-                if wheel.wheel_delta.y < 0.0 && current_zoom_scale < self.max_zoom_scale {
-                    current_zoom_scale += self.zoom_step;
-
-                    if current_zoom_scale > self.max_zoom_scale {
-                        current_zoom_scale = self.max_zoom_scale;
-                    }
-                } else if wheel.wheel_delta.y > 0.0 && current_zoom_scale > self.min_zoom_scale {
-                    current_zoom_scale -= self.zoom_step;
-
-                    if current_zoom_scale < self.min_zoom_scale {
-                        current_zoom_scale = self.min_zoom_scale
-                    }
+            // Ctrl+Shift+Wheel is left to the RotationController
+            Event::Wheel(wheel) if wheel.mods.ctrl() && !wheel.mods.shift() => {
+                let zoom_scale = self.wheel_zoom_scale(data.get_zoom_scale(), wheel.wheel_delta.y);
+                if zoom_scale.is_finite() {
+                    zoom_around(data, wheel.pos, zoom_scale);
                 }
-                // data.zoom_scale = current_zoom_scale;
-                data.set_zoom_scale(current_zoom_scale)
             }
+            Event::Zoom(delta) => {
+                let anchor = self
+                    .pointer
+                    .unwrap_or_else(|| ctx.size().to_rect().center());
+                // A view zoomed out to nothing grows back from a single step
+                let zoom_scale = match data.get_zoom_scale() {
+                    zoom_scale if zoom_scale > 0.0 => zoom_scale,
+                    _ => self.zoom_step,
+                };
+                let zoom_scale = self.clamp(zoom_scale * (1.0 + delta));
+                if zoom_scale.is_finite() {
+                    zoom_around(data, anchor, zoom_scale);
+                }
+                ctx.set_handled();
+                return;
+            }
+            Event::MouseMove(mouse_event) => self.pointer = Some(mouse_event.pos),
 
             _ => (),
         }
         child.event(ctx, event, data, env);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use druid::Point;
+
+    use super::{zoom_around, ZoomController, WHEEL_NOTCH};
+    use crate::snapping::{GridSnapData, ViewTransform};
+
+    #[test]
+    fn zooms_around_the_anchor() {
+        let mut snap_data = GridSnapData::new(10.0);
+        snap_data.view = ViewTransform::new(Point::new(30.0, 40.0), 1.0, 0.4);
+        let anchor = Point::new(200.0, 120.0);
+        let before = snap_data.view.to_world(anchor);
+
+        zoom_around(&mut snap_data, anchor, 2.5);
+        assert_eq!(snap_data.view.scale, 2.5);
+        assert!((snap_data.view.to_world(anchor) - before).hypot() < 1e-9);
+
+        // Nothing is under the anchor of a view zoomed out to nothing
+        snap_data.view.scale = 0.0;
+        let offset = snap_data.view.offset;
+        zoom_around(&mut snap_data, anchor, 1.0);
+        assert_eq!(snap_data.view.offset, offset);
+        assert_eq!(snap_data.view.scale, 1.0);
+    }
+
+    #[test]
+    fn wheel_zoom_follows_the_delta() {
+        let zoom = ZoomController::new(0.0, 4.0, 0.1);
+        let notch = zoom.wheel_zoom_scale(1.0, -WHEEL_NOTCH);
+        assert!((notch - 1.1).abs() < 1e-9);
+        // A pinch sent as many small deltas zooms as far as one notch of the same total
+        let pinch = (0..10).fold(1.0, |scale, _| {
+            zoom.wheel_zoom_scale(scale, -WHEEL_NOTCH / 10.0)
+        });
+        assert!((pinch - notch).abs() < 1e-9);
+        assert!((zoom.wheel_zoom_scale(notch, WHEEL_NOTCH) - 1.0).abs() < 1e-9);
+        assert_eq!(zoom.wheel_zoom_scale(3.9, -10.0 * WHEEL_NOTCH), 4.0);
+
+        // Only zooming in brings back a view zoomed out to nothing
+        assert_eq!(zoom.wheel_zoom_scale(0.0, WHEEL_NOTCH), 0.0);
+        assert!(zoom.wheel_zoom_scale(0.0, -WHEEL_NOTCH) > 0.0);
+    }
+}